@@ -21,10 +21,18 @@ fn main() -> Result<()> {
         .build_client(true)
         .compile(CLIENT_PROTOS, INCLUDES)?;
 
+    const JSON_SERDE_DERIVE: &str = r#"#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]"#;
+
     tonic_build::configure()
         .out_dir("src/api")
         .protoc_arg("--experimental_allow_proto3_optional")
         .file_descriptor_set_path("src/api/FILE_DESCRIPTOR_SET")
+        .type_attribute("sited_io.media.v1.MediaResponse", JSON_SERDE_DERIVE)
+        .type_attribute("sited_io.media.v1.MediaUpload", JSON_SERDE_DERIVE)
+        .field_attribute(
+            "sited_io.media.v1.MediaUpload.data",
+            r#"#[cfg_attr(feature = "json", serde(with = "crate::api::base64_bytes"))]"#,
+        )
         .build_server(true)
         .build_client(false)
         .compile(MEDIA_PROTOS, INCLUDES)?;
@@ -0,0 +1,14 @@
+-- secrets are never stored, they are resolved from the secret store by
+-- `secret_access_key_ref`
+CREATE TABLE shop_buckets (
+  shop_id UUID NOT NULL PRIMARY KEY,
+  bucket_name VARCHAR NOT NULL,
+  bucket_endpoint VARCHAR,
+  access_key_id VARCHAR,
+  secret_access_key_ref VARCHAR
+);
+
+ALTER TABLE
+  medias
+ADD
+  COLUMN bucket_name VARCHAR;
@@ -0,0 +1,4 @@
+ALTER TABLE
+  medias
+ADD
+  COLUMN content_type VARCHAR;
@@ -0,0 +1,7 @@
+CREATE TABLE multipart_parts (
+  upload_id VARCHAR NOT NULL,
+  part_number INT4 NOT NULL,
+  etag VARCHAR NOT NULL,
+  uploaded_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (upload_id, part_number)
+);
@@ -0,0 +1,4 @@
+ALTER TABLE
+  medias
+ADD
+  COLUMN processing_state VARCHAR NOT NULL DEFAULT 'ready';
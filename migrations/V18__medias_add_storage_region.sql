@@ -0,0 +1,4 @@
+ALTER TABLE
+  medias
+ADD
+  COLUMN storage_region VARCHAR;
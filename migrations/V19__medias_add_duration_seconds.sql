@@ -0,0 +1,4 @@
+ALTER TABLE
+  medias
+ADD
+  COLUMN duration_seconds FLOAT8;
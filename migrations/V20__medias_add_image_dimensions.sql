@@ -0,0 +1,6 @@
+ALTER TABLE
+  medias
+ADD
+  COLUMN image_width INT4,
+ADD
+  COLUMN image_height INT4;
@@ -0,0 +1,4 @@
+ALTER TABLE
+  medias
+ADD
+  COLUMN delete_pending BOOL NOT NULL DEFAULT false;
@@ -0,0 +1,46 @@
+UPDATE
+  media_subscriptions
+SET
+  subscription_status = replace(
+    replace(lower(trim(subscription_status)), '-', '_'),
+    ' ',
+    '_'
+  );
+
+-- spellings of the enumerated statuses written by earlier clients
+UPDATE
+  media_subscriptions
+SET
+  subscription_status = CASE
+    subscription_status
+    WHEN 'cancelled' THEN 'canceled'
+    WHEN 'pastdue' THEN 'past_due'
+    WHEN 'incompleteexpired' THEN 'incomplete_expired'
+    WHEN 'subscription_status_active' THEN 'active'
+    WHEN 'subscription_status_past_due' THEN 'past_due'
+    WHEN 'subscription_status_canceled' THEN 'canceled'
+    WHEN 'subscription_status_trialing' THEN 'trialing'
+    WHEN 'subscription_status_paused' THEN 'paused'
+    WHEN 'subscription_status_incomplete' THEN 'incomplete'
+    WHEN 'subscription_status_incomplete_expired' THEN 'incomplete_expired'
+    WHEN 'subscription_status_unpaid' THEN 'unpaid'
+    ELSE subscription_status
+  END;
+
+-- any other status is not guessed, adding the constraint fails on it so
+-- it can be corrected by hand before migrating again
+ALTER TABLE
+  media_subscriptions
+ADD
+  CONSTRAINT check_subscription_status CHECK (
+    subscription_status IN (
+      'active',
+      'past_due',
+      'canceled',
+      'trialing',
+      'paused',
+      'incomplete',
+      'incomplete_expired',
+      'unpaid'
+    )
+  );
@@ -0,0 +1,28 @@
+ALTER TABLE
+  media_subscriptions
+ADD
+  COLUMN revoked_at TIMESTAMP WITH TIME ZONE,
+ADD
+  COLUMN revoked_by VARCHAR,
+ADD
+  COLUMN revoke_reason VARCHAR;
+
+ALTER TABLE
+  media_subscriptions DROP CONSTRAINT check_subscription_status;
+
+ALTER TABLE
+  media_subscriptions
+ADD
+  CONSTRAINT check_subscription_status CHECK (
+    subscription_status IN (
+      'active',
+      'past_due',
+      'canceled',
+      'trialing',
+      'paused',
+      'incomplete',
+      'incomplete_expired',
+      'unpaid',
+      'revoked'
+    )
+  );
@@ -0,0 +1,4 @@
+ALTER TABLE
+  medias
+ADD
+  COLUMN custom_metadata JSONB;
@@ -0,0 +1,8 @@
+DELETE FROM
+  medias_offers AS a USING medias_offers AS b
+WHERE
+  a.media_id = b.media_id
+  AND a.offer_id = b.offer_id
+  AND a.rowid > b.rowid;
+
+CREATE UNIQUE INDEX medias_offers_media_id_offer_id_key ON medias_offers (media_id, offer_id);
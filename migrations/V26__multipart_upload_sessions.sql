@@ -0,0 +1,8 @@
+CREATE TABLE multipart_upload_sessions (
+  upload_id VARCHAR PRIMARY KEY,
+  media_id UUID NOT NULL,
+  user_id VARCHAR NOT NULL,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+  last_error TEXT NULL,
+  error_count INT4 NOT NULL DEFAULT 0
+);
@@ -0,0 +1,4 @@
+ALTER TABLE
+  media_subscriptions
+ADD
+  COLUMN seller_notes TEXT;
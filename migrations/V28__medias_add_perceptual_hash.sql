@@ -0,0 +1,8 @@
+ALTER TABLE
+  medias
+ADD
+  COLUMN perceptual_hash INT8;
+
+CREATE INDEX medias_shop_id_user_id_perceptual_hash_idx ON medias (shop_id, user_id) STORING (perceptual_hash)
+WHERE
+  perceptual_hash IS NOT NULL;
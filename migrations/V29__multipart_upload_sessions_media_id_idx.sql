@@ -0,0 +1 @@
+CREATE INDEX multipart_upload_sessions_media_id_idx ON multipart_upload_sessions (media_id);
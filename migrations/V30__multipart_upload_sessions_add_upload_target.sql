@@ -0,0 +1,3 @@
+ALTER TABLE multipart_upload_sessions ADD COLUMN object_key VARCHAR NULL;
+ALTER TABLE multipart_upload_sessions ADD COLUMN content_type VARCHAR NULL;
+ALTER TABLE multipart_upload_sessions ADD COLUMN replaced_size_bytes INT8 NULL;
@@ -0,0 +1 @@
+ALTER TABLE medias ADD COLUMN scan_id UUID NULL;
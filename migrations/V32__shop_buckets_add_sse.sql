@@ -0,0 +1,4 @@
+-- server-side encryption of shop buckets, the encryption of the service's
+-- own bucket does not apply to them
+ALTER TABLE shop_buckets ADD COLUMN sse_algorithm VARCHAR NULL;
+ALTER TABLE shop_buckets ADD COLUMN sse_kms_key_id VARCHAR NULL;
@@ -31,3 +31,68 @@ pub mod sited_io {
         }
     }
 }
+
+/// Serializes bytes fields as standard base64 strings in JSON
+#[cfg(feature = "json")]
+pub mod base64_bytes {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        bytes: &[u8],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        STANDARD.decode(encoded).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use super::sited_io::media::v1::{MediaResponse, MediaUpload};
+
+    #[test]
+    fn media_upload_data_is_base64() {
+        let upload = MediaUpload {
+            content_type: "image/png".to_string(),
+            data: vec![0, 1, 254, 255],
+        };
+
+        let json = serde_json::to_value(&upload).unwrap();
+
+        assert_eq!(json["data"], "AAH+/w==");
+        assert_eq!(
+            serde_json::from_value::<MediaUpload>(json).unwrap(),
+            upload
+        );
+    }
+
+    #[test]
+    fn media_response_round_trips() {
+        let media = MediaResponse {
+            media_id: "media".to_string(),
+            offer_ids: vec!["offer-a".to_string(), "offer-b".to_string()],
+            custom_metadata: [("color".to_string(), "red".to_string())].into(),
+            duration_seconds: Some(1.5),
+            ..Default::default()
+        };
+
+        let json = serde_json::to_value(&media).unwrap();
+
+        assert_eq!(
+            json["offer_ids"],
+            serde_json::json!(["offer-a", "offer-b"])
+        );
+        assert_eq!(
+            serde_json::from_value::<MediaResponse>(json).unwrap(),
+            media
+        );
+    }
+}
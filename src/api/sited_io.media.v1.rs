@@ -1,3 +1,5 @@
+// This file is @generated by prost-build.
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct MediaResponse {
@@ -19,13 +21,47 @@ pub struct MediaResponse {
     pub file_name: ::prost::alloc::string::String,
     #[prost(int64, tag = "9")]
     pub ordering: i64,
+    #[prost(bool, optional, tag = "10")]
+    pub is_owner: ::core::option::Option<bool>,
+    #[prost(bool, optional, tag = "11")]
+    pub has_access: ::core::option::Option<bool>,
+    #[prost(enumeration = "MediaProcessingState", tag = "12")]
+    pub processing_state: i32,
+    #[prost(float, optional, tag = "13")]
+    pub duration_seconds: ::core::option::Option<f32>,
+    #[prost(int32, optional, tag = "14")]
+    pub image_width: ::core::option::Option<i32>,
+    #[prost(int32, optional, tag = "15")]
+    pub image_height: ::core::option::Option<i32>,
+    #[prost(map = "string, string", tag = "16")]
+    pub custom_metadata: ::std::collections::HashMap<
+        ::prost::alloc::string::String,
+        ::prost::alloc::string::String,
+    >,
+    /// Presigned download url, only set if requested by `include_urls` of
+    /// `ListMediaRequest` and the media can be served
+    #[prost(string, optional, tag = "17")]
+    pub download_url: ::core::option::Option<::prost::alloc::string::String>,
+    /// Stable link to the media independent of where its file is stored, only
+    /// set if the service has a permalink base url configured
+    #[prost(string, optional, tag = "18")]
+    pub permalink: ::core::option::Option<::prost::alloc::string::String>,
+    /// HMAC-SHA256 over the fields of this response and `signed_at`, only set
+    /// if requested by `sign_response`
+    #[prost(string, optional, tag = "19")]
+    pub signature: ::core::option::Option<::prost::alloc::string::String>,
+    /// Unix timestamp in seconds when `signature` was created
+    #[prost(int64, optional, tag = "20")]
+    pub signed_at: ::core::option::Option<i64>,
 }
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct MediaUpload {
     #[prost(string, tag = "1")]
     pub content_type: ::prost::alloc::string::String,
     #[prost(bytes = "vec", tag = "2")]
+    #[cfg_attr(feature = "json", serde(with = "crate::api::base64_bytes"))]
     pub data: ::prost::alloc::vec::Vec<u8>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -39,6 +75,13 @@ pub struct CreateMediaRequest {
     pub file: ::core::option::Option<MediaUpload>,
     #[prost(string, tag = "4")]
     pub file_name: ::prost::alloc::string::String,
+    #[prost(string, optional, tag = "5")]
+    pub offer_id: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(map = "string, string", tag = "6")]
+    pub custom_metadata: ::std::collections::HashMap<
+        ::prost::alloc::string::String,
+        ::prost::alloc::string::String,
+    >,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -51,18 +94,34 @@ pub struct CreateMediaResponse {
 pub struct GetMediaRequest {
     #[prost(string, tag = "1")]
     pub media_id: ::prost::alloc::string::String,
+    #[prost(bool, optional, tag = "2")]
+    pub include_download_url: ::core::option::Option<bool>,
+    /// Return the file inline as `data:` URI instead of a download url if it
+    /// is small enough. Ignored if the server disables inline data, clients
+    /// must handle regular download urls in any case.
+    #[prost(bool, optional, tag = "3")]
+    pub allow_data_uri: ::core::option::Option<bool>,
+    /// Signs the media, fails with `FAILED_PRECONDITION` if the server has no
+    /// signing key configured
+    #[prost(bool, tag = "4")]
+    pub sign_response: bool,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct GetMediaResponse {
     #[prost(message, optional, tag = "1")]
     pub media: ::core::option::Option<MediaResponse>,
+    #[prost(string, optional, tag = "2")]
+    pub download_url: ::core::option::Option<::prost::alloc::string::String>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct DownloadMediaRequest {
     #[prost(string, tag = "1")]
     pub media_id: ::prost::alloc::string::String,
+    /// Lifetime of the download url, capped by the server
+    #[prost(uint32, optional, tag = "2")]
+    pub expires_in_seconds: ::core::option::Option<u32>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -83,6 +142,7 @@ pub struct MediaOrderBy {
 pub struct MediaFilter {
     #[prost(enumeration = "MediaFilterField", tag = "1")]
     pub field: i32,
+    /// Trimmed, filters with empty queries are ignored
     #[prost(string, tag = "2")]
     pub query: ::prost::alloc::string::String,
 }
@@ -97,8 +157,30 @@ pub struct ListMediaRequest {
     >,
     #[prost(message, optional, tag = "3")]
     pub order_by: ::core::option::Option<MediaOrderBy>,
+    /// Deprecated, use `filters`
     #[prost(message, optional, tag = "4")]
     pub filter: ::core::option::Option<MediaFilter>,
+    /// Combined with AND
+    #[prost(message, repeated, tag = "5")]
+    pub filters: ::prost::alloc::vec::Vec<MediaFilter>,
+    /// Shortcut for a filter by offer id
+    #[prost(string, optional, tag = "6")]
+    pub offer_id: ::core::option::Option<::prost::alloc::string::String>,
+    /// Lists media of this user instead of the caller's. Only allowed for
+    /// admins, other callers receive `PERMISSION_DENIED`.
+    #[prost(string, optional, tag = "7")]
+    pub user_id: ::core::option::Option<::prost::alloc::string::String>,
+    /// Includes a presigned download url in each media
+    #[prost(bool, tag = "8")]
+    pub include_urls: bool,
+    /// Signs each media, fails with `FAILED_PRECONDITION` if the server has
+    /// no signing key configured
+    #[prost(bool, tag = "9")]
+    pub sign_response: bool,
+    /// Names of the `MediaResponse` fields to populate, all fields if empty.
+    /// `media_id` is always populated, unknown names are ignored.
+    #[prost(string, repeated, tag = "10")]
+    pub fields: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -109,6 +191,11 @@ pub struct ListMediaResponse {
     pub pagination: ::core::option::Option<
         super::super::pagination::v1::PaginationResponse,
     >,
+    /// Page sizes are capped so pages fit the maximum response size, the
+    /// capped size is returned in `pagination`. Set if media were still left
+    /// out, which only happens for media with unusually long names.
+    #[prost(bool, tag = "3")]
+    pub truncated: bool,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -121,6 +208,9 @@ pub struct ListAccessibleMediaRequest {
     pub order_by: ::core::option::Option<MediaOrderBy>,
     #[prost(message, optional, tag = "4")]
     pub filter: ::core::option::Option<MediaFilter>,
+    /// Shortcut for a filter by offer id
+    #[prost(string, optional, tag = "5")]
+    pub offer_id: ::core::option::Option<::prost::alloc::string::String>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -143,6 +233,18 @@ pub struct UpdateMediaRequest {
     pub file: ::core::option::Option<MediaUpload>,
     #[prost(string, optional, tag = "4")]
     pub file_name: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(map = "string, string", tag = "5")]
+    pub custom_metadata: ::std::collections::HashMap<
+        ::prost::alloc::string::String,
+        ::prost::alloc::string::String,
+    >,
+    /// If set the media is only updated if it belongs to this shop
+    #[prost(string, optional, tag = "6")]
+    pub shop_id: ::core::option::Option<::prost::alloc::string::String>,
+    /// Removes all custom metadata, `custom_metadata` must be empty then. An
+    /// empty `custom_metadata` otherwise leaves it unchanged.
+    #[prost(bool, tag = "7")]
+    pub clear_custom_metadata: bool,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -155,6 +257,9 @@ pub struct UpdateMediaResponse {
 pub struct DeleteMediaRequest {
     #[prost(string, tag = "1")]
     pub media_id: ::prost::alloc::string::String,
+    /// If set the media is only deleted if it belongs to this shop
+    #[prost(string, optional, tag = "2")]
+    pub shop_id: ::core::option::Option<::prost::alloc::string::String>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -174,6 +279,12 @@ pub struct InitiateMultipartUploadResponse {
     pub key: ::prost::alloc::string::String,
     #[prost(string, tag = "2")]
     pub upload_id: ::prost::alloc::string::String,
+    /// Media the upload session is for
+    #[prost(string, tag = "3")]
+    pub media_id: ::prost::alloc::string::String,
+    /// Changes whenever the way `key` is derived changes
+    #[prost(uint32, tag = "4")]
+    pub key_derivation_version: u32,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -251,6 +362,402 @@ pub struct RemoveMediaFromOfferRequest {
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct RemoveMediaFromOfferResponse {}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DownloadMediaRangeRequest {
+    #[prost(string, tag = "1")]
+    pub media_id: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "2")]
+    pub start_byte: u64,
+    #[prost(uint64, tag = "3")]
+    pub end_byte: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DownloadMediaRangeResponse {
+    #[prost(bytes = "vec", tag = "1")]
+    pub data: ::prost::alloc::vec::Vec<u8>,
+    #[prost(string, tag = "2")]
+    pub content_range: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "3")]
+    pub total_size: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RenameMediaRequest {
+    #[prost(string, tag = "1")]
+    pub media_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub name: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RenameMediaResponse {
+    #[prost(message, optional, tag = "1")]
+    pub media: ::core::option::Option<MediaResponse>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DownloadMediaChunksRequest {
+    #[prost(string, tag = "1")]
+    pub media_id: ::prost::alloc::string::String,
+    #[prost(uint64, optional, tag = "2")]
+    pub offset: ::core::option::Option<u64>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DownloadMediaChunksResponse {
+    #[prost(bytes = "vec", tag = "1")]
+    pub chunk: ::prost::alloc::vec::Vec<u8>,
+    #[prost(uint64, tag = "2")]
+    pub offset: u64,
+    #[prost(uint64, optional, tag = "3")]
+    pub total_size: ::core::option::Option<u64>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetShopMediaStatsRequest {
+    #[prost(string, tag = "1")]
+    pub shop_id: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetShopMediaStatsResponse {
+    #[prost(uint64, tag = "1")]
+    pub total_files: u64,
+    #[prost(uint64, tag = "2")]
+    pub total_bytes: u64,
+    #[prost(int64, optional, tag = "3")]
+    pub oldest_created_at: ::core::option::Option<i64>,
+    #[prost(int64, optional, tag = "4")]
+    pub newest_created_at: ::core::option::Option<i64>,
+    #[prost(map = "string, uint64", tag = "5")]
+    pub content_type_counts: ::std::collections::HashMap<
+        ::prost::alloc::string::String,
+        u64,
+    >,
+    /// Number of media to offer relations of the shop
+    #[prost(uint64, tag = "6")]
+    pub total_media_offers: u64,
+    /// Media belonging to more than `max_offers_per_media` offers
+    #[prost(string, repeated, tag = "7")]
+    pub media_over_offer_cap: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// Offers holding more than `max_media_per_offer` media
+    #[prost(string, repeated, tag = "8")]
+    pub offers_over_media_cap: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(uint64, tag = "9")]
+    pub max_offers_per_media: u64,
+    #[prost(uint64, tag = "10")]
+    pub max_media_per_offer: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CheckMediaOwnershipRequest {
+    #[prost(string, repeated, tag = "1")]
+    pub media_ids: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MediaOwnership {
+    #[prost(string, tag = "1")]
+    pub media_id: ::prost::alloc::string::String,
+    #[prost(bool, tag = "2")]
+    pub exists: bool,
+    #[prost(bool, tag = "3")]
+    pub is_owner: bool,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CheckMediaOwnershipResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub ownerships: ::prost::alloc::vec::Vec<MediaOwnership>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MoveMediaBetweenOffersRequest {
+    #[prost(string, tag = "1")]
+    pub media_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub from_offer_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub to_offer_id: ::prost::alloc::string::String,
+    #[prost(int64, optional, tag = "4")]
+    pub ordering: ::core::option::Option<i64>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct OfferMediaItem {
+    #[prost(string, tag = "1")]
+    pub media_id: ::prost::alloc::string::String,
+    #[prost(int64, tag = "2")]
+    pub ordering: i64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MoveMediaBetweenOffersResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub medias: ::prost::alloc::vec::Vec<OfferMediaItem>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MediaRendition {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub format: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub content_type: ::prost::alloc::string::String,
+    #[prost(string, tag = "4")]
+    pub url: ::prost::alloc::string::String,
+    #[prost(int32, tag = "5")]
+    pub width: i32,
+    #[prost(int32, tag = "6")]
+    pub height: i32,
+    #[prost(uint64, tag = "7")]
+    pub size_bytes: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetMediaRenditionsRequest {
+    #[prost(string, tag = "1")]
+    pub media_id: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetMediaRenditionsResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub renditions: ::prost::alloc::vec::Vec<MediaRendition>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BatchRemoveMediaFromOfferRequest {
+    #[prost(string, tag = "1")]
+    pub offer_id: ::prost::alloc::string::String,
+    #[prost(string, repeated, tag = "2")]
+    pub media_ids: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BatchRemoveMediaFromOfferResult {
+    #[prost(string, tag = "1")]
+    pub media_id: ::prost::alloc::string::String,
+    #[prost(bool, tag = "2")]
+    pub removed: bool,
+}
+/// Progress updates are streamed first, the last message holds the results
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BatchRemoveMediaFromOfferResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub results: ::prost::alloc::vec::Vec<BatchRemoveMediaFromOfferResult>,
+    /// Only set on progress updates
+    #[prost(message, optional, tag = "2")]
+    pub progress: ::core::option::Option<OperationProgress>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct OperationProgress {
+    #[prost(uint64, tag = "1")]
+    pub processed: u64,
+    #[prost(uint64, tag = "2")]
+    pub total: u64,
+    #[prost(string, optional, tag = "3")]
+    pub current_item: ::core::option::Option<::prost::alloc::string::String>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AbortMultipartUploadRequest {
+    #[prost(string, tag = "1")]
+    pub media_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub upload_id: ::prost::alloc::string::String,
+    /// Deletes the media if it has no file yet, defaults to true
+    #[prost(bool, optional, tag = "3")]
+    pub delete_on_abort: ::core::option::Option<bool>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AbortMultipartUploadResponse {
+    #[prost(bool, tag = "1")]
+    pub media_deleted: bool,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MigrateStorageKeysRequest {
+    #[prost(uint32, optional, tag = "1")]
+    pub limit: ::core::option::Option<u32>,
+}
+/// Progress updates are streamed first, the last message holds the counts
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MigrateStorageKeysResponse {
+    #[prost(uint32, tag = "1")]
+    pub migrated: u32,
+    #[prost(uint32, tag = "2")]
+    pub failed: u32,
+    /// Only set on progress updates
+    #[prost(message, optional, tag = "3")]
+    pub progress: ::core::option::Option<OperationProgress>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ResolvePermalinkRequest {
+    #[prost(string, tag = "1")]
+    pub permalink: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ResolvePermalinkResponse {
+    #[prost(string, tag = "1")]
+    pub media_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub download_url: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DetachMediaFromAllOffersRequest {
+    #[prost(string, tag = "1")]
+    pub media_id: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DetachMediaFromAllOffersResponse {
+    #[prost(uint64, tag = "1")]
+    pub removed_count: u64,
+    /// Offers the media was removed from
+    #[prost(string, repeated, tag = "2")]
+    pub offer_ids: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SimilarMedia {
+    #[prost(message, optional, tag = "1")]
+    pub media: ::core::option::Option<MediaResponse>,
+    /// Hamming distance between the perceptual hashes, 0 to 64
+    #[prost(uint32, tag = "2")]
+    pub distance: u32,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FindSimilarMediaRequest {
+    #[prost(string, tag = "1")]
+    pub media_id: ::prost::alloc::string::String,
+    /// Defaults to the distance configured on the server, at most 64
+    #[prost(uint32, optional, tag = "2")]
+    pub max_distance: ::core::option::Option<u32>,
+    /// Most similar media returned, defaults to 20, at most 100
+    #[prost(uint32, optional, tag = "3")]
+    pub limit: ::core::option::Option<u32>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FindSimilarMediaResponse {
+    /// Closest first, excludes the requested media
+    #[prost(message, repeated, tag = "1")]
+    pub similar_medias: ::prost::alloc::vec::Vec<SimilarMedia>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExportBoothManifestRequest {
+    #[prost(string, tag = "1")]
+    pub shop_id: ::prost::alloc::string::String,
+}
+/// One media of the exported shop, or a progress update
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExportBoothManifestResponse {
+    #[prost(string, tag = "1")]
+    pub media_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub shop_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(string, tag = "4")]
+    pub file_name: ::prost::alloc::string::String,
+    #[prost(string, optional, tag = "5")]
+    pub content_type: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(uint64, tag = "6")]
+    pub size_bytes: u64,
+    #[prost(int64, tag = "7")]
+    pub created_at: i64,
+    #[prost(int64, tag = "8")]
+    pub updated_at: i64,
+    #[prost(string, repeated, tag = "9")]
+    pub offer_ids: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(map = "string, string", tag = "10")]
+    pub custom_metadata: ::std::collections::HashMap<
+        ::prost::alloc::string::String,
+        ::prost::alloc::string::String,
+    >,
+    /// Presigned, not set for media that cannot be downloaded yet
+    #[prost(string, optional, tag = "11")]
+    pub download_url: ::core::option::Option<::prost::alloc::string::String>,
+    /// ETag of the stored object, also sent with the download. The hex MD5
+    /// of the file for single-shot uploads without KMS encryption.
+    #[prost(string, optional, tag = "12")]
+    pub etag: ::core::option::Option<::prost::alloc::string::String>,
+    /// Hex SHA-256 of the file, only known for content addressed objects
+    #[prost(string, optional, tag = "13")]
+    pub sha256: ::core::option::Option<::prost::alloc::string::String>,
+    /// Only set on progress updates, which carry no media
+    #[prost(message, optional, tag = "14")]
+    pub progress: ::core::option::Option<OperationProgress>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetUploadStatusRequest {
+    /// Exactly one of `media_id` and `upload_id` must be set. Upload ids of
+    /// multipart uploads are known until the upload is completed or aborted.
+    #[prost(string, optional, tag = "1")]
+    pub media_id: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "2")]
+    pub upload_id: ::core::option::Option<::prost::alloc::string::String>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetUploadStatusResponse {
+    #[prost(string, tag = "1")]
+    pub media_id: ::prost::alloc::string::String,
+    #[prost(enumeration = "UploadState", tag = "2")]
+    pub state: i32,
+    /// Why the media failed, or the latest failure of a multipart upload in
+    /// progress, which can be retried
+    #[prost(string, optional, tag = "3")]
+    pub error_reason: ::core::option::Option<::prost::alloc::string::String>,
+    /// Multipart upload in progress
+    #[prost(string, optional, tag = "4")]
+    pub upload_id: ::core::option::Option<::prost::alloc::string::String>,
+    /// Failures of the multipart upload in progress so far
+    #[prost(uint32, tag = "5")]
+    pub error_count: u32,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ImportMediaFromUrlRequest {
+    #[prost(string, tag = "1")]
+    pub shop_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub name: ::prost::alloc::string::String,
+    /// http or https url the file is fetched from by the server, it must not
+    /// point to internal networks
+    #[prost(string, tag = "3")]
+    pub url: ::prost::alloc::string::String,
+    #[prost(string, tag = "4")]
+    pub file_name: ::prost::alloc::string::String,
+    #[prost(string, optional, tag = "5")]
+    pub offer_id: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(map = "string, string", tag = "6")]
+    pub custom_metadata: ::std::collections::HashMap<
+        ::prost::alloc::string::String,
+        ::prost::alloc::string::String,
+    >,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ImportMediaFromUrlResponse {
+    #[prost(message, optional, tag = "1")]
+    pub media: ::core::option::Option<MediaResponse>,
+}
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
 #[repr(i32)]
 pub enum MediaOrderByField {
@@ -258,6 +765,7 @@ pub enum MediaOrderByField {
     CreatedAt = 1,
     UpdatedAt = 2,
     Ordering = 3,
+    Name = 4,
 }
 impl MediaOrderByField {
     /// String value of the enum field names used in the ProtoBuf definition.
@@ -270,6 +778,7 @@ impl MediaOrderByField {
             MediaOrderByField::CreatedAt => "MEDIA_ORDER_BY_FIELD_CREATED_AT",
             MediaOrderByField::UpdatedAt => "MEDIA_ORDER_BY_FIELD_UPDATED_AT",
             MediaOrderByField::Ordering => "MEDIA_ORDER_BY_FIELD_ORDERING",
+            MediaOrderByField::Name => "MEDIA_ORDER_BY_FIELD_NAME",
         }
     }
     /// Creates an enum from field names used in the ProtoBuf definition.
@@ -279,6 +788,7 @@ impl MediaOrderByField {
             "MEDIA_ORDER_BY_FIELD_CREATED_AT" => Some(Self::CreatedAt),
             "MEDIA_ORDER_BY_FIELD_UPDATED_AT" => Some(Self::UpdatedAt),
             "MEDIA_ORDER_BY_FIELD_ORDERING" => Some(Self::Ordering),
+            "MEDIA_ORDER_BY_FIELD_NAME" => Some(Self::Name),
             _ => None,
         }
     }
@@ -287,8 +797,13 @@ impl MediaOrderByField {
 #[repr(i32)]
 pub enum MediaFilterField {
     Unspecified = 0,
+    /// Names containing the query, ignoring case. `%` and `_` match
+    /// literally.
     Name = 1,
     OfferId = 2,
+    ContentType = 3,
+    FileExtension = 5,
+    MetadataKey = 6,
 }
 impl MediaFilterField {
     /// String value of the enum field names used in the ProtoBuf definition.
@@ -300,6 +815,9 @@ impl MediaFilterField {
             MediaFilterField::Unspecified => "MEDIA_FILTER_FIELD_UNSPECIFIED",
             MediaFilterField::Name => "MEDIA_FILTER_FIELD_NAME",
             MediaFilterField::OfferId => "MEDIA_FILTER_FIELD_OFFER_ID",
+            MediaFilterField::ContentType => "MEDIA_FILTER_FIELD_CONTENT_TYPE",
+            MediaFilterField::FileExtension => "MEDIA_FILTER_FIELD_FILE_EXTENSION",
+            MediaFilterField::MetadataKey => "MEDIA_FILTER_FIELD_METADATA_KEY",
         }
     }
     /// Creates an enum from field names used in the ProtoBuf definition.
@@ -308,37 +826,169 @@ impl MediaFilterField {
             "MEDIA_FILTER_FIELD_UNSPECIFIED" => Some(Self::Unspecified),
             "MEDIA_FILTER_FIELD_NAME" => Some(Self::Name),
             "MEDIA_FILTER_FIELD_OFFER_ID" => Some(Self::OfferId),
+            "MEDIA_FILTER_FIELD_CONTENT_TYPE" => Some(Self::ContentType),
+            "MEDIA_FILTER_FIELD_FILE_EXTENSION" => Some(Self::FileExtension),
+            "MEDIA_FILTER_FIELD_METADATA_KEY" => Some(Self::MetadataKey),
             _ => None,
         }
     }
 }
-/// Generated server implementations.
-pub mod media_service_server {
-    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
-    use tonic::codegen::*;
-    /// Generated trait containing gRPC methods that should be implemented for use with MediaServiceServer.
-    #[async_trait]
-    pub trait MediaService: Send + Sync + 'static {
-        async fn create_media(
-            &self,
-            request: tonic::Request<super::CreateMediaRequest>,
-        ) -> std::result::Result<
-            tonic::Response<super::CreateMediaResponse>,
-            tonic::Status,
-        >;
-        async fn get_media(
-            &self,
-            request: tonic::Request<super::GetMediaRequest>,
-        ) -> std::result::Result<
-            tonic::Response<super::GetMediaResponse>,
-            tonic::Status,
-        >;
-        async fn download_media(
-            &self,
-            request: tonic::Request<super::DownloadMediaRequest>,
-        ) -> std::result::Result<
-            tonic::Response<super::DownloadMediaResponse>,
-            tonic::Status,
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum MediaProcessingState {
+    Unspecified = 0,
+    Pending = 1,
+    Ready = 2,
+    Failed = 3,
+    Quarantined = 4,
+}
+impl MediaProcessingState {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            MediaProcessingState::Unspecified => "MEDIA_PROCESSING_STATE_UNSPECIFIED",
+            MediaProcessingState::Pending => "MEDIA_PROCESSING_STATE_PENDING",
+            MediaProcessingState::Ready => "MEDIA_PROCESSING_STATE_READY",
+            MediaProcessingState::Failed => "MEDIA_PROCESSING_STATE_FAILED",
+            MediaProcessingState::Quarantined => "MEDIA_PROCESSING_STATE_QUARANTINED",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "MEDIA_PROCESSING_STATE_UNSPECIFIED" => Some(Self::Unspecified),
+            "MEDIA_PROCESSING_STATE_PENDING" => Some(Self::Pending),
+            "MEDIA_PROCESSING_STATE_READY" => Some(Self::Ready),
+            "MEDIA_PROCESSING_STATE_FAILED" => Some(Self::Failed),
+            "MEDIA_PROCESSING_STATE_QUARANTINED" => Some(Self::Quarantined),
+            _ => None,
+        }
+    }
+}
+/// Progress of the upload of a media file, across upload modes
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum UploadState {
+    Unspecified = 0,
+    /// No file uploaded yet, or a multipart upload without parts
+    PendingUpload = 1,
+    /// Parts of a multipart upload were received, it is not completed yet
+    Uploading = 2,
+    /// The file is stored and being scanned
+    Processing = 3,
+    Ready = 4,
+    Failed = 5,
+}
+impl UploadState {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            UploadState::Unspecified => "UPLOAD_STATE_UNSPECIFIED",
+            UploadState::PendingUpload => "UPLOAD_STATE_PENDING_UPLOAD",
+            UploadState::Uploading => "UPLOAD_STATE_UPLOADING",
+            UploadState::Processing => "UPLOAD_STATE_PROCESSING",
+            UploadState::Ready => "UPLOAD_STATE_READY",
+            UploadState::Failed => "UPLOAD_STATE_FAILED",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "UPLOAD_STATE_UNSPECIFIED" => Some(Self::Unspecified),
+            "UPLOAD_STATE_PENDING_UPLOAD" => Some(Self::PendingUpload),
+            "UPLOAD_STATE_UPLOADING" => Some(Self::Uploading),
+            "UPLOAD_STATE_PROCESSING" => Some(Self::Processing),
+            "UPLOAD_STATE_READY" => Some(Self::Ready),
+            "UPLOAD_STATE_FAILED" => Some(Self::Failed),
+            _ => None,
+        }
+    }
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum SubscriptionStatus {
+    Unspecified = 0,
+    Active = 1,
+    PastDue = 2,
+    Canceled = 3,
+    Trialing = 4,
+    Paused = 5,
+    Incomplete = 6,
+    IncompleteExpired = 7,
+    Unpaid = 8,
+    Revoked = 9,
+}
+impl SubscriptionStatus {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            SubscriptionStatus::Unspecified => "SUBSCRIPTION_STATUS_UNSPECIFIED",
+            SubscriptionStatus::Active => "SUBSCRIPTION_STATUS_ACTIVE",
+            SubscriptionStatus::PastDue => "SUBSCRIPTION_STATUS_PAST_DUE",
+            SubscriptionStatus::Canceled => "SUBSCRIPTION_STATUS_CANCELED",
+            SubscriptionStatus::Trialing => "SUBSCRIPTION_STATUS_TRIALING",
+            SubscriptionStatus::Paused => "SUBSCRIPTION_STATUS_PAUSED",
+            SubscriptionStatus::Incomplete => "SUBSCRIPTION_STATUS_INCOMPLETE",
+            SubscriptionStatus::IncompleteExpired => {
+                "SUBSCRIPTION_STATUS_INCOMPLETE_EXPIRED"
+            }
+            SubscriptionStatus::Unpaid => "SUBSCRIPTION_STATUS_UNPAID",
+            SubscriptionStatus::Revoked => "SUBSCRIPTION_STATUS_REVOKED",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "SUBSCRIPTION_STATUS_UNSPECIFIED" => Some(Self::Unspecified),
+            "SUBSCRIPTION_STATUS_ACTIVE" => Some(Self::Active),
+            "SUBSCRIPTION_STATUS_PAST_DUE" => Some(Self::PastDue),
+            "SUBSCRIPTION_STATUS_CANCELED" => Some(Self::Canceled),
+            "SUBSCRIPTION_STATUS_TRIALING" => Some(Self::Trialing),
+            "SUBSCRIPTION_STATUS_PAUSED" => Some(Self::Paused),
+            "SUBSCRIPTION_STATUS_INCOMPLETE" => Some(Self::Incomplete),
+            "SUBSCRIPTION_STATUS_INCOMPLETE_EXPIRED" => Some(Self::IncompleteExpired),
+            "SUBSCRIPTION_STATUS_UNPAID" => Some(Self::Unpaid),
+            "SUBSCRIPTION_STATUS_REVOKED" => Some(Self::Revoked),
+            _ => None,
+        }
+    }
+}
+/// Generated server implementations.
+pub mod media_service_server {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::*;
+    /// Generated trait containing gRPC methods that should be implemented for use with MediaServiceServer.
+    #[async_trait]
+    pub trait MediaService: Send + Sync + 'static {
+        async fn create_media(
+            &self,
+            request: tonic::Request<super::CreateMediaRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::CreateMediaResponse>,
+            tonic::Status,
+        >;
+        async fn get_media(
+            &self,
+            request: tonic::Request<super::GetMediaRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetMediaResponse>,
+            tonic::Status,
+        >;
+        async fn download_media(
+            &self,
+            request: tonic::Request<super::DownloadMediaRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::DownloadMediaResponse>,
+            tonic::Status,
         >;
         async fn list_media(
             &self,
@@ -410,6 +1060,154 @@ pub mod media_service_server {
             tonic::Response<super::RemoveMediaFromOfferResponse>,
             tonic::Status,
         >;
+        async fn download_media_range(
+            &self,
+            request: tonic::Request<super::DownloadMediaRangeRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::DownloadMediaRangeResponse>,
+            tonic::Status,
+        >;
+        async fn rename_media(
+            &self,
+            request: tonic::Request<super::RenameMediaRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::RenameMediaResponse>,
+            tonic::Status,
+        >;
+        /// Server streaming response type for the DownloadMediaChunks method.
+        type DownloadMediaChunksStream: tonic::codegen::tokio_stream::Stream<
+                Item = std::result::Result<
+                    super::DownloadMediaChunksResponse,
+                    tonic::Status,
+                >,
+            >
+            + Send
+            + 'static;
+        async fn download_media_chunks(
+            &self,
+            request: tonic::Request<super::DownloadMediaChunksRequest>,
+        ) -> std::result::Result<
+            tonic::Response<Self::DownloadMediaChunksStream>,
+            tonic::Status,
+        >;
+        async fn get_shop_media_stats(
+            &self,
+            request: tonic::Request<super::GetShopMediaStatsRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetShopMediaStatsResponse>,
+            tonic::Status,
+        >;
+        async fn check_media_ownership(
+            &self,
+            request: tonic::Request<super::CheckMediaOwnershipRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::CheckMediaOwnershipResponse>,
+            tonic::Status,
+        >;
+        async fn move_media_between_offers(
+            &self,
+            request: tonic::Request<super::MoveMediaBetweenOffersRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::MoveMediaBetweenOffersResponse>,
+            tonic::Status,
+        >;
+        async fn get_media_renditions(
+            &self,
+            request: tonic::Request<super::GetMediaRenditionsRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetMediaRenditionsResponse>,
+            tonic::Status,
+        >;
+        /// Server streaming response type for the BatchRemoveMediaFromOffer method.
+        type BatchRemoveMediaFromOfferStream: tonic::codegen::tokio_stream::Stream<
+                Item = std::result::Result<
+                    super::BatchRemoveMediaFromOfferResponse,
+                    tonic::Status,
+                >,
+            >
+            + Send
+            + 'static;
+        async fn batch_remove_media_from_offer(
+            &self,
+            request: tonic::Request<super::BatchRemoveMediaFromOfferRequest>,
+        ) -> std::result::Result<
+            tonic::Response<Self::BatchRemoveMediaFromOfferStream>,
+            tonic::Status,
+        >;
+        async fn abort_multipart_upload(
+            &self,
+            request: tonic::Request<super::AbortMultipartUploadRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::AbortMultipartUploadResponse>,
+            tonic::Status,
+        >;
+        /// Server streaming response type for the MigrateStorageKeys method.
+        type MigrateStorageKeysStream: tonic::codegen::tokio_stream::Stream<
+                Item = std::result::Result<
+                    super::MigrateStorageKeysResponse,
+                    tonic::Status,
+                >,
+            >
+            + Send
+            + 'static;
+        async fn migrate_storage_keys(
+            &self,
+            request: tonic::Request<super::MigrateStorageKeysRequest>,
+        ) -> std::result::Result<
+            tonic::Response<Self::MigrateStorageKeysStream>,
+            tonic::Status,
+        >;
+        async fn resolve_permalink(
+            &self,
+            request: tonic::Request<super::ResolvePermalinkRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::ResolvePermalinkResponse>,
+            tonic::Status,
+        >;
+        async fn detach_media_from_all_offers(
+            &self,
+            request: tonic::Request<super::DetachMediaFromAllOffersRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::DetachMediaFromAllOffersResponse>,
+            tonic::Status,
+        >;
+        async fn find_similar_media(
+            &self,
+            request: tonic::Request<super::FindSimilarMediaRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::FindSimilarMediaResponse>,
+            tonic::Status,
+        >;
+        /// Server streaming response type for the ExportBoothManifest method.
+        type ExportBoothManifestStream: tonic::codegen::tokio_stream::Stream<
+                Item = std::result::Result<
+                    super::ExportBoothManifestResponse,
+                    tonic::Status,
+                >,
+            >
+            + Send
+            + 'static;
+        async fn export_booth_manifest(
+            &self,
+            request: tonic::Request<super::ExportBoothManifestRequest>,
+        ) -> std::result::Result<
+            tonic::Response<Self::ExportBoothManifestStream>,
+            tonic::Status,
+        >;
+        async fn get_upload_status(
+            &self,
+            request: tonic::Request<super::GetUploadStatusRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetUploadStatusResponse>,
+            tonic::Status,
+        >;
+        async fn import_media_from_url(
+            &self,
+            request: tonic::Request<super::ImportMediaFromUrlRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::ImportMediaFromUrlResponse>,
+            tonic::Status,
+        >;
     }
     #[derive(Debug)]
     pub struct MediaServiceServer<T: MediaService> {
@@ -813,30 +1611,808 @@ pub mod media_service_server {
                     };
                     Box::pin(fut)
                 }
-                "/sited_io.media.v1.MediaService/InitiateMultipartUpload" => {
+                "/sited_io.media.v1.MediaService/InitiateMultipartUpload" => {
+                    #[allow(non_camel_case_types)]
+                    struct InitiateMultipartUploadSvc<T: MediaService>(pub Arc<T>);
+                    impl<
+                        T: MediaService,
+                    > tonic::server::UnaryService<super::InitiateMultipartUploadRequest>
+                    for InitiateMultipartUploadSvc<T> {
+                        type Response = super::InitiateMultipartUploadResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<
+                                super::InitiateMultipartUploadRequest,
+                            >,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as MediaService>::initiate_multipart_upload(
+                                        &inner,
+                                        request,
+                                    )
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = InitiateMultipartUploadSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/sited_io.media.v1.MediaService/PutMultipartChunk" => {
+                    #[allow(non_camel_case_types)]
+                    struct PutMultipartChunkSvc<T: MediaService>(pub Arc<T>);
+                    impl<
+                        T: MediaService,
+                    > tonic::server::UnaryService<super::PutMultipartChunkRequest>
+                    for PutMultipartChunkSvc<T> {
+                        type Response = super::PutMultipartChunkResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::PutMultipartChunkRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as MediaService>::put_multipart_chunk(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = PutMultipartChunkSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/sited_io.media.v1.MediaService/CompleteMultipartUpload" => {
+                    #[allow(non_camel_case_types)]
+                    struct CompleteMultipartUploadSvc<T: MediaService>(pub Arc<T>);
+                    impl<
+                        T: MediaService,
+                    > tonic::server::UnaryService<super::CompleteMultipartUploadRequest>
+                    for CompleteMultipartUploadSvc<T> {
+                        type Response = super::CompleteMultipartUploadResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<
+                                super::CompleteMultipartUploadRequest,
+                            >,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as MediaService>::complete_multipart_upload(
+                                        &inner,
+                                        request,
+                                    )
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = CompleteMultipartUploadSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/sited_io.media.v1.MediaService/AddMediaToOffer" => {
+                    #[allow(non_camel_case_types)]
+                    struct AddMediaToOfferSvc<T: MediaService>(pub Arc<T>);
+                    impl<
+                        T: MediaService,
+                    > tonic::server::UnaryService<super::AddMediaToOfferRequest>
+                    for AddMediaToOfferSvc<T> {
+                        type Response = super::AddMediaToOfferResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::AddMediaToOfferRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as MediaService>::add_media_to_offer(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = AddMediaToOfferSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/sited_io.media.v1.MediaService/UpdateMediaOfferOrdering" => {
+                    #[allow(non_camel_case_types)]
+                    struct UpdateMediaOfferOrderingSvc<T: MediaService>(pub Arc<T>);
+                    impl<
+                        T: MediaService,
+                    > tonic::server::UnaryService<super::UpdateMediaOfferOrderingRequest>
+                    for UpdateMediaOfferOrderingSvc<T> {
+                        type Response = super::UpdateMediaOfferOrderingResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<
+                                super::UpdateMediaOfferOrderingRequest,
+                            >,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as MediaService>::update_media_offer_ordering(
+                                        &inner,
+                                        request,
+                                    )
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = UpdateMediaOfferOrderingSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/sited_io.media.v1.MediaService/RemoveMediaFromOffer" => {
+                    #[allow(non_camel_case_types)]
+                    struct RemoveMediaFromOfferSvc<T: MediaService>(pub Arc<T>);
+                    impl<
+                        T: MediaService,
+                    > tonic::server::UnaryService<super::RemoveMediaFromOfferRequest>
+                    for RemoveMediaFromOfferSvc<T> {
+                        type Response = super::RemoveMediaFromOfferResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::RemoveMediaFromOfferRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as MediaService>::remove_media_from_offer(
+                                        &inner,
+                                        request,
+                                    )
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = RemoveMediaFromOfferSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/sited_io.media.v1.MediaService/DownloadMediaRange" => {
+                    #[allow(non_camel_case_types)]
+                    struct DownloadMediaRangeSvc<T: MediaService>(pub Arc<T>);
+                    impl<
+                        T: MediaService,
+                    > tonic::server::UnaryService<super::DownloadMediaRangeRequest>
+                    for DownloadMediaRangeSvc<T> {
+                        type Response = super::DownloadMediaRangeResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::DownloadMediaRangeRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as MediaService>::download_media_range(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = DownloadMediaRangeSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/sited_io.media.v1.MediaService/RenameMedia" => {
+                    #[allow(non_camel_case_types)]
+                    struct RenameMediaSvc<T: MediaService>(pub Arc<T>);
+                    impl<
+                        T: MediaService,
+                    > tonic::server::UnaryService<super::RenameMediaRequest>
+                    for RenameMediaSvc<T> {
+                        type Response = super::RenameMediaResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::RenameMediaRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as MediaService>::rename_media(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = RenameMediaSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/sited_io.media.v1.MediaService/DownloadMediaChunks" => {
+                    #[allow(non_camel_case_types)]
+                    struct DownloadMediaChunksSvc<T: MediaService>(pub Arc<T>);
+                    impl<
+                        T: MediaService,
+                    > tonic::server::ServerStreamingService<
+                        super::DownloadMediaChunksRequest,
+                    > for DownloadMediaChunksSvc<T> {
+                        type Response = super::DownloadMediaChunksResponse;
+                        type ResponseStream = T::DownloadMediaChunksStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::DownloadMediaChunksRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as MediaService>::download_media_chunks(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = DownloadMediaChunksSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/sited_io.media.v1.MediaService/GetShopMediaStats" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetShopMediaStatsSvc<T: MediaService>(pub Arc<T>);
+                    impl<
+                        T: MediaService,
+                    > tonic::server::UnaryService<super::GetShopMediaStatsRequest>
+                    for GetShopMediaStatsSvc<T> {
+                        type Response = super::GetShopMediaStatsResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetShopMediaStatsRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as MediaService>::get_shop_media_stats(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetShopMediaStatsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/sited_io.media.v1.MediaService/CheckMediaOwnership" => {
+                    #[allow(non_camel_case_types)]
+                    struct CheckMediaOwnershipSvc<T: MediaService>(pub Arc<T>);
+                    impl<
+                        T: MediaService,
+                    > tonic::server::UnaryService<super::CheckMediaOwnershipRequest>
+                    for CheckMediaOwnershipSvc<T> {
+                        type Response = super::CheckMediaOwnershipResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::CheckMediaOwnershipRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as MediaService>::check_media_ownership(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = CheckMediaOwnershipSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/sited_io.media.v1.MediaService/MoveMediaBetweenOffers" => {
+                    #[allow(non_camel_case_types)]
+                    struct MoveMediaBetweenOffersSvc<T: MediaService>(pub Arc<T>);
+                    impl<
+                        T: MediaService,
+                    > tonic::server::UnaryService<super::MoveMediaBetweenOffersRequest>
+                    for MoveMediaBetweenOffersSvc<T> {
+                        type Response = super::MoveMediaBetweenOffersResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::MoveMediaBetweenOffersRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as MediaService>::move_media_between_offers(
+                                        &inner,
+                                        request,
+                                    )
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = MoveMediaBetweenOffersSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/sited_io.media.v1.MediaService/GetMediaRenditions" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetMediaRenditionsSvc<T: MediaService>(pub Arc<T>);
+                    impl<
+                        T: MediaService,
+                    > tonic::server::UnaryService<super::GetMediaRenditionsRequest>
+                    for GetMediaRenditionsSvc<T> {
+                        type Response = super::GetMediaRenditionsResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetMediaRenditionsRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as MediaService>::get_media_renditions(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetMediaRenditionsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/sited_io.media.v1.MediaService/BatchRemoveMediaFromOffer" => {
+                    #[allow(non_camel_case_types)]
+                    struct BatchRemoveMediaFromOfferSvc<T: MediaService>(pub Arc<T>);
+                    impl<
+                        T: MediaService,
+                    > tonic::server::ServerStreamingService<
+                        super::BatchRemoveMediaFromOfferRequest,
+                    > for BatchRemoveMediaFromOfferSvc<T> {
+                        type Response = super::BatchRemoveMediaFromOfferResponse;
+                        type ResponseStream = T::BatchRemoveMediaFromOfferStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<
+                                super::BatchRemoveMediaFromOfferRequest,
+                            >,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as MediaService>::batch_remove_media_from_offer(
+                                        &inner,
+                                        request,
+                                    )
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = BatchRemoveMediaFromOfferSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/sited_io.media.v1.MediaService/AbortMultipartUpload" => {
+                    #[allow(non_camel_case_types)]
+                    struct AbortMultipartUploadSvc<T: MediaService>(pub Arc<T>);
+                    impl<
+                        T: MediaService,
+                    > tonic::server::UnaryService<super::AbortMultipartUploadRequest>
+                    for AbortMultipartUploadSvc<T> {
+                        type Response = super::AbortMultipartUploadResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::AbortMultipartUploadRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as MediaService>::abort_multipart_upload(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = AbortMultipartUploadSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/sited_io.media.v1.MediaService/MigrateStorageKeys" => {
+                    #[allow(non_camel_case_types)]
+                    struct MigrateStorageKeysSvc<T: MediaService>(pub Arc<T>);
+                    impl<
+                        T: MediaService,
+                    > tonic::server::ServerStreamingService<
+                        super::MigrateStorageKeysRequest,
+                    > for MigrateStorageKeysSvc<T> {
+                        type Response = super::MigrateStorageKeysResponse;
+                        type ResponseStream = T::MigrateStorageKeysStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::MigrateStorageKeysRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as MediaService>::migrate_storage_keys(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = MigrateStorageKeysSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/sited_io.media.v1.MediaService/ResolvePermalink" => {
                     #[allow(non_camel_case_types)]
-                    struct InitiateMultipartUploadSvc<T: MediaService>(pub Arc<T>);
+                    struct ResolvePermalinkSvc<T: MediaService>(pub Arc<T>);
                     impl<
                         T: MediaService,
-                    > tonic::server::UnaryService<super::InitiateMultipartUploadRequest>
-                    for InitiateMultipartUploadSvc<T> {
-                        type Response = super::InitiateMultipartUploadResponse;
+                    > tonic::server::UnaryService<super::ResolvePermalinkRequest>
+                    for ResolvePermalinkSvc<T> {
+                        type Response = super::ResolvePermalinkResponse;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<
-                                super::InitiateMultipartUploadRequest,
-                            >,
+                            request: tonic::Request<super::ResolvePermalinkRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as MediaService>::initiate_multipart_upload(
-                                        &inner,
-                                        request,
-                                    )
+                                <T as MediaService>::resolve_permalink(&inner, request)
                                     .await
                             };
                             Box::pin(fut)
@@ -849,7 +2425,7 @@ pub mod media_service_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = InitiateMultipartUploadSvc(inner);
+                        let method = ResolvePermalinkSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -865,25 +2441,30 @@ pub mod media_service_server {
                     };
                     Box::pin(fut)
                 }
-                "/sited_io.media.v1.MediaService/PutMultipartChunk" => {
+                "/sited_io.media.v1.MediaService/DetachMediaFromAllOffers" => {
                     #[allow(non_camel_case_types)]
-                    struct PutMultipartChunkSvc<T: MediaService>(pub Arc<T>);
+                    struct DetachMediaFromAllOffersSvc<T: MediaService>(pub Arc<T>);
                     impl<
                         T: MediaService,
-                    > tonic::server::UnaryService<super::PutMultipartChunkRequest>
-                    for PutMultipartChunkSvc<T> {
-                        type Response = super::PutMultipartChunkResponse;
+                    > tonic::server::UnaryService<super::DetachMediaFromAllOffersRequest>
+                    for DetachMediaFromAllOffersSvc<T> {
+                        type Response = super::DetachMediaFromAllOffersResponse;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::PutMultipartChunkRequest>,
+                            request: tonic::Request<
+                                super::DetachMediaFromAllOffersRequest,
+                            >,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as MediaService>::put_multipart_chunk(&inner, request)
+                                <T as MediaService>::detach_media_from_all_offers(
+                                        &inner,
+                                        request,
+                                    )
                                     .await
                             };
                             Box::pin(fut)
@@ -896,7 +2477,7 @@ pub mod media_service_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = PutMultipartChunkSvc(inner);
+                        let method = DetachMediaFromAllOffersSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -912,30 +2493,25 @@ pub mod media_service_server {
                     };
                     Box::pin(fut)
                 }
-                "/sited_io.media.v1.MediaService/CompleteMultipartUpload" => {
+                "/sited_io.media.v1.MediaService/FindSimilarMedia" => {
                     #[allow(non_camel_case_types)]
-                    struct CompleteMultipartUploadSvc<T: MediaService>(pub Arc<T>);
+                    struct FindSimilarMediaSvc<T: MediaService>(pub Arc<T>);
                     impl<
                         T: MediaService,
-                    > tonic::server::UnaryService<super::CompleteMultipartUploadRequest>
-                    for CompleteMultipartUploadSvc<T> {
-                        type Response = super::CompleteMultipartUploadResponse;
+                    > tonic::server::UnaryService<super::FindSimilarMediaRequest>
+                    for FindSimilarMediaSvc<T> {
+                        type Response = super::FindSimilarMediaResponse;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<
-                                super::CompleteMultipartUploadRequest,
-                            >,
+                            request: tonic::Request<super::FindSimilarMediaRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as MediaService>::complete_multipart_upload(
-                                        &inner,
-                                        request,
-                                    )
+                                <T as MediaService>::find_similar_media(&inner, request)
                                     .await
                             };
                             Box::pin(fut)
@@ -948,7 +2524,7 @@ pub mod media_service_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = CompleteMultipartUploadSvc(inner);
+                        let method = FindSimilarMediaSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -964,25 +2540,27 @@ pub mod media_service_server {
                     };
                     Box::pin(fut)
                 }
-                "/sited_io.media.v1.MediaService/AddMediaToOffer" => {
+                "/sited_io.media.v1.MediaService/ExportBoothManifest" => {
                     #[allow(non_camel_case_types)]
-                    struct AddMediaToOfferSvc<T: MediaService>(pub Arc<T>);
+                    struct ExportBoothManifestSvc<T: MediaService>(pub Arc<T>);
                     impl<
                         T: MediaService,
-                    > tonic::server::UnaryService<super::AddMediaToOfferRequest>
-                    for AddMediaToOfferSvc<T> {
-                        type Response = super::AddMediaToOfferResponse;
+                    > tonic::server::ServerStreamingService<
+                        super::ExportBoothManifestRequest,
+                    > for ExportBoothManifestSvc<T> {
+                        type Response = super::ExportBoothManifestResponse;
+                        type ResponseStream = T::ExportBoothManifestStream;
                         type Future = BoxFuture<
-                            tonic::Response<Self::Response>,
+                            tonic::Response<Self::ResponseStream>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::AddMediaToOfferRequest>,
+                            request: tonic::Request<super::ExportBoothManifestRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as MediaService>::add_media_to_offer(&inner, request)
+                                <T as MediaService>::export_booth_manifest(&inner, request)
                                     .await
                             };
                             Box::pin(fut)
@@ -995,7 +2573,7 @@ pub mod media_service_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = AddMediaToOfferSvc(inner);
+                        let method = ExportBoothManifestSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -1006,35 +2584,30 @@ pub mod media_service_server {
                                 max_decoding_message_size,
                                 max_encoding_message_size,
                             );
-                        let res = grpc.unary(method, req).await;
+                        let res = grpc.server_streaming(method, req).await;
                         Ok(res)
                     };
                     Box::pin(fut)
                 }
-                "/sited_io.media.v1.MediaService/UpdateMediaOfferOrdering" => {
+                "/sited_io.media.v1.MediaService/GetUploadStatus" => {
                     #[allow(non_camel_case_types)]
-                    struct UpdateMediaOfferOrderingSvc<T: MediaService>(pub Arc<T>);
+                    struct GetUploadStatusSvc<T: MediaService>(pub Arc<T>);
                     impl<
                         T: MediaService,
-                    > tonic::server::UnaryService<super::UpdateMediaOfferOrderingRequest>
-                    for UpdateMediaOfferOrderingSvc<T> {
-                        type Response = super::UpdateMediaOfferOrderingResponse;
+                    > tonic::server::UnaryService<super::GetUploadStatusRequest>
+                    for GetUploadStatusSvc<T> {
+                        type Response = super::GetUploadStatusResponse;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<
-                                super::UpdateMediaOfferOrderingRequest,
-                            >,
+                            request: tonic::Request<super::GetUploadStatusRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as MediaService>::update_media_offer_ordering(
-                                        &inner,
-                                        request,
-                                    )
+                                <T as MediaService>::get_upload_status(&inner, request)
                                     .await
                             };
                             Box::pin(fut)
@@ -1047,7 +2620,7 @@ pub mod media_service_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = UpdateMediaOfferOrderingSvc(inner);
+                        let method = GetUploadStatusSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -1063,28 +2636,25 @@ pub mod media_service_server {
                     };
                     Box::pin(fut)
                 }
-                "/sited_io.media.v1.MediaService/RemoveMediaFromOffer" => {
+                "/sited_io.media.v1.MediaService/ImportMediaFromUrl" => {
                     #[allow(non_camel_case_types)]
-                    struct RemoveMediaFromOfferSvc<T: MediaService>(pub Arc<T>);
+                    struct ImportMediaFromUrlSvc<T: MediaService>(pub Arc<T>);
                     impl<
                         T: MediaService,
-                    > tonic::server::UnaryService<super::RemoveMediaFromOfferRequest>
-                    for RemoveMediaFromOfferSvc<T> {
-                        type Response = super::RemoveMediaFromOfferResponse;
+                    > tonic::server::UnaryService<super::ImportMediaFromUrlRequest>
+                    for ImportMediaFromUrlSvc<T> {
+                        type Response = super::ImportMediaFromUrlResponse;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::RemoveMediaFromOfferRequest>,
+                            request: tonic::Request<super::ImportMediaFromUrlRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as MediaService>::remove_media_from_offer(
-                                        &inner,
-                                        request,
-                                    )
+                                <T as MediaService>::import_media_from_url(&inner, request)
                                     .await
                             };
                             Box::pin(fut)
@@ -1097,7 +2667,7 @@ pub mod media_service_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = RemoveMediaFromOfferSvc(inner);
+                        let method = ImportMediaFromUrlSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -1169,8 +2739,8 @@ pub struct MediaSubscriptionResponse {
     pub current_period_start: u64,
     #[prost(uint64, tag = "7")]
     pub current_period_end: u64,
-    #[prost(string, tag = "8")]
-    pub subscription_status: ::prost::alloc::string::String,
+    #[prost(enumeration = "SubscriptionStatus", tag = "8")]
+    pub subscription_status: i32,
     #[prost(uint64, tag = "9")]
     pub payed_at: u64,
     #[prost(uint64, tag = "10")]
@@ -1181,6 +2751,9 @@ pub struct MediaSubscriptionResponse {
     pub canceled_at: ::core::option::Option<u64>,
     #[prost(uint64, optional, tag = "13")]
     pub cancel_at: ::core::option::Option<u64>,
+    /// Notes of the seller about the subscriber, never returned to buyers
+    #[prost(string, optional, tag = "14")]
+    pub seller_notes: ::core::option::Option<::prost::alloc::string::String>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -1195,8 +2768,8 @@ pub struct PutMediaSubscriptionRequest {
     pub current_period_start: u64,
     #[prost(uint64, tag = "5")]
     pub current_period_end: u64,
-    #[prost(string, tag = "6")]
-    pub subscription_status: ::prost::alloc::string::String,
+    #[prost(enumeration = "SubscriptionStatus", tag = "6")]
+    pub subscription_status: i32,
     #[prost(uint64, tag = "7")]
     pub payed_at: u64,
     #[prost(uint64, tag = "8")]
@@ -1267,6 +2840,46 @@ pub struct ResumeMediaSubscriptionRequest {
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ResumeMediaSubscriptionResponse {}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RevokeMediaSubscriptionRequest {
+    #[prost(string, tag = "1")]
+    pub media_subscription_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub reason: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RevokeMediaSubscriptionResponse {
+    #[prost(message, optional, tag = "1")]
+    pub media_subscription: ::core::option::Option<MediaSubscriptionResponse>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UpdateMediaSubscriptionNotesRequest {
+    #[prost(string, tag = "1")]
+    pub media_subscription_id: ::prost::alloc::string::String,
+    #[prost(string, optional, tag = "2")]
+    pub notes: ::core::option::Option<::prost::alloc::string::String>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UpdateMediaSubscriptionNotesResponse {
+    #[prost(message, optional, tag = "1")]
+    pub media_subscription: ::core::option::Option<MediaSubscriptionResponse>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetShopMediaSubscriptionRequest {
+    #[prost(string, tag = "1")]
+    pub media_subscription_id: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetShopMediaSubscriptionResponse {
+    #[prost(message, optional, tag = "1")]
+    pub media_subscription: ::core::option::Option<MediaSubscriptionResponse>,
+}
 /// Generated server implementations.
 pub mod media_subscription_service_server {
     #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
@@ -1309,6 +2922,27 @@ pub mod media_subscription_service_server {
             tonic::Response<super::ResumeMediaSubscriptionResponse>,
             tonic::Status,
         >;
+        async fn revoke_media_subscription(
+            &self,
+            request: tonic::Request<super::RevokeMediaSubscriptionRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::RevokeMediaSubscriptionResponse>,
+            tonic::Status,
+        >;
+        async fn update_media_subscription_notes(
+            &self,
+            request: tonic::Request<super::UpdateMediaSubscriptionNotesRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::UpdateMediaSubscriptionNotesResponse>,
+            tonic::Status,
+        >;
+        async fn get_shop_media_subscription(
+            &self,
+            request: tonic::Request<super::GetShopMediaSubscriptionRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetShopMediaSubscriptionResponse>,
+            tonic::Status,
+        >;
     }
     #[derive(Debug)]
     pub struct MediaSubscriptionServiceServer<T: MediaSubscriptionService> {
@@ -1654,6 +3288,169 @@ pub mod media_subscription_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/sited_io.media.v1.MediaSubscriptionService/RevokeMediaSubscription" => {
+                    #[allow(non_camel_case_types)]
+                    struct RevokeMediaSubscriptionSvc<T: MediaSubscriptionService>(
+                        pub Arc<T>,
+                    );
+                    impl<
+                        T: MediaSubscriptionService,
+                    > tonic::server::UnaryService<super::RevokeMediaSubscriptionRequest>
+                    for RevokeMediaSubscriptionSvc<T> {
+                        type Response = super::RevokeMediaSubscriptionResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<
+                                super::RevokeMediaSubscriptionRequest,
+                            >,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as MediaSubscriptionService>::revoke_media_subscription(
+                                        &inner,
+                                        request,
+                                    )
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = RevokeMediaSubscriptionSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/sited_io.media.v1.MediaSubscriptionService/UpdateMediaSubscriptionNotes" => {
+                    #[allow(non_camel_case_types)]
+                    struct UpdateMediaSubscriptionNotesSvc<T: MediaSubscriptionService>(
+                        pub Arc<T>,
+                    );
+                    impl<
+                        T: MediaSubscriptionService,
+                    > tonic::server::UnaryService<
+                        super::UpdateMediaSubscriptionNotesRequest,
+                    > for UpdateMediaSubscriptionNotesSvc<T> {
+                        type Response = super::UpdateMediaSubscriptionNotesResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<
+                                super::UpdateMediaSubscriptionNotesRequest,
+                            >,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as MediaSubscriptionService>::update_media_subscription_notes(
+                                        &inner,
+                                        request,
+                                    )
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = UpdateMediaSubscriptionNotesSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/sited_io.media.v1.MediaSubscriptionService/GetShopMediaSubscription" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetShopMediaSubscriptionSvc<T: MediaSubscriptionService>(
+                        pub Arc<T>,
+                    );
+                    impl<
+                        T: MediaSubscriptionService,
+                    > tonic::server::UnaryService<super::GetShopMediaSubscriptionRequest>
+                    for GetShopMediaSubscriptionSvc<T> {
+                        type Response = super::GetShopMediaSubscriptionResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<
+                                super::GetShopMediaSubscriptionRequest,
+                            >,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as MediaSubscriptionService>::get_shop_media_subscription(
+                                        &inner,
+                                        request,
+                                    )
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetShopMediaSubscriptionSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => {
                     Box::pin(async move {
                         Ok(
@@ -1,3 +1,4 @@
+// This file is @generated by prost-build.
 /// deprecate in favor of PaginationRequest/PaginationResponse
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -24,4 +25,11 @@ pub struct PaginationResponse {
     pub size: u32,
     #[prost(uint32, tag = "3")]
     pub total_elements: u32,
+    /// 0 if there are no elements
+    #[prost(uint64, tag = "4")]
+    pub total_pages: u64,
+    #[prost(bool, tag = "5")]
+    pub has_next_page: bool,
+    #[prost(bool, tag = "6")]
+    pub has_prev_page: bool,
 }
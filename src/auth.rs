@@ -81,3 +81,27 @@ pub async fn verify_service_user(
         Err(Status::unauthenticated(""))
     }
 }
+
+/// Returns the user id of the caller if it has the admin role
+pub async fn verify_admin_user(
+    metadata: &MetadataMap,
+    verifier: &RemoteJwksVerifier,
+) -> Result<String, Status> {
+    let token = get_token(metadata)?;
+
+    let verified = verifier
+        .verify::<ExtraClaims>(&token)
+        .await
+        .map_err(|err| Status::unauthenticated(err.to_string()))?;
+    let claims = verified.claims();
+
+    match claims.extra.metadata.get("role") {
+        Some(role) if role == "YWRtaW4" => {} // 'admin' in base64
+        _ => return Err(Status::permission_denied("")),
+    }
+
+    claims
+        .sub
+        .clone()
+        .ok_or_else(|| Status::unauthenticated(""))
+}
@@ -1,28 +1,134 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
 use http::header::AUTHORIZATION;
 use tonic::metadata::MetadataMap;
-use tonic::transport::Channel;
-use tonic::{Request, Status};
+use tonic::transport::{Channel, Endpoint};
+use tonic::{async_trait, Code, Request, Status};
+use tonic_health::pb::health_check_response::ServingStatus;
+use tonic_health::pb::health_client::HealthClient;
+use tonic_health::pb::HealthCheckRequest;
 
 use crate::api::sited_io::commerce::v1::offer_service_client::OfferServiceClient;
 use crate::api::sited_io::commerce::v1::shop_service_client::ShopServiceClient;
 use crate::api::sited_io::commerce::v1::{
-    GetOfferRequest, GetShopRequest,
+    GetOfferRequest, GetShopRequest, OfferResponse,
 };
 
+/// Ownership checks against the commerce service
+#[async_trait]
+pub trait Commerce: Send + Sync {
+    async fn check_shop_and_owner(
+        &self,
+        shop_id: &String,
+        user_id: &String,
+        metadata: &MetadataMap,
+    ) -> Result<(), Status>;
+
+    async fn check_offer_and_owner(
+        &self,
+        offer_id: &String,
+        user_id: &String,
+        metadata: &MetadataMap,
+    ) -> Result<(), Status>;
+
+    /// Like `check_offer_and_owner`, also fails if the offer belongs to
+    /// another shop than `shop_id`
+    async fn check_offer_of_shop_and_owner(
+        &self,
+        offer_id: &String,
+        shop_id: &String,
+        user_id: &String,
+        metadata: &MetadataMap,
+    ) -> Result<(), Status>;
+
+    /// `false` if the shop was closed or does not exist anymore
+    async fn is_shop_active(&self, shop_id: &String) -> Result<bool, Status>;
+
+    /// Fails if the commerce service is not serving
+    async fn check_health(&self) -> Result<(), Status>;
+}
+
+/// Clones share the connection and the reachability state
+#[derive(Clone)]
 pub struct CommerceService {
     shop_client: ShopServiceClient<Channel>,
     offer_client: OfferServiceClient<Channel>,
+    health_client: HealthClient<Channel>,
+    reachable: Arc<AtomicBool>,
 }
 
 impl CommerceService {
+    /// Connects lazily, so startup does not depend on commerce being
+    /// reachable. Only fails if `url` is invalid.
     pub async fn init(url: String) -> Result<Self, tonic::transport::Error> {
+        let channel = Endpoint::from_shared(url)?.connect_lazy();
+
         Ok(Self {
-            shop_client: ShopServiceClient::connect(url.clone()).await?,
-            offer_client: OfferServiceClient::connect(url).await?,
+            shop_client: ShopServiceClient::new(channel.clone()),
+            offer_client: OfferServiceClient::new(channel.clone()),
+            health_client: HealthClient::new(channel),
+            reachable: Arc::new(AtomicBool::new(false)),
         })
     }
 
-    pub async fn check_shop_and_owner(
+    async fn get_offer(
+        &self,
+        offer_id: &String,
+        metadata: &MetadataMap,
+    ) -> Result<OfferResponse, Status> {
+        let mut client = self.offer_client.clone();
+
+        let mut request = Request::new(GetOfferRequest {
+            offer_id: offer_id.to_owned(),
+        });
+
+        if let Some(token) = metadata.get(AUTHORIZATION.as_str()) {
+            request
+                .metadata_mut()
+                .insert(AUTHORIZATION.as_str(), token.to_owned());
+        }
+
+        self.on_response(client.get_offer(request).await, "offer")?
+            .into_inner()
+            .offer
+            .ok_or_else(|| Status::not_found("offer response was empty"))
+    }
+
+    fn on_response<T>(
+        &self,
+        result: Result<T, Status>,
+        not_found: &'static str,
+    ) -> Result<T, Status> {
+        match result {
+            Ok(response) => {
+                if !self.reachable.swap(true, Ordering::Relaxed) {
+                    tracing::log::info!(
+                        "[CommerceService]: commerce service is reachable"
+                    );
+                }
+                Ok(response)
+            }
+            Err(err) if err.code() == Code::Unavailable => {
+                if self.reachable.swap(false, Ordering::Relaxed) {
+                    tracing::log::warn!(
+                        "[CommerceService]: commerce service became unreachable"
+                    );
+                }
+                tracing::error!("{}", err);
+                Err(Status::unavailable("commerce service is unavailable"))
+            }
+            Err(err) => {
+                tracing::error!("{}", err);
+                Err(Status::not_found(not_found))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Commerce for CommerceService {
+    async fn check_shop_and_owner(
         &self,
         shop_id: &String,
         user_id: &String,
@@ -42,13 +148,8 @@ impl CommerceService {
                 .insert(AUTHORIZATION.as_str(), auth_header.to_owned());
         }
 
-        let shop = client
-            .get_shop(request)
-            .await
-            .map_err(|err| {
-                tracing::error!("{}", err);
-                Status::not_found("shop")
-            })?
+        let shop = self
+            .on_response(client.get_shop(request).await, "shop")?
             .into_inner()
             .shop
             .ok_or_else(|| Status::not_found("shop response was empty"))?;
@@ -60,39 +161,78 @@ impl CommerceService {
         }
     }
 
-    pub async fn check_offer_and_owner(
+    async fn check_offer_and_owner(
         &self,
         offer_id: &String,
         user_id: &String,
         metadata: &MetadataMap,
     ) -> Result<(), Status> {
-        let mut client = self.offer_client.clone();
+        let offer = self.get_offer(offer_id, metadata).await?;
 
-        let mut request = Request::new(GetOfferRequest {
-            offer_id: offer_id.to_owned(),
+        if offer.user_id == *user_id {
+            Ok(())
+        } else {
+            Err(Status::not_found("user is not owner of this offer"))
+        }
+    }
+
+    async fn check_offer_of_shop_and_owner(
+        &self,
+        offer_id: &String,
+        shop_id: &String,
+        user_id: &String,
+        metadata: &MetadataMap,
+    ) -> Result<(), Status> {
+        let offer = self.get_offer(offer_id, metadata).await?;
+
+        if offer.user_id != *user_id {
+            Err(Status::not_found("user is not owner of this offer"))
+        } else if offer.shop_id != *shop_id {
+            Err(Status::invalid_argument(
+                "offer does not belong to this shop",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn is_shop_active(&self, shop_id: &String) -> Result<bool, Status> {
+        let mut client = self.shop_client.clone();
+
+        let request = Request::new(GetShopRequest {
+            shop_id: Some(shop_id.to_owned()),
+            extended: None,
+            ..Default::default()
         });
 
-        if let Some(token) = metadata.get(AUTHORIZATION.as_str()) {
-            request
-                .metadata_mut()
-                .insert(AUTHORIZATION.as_str(), token.to_owned());
+        match self.on_response(client.get_shop(request).await, "shop") {
+            Ok(response) => Ok(response
+                .into_inner()
+                .shop
+                .is_some_and(|shop| shop.is_active)),
+            Err(err) if err.code() == Code::NotFound => Ok(false),
+            Err(err) => Err(err),
         }
+    }
 
-        let offer = client
-            .get_offer(request)
-            .await
-            .map_err(|err| {
-                tracing::error!("{}", err);
-                Status::not_found("offer")
-            })?
+    async fn check_health(&self) -> Result<(), Status> {
+        let mut client = self.health_client.clone();
+
+        let status = client
+            .check(HealthCheckRequest {
+                service: String::new(),
+            })
+            .await?
             .into_inner()
-            .offer
-            .ok_or_else(|| Status::not_found("offer response was empty"))?;
+            .status();
 
-        if offer.user_id == *user_id {
+        if status == ServingStatus::Serving {
             Ok(())
         } else {
-            Err(Status::not_found("user is not owner of this offer"))
+            Err(Status::unavailable(format!(
+                "commerce service is {}",
+                status.as_str_name()
+            )))
         }
     }
 }
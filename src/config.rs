@@ -0,0 +1,480 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::db::NameMatching;
+use crate::files::{
+    ObjectEncryption, ObjectKeyStrategy, ObjectTag, PresignedUrlExpiry,
+    StorageRoute,
+};
+use crate::processing_limit::ProcessingLimiter;
+use crate::remote_fetch::RemoteFetchService;
+use crate::request_timeout::TimeoutPolicy;
+use crate::signing::ResponseSigner;
+use crate::upload_policy::{ContentTypeAllowlist, UploadPolicy};
+use crate::ClosedShopListing;
+
+/// Longest time subscriptions keep granting access after `payed_until`
+const MAX_SUBSCRIPTION_GRACE_PERIOD: Duration =
+    Duration::from_secs(365 * 86400);
+
+/// Prefix of the variables holding secret access keys of shop buckets,
+/// followed by the reference stored with the bucket
+const SHOP_BUCKET_SECRET_PREFIX: &str = "SHOP_BUCKET_SECRET_";
+
+/// Environment variable that is missing or could not be parsed
+#[derive(Debug)]
+pub enum ConfigError {
+    Missing(&'static str),
+    Invalid { var: &'static str, reason: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing(var) => {
+                write!(f, "missing environment variable '{var}'")
+            }
+            Self::Invalid { var, reason } => {
+                write!(f, "invalid environment variable '{var}': {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+fn required(var: &'static str) -> Result<String, ConfigError> {
+    std::env::var(var).map_err(|_| ConfigError::Missing(var))
+}
+
+fn parse<T>(var: &'static str, value: &str) -> Result<T, ConfigError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    value.parse().map_err(|err| ConfigError::Invalid {
+        var,
+        reason: format!("{err}"),
+    })
+}
+
+fn required_parsed<T>(var: &'static str) -> Result<T, ConfigError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    parse(var, &required(var)?)
+}
+
+fn optional<T>(var: &'static str) -> Result<Option<T>, ConfigError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    std::env::var(var).ok().map(|v| parse(var, &v)).transpose()
+}
+
+/// Secret access keys of shop buckets by their reference
+fn shop_bucket_secrets(
+    vars: impl Iterator<Item = (String, String)>,
+) -> HashMap<String, String> {
+    vars.filter_map(|(var, value)| {
+        var.strip_prefix(SHOP_BUCKET_SECRET_PREFIX)
+            .filter(|reference| !reference.is_empty())
+            .map(|reference| (reference.to_owned(), value))
+    })
+    .collect()
+}
+
+fn optional_secs(
+    var: &'static str,
+    default: Duration,
+) -> Result<Duration, ConfigError> {
+    Ok(optional(var)?.map(Duration::from_secs).unwrap_or(default))
+}
+
+pub struct DbConfig {
+    pub host: String,
+    pub port: u16,
+    pub user: String,
+    pub password: String,
+    pub dbname: String,
+    pub root_cert: Option<String>,
+    /// Reads are served by the primary if `None`
+    pub replica_host: Option<String>,
+    pub replica_port: u16,
+    pub slow_query_threshold: Duration,
+    pub pool_acquire_timeout: Duration,
+    pub name_collation: Option<String>,
+    pub name_matching: NameMatching,
+}
+
+impl DbConfig {
+    fn from_env() -> Result<Self, ConfigError> {
+        let port = required_parsed("DB_PORT")?;
+
+        Ok(Self {
+            host: required("DB_HOST")?,
+            port,
+            user: required("DB_USER")?,
+            password: required("DB_PASSWORD")?,
+            dbname: required("DB_DBNAME")?,
+            root_cert: optional("DB_ROOT_CERT")?,
+            replica_host: optional("DB_REPLICA_HOST")?,
+            replica_port: optional("DB_REPLICA_PORT")?.unwrap_or(port),
+            slow_query_threshold: Duration::from_millis(
+                optional("SLOW_QUERY_THRESHOLD_MS")?.unwrap_or(500),
+            ),
+            pool_acquire_timeout: optional_secs(
+                "DB_POOL_ACQUIRE_TIMEOUT_SECS",
+                Duration::from_secs(5),
+            )?,
+            name_collation: optional("NAME_COLLATION")?,
+            name_matching: optional("NAME_MATCHING")?.unwrap_or_default(),
+        })
+    }
+}
+
+pub struct BucketConfig {
+    pub name: String,
+    pub endpoint: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub object_tags: Vec<ObjectTag>,
+    /// Endpoint and region of the replicated bucket
+    pub secondary_region: Option<(String, String)>,
+    pub encryption: Option<ObjectEncryption>,
+    pub content_routes: Option<HashMap<String, StorageRoute>>,
+    pub read_endpoint: Option<String>,
+    pub read_after_write_window: Duration,
+    /// Secret access keys of shop buckets by the reference stored with the
+    /// bucket, read from `SHOP_BUCKET_SECRET_<reference>`
+    pub shop_bucket_secrets: HashMap<String, String>,
+}
+
+impl BucketConfig {
+    fn from_env() -> Result<Self, ConfigError> {
+        let object_tags = std::env::var("BUCKET_OBJECT_TAGS")
+            .ok()
+            .map(|tags| ObjectTag::parse_list(&tags))
+            .transpose()
+            .map_err(|reason| ConfigError::Invalid {
+                var: "BUCKET_OBJECT_TAGS",
+                reason,
+            })?
+            .unwrap_or_default();
+
+        let secondary_region = match (
+            optional::<String>("BUCKET_SECONDARY_ENDPOINT")?,
+            optional::<String>("BUCKET_SECONDARY_REGION")?,
+        ) {
+            (Some(endpoint), Some(region)) => Some((endpoint, region)),
+            (None, None) => None,
+            (Some(_), None) => {
+                return Err(ConfigError::Missing("BUCKET_SECONDARY_REGION"))
+            }
+            (None, Some(_)) => {
+                return Err(ConfigError::Missing("BUCKET_SECONDARY_ENDPOINT"))
+            }
+        };
+
+        let encryption = ObjectEncryption::parse(
+            &std::env::var("BUCKET_SSE_ALGORITHM").unwrap_or_default(),
+            optional("BUCKET_KMS_KEY_ID")?,
+        )
+        .map_err(|reason| ConfigError::Invalid {
+            var: "BUCKET_SSE_ALGORITHM",
+            reason,
+        })?;
+
+        let content_routes = std::env::var("BUCKET_CONTENT_ROUTES")
+            .ok()
+            .map(|routes| StorageRoute::parse_map(&routes))
+            .transpose()
+            .map_err(|reason| ConfigError::Invalid {
+                var: "BUCKET_CONTENT_ROUTES",
+                reason,
+            })?;
+
+        Ok(Self {
+            name: required("BUCKET_NAME")?,
+            endpoint: required("BUCKET_ENDPOINT")?,
+            access_key_id: required("BUCKET_ACCESS_KEY_ID")?,
+            secret_access_key: required("BUCKET_SECRET_ACCESS_KEY")?,
+            object_tags,
+            secondary_region,
+            encryption,
+            content_routes,
+            read_endpoint: optional("BUCKET_READ_ENDPOINT")?,
+            read_after_write_window: optional_secs(
+                "BUCKET_READ_AFTER_WRITE_WINDOW_SECS",
+                Duration::from_secs(60),
+            )?,
+            shop_bucket_secrets: shop_bucket_secrets(std::env::vars()),
+        })
+    }
+}
+
+pub struct VirusScanConfig {
+    pub clamd_host: String,
+    pub clamd_port: u16,
+    pub sync_max_bytes: usize,
+}
+
+impl VirusScanConfig {
+    /// `None` if no clamd is configured
+    fn from_env() -> Result<Option<Self>, ConfigError> {
+        let Some(clamd_host) = optional("CLAMD_HOST")? else {
+            return Ok(None);
+        };
+
+        Ok(Some(Self {
+            clamd_host,
+            clamd_port: optional("CLAMD_PORT")?.unwrap_or(3310),
+            sync_max_bytes: optional("VIRUS_SCAN_SYNC_MAX_BYTES")?.unwrap_or(0),
+        }))
+    }
+}
+
+/// Settings of `MediaService` that do not concern its dependencies
+pub struct MediaServiceConfig {
+    pub key_strategy: ObjectKeyStrategy,
+    pub legacy_flat_keys: bool,
+    pub upload_policy: UploadPolicy,
+    /// Largest file returned inline as `data:` URI, disabled if `None`
+    pub data_uri_max_bytes: Option<u64>,
+    pub subscription_grace_period: Duration,
+    pub media_cache_ttl: Duration,
+    pub closed_shop_listing: ClosedShopListing,
+    pub presigned_url_expiry: PresignedUrlExpiry,
+    pub permalink_base_url: Option<String>,
+    /// Responses cannot be signed if `None`
+    pub response_signer: Option<ResponseSigner>,
+    /// Bounds decoding of uploaded files
+    pub processing_limiter: ProcessingLimiter,
+    /// Hamming distance of perceptual hashes up to which media count as
+    /// similar, at most 64
+    pub similar_media_max_distance: u32,
+    /// Bounds single-shot uploads, whose file data is sent inside the
+    /// request message, so it is the effective maximum file size of
+    /// CreateMedia/UpdateMedia. Larger files have to use multipart upload
+    /// with chunks below this limit.
+    pub max_decoding_message_size: usize,
+    /// Bounds responses such as DownloadMediaRange and pages of ListMedia
+    pub max_encoding_message_size: usize,
+    /// Fetches files of ImportMediaFromUrl
+    pub remote_fetch: RemoteFetchService,
+}
+
+impl MediaServiceConfig {
+    fn from_env() -> Result<Self, ConfigError> {
+        // inline file contents can be disabled fleet-wide, clients then
+        // always receive download urls
+        let disable_inline_data =
+            optional("DISABLE_INLINE_DATA")?.unwrap_or(false);
+        let data_uri_max_bytes = if disable_inline_data {
+            None
+        } else {
+            optional("DATA_URI_MAX_BYTES")?
+        };
+
+        let presigned_url_expiry = {
+            let defaults = PresignedUrlExpiry::default();
+            PresignedUrlExpiry {
+                download: optional_secs(
+                    "PRESIGNED_DOWNLOAD_EXPIRES_IN_SECS",
+                    defaults.download,
+                )?,
+                owner_download: optional_secs(
+                    "PRESIGNED_OWNER_DOWNLOAD_EXPIRES_IN_SECS",
+                    defaults.owner_download,
+                )?,
+                max: optional_secs(
+                    "PRESIGNED_MAX_EXPIRES_IN_SECS",
+                    defaults.max,
+                )?,
+            }
+        };
+
+        let max_decoding_message_size =
+            optional("GRPC_MAX_DECODING_MESSAGE_SIZE")?
+                .unwrap_or(4 * 1024 * 1024);
+        if max_decoding_message_size == 0 {
+            return Err(ConfigError::Invalid {
+                var: "GRPC_MAX_DECODING_MESSAGE_SIZE",
+                reason: String::from("must be greater than 0"),
+            });
+        }
+
+        let similar_media_max_distance =
+            optional("SIMILAR_MEDIA_MAX_DISTANCE")?.unwrap_or(10);
+        if similar_media_max_distance > 64 {
+            return Err(ConfigError::Invalid {
+                var: "SIMILAR_MEDIA_MAX_DISTANCE",
+                reason: String::from("must be at most 64"),
+            });
+        }
+
+        let subscription_grace_period = optional_secs(
+            "SUBSCRIPTION_PAYED_UNTIL_WINDOW_SECS",
+            Duration::from_secs(86400),
+        )?;
+        if subscription_grace_period > MAX_SUBSCRIPTION_GRACE_PERIOD {
+            return Err(ConfigError::Invalid {
+                var: "SUBSCRIPTION_PAYED_UNTIL_WINDOW_SECS",
+                reason: format!(
+                    "must be at most {}",
+                    MAX_SUBSCRIPTION_GRACE_PERIOD.as_secs()
+                ),
+            });
+        }
+
+        let processing_concurrency = optional("PROCESSING_CONCURRENCY")?
+            .unwrap_or_else(ProcessingLimiter::default_concurrency);
+        if processing_concurrency == 0 {
+            return Err(ConfigError::Invalid {
+                var: "PROCESSING_CONCURRENCY",
+                reason: String::from("must be greater than 0"),
+            });
+        }
+
+        Ok(Self {
+            key_strategy: optional("BUCKET_KEY_STRATEGY")?.unwrap_or_default(),
+            legacy_flat_keys: optional("LEGACY_FLAT_KEY_MODE")?
+                .unwrap_or(false),
+            upload_policy: UploadPolicy {
+                single: std::env::var("SINGLE_UPLOAD_CONTENT_TYPES")
+                    .ok()
+                    .map(|c| ContentTypeAllowlist::parse(&c)),
+                multipart: std::env::var("MULTIPART_UPLOAD_CONTENT_TYPES")
+                    .ok()
+                    .map(|c| ContentTypeAllowlist::parse(&c)),
+                min_image_width: optional("MIN_IMAGE_WIDTH")?,
+                min_image_height: optional("MIN_IMAGE_HEIGHT")?,
+                max_multipart_parts: optional("MAX_MULTIPART_PARTS")?,
+                max_multipart_bytes: optional("MAX_MULTIPART_BYTES")?,
+                content_type_replacement: optional("CONTENT_TYPE_REPLACEMENT")?
+                    .unwrap_or_default(),
+            },
+            data_uri_max_bytes,
+            subscription_grace_period,
+            media_cache_ttl: optional_secs(
+                "MEDIA_CACHE_TTL_SECS",
+                Duration::from_secs(10),
+            )?,
+            closed_shop_listing: optional("CLOSED_SHOP_LISTING")?
+                .unwrap_or_default(),
+            presigned_url_expiry,
+            permalink_base_url: optional("PERMALINK_BASE_URL")?,
+            response_signer: optional::<String>("RESPONSE_SIGNING_KEY")?
+                .map(ResponseSigner::new),
+            processing_limiter: ProcessingLimiter::new(
+                processing_concurrency,
+                optional("PROCESSING_MAX_QUEUED")?.unwrap_or(64),
+            ),
+            similar_media_max_distance,
+            max_decoding_message_size,
+            max_encoding_message_size: optional(
+                "GRPC_MAX_ENCODING_MESSAGE_SIZE",
+            )?
+            .unwrap_or(usize::MAX),
+            remote_fetch: RemoteFetchService::new(
+                std::env::var("URL_IMPORT_ALLOWED_HOSTS").ok().map(|hosts| {
+                    hosts
+                        .split(',')
+                        .map(|h| h.trim().to_lowercase())
+                        .filter(|h| !h.is_empty())
+                        .collect()
+                }),
+            ),
+        })
+    }
+}
+
+/// Everything configurable through the environment, read once at startup so
+/// invalid values fail fast instead of on first use
+pub struct Config {
+    pub host: String,
+    pub jwks_url: String,
+    pub jwks_host: String,
+    pub db: DbConfig,
+    pub oauth_url: String,
+    pub oauth_host: String,
+    pub service_user_client_id: String,
+    pub service_user_client_secret: String,
+    pub bucket: BucketConfig,
+    pub payment_service_url: String,
+    pub commerce_service_url: String,
+    pub default_user_quota_mib: u64,
+    /// Uploads are not scanned if `None`
+    pub virus_scan: Option<VirusScanConfig>,
+    pub timeout_policy: TimeoutPolicy,
+    pub enable_grpc_web: bool,
+    pub media_service: MediaServiceConfig,
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let timeout_policy = {
+            let defaults = TimeoutPolicy::default();
+            TimeoutPolicy {
+                read: optional_secs("GRPC_READ_TIMEOUT_SECS", defaults.read)?,
+                write: optional_secs(
+                    "GRPC_WRITE_TIMEOUT_SECS",
+                    defaults.write,
+                )?,
+                upload: optional_secs(
+                    "GRPC_UPLOAD_TIMEOUT_SECS",
+                    defaults.upload,
+                )?,
+                batch: optional_secs(
+                    "GRPC_BATCH_TIMEOUT_SECS",
+                    defaults.batch,
+                )?,
+            }
+        };
+
+        Ok(Self {
+            host: required("HOST")?,
+            jwks_url: required("JWKS_URL")?,
+            jwks_host: required("JWKS_HOST")?,
+            db: DbConfig::from_env()?,
+            oauth_url: required("OAUTH_URL")?,
+            oauth_host: required("OAUTH_HOST")?,
+            service_user_client_id: required("SERVICE_USER_CLIENT_ID")?,
+            service_user_client_secret: required("SERVICE_USER_CLIENT_SECRET")?,
+            bucket: BucketConfig::from_env()?,
+            payment_service_url: required("PAYMENT_SERVICE_URL")?,
+            commerce_service_url: required("COMMERCE_SERVICE_URL")?,
+            default_user_quota_mib: required_parsed("DEFAULT_USER_QUOTA_MIB")?,
+            virus_scan: VirusScanConfig::from_env()?,
+            timeout_policy,
+            enable_grpc_web: optional("ENABLE_GRPC_WEB")?.unwrap_or(true),
+            media_service: MediaServiceConfig::from_env()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shop_bucket_secrets_by_reference() {
+        let vars = [
+            ("SHOP_BUCKET_SECRET_shop_a", "secret-a"),
+            ("SHOP_BUCKET_SECRET_", "no reference"),
+            ("BUCKET_SECRET_ACCESS_KEY", "default bucket"),
+        ]
+        .map(|(var, value)| (var.to_string(), value.to_string()));
+
+        let secrets = shop_bucket_secrets(vars.into_iter());
+
+        assert_eq!(
+            secrets,
+            HashMap::from([("shop_a".to_string(), "secret-a".to_string())])
+        );
+    }
+}
@@ -1,19 +1,103 @@
+use std::future::Future;
 use std::ops::DerefMut;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 
 use deadpool_postgres::tokio_postgres::error::SqlState;
 use deadpool_postgres::tokio_postgres::types::{FromSql, Type, WrongType};
 use deadpool_postgres::tokio_postgres::Row;
 use deadpool_postgres::{
-    tokio_postgres::NoTls, Config, CreatePoolError, Pool, PoolError, Runtime,
-    SslMode,
+    tokio_postgres::NoTls, Client, Config, CreatePoolError, Pool, PoolError,
+    Runtime, SslMode,
 };
 
 use openssl::ssl::{SslConnector, SslMethod};
 use postgres_openssl::MakeTlsConnector;
+use prometheus::IntCounter;
 use refinery::Target;
-use sea_query::Iden;
+use sea_query::{Alias, Expr, PostgresQueryBuilder, Query};
+use sea_query_postgres::PostgresBinder;
 use tonic::Status;
 
+static SLOW_QUERY_THRESHOLD: OnceLock<Duration> = OnceLock::new();
+static DB_SLOW_QUERIES_TOTAL: OnceLock<IntCounter> = OnceLock::new();
+static NAME_COLLATION: OnceLock<String> = OnceLock::new();
+static NAME_MATCHING: OnceLock<NameMatching> = OnceLock::new();
+static POOL_ACQUIRE_TIMEOUT: OnceLock<Duration> = OnceLock::new();
+
+const DEFAULT_SLOW_QUERY_THRESHOLD: Duration = Duration::from_millis(500);
+const DEFAULT_POOL_ACQUIRE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Columns the service relies on, checked at startup in case a migration was
+/// rolled back manually or applied out of order
+const EXPECTED_SCHEMA: &[(&str, &str)] = &[
+    ("medias", "media_id"),
+    ("medias", "shop_id"),
+    ("medias", "user_id"),
+    ("medias", "created_at"),
+    ("medias", "updated_at"),
+    ("medias", "name"),
+    ("medias", "data_url"),
+    ("medias", "size_bytes"),
+    ("medias", "file_name"),
+    ("medias", "bucket_name"),
+    ("medias", "content_type"),
+    ("medias", "processing_state"),
+    ("medias", "storage_region"),
+    ("medias", "duration_seconds"),
+    ("medias", "image_width"),
+    ("medias", "image_height"),
+    ("medias", "delete_pending"),
+    ("medias", "custom_metadata"),
+    ("medias", "perceptual_hash"),
+    ("medias", "scan_id"),
+    ("medias_offers", "media_id"),
+    ("medias_offers", "offer_id"),
+    ("medias_offers", "user_id"),
+    ("medias_offers", "ordering"),
+    ("medias_quota", "user_id"),
+    ("medias_quota", "max_size_mib"),
+    ("media_subscriptions", "media_subscription_id"),
+    ("media_subscriptions", "buyer_user_id"),
+    ("media_subscriptions", "offer_id"),
+    ("media_subscriptions", "shop_id"),
+    ("media_subscriptions", "current_period_start"),
+    ("media_subscriptions", "current_period_end"),
+    ("media_subscriptions", "subscription_status"),
+    ("media_subscriptions", "payed_at"),
+    ("media_subscriptions", "payed_until"),
+    ("media_subscriptions", "stripe_subscription_id"),
+    ("media_subscriptions", "canceled_at"),
+    ("media_subscriptions", "cancel_at"),
+    ("media_subscriptions", "revoked_at"),
+    ("media_subscriptions", "revoked_by"),
+    ("media_subscriptions", "revoke_reason"),
+    ("media_subscriptions", "seller_notes"),
+    ("media_subscriptions", "created_at"),
+    ("media_subscriptions", "updated_at"),
+    ("shop_buckets", "shop_id"),
+    ("shop_buckets", "bucket_name"),
+    ("shop_buckets", "bucket_endpoint"),
+    ("shop_buckets", "access_key_id"),
+    ("shop_buckets", "secret_access_key_ref"),
+    ("shop_buckets", "sse_algorithm"),
+    ("shop_buckets", "sse_kms_key_id"),
+    ("multipart_parts", "upload_id"),
+    ("multipart_parts", "part_number"),
+    ("multipart_parts", "etag"),
+    ("multipart_parts", "uploaded_at"),
+    ("multipart_upload_sessions", "upload_id"),
+    ("multipart_upload_sessions", "media_id"),
+    ("multipart_upload_sessions", "user_id"),
+    ("multipart_upload_sessions", "created_at"),
+    ("multipart_upload_sessions", "last_error"),
+    ("multipart_upload_sessions", "error_count"),
+    ("multipart_upload_sessions", "object_key"),
+    ("multipart_upload_sessions", "content_type"),
+    ("multipart_upload_sessions", "replaced_size_bytes"),
+];
+
 mod embedded {
     use refinery::embed_migrations;
     embed_migrations!("./migrations");
@@ -23,8 +107,12 @@ mod embedded {
 pub enum DbError {
     TokioPostgres(deadpool_postgres::tokio_postgres::Error),
     Pool(PoolError),
+    /// No connection became available within the pool acquire timeout
+    PoolTimeout,
     CreatePool(CreatePoolError),
     SeaQuery(sea_query::error::Error),
+    /// A statement expected to affect a row did not find one
+    NotFound,
     Other(Option<String>),
 }
 
@@ -85,6 +173,12 @@ impl From<DbError> for Status {
                         SqlState::FOREIGN_KEY_VIOLATION => {
                             Status::failed_precondition(err.message())
                         }
+                        // CockroachDB runs transactions serializable and
+                        // aborts one of two conflicting ones
+                        SqlState::T_R_SERIALIZATION_FAILURE => {
+                            tracing::log::warn!("{err:?}");
+                            Status::aborted("concurrent change, retry")
+                        }
                         _ => {
                             tracing::log::error!("{tp_err:?}");
                             Status::internal("")
@@ -99,6 +193,10 @@ impl From<DbError> for Status {
                 tracing::log::error!("{pool_err:?}");
                 Status::internal("")
             }
+            DbError::PoolTimeout => {
+                tracing::log::error!("timed out acquiring database connection");
+                Status::unavailable("")
+            }
             DbError::CreatePool(create_pool_err) => {
                 tracing::log::error!("{create_pool_err:?}");
                 Status::internal("")
@@ -107,6 +205,7 @@ impl From<DbError> for Status {
                 tracing::log::error!("{sea_query_err:?}");
                 Status::internal("")
             }
+            DbError::NotFound => Status::not_found(""),
             DbError::Other(other_err) => {
                 tracing::log::error!("{other_err:?}");
                 Status::internal("")
@@ -155,14 +254,201 @@ pub async fn migrate(pool: &Pool) -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-pub struct ArrayAgg;
+/// Runs `SELECT 1` to check the database is reachable
+pub async fn ping(pool: &Pool) -> Result<(), DbError> {
+    let client = get_client(pool, pool_acquire_timeout()).await?;
+
+    observe_query("SELECT 1", client.query_one("SELECT 1", &[])).await?;
+
+    Ok(())
+}
+
+/// Fails if any column of `EXPECTED_SCHEMA` is missing from the database.
+pub async fn validate_schema(pool: &Pool) -> Result<(), DbError> {
+    let client = get_client(pool, pool_acquire_timeout()).await?;
+
+    let mut tables: Vec<&str> =
+        EXPECTED_SCHEMA.iter().map(|(t, _)| *t).collect();
+    tables.sort_unstable();
+    tables.dedup();
+
+    let (sql, values) = Query::select()
+        .columns([Alias::new("table_name"), Alias::new("column_name")])
+        .from((Alias::new("information_schema"), Alias::new("columns")))
+        .and_where(
+            Expr::col(Alias::new("table_schema"))
+                .eq(Expr::cust("current_schema()")),
+        )
+        .and_where(
+            Expr::col(Alias::new("table_name")).is_in(tables.iter().copied()),
+        )
+        .build_postgres(PostgresQueryBuilder);
+
+    let rows =
+        observe_query(&sql, client.query(sql.as_str(), &values.as_params()))
+            .await?;
+
+    let existing: Vec<(String, String)> = rows
+        .iter()
+        .map(|row| (row.get("table_name"), row.get("column_name")))
+        .collect();
+
+    let missing: Vec<String> = EXPECTED_SCHEMA
+        .iter()
+        .filter(|(table, column)| {
+            !existing.iter().any(|(t, c)| t == table && c == column)
+        })
+        .map(|(table, column)| format!("{table}.{column}"))
+        .collect();
+
+    if !missing.is_empty() {
+        return Err(DbError::Other(Some(format!(
+            "missing database columns: {}",
+            missing.join(", ")
+        ))));
+    }
+
+    tracing::log::info!("validated database schema of {} tables", tables.len());
+
+    Ok(())
+}
+
+/// Sets the duration after which queries are logged as slow. Must be called
+/// once at startup, otherwise 500 ms is used.
+pub fn init_slow_query_threshold(threshold: Duration) {
+    if SLOW_QUERY_THRESHOLD.set(threshold).is_err() {
+        tracing::warn!("slow query threshold was already initialized");
+    }
+}
+
+/// Sets how long to wait for a free connection of a pool. Must be called once
+/// at startup, otherwise 5 s is used.
+pub fn init_pool_acquire_timeout(timeout: Duration) {
+    if POOL_ACQUIRE_TIMEOUT.set(timeout).is_err() {
+        tracing::warn!("pool acquire timeout was already initialized");
+    }
+}
+
+pub fn pool_acquire_timeout() -> Duration {
+    *POOL_ACQUIRE_TIMEOUT
+        .get()
+        .unwrap_or(&DEFAULT_POOL_ACQUIRE_TIMEOUT)
+}
+
+/// Gets a connection from `pool`, giving up after `timeout` instead of
+/// waiting indefinitely for one to become available
+pub async fn get_client(
+    pool: &Pool,
+    timeout: Duration,
+) -> Result<Client, DbError> {
+    tokio::time::timeout(timeout, pool.get())
+        .await
+        .map_err(|_| DbError::PoolTimeout)?
+        .map_err(DbError::from)
+}
+
+/// Sets the collation used when ordering by name, e.g. an ICU collation like
+/// `und-u-ks-level2`. Must be called once at startup, otherwise the default
+/// collation of the column is used.
+pub fn init_name_collation(collation: String) -> Result<(), String> {
+    // the collation is interpolated into queries as an identifier
+    if collation.is_empty()
+        || !collation
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!("invalid collation '{collation}'"));
+    }
+
+    if NAME_COLLATION.set(collation).is_err() {
+        tracing::warn!("name collation was already initialized");
+    }
+
+    Ok(())
+}
+
+pub fn name_collation() -> Option<&'static str> {
+    NAME_COLLATION.get().map(String::as_str)
+}
+
+/// How name filter queries are matched against media names
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NameMatching {
+    /// Ignores surrounding whitespace of the query and case
+    #[default]
+    Trimmed,
+    /// Matches the query as given, including whitespace and case
+    Literal,
+}
+
+impl FromStr for NameMatching {
+    type Err = String;
 
-impl Iden for ArrayAgg {
-    fn unquoted(&self, s: &mut dyn std::fmt::Write) {
-        write!(s, "ARRAY_AGG").unwrap()
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "trimmed" => Ok(Self::Trimmed),
+            "literal" => Ok(Self::Literal),
+            _ => Err(format!("unknown name matching '{s}'")),
+        }
+    }
+}
+
+/// Sets how name filters match. Must be called once at startup, otherwise
+/// `NameMatching::Trimmed` is used.
+pub fn init_name_matching(name_matching: NameMatching) {
+    if NAME_MATCHING.set(name_matching).is_err() {
+        tracing::warn!("name matching was already initialized");
     }
 }
 
+pub fn name_matching() -> NameMatching {
+    NAME_MATCHING.get().copied().unwrap_or_default()
+}
+
+pub fn slow_query_threshold() -> Duration {
+    *SLOW_QUERY_THRESHOLD
+        .get()
+        .unwrap_or(&DEFAULT_SLOW_QUERY_THRESHOLD)
+}
+
+fn db_slow_queries_total() -> &'static IntCounter {
+    DB_SLOW_QUERIES_TOTAL.get_or_init(|| {
+        let counter = IntCounter::new(
+            "db_slow_queries_total",
+            "Number of database queries exceeding the slow query threshold",
+        )
+        .expect("metric options should be valid");
+
+        if let Err(err) = prometheus::register(Box::new(counter.clone())) {
+            tracing::error!("could not register db_slow_queries_total: {err}");
+        }
+
+        counter
+    })
+}
+
+/// Awaits `query` and logs it as slow if it took longer than the configured
+/// threshold. `sql` must be the statement with placeholders only, so that no
+/// parameter values end up in logs. The originating gRPC method is part of the
+/// surrounding request span.
+pub async fn observe_query<T, F>(sql: &str, query: F) -> Result<T, DbError>
+where
+    F: Future<Output = Result<T, deadpool_postgres::tokio_postgres::Error>>,
+{
+    tracing::debug!("SQL: {sql}");
+
+    let started = Instant::now();
+    let result = query.await;
+    let elapsed = started.elapsed();
+
+    if elapsed > slow_query_threshold() {
+        db_slow_queries_total().inc();
+        tracing::warn!(elapsed_ms = elapsed.as_millis(), "slow query: {sql}");
+    }
+
+    Ok(result?)
+}
+
 pub fn get_type_from_oid<'a, T>(
     oid: i32,
 ) -> Result<Type, Box<dyn std::error::Error + Sync + Send>>
@@ -187,3 +473,82 @@ pub fn get_count_from_rows(rows: &[Row]) -> i64 {
         .map(|row| row.get::<&str, i64>("count"))
         .unwrap_or(0)
 }
+
+/// CockroachDB started in a container for tests of the queries, needs docker
+#[cfg(test)]
+pub mod test_db {
+    use std::sync::OnceLock;
+
+    use deadpool_postgres::Pool;
+    use testcontainers::clients::Cli;
+    use testcontainers::core::WaitFor;
+    use testcontainers::{Container, GenericImage, RunnableImage};
+
+    use super::{init_db_pool, migrate};
+
+    const COCKROACH_PORT: u16 = 26257;
+
+    static DOCKER: OnceLock<Cli> = OnceLock::new();
+
+    /// Docker client shared by the containers of all tests
+    pub fn docker() -> &'static Cli {
+        DOCKER.get_or_init(Cli::default)
+    }
+
+    /// Database with the latest schema, removed again on drop
+    pub struct TestDb {
+        pub pool: Pool,
+        _container: Container<'static, GenericImage>,
+    }
+
+    impl TestDb {
+        pub async fn start() -> Self {
+            let image = GenericImage::new("cockroachdb/cockroach", "v23.1.11")
+                .with_exposed_port(COCKROACH_PORT)
+                .with_wait_for(WaitFor::message_on_stdout(
+                    "CockroachDB node starting",
+                ));
+            let args =
+                vec!["start-single-node".to_string(), "--insecure".to_string()];
+            let container = docker().run(RunnableImage::from((image, args)));
+
+            let pool = init_db_pool(
+                "127.0.0.1".to_string(),
+                container.get_host_port_ipv4(COCKROACH_PORT),
+                "root".to_string(),
+                String::new(),
+                "defaultdb".to_string(),
+                None,
+            )
+            .unwrap();
+            migrate(&pool).await.unwrap();
+
+            Self {
+                pool,
+                _container: container,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic::Code;
+
+    use super::test_db::TestDb;
+    use super::*;
+
+    #[tokio::test]
+    async fn acquiring_from_exhausted_pool_times_out() {
+        let db = TestDb::start().await;
+        db.pool.resize(1);
+        let _held = get_client(&db.pool, Duration::from_secs(5)).await.unwrap();
+
+        let err = get_client(&db.pool, Duration::from_millis(50))
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, DbError::PoolTimeout), "{err:?}");
+        assert_eq!(Status::from(err).code(), Code::Unavailable);
+    }
+}
@@ -0,0 +1,118 @@
+use std::time::Duration;
+
+use tonic::{Code, Status};
+use tonic_types::{ErrorDetails, StatusExt};
+
+/// Builds `Status` errors carrying structured details in the
+/// `grpc-status-details-bin` trailer.
+#[derive(Debug, Clone)]
+pub struct StatusBuilder {
+    code: Code,
+    message: String,
+    details: ErrorDetails,
+}
+
+impl StatusBuilder {
+    pub fn new(code: Code, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            details: ErrorDetails::new(),
+        }
+    }
+
+    pub fn invalid_argument(message: impl Into<String>) -> Self {
+        Self::new(Code::InvalidArgument, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(Code::NotFound, message)
+    }
+
+    pub fn out_of_range(message: impl Into<String>) -> Self {
+        Self::new(Code::OutOfRange, message)
+    }
+
+    pub fn aborted(message: impl Into<String>) -> Self {
+        Self::new(Code::Aborted, message)
+    }
+
+    pub fn failed_precondition(message: impl Into<String>) -> Self {
+        Self::new(Code::FailedPrecondition, message)
+    }
+
+    pub fn resource_exhausted(message: impl Into<String>) -> Self {
+        Self::new(Code::ResourceExhausted, message)
+    }
+
+    pub fn with_bad_request_field(
+        mut self,
+        field: impl Into<String>,
+        description: impl Into<String>,
+    ) -> Self {
+        self.details.add_bad_request_violation(field, description);
+        self
+    }
+
+    pub fn with_precondition_violation(
+        mut self,
+        violation_type: impl Into<String>,
+        subject: impl Into<String>,
+        description: impl Into<String>,
+    ) -> Self {
+        self.details.add_precondition_failure_violation(
+            violation_type,
+            subject,
+            description,
+        );
+        self
+    }
+
+    pub fn with_resource_info(
+        mut self,
+        resource_type: impl Into<String>,
+        resource_name: impl Into<String>,
+    ) -> Self {
+        self.details
+            .set_resource_info(resource_type, resource_name, "", "");
+        self
+    }
+
+    pub fn with_retry_info(mut self, delay_ms: u64) -> Self {
+        self.details
+            .set_retry_info(Some(Duration::from_millis(delay_ms)));
+        self
+    }
+
+    pub fn build(self) -> Status {
+        Status::with_error_details(self.code, self.message, self.details)
+    }
+}
+
+impl From<StatusBuilder> for Status {
+    fn from(builder: StatusBuilder) -> Self {
+        builder.build()
+    }
+}
+
+/// Dependency that could not be reached while building a service
+#[derive(Debug)]
+pub enum BuildError {
+    DbUnreachable(String),
+    StorageUnreachable(String),
+}
+
+impl std::fmt::Display for BuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DbUnreachable(err) => {
+                write!(f, "database unreachable: {err}")
+            }
+            Self::StorageUnreachable(err) => {
+                write!(f, "storage unreachable: {err}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
@@ -0,0 +1,198 @@
+use std::io::Cursor;
+
+use image::io::{Limits, Reader};
+use image::ImageError;
+use tonic::Status;
+
+use crate::api::sited_io::media::v1::MediaUpload;
+use crate::processing_limit::ProcessingLimiter;
+
+const IMAGE_CONTENT_TYPES: [&str; 4] =
+    ["image/jpeg", "image/png", "image/webp", "image/gif"];
+const VIDEO_MP4_CONTENT_TYPES: [&str; 3] =
+    ["video/mp4", "video/quicktime", "video/x-m4v"];
+/// Larger images are not hashed, decoding them takes too long
+const MAX_HASHED_IMAGE_DIMENSION: u32 = 16_384;
+/// Most memory allocated for decoding an image to hash it
+const MAX_HASHED_IMAGE_ALLOC: u64 = 256 * 1024 * 1024;
+
+/// Metadata read from uploaded files. Fields are `None` if they do not apply
+/// to the content type or could not be read.
+#[derive(Debug, Clone, Default)]
+pub struct FileInfo {
+    pub duration_seconds: Option<f64>,
+    pub image_width: Option<i32>,
+    pub image_height: Option<i32>,
+    /// Difference hash of images, see `image_perceptual_hash`
+    pub perceptual_hash: Option<i64>,
+}
+
+impl FileInfo {
+    /// Parses `upload` on the blocking thread pool and hands it back
+    /// afterwards to avoid copying the data. Waits for a slot of `limiter`
+    /// first, as decoding images is CPU heavy.
+    pub async fn extract(
+        upload: MediaUpload,
+        limiter: &ProcessingLimiter,
+    ) -> Result<(MediaUpload, Self), Status> {
+        let permit = limiter.acquire().await?;
+
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            let (image_width, image_height) =
+                image_dimensions(&upload.content_type, &upload.data).unzip();
+            let file_info = Self {
+                duration_seconds: video_duration(
+                    &upload.content_type,
+                    &upload.data,
+                ),
+                image_width,
+                image_height,
+                perceptual_hash: image_perceptual_hash(
+                    &upload.content_type,
+                    &upload.data,
+                ),
+            };
+            (upload, file_info)
+        })
+        .await
+        .map_err(|err| {
+            tracing::log::error!("[FileInfo.extract]: {err}");
+            Status::internal("")
+        })
+    }
+}
+
+fn video_duration(content_type: &str, data: &[u8]) -> Option<f64> {
+    if !VIDEO_MP4_CONTENT_TYPES.contains(&content_type) {
+        return None;
+    }
+
+    let size = u64::try_from(data.len()).ok()?;
+
+    match mp4::Mp4Reader::read_header(Cursor::new(data), size) {
+        Ok(reader) => Some(reader.duration().as_secs_f64()),
+        Err(err) => {
+            tracing::log::debug!("[FileInfo.video_duration]: {err}");
+            None
+        }
+    }
+}
+
+/// Reads only the image header
+fn image_dimensions(content_type: &str, data: &[u8]) -> Option<(i32, i32)> {
+    if !IMAGE_CONTENT_TYPES.contains(&content_type) {
+        return None;
+    }
+
+    match imagesize::blob_size(data) {
+        Ok(size) => Some((
+            i32::try_from(size.width).ok()?,
+            i32::try_from(size.height).ok()?,
+        )),
+        Err(err) => {
+            tracing::log::debug!("[FileInfo.image_dimensions]: {err}");
+            None
+        }
+    }
+}
+
+/// Difference hash (dHash) of the image scaled down to 9x8 grayscale pixels,
+/// one bit per pair of horizontally adjacent pixels. Images differing only
+/// in resolution or compression have hashes within a small Hamming distance.
+/// Decodes the whole image, unlike `image_dimensions`, so images exceeding
+/// the decoding limits are not hashed.
+fn image_perceptual_hash(content_type: &str, data: &[u8]) -> Option<i64> {
+    if !IMAGE_CONTENT_TYPES.contains(&content_type) {
+        return None;
+    }
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_HASHED_IMAGE_DIMENSION);
+    limits.max_image_height = Some(MAX_HASHED_IMAGE_DIMENSION);
+    limits.max_alloc = Some(MAX_HASHED_IMAGE_ALLOC);
+
+    let image = match Reader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(ImageError::IoError)
+        .and_then(|mut reader| {
+            reader.limits(limits);
+            reader.decode()
+        }) {
+        Ok(image) => image,
+        Err(err) => {
+            tracing::log::debug!("[FileInfo.image_perceptual_hash]: {err}");
+            return None;
+        }
+    };
+
+    let pixels = image
+        .resize_exact(9, 8, image::imageops::FilterType::Triangle)
+        .into_luma8();
+
+    let mut hash: u64 = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            let brighter =
+                pixels.get_pixel(x, y)[0] < pixels.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | u64::from(brighter);
+        }
+    }
+
+    // stored as INT8, only the bits matter
+    Some(i64::from_be_bytes(hash.to_be_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, GrayImage, ImageOutputFormat, Luma};
+
+    use super::*;
+
+    fn encode_png(image: GrayImage) -> Vec<u8> {
+        let mut data = Cursor::new(Vec::new());
+        DynamicImage::ImageLuma8(image)
+            .write_to(&mut data, ImageOutputFormat::Png)
+            .unwrap();
+        data.into_inner()
+    }
+
+    fn gradient(width: u32, height: u32) -> GrayImage {
+        GrayImage::from_fn(width, height, |x, _| {
+            Luma([u8::try_from(x * 255 / width).unwrap()])
+        })
+    }
+
+    #[test]
+    fn hashes_resized_images_alike() {
+        let small = encode_png(gradient(90, 80));
+        let large = encode_png(gradient(900, 800));
+
+        let small_hash = image_perceptual_hash("image/png", &small).unwrap();
+        let large_hash = image_perceptual_hash("image/png", &large).unwrap();
+
+        assert!((small_hash ^ large_hash).count_ones() <= 4);
+    }
+
+    #[test]
+    fn skips_images_beyond_limits() {
+        let data = encode_png(gradient(MAX_HASHED_IMAGE_DIMENSION + 1, 1));
+
+        assert_eq!(image_perceptual_hash("image/png", &data), None);
+    }
+
+    #[test]
+    fn skips_other_content_types() {
+        let data = encode_png(gradient(90, 80));
+
+        assert_eq!(image_perceptual_hash("application/pdf", &data), None);
+        assert_eq!(image_dimensions("application/pdf", &data), None);
+    }
+
+    #[test]
+    fn reads_image_dimensions() {
+        let data = encode_png(gradient(90, 80));
+
+        assert_eq!(image_dimensions("image/png", &data), Some((90, 80)));
+    }
+}
@@ -1,25 +1,580 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
 use std::time::Duration;
 
+use aws_config::SdkConfig;
 use aws_credential_types::Credentials;
 use aws_sdk_s3::config::Region;
+use aws_sdk_s3::error::{ProvideErrorMetadata, SdkError};
 use aws_sdk_s3::presigning::PresigningConfig;
 use aws_sdk_s3::primitives::ByteStream;
-use aws_sdk_s3::types::{CompletedMultipartUpload, CompletedPart};
+use aws_sdk_s3::types::{
+    CompletedMultipartUpload, CompletedPart, ServerSideEncryption,
+};
 use aws_sdk_s3::Client;
+use chrono::{DateTime, Utc};
+use moka::future::Cache;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use sha2::{Digest, Sha256};
 use tonic::Status;
+use uuid::Uuid;
+
+use crate::config::BucketConfig;
+use crate::StatusBuilder;
+
+/// Metadata written to stored objects so that bucket-side tooling can
+/// attribute them without querying the database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectTag {
+    ShopId,
+    MediaId,
+    ContentCategory,
+}
+
+impl ObjectTag {
+    fn key(&self) -> &'static str {
+        match self {
+            Self::ShopId => "shop-id",
+            Self::MediaId => "media-id",
+            Self::ContentCategory => "content-category",
+        }
+    }
+
+    /// Parses comma separated list like `shop_id,media_id,content_category`
+    pub fn parse_list(tags: &str) -> Result<Vec<Self>, String> {
+        tags.split(',')
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(Self::from_str)
+            .collect()
+    }
+}
+
+impl FromStr for ObjectTag {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "shop_id" => Ok(Self::ShopId),
+            "media_id" => Ok(Self::MediaId),
+            "content_category" => Ok(Self::ContentCategory),
+            _ => Err(format!("unknown object tag '{s}'")),
+        }
+    }
+}
+
+/// How keys of objects storing uploaded files are derived
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ObjectKeyStrategy {
+    /// `{shop_id}/{media_id}`, one object per media. Objects are grouped
+    /// by shop so bucket policies and lifecycle rules can target a shop.
+    #[default]
+    Path,
+    /// `content/{sha256}`, media with identical files share one object that
+    /// never changes and can be cached indefinitely
+    ContentHash,
+}
+
+impl ObjectKeyStrategy {
+    const CONTENT_KEY_PREFIX: &'static str = "content/";
+    const IMMUTABLE_CACHE_CONTROL: &'static str =
+        "public, max-age=31536000, immutable";
+
+    /// Key derived from `data`, `None` if keys do not depend on content
+    pub fn content_key(&self, data: &[u8]) -> Option<String> {
+        match self {
+            Self::Path => None,
+            Self::ContentHash => Some(format!(
+                "{}{:x}",
+                Self::CONTENT_KEY_PREFIX,
+                Sha256::digest(data)
+            )),
+        }
+    }
+
+    /// Hex SHA-256 of the file stored at `object_key` if the key was
+    /// derived from its content, possibly behind a storage route prefix
+    pub fn content_hash(object_key: &str) -> Option<&str> {
+        let (_, hash) = object_key.rsplit_once(Self::CONTENT_KEY_PREFIX)?;
+
+        (hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+            .then_some(hash)
+    }
+
+    /// Key of the object of a media if keys do not depend on content.
+    /// `legacy` keys are additionally prefixed by the owner as before keys
+    /// were grouped by shop.
+    pub fn path_key(
+        user_id: &String,
+        shop_id: &Uuid,
+        media_id: &Uuid,
+        legacy: bool,
+    ) -> String {
+        if legacy {
+            format!("{user_id}/{shop_id}/{media_id}")
+        } else {
+            format!("{shop_id}/{media_id}")
+        }
+    }
+
+    /// Version of the layout returned by `path_key`, exposed to clients
+    /// deriving keys themselves. `1` is the legacy layout.
+    pub fn path_key_version(legacy: bool) -> u32 {
+        if legacy {
+            1
+        } else {
+            2
+        }
+    }
+
+    fn cache_control(file_path: &str) -> Option<String> {
+        // content keys may be prefixed by a storage route
+        (file_path.starts_with(Self::CONTENT_KEY_PREFIX)
+            || file_path.contains(&format!("/{}", Self::CONTENT_KEY_PREFIX)))
+        .then(|| Self::IMMUTABLE_CACHE_CONTROL.to_owned())
+    }
+}
+
+impl FromStr for ObjectKeyStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "path" => Ok(Self::Path),
+            "content_hash" => Ok(Self::ContentHash),
+            _ => Err(format!("unknown object key strategy '{s}'")),
+        }
+    }
+}
+
+/// Lifetimes of presigned urls
+#[derive(Debug, Clone, Copy)]
+pub struct PresignedUrlExpiry {
+    /// Download urls of buyers
+    pub download: Duration,
+    /// Download urls of owners, e.g. returned by `GetMedia`
+    pub owner_download: Duration,
+    /// Upper bound of lifetimes requested by clients
+    pub max: Duration,
+}
+
+impl Default for PresignedUrlExpiry {
+    fn default() -> Self {
+        Self {
+            download: Duration::from_secs(1800),
+            owner_download: Duration::from_secs(3600),
+            max: Duration::from_secs(86400),
+        }
+    }
+}
+
+impl PresignedUrlExpiry {
+    /// Returns `requested` capped at `max`, or `default` if not requested
+    pub fn bounded(
+        &self,
+        requested: Option<Duration>,
+        default: Duration,
+    ) -> Duration {
+        requested.unwrap_or(default).min(self.max)
+    }
+}
+
+/// Server-side encryption requested for written objects
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ObjectEncryption {
+    Aes256,
+    /// Uses the key managed by the provider if `key_id` is `None`
+    Kms {
+        key_id: Option<String>,
+    },
+}
+
+impl ObjectEncryption {
+    /// Parses `AES256` or `aws:kms`, an empty algorithm disables encryption
+    pub fn parse(
+        algorithm: &str,
+        kms_key_id: Option<String>,
+    ) -> Result<Option<Self>, String> {
+        match algorithm.trim() {
+            "" => Ok(None),
+            "AES256" => Ok(Some(Self::Aes256)),
+            "aws:kms" => Ok(Some(Self::Kms {
+                key_id: kms_key_id.filter(|k| !k.trim().is_empty()),
+            })),
+            _ => Err(format!("unknown encryption algorithm '{algorithm}'")),
+        }
+    }
+
+    fn algorithm(&self) -> ServerSideEncryption {
+        match self {
+            Self::Aes256 => ServerSideEncryption::Aes256,
+            Self::Kms { .. } => ServerSideEncryption::AwsKms,
+        }
+    }
+
+    fn kms_key_id(&self) -> Option<String> {
+        match self {
+            Self::Aes256 => None,
+            Self::Kms { key_id } => key_id.clone(),
+        }
+    }
+}
+
+/// Bucket and key prefix uploads of a content category are stored at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageRoute {
+    pub bucket_name: String,
+    pub key_prefix: Option<String>,
+}
+
+impl StorageRoute {
+    /// Parses comma separated `category=bucket[/prefix]` pairs like
+    /// `image=media-cdn/images,video=media-archive`. The category is the
+    /// part of the content type before `/`.
+    pub fn parse_map(routes: &str) -> Result<HashMap<String, Self>, String> {
+        routes
+            .split(',')
+            .map(str::trim)
+            .filter(|r| !r.is_empty())
+            .map(|route| {
+                let (category, location) =
+                    route.split_once('=').ok_or_else(|| {
+                        format!("invalid storage route '{route}'")
+                    })?;
+                let (bucket_name, key_prefix) = match location.split_once('/') {
+                    Some((bucket_name, key_prefix)) => (
+                        bucket_name,
+                        Some(key_prefix.trim_matches('/').to_owned())
+                            .filter(|p| !p.is_empty()),
+                    ),
+                    None => (location, None),
+                };
+                if category.is_empty() || bucket_name.is_empty() {
+                    return Err(format!("invalid storage route '{route}'"));
+                }
+                Ok((
+                    category.to_lowercase(),
+                    Self {
+                        bucket_name: bucket_name.to_owned(),
+                        key_prefix,
+                    },
+                ))
+            })
+            .collect()
+    }
+
+    pub fn key(&self, file_path: String) -> String {
+        match &self.key_prefix {
+            Some(key_prefix) => format!("{key_prefix}/{file_path}"),
+            None => file_path,
+        }
+    }
+}
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Characters escaped in copy sources, keeping `/` between bucket and key
+const COPY_SOURCE_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~')
+    .remove(b'/');
+
+/// Errors of storage operations
+#[derive(Debug)]
+pub enum FileError {
+    ObjectNotFound {
+        key: String,
+    },
+    /// The credentials of the service are not allowed to access the bucket
+    BucketPermissionDenied,
+    BucketUnreachable {
+        bucket_name: String,
+        source: BoxError,
+    },
+    UploadFailed {
+        key: String,
+        source: BoxError,
+    },
+    DownloadFailed {
+        key: String,
+        source: BoxError,
+    },
+    DeleteFailed {
+        key: String,
+        source: BoxError,
+    },
+    MultipartOperationFailed {
+        upload_id: String,
+        source: BoxError,
+    },
+    /// The configured KMS key is missing or not usable
+    EncryptionConfigError {
+        source: BoxError,
+    },
+    InvalidPartNumber(u32),
+    /// Completing a multipart upload failed because parts were not uploaded
+    /// or were uploaded with different content than listed
+    InvalidParts {
+        upload_id: String,
+        missing: Vec<u32>,
+        mismatched: Vec<u32>,
+    },
+    /// A shop bucket references a secret access key that is not configured
+    MissingBucketSecret {
+        reference: String,
+    },
+}
+
+impl FileError {
+    /// Classifies a failed request concerning the object at `key`. Failures
+    /// not caused by permissions or a missing object are built by `other`.
+    fn from_sdk<E>(
+        err: SdkError<E>,
+        key: &String,
+        other: impl FnOnce(BoxError) -> Self,
+    ) -> Self
+    where
+        SdkError<E>:
+            ProvideErrorMetadata + std::error::Error + Send + Sync + 'static,
+    {
+        match err.code() {
+            Some("AccessDenied") => Self::BucketPermissionDenied,
+            Some(code) if code.starts_with("KMS") => {
+                Self::EncryptionConfigError {
+                    source: Box::new(err),
+                }
+            }
+            Some("NoSuchKey") => Self::ObjectNotFound {
+                key: key.to_owned(),
+            },
+            _ => other(Box::new(err)),
+        }
+    }
+
+    fn download<E>(err: SdkError<E>, key: &String) -> Self
+    where
+        SdkError<E>:
+            ProvideErrorMetadata + std::error::Error + Send + Sync + 'static,
+    {
+        Self::from_sdk(err, key, |source| Self::DownloadFailed {
+            key: key.to_owned(),
+            source,
+        })
+    }
+
+    fn multipart<E>(err: SdkError<E>, key: &String, upload_id: &String) -> Self
+    where
+        SdkError<E>:
+            ProvideErrorMetadata + std::error::Error + Send + Sync + 'static,
+    {
+        Self::from_sdk(err, key, |source| Self::MultipartOperationFailed {
+            upload_id: upload_id.to_owned(),
+            source,
+        })
+    }
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ObjectNotFound { key } => write!(f, "object {key} not found"),
+            Self::BucketPermissionDenied => {
+                write!(f, "permission to access bucket denied")
+            }
+            Self::BucketUnreachable {
+                bucket_name,
+                source,
+            } => write!(f, "bucket {bucket_name} unreachable: {source}"),
+            Self::UploadFailed { key, source } => {
+                write!(f, "upload of {key} failed: {source}")
+            }
+            Self::DownloadFailed { key, source } => {
+                write!(f, "download of {key} failed: {source}")
+            }
+            Self::DeleteFailed { key, source } => {
+                write!(f, "deleting {key} failed: {source}")
+            }
+            Self::MultipartOperationFailed { upload_id, source } => {
+                write!(f, "multipart upload {upload_id} failed: {source}")
+            }
+            Self::EncryptionConfigError { source } => {
+                write!(f, "encryption misconfigured: {source}")
+            }
+            Self::InvalidPartNumber(part_number) => {
+                write!(f, "invalid part number {part_number}")
+            }
+            Self::InvalidParts {
+                upload_id,
+                missing,
+                mismatched,
+            } => write!(
+                f,
+                "multipart upload {upload_id} has missing parts {missing:?} and mismatched parts {mismatched:?}"
+            ),
+            Self::MissingBucketSecret { reference } => {
+                write!(f, "bucket secret {reference} is not configured")
+            }
+        }
+    }
+}
+
+impl std::error::Error for FileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::BucketUnreachable { source, .. }
+            | Self::UploadFailed { source, .. }
+            | Self::DownloadFailed { source, .. }
+            | Self::DeleteFailed { source, .. }
+            | Self::MultipartOperationFailed { source, .. }
+            | Self::EncryptionConfigError { source } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<FileError> for Status {
+    fn from(err: FileError) -> Self {
+        match err {
+            FileError::ObjectNotFound { key } => {
+                tracing::log::warn!("[FileService]: object {key} not found");
+                Status::not_found("file")
+            }
+            // the service's credentials are misconfigured or were revoked,
+            // nothing the client can change
+            FileError::BucketUnreachable { .. }
+            | FileError::BucketPermissionDenied => {
+                tracing::log::error!("[FileService]: {err}");
+                Status::unavailable("")
+            }
+            FileError::InvalidPartNumber(_) => {
+                Status::invalid_argument("part_number")
+            }
+            FileError::InvalidParts {
+                ref missing,
+                ref mismatched,
+                ..
+            } => {
+                let builder = StatusBuilder::failed_precondition(format!(
+                    "parts must be uploaded again, missing: {missing:?}, mismatched: {mismatched:?}"
+                ));
+                missing
+                    .iter()
+                    .map(|part_number| (part_number, "MISSING", "not uploaded"))
+                    .chain(mismatched.iter().map(|part_number| {
+                        (part_number, "MISMATCHED", "etag does not match")
+                    }))
+                    .fold(
+                        builder,
+                        |builder, (part_number, kind, description)| {
+                            builder.with_precondition_violation(
+                                kind,
+                                format!("part {part_number}"),
+                                description,
+                            )
+                        },
+                    )
+                    .build()
+            }
+            FileError::EncryptionConfigError { .. }
+            | FileError::UploadFailed { .. }
+            | FileError::DownloadFailed { .. }
+            | FileError::DeleteFailed { .. }
+            | FileError::MultipartOperationFailed { .. }
+            | FileError::MissingBucketSecret { .. } => {
+                tracing::log::error!("[FileService]: {err}");
+                Status::internal("")
+            }
+        }
+    }
+}
+
+/// Values of the media an object belongs to
+#[derive(Debug, Clone)]
+pub struct ObjectTagValues {
+    pub shop_id: String,
+    pub media_id: String,
+    pub content_type: String,
+}
 
 #[derive(Debug, Clone)]
 pub struct FileService {
+    sdk_config: SdkConfig,
     client: Client,
     bucket_name: String,
+    object_tags: Vec<ObjectTag>,
+    secondary: Option<SecondaryRegion>,
+    read_replica: Option<ReadReplica>,
+    routes: HashMap<String, StorageRoute>,
+    encryption: Option<ObjectEncryption>,
+    shop_bucket_secrets: BucketSecrets,
+    /// Clients of other buckets, shared by all services derived from this
+    /// one
+    bucket_clients: Cache<BucketClientConfig, Client>,
+}
+
+/// Secret access keys of shop buckets by their reference, never printed
+#[derive(Clone, Default)]
+struct BucketSecrets(Arc<HashMap<String, String>>);
+
+impl fmt::Debug for BucketSecrets {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.0.keys()).finish()
+    }
+}
+
+/// Settings a client of another bucket is created with, clients are reused
+/// for buckets with the same settings
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct BucketClientConfig {
+    endpoint: Option<String>,
+    access_key_id: Option<String>,
+    secret_access_key: Option<String>,
+}
+
+impl fmt::Debug for BucketClientConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BucketClientConfig")
+            .field("endpoint", &self.endpoint)
+            .field("access_key_id", &self.access_key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Bucket of the same name in another region used when writing to the
+/// primary region fails
+#[derive(Debug, Clone)]
+struct SecondaryRegion {
+    region: String,
+    client: Client,
+}
+
+/// Replicated bucket endpoint serving reads. Objects written within
+/// `read_after_write_window` are read from the primary endpoint, as they
+/// might not be replicated yet.
+#[derive(Debug, Clone)]
+struct ReadReplica {
+    client: Client,
+    read_after_write_window: Duration,
 }
 
 impl FileService {
+    const BUCKET_CLIENTS_MAX_CAPACITY: u64 = 1000;
+    const BUCKET_CLIENTS_TIME_TO_IDLE: Duration = Duration::from_secs(3600);
+    /// Largest object a single `CopyObject` request can copy
+    const MAX_COPY_OBJECT_BYTES: i64 = 5 * 1024 * 1024 * 1024;
+    /// Part size of copies of larger objects, at most 10000 parts are allowed
+    const COPY_PART_BYTES: i64 = 512 * 1024 * 1024;
+
     pub async fn new(
         bucket_name: String,
         bucket_endpoint: String,
         access_key_id: String,
         secret_access_key: String,
+        object_tags: Vec<ObjectTag>,
     ) -> Self {
         let credentials =
             Credentials::from_keys(access_key_id, secret_access_key, None);
@@ -34,31 +589,342 @@ impl FileService {
         let client = Client::new(&config);
 
         Self {
+            sdk_config: config,
             bucket_name,
             client,
+            object_tags,
+            secondary: None,
+            read_replica: None,
+            routes: HashMap::new(),
+            encryption: None,
+            shop_bucket_secrets: BucketSecrets::default(),
+            bucket_clients: Cache::builder()
+                .max_capacity(Self::BUCKET_CLIENTS_MAX_CAPACITY)
+                .time_to_idle(Self::BUCKET_CLIENTS_TIME_TO_IDLE)
+                .build(),
+        }
+    }
+
+    /// Builds the service of the configured bucket including its secondary
+    /// region, encryption, routes and read replica
+    pub async fn from_config(config: BucketConfig) -> Self {
+        let mut file_service = Self::new(
+            config.name,
+            config.endpoint,
+            config.access_key_id,
+            config.secret_access_key,
+            config.object_tags,
+        )
+        .await;
+
+        if let Some((endpoint, region)) = config.secondary_region {
+            tracing::log::info!("Bucket secondary region: {region}");
+            file_service = file_service.with_secondary_region(endpoint, region);
+        }
+
+        tracing::log::info!(
+            "Bucket server-side encryption: {:?}",
+            config.encryption
+        );
+        file_service = file_service.with_encryption(config.encryption);
+
+        if let Some(routes) = config.content_routes {
+            tracing::log::info!("Bucket content routes: {routes:?}");
+            file_service = file_service.with_routes(routes);
+        }
+
+        if let Some(endpoint) = config.read_endpoint {
+            tracing::log::info!("Bucket read endpoint: {endpoint}");
+            file_service = file_service
+                .with_read_replica(endpoint, config.read_after_write_window);
+        }
+
+        file_service.shop_bucket_secrets =
+            BucketSecrets(Arc::new(config.shop_bucket_secrets));
+
+        file_service
+    }
+
+    /// Resolves the secret access key a shop bucket references
+    pub fn shop_bucket_secret(
+        &self,
+        reference: &str,
+    ) -> Result<String, FileError> {
+        self.shop_bucket_secrets
+            .0
+            .get(reference)
+            .cloned()
+            .ok_or_else(|| FileError::MissingBucketSecret {
+                reference: reference.to_owned(),
+            })
+    }
+
+    /// Enables failover of uploads to the same bucket at `bucket_endpoint`
+    pub fn with_secondary_region(
+        mut self,
+        bucket_endpoint: String,
+        region: String,
+    ) -> Self {
+        let config = aws_sdk_s3::config::Builder::from(&self.sdk_config)
+            .endpoint_url(bucket_endpoint)
+            .region(Region::new(region.clone()))
+            .build();
+
+        self.secondary = Some(SecondaryRegion {
+            region,
+            client: Client::from_conf(config),
+        });
+        self
+    }
+
+    /// Stores uploads of the content categories in `routes` at their own
+    /// bucket and prefix instead of the default bucket
+    pub fn with_routes(
+        mut self,
+        routes: HashMap<String, StorageRoute>,
+    ) -> Self {
+        self.routes = routes;
+        self
+    }
+
+    /// Encrypts objects written from now on with `encryption`
+    pub fn with_encryption(
+        mut self,
+        encryption: Option<ObjectEncryption>,
+    ) -> Self {
+        self.encryption = encryption;
+        self
+    }
+
+    /// Returns the route configured for the category of `content_type`
+    pub fn route(&self, content_type: &str) -> Option<&StorageRoute> {
+        let category = content_type.split('/').next().unwrap_or_default();
+        self.routes.get(&category.trim().to_lowercase())
+    }
+
+    /// Serves reads from the replicated bucket at `bucket_endpoint`
+    pub fn with_read_replica(
+        mut self,
+        bucket_endpoint: String,
+        read_after_write_window: Duration,
+    ) -> Self {
+        let config = aws_sdk_s3::config::Builder::from(&self.sdk_config)
+            .endpoint_url(bucket_endpoint)
+            .build();
+
+        self.read_replica = Some(ReadReplica {
+            client: Client::from_conf(config),
+            read_after_write_window,
+        });
+        self
+    }
+
+    /// Returns a `FileService` reading from the primary endpoint if the
+    /// object was written at `written_at` within the read-after-write window
+    pub fn for_object_written_at(&self, written_at: DateTime<Utc>) -> Self {
+        let mut file_service = self.clone();
+
+        if let Some(read_replica) = &self.read_replica {
+            let elapsed =
+                (Utc::now() - written_at).to_std().unwrap_or_default();
+            if elapsed < read_replica.read_after_write_window {
+                file_service.read_replica = None;
+            }
+        }
+
+        file_service
+    }
+
+    fn read_client(&self) -> &Client {
+        self.read_replica
+            .as_ref()
+            .map(|r| &r.client)
+            .unwrap_or(&self.client)
+    }
+
+    /// Returns a `FileService` targeting `storage_region` as stored with a
+    /// media, `None` being the primary region.
+    pub fn in_region(&self, storage_region: Option<&String>) -> Self {
+        match (&self.secondary, storage_region) {
+            (Some(secondary), Some(region)) if secondary.region == *region => {
+                Self {
+                    sdk_config: self.sdk_config.clone(),
+                    client: secondary.client.clone(),
+                    bucket_name: self.bucket_name.clone(),
+                    object_tags: self.object_tags.clone(),
+                    secondary: None,
+                    read_replica: None,
+                    routes: HashMap::new(),
+                    encryption: self.encryption.clone(),
+                    shop_bucket_secrets: self.shop_bucket_secrets.clone(),
+                    bucket_clients: self.bucket_clients.clone(),
+                }
+            }
+            _ => self.clone(),
+        }
+    }
+
+    pub fn bucket_name(&self) -> &String {
+        &self.bucket_name
+    }
+
+    /// Encryption of objects written to the bucket of this service
+    pub fn encryption(&self) -> Option<&ObjectEncryption> {
+        self.encryption.as_ref()
+    }
+
+    /// Returns a `FileService` targeting another bucket. Endpoint and
+    /// credentials fall back to the ones of the default bucket, encryption
+    /// does not as the bucket might not support it.
+    pub async fn with_bucket(
+        &self,
+        bucket_name: String,
+        bucket_endpoint: Option<String>,
+        access_key_id: Option<String>,
+        secret_access_key: Option<String>,
+        encryption: Option<ObjectEncryption>,
+    ) -> Self {
+        let client_config = BucketClientConfig {
+            endpoint: bucket_endpoint,
+            access_key_id,
+            secret_access_key,
+        };
+        let client = self
+            .bucket_clients
+            .get_with(client_config.clone(), async {
+                self.build_bucket_client(&client_config)
+            })
+            .await;
+
+        Self {
+            sdk_config: self.sdk_config.clone(),
+            client,
+            bucket_name,
+            object_tags: self.object_tags.clone(),
+            secondary: None,
+            read_replica: None,
+            routes: HashMap::new(),
+            encryption,
+            shop_bucket_secrets: self.shop_bucket_secrets.clone(),
+            bucket_clients: self.bucket_clients.clone(),
         }
     }
 
+    fn build_bucket_client(
+        &self,
+        client_config: &BucketClientConfig,
+    ) -> Client {
+        let mut config_builder =
+            aws_sdk_s3::config::Builder::from(&self.sdk_config);
+
+        if let Some(bucket_endpoint) = &client_config.endpoint {
+            config_builder = config_builder.endpoint_url(bucket_endpoint);
+        }
+
+        if let (Some(access_key_id), Some(secret_access_key)) = (
+            &client_config.access_key_id,
+            &client_config.secret_access_key,
+        ) {
+            config_builder = config_builder.credentials_provider(
+                Credentials::from_keys(access_key_id, secret_access_key, None),
+            );
+        }
+
+        Client::from_conf(config_builder.build())
+    }
+
+    fn get_object_metadata(
+        &self,
+        tag_values: &ObjectTagValues,
+    ) -> Option<HashMap<String, String>> {
+        if self.object_tags.is_empty() {
+            return None;
+        }
+
+        Some(
+            self.object_tags
+                .iter()
+                .map(|tag| {
+                    let value = match tag {
+                        ObjectTag::ShopId => tag_values.shop_id.clone(),
+                        ObjectTag::MediaId => tag_values.media_id.clone(),
+                        ObjectTag::ContentCategory => tag_values
+                            .content_type
+                            .split('/')
+                            .next()
+                            .unwrap_or_default()
+                            .to_string(),
+                    };
+                    (tag.key().to_string(), value)
+                })
+                .collect(),
+        )
+    }
+
+    /// Returns the secondary region if the file had to be written there
     pub async fn put_file(
         &self,
         file_path: &String,
         file_data: &[u8],
         content_type: &String,
-    ) -> Result<(), Status> {
-        self.client
-            .put_object()
-            .bucket(&self.bucket_name)
-            .key(file_path)
-            .body(ByteStream::from(file_data.to_vec()))
-            .content_type(content_type)
-            .send()
-            .await
-            .map_err(|err| {
-                tracing::log::error!("[FileService.put_file]: {err}");
-                Status::internal("")
-            })?;
+        tag_values: &ObjectTagValues,
+    ) -> Result<Option<String>, FileError> {
+        let put_object = |client: &Client| {
+            client
+                .put_object()
+                .bucket(&self.bucket_name)
+                .key(file_path)
+                .body(ByteStream::from(file_data.to_vec()))
+                .content_type(content_type)
+                .set_cache_control(ObjectKeyStrategy::cache_control(file_path))
+                .set_metadata(self.get_object_metadata(tag_values))
+                .set_server_side_encryption(
+                    self.encryption.as_ref().map(ObjectEncryption::algorithm),
+                )
+                .set_ssekms_key_id(
+                    self.encryption
+                        .as_ref()
+                        .and_then(ObjectEncryption::kms_key_id),
+                )
+                .send()
+        };
 
-        Ok(())
+        let primary_err = match put_object(&self.client).await {
+            Ok(_) => return Ok(None),
+            Err(err) => err,
+        };
+
+        let Some(secondary) = &self.secondary else {
+            return Err(FileError::from_sdk(
+                primary_err,
+                file_path,
+                |source| FileError::UploadFailed {
+                    key: file_path.to_owned(),
+                    source,
+                },
+            ));
+        };
+
+        match put_object(&secondary.client).await {
+            Ok(_) => {
+                tracing::log::warn!(
+                    "[FileService.put_file]: wrote {file_path} to secondary region {} after primary failed: {primary_err}",
+                    secondary.region
+                );
+                Ok(Some(secondary.region.clone()))
+            }
+            Err(err) => {
+                tracing::log::error!(
+                    "[FileService.put_file]: primary failed: {primary_err}"
+                );
+                Err(FileError::from_sdk(err, file_path, |source| {
+                    FileError::UploadFailed {
+                        key: file_path.to_owned(),
+                        source,
+                    }
+                }))
+            }
+        }
     }
 
     /// Returns `upload_id`
@@ -66,27 +932,38 @@ impl FileService {
         &self,
         file_path: &String,
         content_type: &String,
-    ) -> Result<String, Status> {
+        tag_values: &ObjectTagValues,
+    ) -> Result<String, FileError> {
         let response = self
             .client
             .create_multipart_upload()
             .bucket(&self.bucket_name)
             .key(file_path)
             .content_type(content_type)
+            .set_metadata(self.get_object_metadata(tag_values))
+            .set_server_side_encryption(
+                self.encryption.as_ref().map(ObjectEncryption::algorithm),
+            )
+            .set_ssekms_key_id(
+                self.encryption
+                    .as_ref()
+                    .and_then(ObjectEncryption::kms_key_id),
+            )
             .send()
             .await
             .map_err(|err| {
-                tracing::log::error!(
-                    "[FileService.initiate_multipart_upload]: {err}"
-                );
-                Status::internal("")
+                FileError::from_sdk(err, file_path, |source| {
+                    FileError::UploadFailed {
+                        key: file_path.to_owned(),
+                        source,
+                    }
+                })
             })?;
 
-        if let Some(upload_id) = response.upload_id {
-            Ok(upload_id)
-        } else {
-            Err(Status::data_loss("upload_id"))
-        }
+        response.upload_id.ok_or_else(|| FileError::UploadFailed {
+            key: file_path.to_owned(),
+            source: "response is missing upload_id".into(),
+        })
     }
 
     /// Returns `e_tag`
@@ -96,10 +973,10 @@ impl FileService {
         upload_id: &String,
         part_number: u32,
         file_data: &[u8],
-    ) -> Result<String, Status> {
+    ) -> Result<String, FileError> {
         let part_number = part_number
             .try_into()
-            .map_err(|_| Status::invalid_argument("part_number"))?;
+            .map_err(|_| FileError::InvalidPartNumber(part_number))?;
 
         let part = self
             .client
@@ -111,27 +988,78 @@ impl FileService {
             .body(ByteStream::from(file_data.to_vec()))
             .send()
             .await
-            .map_err(|err| {
-                tracing::log::error!(
-                    "[FileService.put_multipart_chunk]: {err}"
-                );
-                Status::internal("")
-            })?;
+            .map_err(|err| FileError::multipart(err, file_path, upload_id))?;
 
         Ok(part.e_tag.unwrap_or_default())
     }
 
+    /// Returns all parts uploaded so far as `(part_number, e_tag)`
+    pub async fn list_parts(
+        &self,
+        file_path: &String,
+        upload_id: &String,
+    ) -> Result<Vec<(u32, String)>, FileError> {
+        let mut parts = Vec::new();
+        let mut part_number_marker = None;
+
+        loop {
+            let response = self
+                .client
+                .list_parts()
+                .bucket(&self.bucket_name)
+                .key(file_path)
+                .upload_id(upload_id)
+                .set_part_number_marker(part_number_marker)
+                .send()
+                .await
+                .map_err(|err| {
+                    FileError::multipart(err, file_path, upload_id)
+                })?;
+
+            for part in response.parts().unwrap_or_default() {
+                if let Ok(part_number) = u32::try_from(part.part_number()) {
+                    parts.push((
+                        part_number,
+                        part.e_tag().unwrap_or_default().to_string(),
+                    ));
+                }
+            }
+
+            if !response.is_truncated() {
+                break;
+            }
+
+            part_number_marker =
+                response.next_part_number_marker().map(String::from);
+        }
+
+        Ok(parts)
+    }
+
     pub async fn complete_multipart_upload(
         &self,
         file_path: &String,
         upload_id: &String,
         parts: Vec<CompletedPart>,
-    ) -> Result<(), Status> {
+    ) -> Result<(), FileError> {
         let completed_multipart_upload = CompletedMultipartUpload::builder()
             .set_parts(Some(parts))
             .build();
 
-        self.client
+        let requested_parts: Vec<_> = completed_multipart_upload
+            .parts()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|part| {
+                Some((
+                    u32::try_from(part.part_number()).ok()?,
+                    part.e_tag().unwrap_or_default().to_string(),
+                ))
+            })
+            .collect();
+
+        match self
+            .client
             .complete_multipart_upload()
             .bucket(&self.bucket_name)
             .key(file_path)
@@ -139,21 +1067,63 @@ impl FileService {
             .multipart_upload(completed_multipart_upload)
             .send()
             .await
-            .map_err(|err| {
-                tracing::log::error!(
-                    "[FileService.complete_multipart_upload]: {err}"
-                );
-                Status::internal("")
-            })?;
+        {
+            Ok(_) => Ok(()),
+            // S3 does not tell which parts are invalid, compare with the
+            // uploaded parts to let clients upload just those again
+            Err(err) if err.code() == Some("InvalidPart") => {
+                let uploaded_parts =
+                    self.list_parts(file_path, upload_id).await?;
+                let (missing, mismatched) =
+                    Self::diff_parts(&requested_parts, &uploaded_parts);
 
-        Ok(())
+                if missing.is_empty() && mismatched.is_empty() {
+                    Err(FileError::multipart(err, file_path, upload_id))
+                } else {
+                    Err(FileError::InvalidParts {
+                        upload_id: upload_id.to_owned(),
+                        missing,
+                        mismatched,
+                    })
+                }
+            }
+            Err(err) => Err(FileError::multipart(err, file_path, upload_id)),
+        }
+    }
+
+    /// Returns the part numbers of `requested` that were not uploaded and
+    /// those uploaded with another etag. Etags are compared without quotes.
+    fn diff_parts(
+        requested: &[(u32, String)],
+        uploaded: &[(u32, String)],
+    ) -> (Vec<u32>, Vec<u32>) {
+        let uploaded: HashMap<_, _> = uploaded
+            .iter()
+            .map(|(part_number, etag)| (*part_number, etag.trim_matches('"')))
+            .collect();
+
+        let mut missing = Vec::new();
+        let mut mismatched = Vec::new();
+        for (part_number, etag) in requested {
+            match uploaded.get(part_number) {
+                None => missing.push(*part_number),
+                Some(uploaded_etag)
+                    if *uploaded_etag != etag.trim_matches('"') =>
+                {
+                    mismatched.push(*part_number)
+                }
+                Some(_) => {}
+            }
+        }
+
+        (missing, mismatched)
     }
 
     pub async fn abort_multipart_upload(
         &self,
         file_path: &String,
         upload_id: &String,
-    ) -> Result<(), Status> {
+    ) -> Result<(), FileError> {
         self.client
             .abort_multipart_upload()
             .bucket(&self.bucket_name)
@@ -161,31 +1131,65 @@ impl FileService {
             .upload_id(upload_id)
             .send()
             .await
-            .map_err(|err| {
-                tracing::log::error!(
-                    "[FileService.abort_multipart_upload]: {err}"
-                );
-                Status::internal("")
-            })?;
+            .map_err(|err| FileError::multipart(err, file_path, upload_id))?;
 
         Ok(())
     }
 
+    pub async fn object_exists(
+        &self,
+        file_path: &String,
+    ) -> Result<bool, FileError> {
+        match self
+            .client
+            .head_object()
+            .bucket(&self.bucket_name)
+            .key(file_path)
+            .send()
+            .await
+        {
+            Ok(_) => Ok(true),
+            Err(SdkError::ServiceError(err)) if err.err().is_not_found() => {
+                Ok(false)
+            }
+            Err(err) => Err(FileError::download(err, file_path)),
+        }
+    }
+
+    pub async fn get_etag(
+        &self,
+        file_path: &String,
+    ) -> Result<String, FileError> {
+        let head = self
+            .read_client()
+            .head_object()
+            .bucket(&self.bucket_name)
+            .key(file_path)
+            .send()
+            .await
+            .map_err(|err| FileError::download(err, file_path))?;
+
+        Ok(head
+            .e_tag()
+            .unwrap_or_default()
+            .trim_matches('"')
+            .to_string())
+    }
+
     pub async fn get_presigned_url(
         &self,
         file_path: &String,
         file_name: &String,
-    ) -> Result<String, Status> {
-        let presigned_config = PresigningConfig::expires_in(
-            Duration::from_secs(1800),
-        )
-        .map_err(|err| {
-            tracing::log::error!("[FileService.get_presigned_url]: {err}");
-            Status::internal("")
-        })?;
+        expires_in: Duration,
+    ) -> Result<String, FileError> {
+        let presigned_config = PresigningConfig::expires_in(expires_in)
+            .map_err(|err| FileError::DownloadFailed {
+                key: file_path.to_owned(),
+                source: Box::new(err),
+            })?;
 
         let uri = self
-            .client
+            .read_client()
             .get_object()
             .bucket(&self.bucket_name)
             .key(file_path)
@@ -194,17 +1198,246 @@ impl FileService {
             ))
             .presigned(presigned_config)
             .await
-            .map_err(|err| {
-                tracing::log::error!("[FileService.get_presigned_url]: {err}");
-                Status::internal("")
-            })?
+            .map_err(|err| FileError::download(err, file_path))?
             .uri()
             .clone();
 
         Ok(uri.to_string())
     }
 
-    pub async fn remove_file(&self, file_path: &String) -> Result<(), Status> {
+    /// Returns bytes `start` to `end` (both inclusive) of the file
+    pub async fn get_object_range(
+        &self,
+        file_path: &String,
+        start: u64,
+        end: u64,
+    ) -> Result<Vec<u8>, FileError> {
+        let response = self
+            .read_client()
+            .get_object()
+            .bucket(&self.bucket_name)
+            .key(file_path)
+            .range(format!("bytes={start}-{end}"))
+            .send()
+            .await
+            .map_err(|err| FileError::download(err, file_path))?;
+
+        let data = response.body.collect().await.map_err(|err| {
+            FileError::DownloadFailed {
+                key: file_path.to_owned(),
+                source: Box::new(err),
+            }
+        })?;
+
+        Ok(data.into_bytes().to_vec())
+    }
+
+    /// Returns the file contents starting at byte `offset`
+    pub async fn get_object_from(
+        &self,
+        file_path: &String,
+        offset: u64,
+    ) -> Result<ByteStream, FileError> {
+        let response = self
+            .read_client()
+            .get_object()
+            .bucket(&self.bucket_name)
+            .key(file_path)
+            .range(format!("bytes={offset}-"))
+            .send()
+            .await
+            .map_err(|err| FileError::download(err, file_path))?;
+
+        Ok(response.body)
+    }
+
+    /// Checks the bucket exists and is accessible with the credentials
+    pub async fn head_bucket(&self) -> Result<(), FileError> {
+        self.client
+            .head_bucket()
+            .bucket(&self.bucket_name)
+            .send()
+            .await
+            .map_err(|err| {
+                FileError::from_sdk(err, &self.bucket_name, |source| {
+                    FileError::BucketUnreachable {
+                        bucket_name: self.bucket_name.clone(),
+                        source,
+                    }
+                })
+            })?;
+
+        Ok(())
+    }
+
+    /// `bucket/key` of `key` as copy source, which has to be URL-encoded
+    fn copy_source(&self, key: &str) -> String {
+        utf8_percent_encode(
+            &format!("{}/{key}", self.bucket_name),
+            COPY_SOURCE_ENCODE_SET,
+        )
+        .to_string()
+    }
+
+    /// Byte ranges of the parts an object of `size` bytes is copied in
+    fn copy_part_ranges(size: i64) -> Vec<String> {
+        (0..size)
+            .step_by(usize::try_from(Self::COPY_PART_BYTES).unwrap())
+            .map(|start| {
+                let end = (start + Self::COPY_PART_BYTES).min(size) - 1;
+                format!("bytes={start}-{end}")
+            })
+            .collect()
+    }
+
+    /// Copies the object at `from` including its metadata to `to`. Objects
+    /// larger than a single `CopyObject` request allows are copied in parts.
+    pub async fn copy_object(
+        &self,
+        from: &String,
+        to: &String,
+    ) -> Result<(), FileError> {
+        let head = self
+            .client
+            .head_object()
+            .bucket(&self.bucket_name)
+            .key(from)
+            .send()
+            .await
+            .map_err(|err| FileError::download(err, from))?;
+
+        if head.content_length() > Self::MAX_COPY_OBJECT_BYTES {
+            let upload_id = self
+                .initiate_multipart_copy(
+                    to,
+                    head.content_type(),
+                    head.metadata(),
+                )
+                .await?;
+
+            let result = self
+                .copy_parts(from, to, &upload_id, head.content_length())
+                .await;
+            if result.is_err() {
+                if let Err(err) =
+                    self.abort_multipart_upload(to, &upload_id).await
+                {
+                    tracing::log::error!(
+                        "[FileService.copy_object]: aborting copy to {to} failed: {err}"
+                    );
+                }
+            }
+            return result;
+        }
+
+        self.client
+            .copy_object()
+            .bucket(&self.bucket_name)
+            .copy_source(self.copy_source(from))
+            .key(to)
+            .set_server_side_encryption(
+                self.encryption.as_ref().map(ObjectEncryption::algorithm),
+            )
+            .set_ssekms_key_id(
+                self.encryption
+                    .as_ref()
+                    .and_then(ObjectEncryption::kms_key_id),
+            )
+            .send()
+            .await
+            .map_err(|err| {
+                FileError::from_sdk(err, from, |source| {
+                    FileError::UploadFailed {
+                        key: to.to_owned(),
+                        source,
+                    }
+                })
+            })?;
+
+        Ok(())
+    }
+
+    /// Multipart copies do not take over content type and metadata
+    async fn initiate_multipart_copy(
+        &self,
+        to: &String,
+        content_type: Option<&str>,
+        metadata: Option<&HashMap<String, String>>,
+    ) -> Result<String, FileError> {
+        let response = self
+            .client
+            .create_multipart_upload()
+            .bucket(&self.bucket_name)
+            .key(to)
+            .set_content_type(content_type.map(str::to_owned))
+            .set_cache_control(ObjectKeyStrategy::cache_control(to))
+            .set_metadata(metadata.cloned())
+            .set_server_side_encryption(
+                self.encryption.as_ref().map(ObjectEncryption::algorithm),
+            )
+            .set_ssekms_key_id(
+                self.encryption
+                    .as_ref()
+                    .and_then(ObjectEncryption::kms_key_id),
+            )
+            .send()
+            .await
+            .map_err(|err| {
+                FileError::from_sdk(err, to, |source| FileError::UploadFailed {
+                    key: to.to_owned(),
+                    source,
+                })
+            })?;
+
+        response.upload_id.ok_or_else(|| FileError::UploadFailed {
+            key: to.to_owned(),
+            source: "response is missing upload_id".into(),
+        })
+    }
+
+    async fn copy_parts(
+        &self,
+        from: &String,
+        to: &String,
+        upload_id: &String,
+        size: i64,
+    ) -> Result<(), FileError> {
+        let mut parts = Vec::new();
+
+        for (part_number, range) in (1..).zip(Self::copy_part_ranges(size)) {
+            let response = self
+                .client
+                .upload_part_copy()
+                .bucket(&self.bucket_name)
+                .key(to)
+                .upload_id(upload_id)
+                .part_number(part_number)
+                .copy_source(self.copy_source(from))
+                .copy_source_range(range)
+                .send()
+                .await
+                .map_err(|err| FileError::multipart(err, to, upload_id))?;
+
+            parts.push(
+                CompletedPart::builder()
+                    .set_e_tag(
+                        response
+                            .copy_part_result()
+                            .and_then(|result| result.e_tag())
+                            .map(str::to_owned),
+                    )
+                    .part_number(part_number)
+                    .build(),
+            );
+        }
+
+        self.complete_multipart_upload(to, upload_id, parts).await
+    }
+
+    pub async fn remove_file(
+        &self,
+        file_path: &String,
+    ) -> Result<(), FileError> {
         self.client
             .delete_object()
             .bucket(&self.bucket_name)
@@ -212,10 +1445,349 @@ impl FileService {
             .send()
             .await
             .map_err(|err| {
-                tracing::log::error!("[FileService.remove_file]: {err}");
-                Status::internal("")
+                FileError::from_sdk(err, file_path, |source| {
+                    FileError::DeleteFailed {
+                        key: file_path.to_owned(),
+                        source,
+                    }
+                })
             })?;
 
         Ok(())
     }
 }
+
+/// Bucket in MinIO started in a container for tests of storage operations,
+/// needs docker
+#[cfg(test)]
+pub mod test_bucket {
+    use aws_sdk_s3::Client;
+    use testcontainers::core::WaitFor;
+    use testcontainers::{Container, GenericImage, RunnableImage};
+
+    use super::FileService;
+    use crate::db::test_db::docker;
+
+    const MINIO_PORT: u16 = 9000;
+
+    /// Bucket in a MinIO container, removed again on drop
+    pub struct TestBucket {
+        pub file_service: FileService,
+        _container: Container<'static, GenericImage>,
+    }
+
+    impl TestBucket {
+        pub async fn start() -> Self {
+            let image = GenericImage::new(
+                "minio/minio",
+                "RELEASE.2023-09-30T07-02-29Z",
+            )
+            .with_exposed_port(MINIO_PORT)
+            .with_wait_for(WaitFor::message_on_stdout("API:"));
+            let args = vec!["server".to_string(), "/data".to_string()];
+            let container = docker().run(RunnableImage::from((image, args)));
+
+            let mut file_service = FileService::new(
+                "media".to_string(),
+                format!(
+                    "http://127.0.0.1:{}",
+                    container.get_host_port_ipv4(MINIO_PORT)
+                ),
+                "minioadmin".to_string(),
+                "minioadmin".to_string(),
+                Vec::new(),
+            )
+            .await;
+            // MinIO does not resolve buckets from the host name
+            file_service.client = Client::from_conf(
+                aws_sdk_s3::config::Builder::from(&file_service.sdk_config)
+                    .force_path_style(true)
+                    .build(),
+            );
+            file_service
+                .client
+                .create_bucket()
+                .bucket(file_service.bucket_name())
+                .send()
+                .await
+                .unwrap();
+
+            Self {
+                file_service,
+                _container: container,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic::Code;
+
+    use super::test_bucket::TestBucket;
+    use super::*;
+
+    fn completed_part(part_number: i32, e_tag: &str) -> CompletedPart {
+        CompletedPart::builder()
+            .part_number(part_number)
+            .e_tag(e_tag)
+            .build()
+    }
+
+    #[tokio::test]
+    async fn multipart_upload_lifecycle() {
+        let bucket = TestBucket::start().await;
+        let file_service = &bucket.file_service;
+        let file_path = "user/shop/media".to_string();
+        let tag_values = ObjectTagValues {
+            shop_id: "shop".to_string(),
+            media_id: "media".to_string(),
+            content_type: "application/pdf".to_string(),
+        };
+
+        let upload_id = file_service
+            .initiate_multipart_upload(
+                &file_path,
+                &tag_values.content_type,
+                &tag_values,
+            )
+            .await
+            .unwrap();
+        // all parts but the last need at least 5 MiB
+        let first = file_service
+            .put_multipart_chunk(&file_path, &upload_id, 1, &[1; 5 << 20])
+            .await
+            .unwrap();
+        let second = file_service
+            .put_multipart_chunk(&file_path, &upload_id, 2, &[2; 1024])
+            .await
+            .unwrap();
+        assert_eq!(
+            file_service
+                .list_parts(&file_path, &upload_id)
+                .await
+                .unwrap(),
+            vec![(1, first.clone()), (2, second.clone())]
+        );
+
+        let err = file_service
+            .complete_multipart_upload(
+                &file_path,
+                &upload_id,
+                vec![completed_part(1, "\"0\""), completed_part(2, &second)],
+            )
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                &err,
+                FileError::InvalidParts { missing, mismatched, .. }
+                    if missing.is_empty() && *mismatched == [1]
+            ),
+            "{err}"
+        );
+
+        file_service
+            .complete_multipart_upload(
+                &file_path,
+                &upload_id,
+                vec![completed_part(1, &first), completed_part(2, &second)],
+            )
+            .await
+            .unwrap();
+        assert!(file_service.object_exists(&file_path).await.unwrap());
+        assert!(file_service
+            .get_etag(&file_path)
+            .await
+            .unwrap()
+            .ends_with("-2"));
+    }
+
+    #[tokio::test]
+    async fn aborted_multipart_upload_leaves_no_object() {
+        let bucket = TestBucket::start().await;
+        let file_service = &bucket.file_service;
+        let file_path = "user/shop/aborted".to_string();
+        let tag_values = ObjectTagValues {
+            shop_id: "shop".to_string(),
+            media_id: "aborted".to_string(),
+            content_type: "application/pdf".to_string(),
+        };
+
+        let upload_id = file_service
+            .initiate_multipart_upload(
+                &file_path,
+                &tag_values.content_type,
+                &tag_values,
+            )
+            .await
+            .unwrap();
+        file_service
+            .put_multipart_chunk(&file_path, &upload_id, 1, &[1; 1024])
+            .await
+            .unwrap();
+        file_service
+            .abort_multipart_upload(&file_path, &upload_id)
+            .await
+            .unwrap();
+
+        assert!(file_service
+            .list_parts(&file_path, &upload_id)
+            .await
+            .is_err());
+        assert!(!file_service.object_exists(&file_path).await.unwrap());
+    }
+
+    #[test]
+    fn bucket_secrets_are_not_printed() {
+        let secrets = BucketSecrets(Arc::new(HashMap::from([(
+            "shop_a".to_string(),
+            "secret-a".to_string(),
+        )])));
+        let client_config = BucketClientConfig {
+            endpoint: None,
+            access_key_id: Some("key-id".to_string()),
+            secret_access_key: Some("secret-a".to_string()),
+        };
+
+        for printed in [format!("{secrets:?}"), format!("{client_config:?}")] {
+            assert!(!printed.contains("secret-a"), "{printed}");
+        }
+        assert!(format!("{secrets:?}").contains("shop_a"));
+    }
+
+    async fn file_service() -> FileService {
+        FileService::new(
+            "media".to_string(),
+            "http://localhost:9000".to_string(),
+            "key-id".to_string(),
+            "secret".to_string(),
+            Vec::new(),
+        )
+        .await
+    }
+
+    #[test]
+    fn storage_errors_map_to_status() {
+        let code = |err: FileError| Status::from(err).code();
+
+        assert_eq!(
+            code(FileError::ObjectNotFound {
+                key: "key".to_string()
+            }),
+            Code::NotFound
+        );
+        assert_eq!(code(FileError::BucketPermissionDenied), Code::Unavailable);
+        assert_eq!(
+            code(FileError::InvalidPartNumber(0)),
+            Code::InvalidArgument
+        );
+        assert_eq!(
+            code(FileError::MissingBucketSecret {
+                reference: "shop_a".to_string()
+            }),
+            Code::Internal
+        );
+    }
+
+    #[tokio::test]
+    async fn copy_source_is_url_encoded() {
+        let file_service = file_service().await;
+
+        assert_eq!(
+            file_service.copy_source("user/shop/a file+1ä.pdf"),
+            "media/user/shop/a%20file%2B1%C3%A4.pdf"
+        );
+    }
+
+    #[test]
+    fn large_objects_are_copied_in_parts() {
+        let part = FileService::COPY_PART_BYTES;
+        let size = FileService::MAX_COPY_OBJECT_BYTES + 1;
+
+        let ranges = FileService::copy_part_ranges(size);
+
+        assert_eq!(ranges.len(), 11);
+        assert_eq!(ranges[0], format!("bytes=0-{}", part - 1));
+        assert_eq!(ranges[10], format!("bytes={}-{}", 10 * part, size - 1));
+        assert_eq!(FileService::copy_part_ranges(1), vec!["bytes=0-0"]);
+    }
+
+    #[tokio::test]
+    async fn with_bucket_uses_given_encryption() {
+        let file_service = file_service()
+            .await
+            .with_encryption(Some(ObjectEncryption::Aes256));
+        let kms = ObjectEncryption::Kms {
+            key_id: Some("shop-key".to_string()),
+        };
+
+        let unencrypted = file_service
+            .with_bucket("shop-bucket".to_string(), None, None, None, None)
+            .await;
+        let encrypted = file_service
+            .with_bucket(
+                "shop-bucket".to_string(),
+                None,
+                None,
+                None,
+                Some(kms.clone()),
+            )
+            .await;
+
+        assert_eq!(unencrypted.encryption(), None);
+        assert_eq!(encrypted.encryption(), Some(&kms));
+        assert_eq!(file_service.encryption(), Some(&ObjectEncryption::Aes256));
+    }
+
+    #[test]
+    fn encryption_algorithm_is_parsed() {
+        assert_eq!(ObjectEncryption::parse("", None).unwrap(), None);
+        assert_eq!(
+            ObjectEncryption::parse(" AES256 ", Some("key".to_string()))
+                .unwrap(),
+            Some(ObjectEncryption::Aes256)
+        );
+        assert_eq!(
+            ObjectEncryption::parse("aws:kms", Some(" ".to_string())).unwrap(),
+            Some(ObjectEncryption::Kms { key_id: None })
+        );
+        assert_eq!(
+            ObjectEncryption::parse("aws:kms", Some("key".to_string()))
+                .unwrap(),
+            Some(ObjectEncryption::Kms {
+                key_id: Some("key".to_string())
+            })
+        );
+        assert!(ObjectEncryption::parse("aes256", None).is_err());
+    }
+
+    #[test]
+    fn parts_are_diffed_by_number_and_etag() {
+        let requested = vec![
+            (1, "\"a\"".to_string()),
+            (2, "b".to_string()),
+            (3, "c".to_string()),
+            (4, "d".to_string()),
+        ];
+        let uploaded = vec![
+            (1, "a".to_string()),
+            (2, "\"b\"".to_string()),
+            (4, "e".to_string()),
+            (5, "f".to_string()),
+        ];
+
+        let (missing, mismatched) =
+            FileService::diff_parts(&requested, &uploaded);
+        assert_eq!(missing, [3]);
+        assert_eq!(mismatched, [4]);
+
+        let (missing, mismatched) = FileService::diff_parts(&requested, &[]);
+        assert_eq!(missing, [1, 2, 3, 4]);
+        assert!(mismatched.is_empty());
+
+        let (missing, mismatched) = FileService::diff_parts(&[], &uploaded);
+        assert!(missing.is_empty());
+        assert!(mismatched.is_empty());
+    }
+}
@@ -1,24 +1,30 @@
 pub mod api;
 mod auth;
 mod commerce;
+pub mod config;
 mod credentials;
 pub mod db;
+mod errors;
+mod file_info;
 pub mod files;
 pub mod logging;
 mod model;
 mod payment;
+pub mod processing_limit;
+pub mod progress;
 mod quota;
+pub mod remote_fetch;
+pub mod request_size_limit;
+pub mod request_timeout;
 mod services;
+pub mod signing;
+pub mod upload_policy;
+pub mod virus_scan;
 
 pub use auth::init_jwks_verifier;
-pub use commerce::CommerceService;
+pub use commerce::{Commerce, CommerceService};
 pub use credentials::CredentialsService;
+pub use errors::{BuildError, StatusBuilder};
 pub use payment::PaymentService;
 pub use quota::QuotaService;
 pub use services::*;
-
-pub fn get_env_var(var: &str) -> String {
-    std::env::var(var).unwrap_or_else(|_| {
-        panic!("ERROR: Missing environment variable '{var}'")
-    })
-}
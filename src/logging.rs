@@ -1,29 +1,86 @@
 use std::fmt::Debug;
+use std::time::Duration;
 
 use tower_http::{
     classify::GrpcFailureClass,
-    trace::{OnFailure, OnRequest, OnResponse},
+    trace::{MakeSpan, OnFailure, OnRequest, OnResponse},
 };
 
+use crate::db::slow_query_threshold;
+
 const HEALTH_PATH: &str = "/grpc.health.v1.Health/Check";
 const REFLECTION_PATH: &str =
     "/grpc.reflection.v1alpha.ServerReflection/ServerReflectionInfo";
+const GRPC_TIMEOUT_HEADER: &str = "grpc-timeout";
+
+/// Parses `grpc-timeout` header value as defined by the gRPC over HTTP2 spec,
+/// e.g. `1000m` is 1000 milliseconds.
+fn parse_grpc_timeout(value: &str) -> Option<Duration> {
+    if value.len() < 2 || value.len() > 9 {
+        return None;
+    }
+    let (amount, unit) = value.split_at(value.len() - 1);
+    let amount: u64 = amount.parse().ok()?;
+
+    match unit {
+        "H" => Some(Duration::from_secs(amount.checked_mul(60 * 60)?)),
+        "M" => Some(Duration::from_secs(amount.checked_mul(60)?)),
+        "S" => Some(Duration::from_secs(amount)),
+        "m" => Some(Duration::from_millis(amount)),
+        "u" => Some(Duration::from_micros(amount)),
+        "n" => Some(Duration::from_nanos(amount)),
+        _ => None,
+    }
+}
+
+/// Same as the default span of `TraceLayer` with an additional
+/// `grpc.timeout_ms` field filled by `LogOnRequest`.
+#[derive(Debug, Clone, Default)]
+pub struct MakeRequestSpan {}
+
+impl<B> MakeSpan<B> for MakeRequestSpan {
+    fn make_span(&mut self, request: &http::Request<B>) -> tracing::Span {
+        tracing::debug_span!(
+            "request",
+            method = %request.method(),
+            uri = %request.uri(),
+            version = ?request.version(),
+            grpc.timeout_ms = tracing::field::Empty,
+        )
+    }
+}
 
 #[derive(Debug, Clone, Default)]
 pub struct LogOnRequest {}
 
 impl<B> OnRequest<B> for LogOnRequest {
-    fn on_request(
-        &mut self,
-        request: &http::Request<B>,
-        _span: &tracing::Span,
-    ) {
+    fn on_request(&mut self, request: &http::Request<B>, span: &tracing::Span) {
         if request.uri().path() == HEALTH_PATH
             || request.uri().path() == REFLECTION_PATH
         {
             return;
         }
 
+        if let Some(timeout) = request
+            .headers()
+            .get(GRPC_TIMEOUT_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_grpc_timeout)
+        {
+            let timeout_ms =
+                u64::try_from(timeout.as_millis()).unwrap_or(u64::MAX);
+            span.record("grpc.timeout_ms", timeout_ms);
+
+            if timeout < slow_query_threshold() {
+                tracing::log::warn!(
+                    target: "grpc-request",
+                    "{} client deadline of {}ms is shorter than slow query threshold",
+                    request.uri().path(),
+                    timeout_ms
+                );
+            }
+        }
+
         tracing::log::debug!(
             target: "grpc-request",
             "{:?} {} {} {:?}",
@@ -1,76 +1,182 @@
 use http::header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE};
 use http::{HeaderName, Method};
+use prost::Message;
+use prost_types::FileDescriptorSet;
 use tonic::transport::Server;
+use tonic_web::GrpcWebLayer;
+use tower::util::option_layer;
 use tower_http::cors::{AllowOrigin, CorsLayer};
 use tower_http::trace::TraceLayer;
 
 use media::api::sited_io::media::v1::media_service_server::MediaServiceServer;
-use media::db::{init_db_pool, migrate};
+use media::api::sited_io::media::v1::media_subscription_service_server::MediaSubscriptionServiceServer;
+use media::config::{Config, ConfigError};
+use media::db::{
+    init_db_pool, init_name_collation, init_name_matching,
+    init_pool_acquire_timeout, init_slow_query_threshold, migrate,
+    validate_schema,
+};
 use media::files::FileService;
-use media::logging::{LogOnFailure, LogOnRequest, LogOnResponse};
+use media::logging::{
+    LogOnFailure, LogOnRequest, LogOnResponse, MakeRequestSpan,
+};
+use media::request_size_limit::RequestSizeLimitLayer;
+use media::request_timeout::RequestTimeoutLayer;
+use media::virus_scan::VirusScanner;
 use media::{
-    get_env_var, init_jwks_verifier, CommerceService, CredentialsService,
-    MediaService, MediaSubscriptionService, PaymentService, QuotaService,
+    init_jwks_verifier, CommerceService, CredentialsService, MediaService,
+    MediaSubscriptionService, PaymentService, QuotaService,
 };
 
+const REFLECTED_FILE_DESCRIPTOR_SETS: [&[u8]; 2] = [
+    tonic_health::pb::FILE_DESCRIPTOR_SET,
+    media::api::sited_io::FILE_DESCRIPTOR_SET,
+];
+
+/// Ensures all file descriptor sets registered for reflection can be parsed
+/// and logs the reflected services.
+fn validate_reflection_service() -> Result<(), Box<dyn std::error::Error>> {
+    let mut builder = tonic_reflection::server::Builder::configure();
+    let mut service_names = Vec::new();
+
+    for encoded_file_descriptor_set in REFLECTED_FILE_DESCRIPTOR_SETS {
+        let file_descriptor_set =
+            FileDescriptorSet::decode(encoded_file_descriptor_set)?;
+
+        for file in file_descriptor_set.file.iter() {
+            for service in file.service.iter() {
+                service_names.push(format!(
+                    "{}.{}",
+                    file.package(),
+                    service.name()
+                ));
+            }
+        }
+
+        builder = builder.register_file_descriptor_set(file_descriptor_set);
+    }
+
+    builder.build()?;
+
+    tracing::log::info!("gRPC reflection services: {:?}", service_names);
+
+    Ok(())
+}
+
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // initialize logging
     tracing_subscriber::fmt::init();
 
-    // get required environment variables
-    let host = get_env_var("HOST");
+    // read configuration, fails on missing or invalid variables
+    let Config {
+        host,
+        jwks_url,
+        jwks_host,
+        db,
+        oauth_url,
+        oauth_host,
+        service_user_client_id,
+        service_user_client_secret,
+        bucket,
+        payment_service_url,
+        commerce_service_url,
+        default_user_quota_mib,
+        virus_scan,
+        timeout_policy,
+        enable_grpc_web,
+        media_service: media_service_config,
+    } = Config::from_env()?;
 
-    let jwks_url = get_env_var("JWKS_URL");
-    let jwks_host = get_env_var("JWKS_HOST");
+    let max_request_bytes = u64::try_from(
+        media_service_config.max_decoding_message_size,
+    )
+    .map_err(|_| ConfigError::Invalid {
+        var: "GRPC_MAX_DECODING_MESSAGE_SIZE",
+        reason: String::from("too large"),
+    })?;
 
     // initialize database connection and migrate
     let db_pool = init_db_pool(
-        get_env_var("DB_HOST"),
-        get_env_var("DB_PORT").parse().unwrap(),
-        get_env_var("DB_USER"),
-        get_env_var("DB_PASSWORD"),
-        get_env_var("DB_DBNAME"),
-        std::env::var("DB_ROOT_CERT").ok(),
+        db.host,
+        db.port,
+        db.user.clone(),
+        db.password.clone(),
+        db.dbname.clone(),
+        db.root_cert.clone(),
     )?;
     migrate(&db_pool).await?;
+    validate_schema(&db_pool)
+        .await
+        .map_err(|err| format!("{err:?}"))?;
+
+    let read_db_pool = match db.replica_host {
+        Some(replica_host) => {
+            tracing::log::info!("Using read replica {replica_host}");
+            init_db_pool(
+                replica_host,
+                db.replica_port,
+                db.user,
+                db.password,
+                db.dbname,
+                db.root_cert,
+            )?
+        }
+        None => db_pool.clone(),
+    };
+
+    init_slow_query_threshold(db.slow_query_threshold);
+    init_pool_acquire_timeout(db.pool_acquire_timeout);
+
+    if let Some(collation) = db.name_collation {
+        init_name_collation(collation)?;
+    }
+    init_name_matching(db.name_matching);
 
     // initialize credentials service
     let credentials_service = CredentialsService::new(
-        get_env_var("OAUTH_URL"),
-        get_env_var("OAUTH_HOST"),
-        get_env_var("SERVICE_USER_CLIENT_ID"),
-        get_env_var("SERVICE_USER_CLIENT_SECRET"),
+        oauth_url,
+        oauth_host,
+        service_user_client_id,
+        service_user_client_secret,
     );
 
     // initialize file service
-    let file_service = FileService::new(
-        get_env_var("BUCKET_NAME"),
-        get_env_var("BUCKET_ENDPOINT"),
-        get_env_var("BUCKET_ACCESS_KEY_ID"),
-        get_env_var("BUCKET_SECRET_ACCESS_KEY"),
-    )
-    .await;
+    let file_service = FileService::from_config(bucket).await;
 
     // initialize payment service
-    let payment_service = PaymentService::init(
-        get_env_var("PAYMENT_SERVICE_URL"),
-        credentials_service,
-    )
-    .await?;
+    let payment_service =
+        PaymentService::init(payment_service_url, credentials_service).await?;
 
-    let max_message_size_bytes =
-        get_env_var("MAX_MESSAGE_SIZE_BYTES").parse().unwrap();
+    if media_service_config.data_uri_max_bytes.is_none() {
+        tracing::log::info!("Inline data is disabled, returning urls only");
+    }
 
-    // initialize commerce service client
-    let commerce_service =
-        CommerceService::init(get_env_var("COMMERCE_SERVICE_URL")).await?;
+    // initialize commerce service client, shared by both services. It
+    // connects lazily, so an unreachable service does not fail the startup
+    let commerce_service = CommerceService::init(commerce_service_url)
+        .await
+        .map_err(|err| ConfigError::Invalid {
+            var: "COMMERCE_SERVICE_URL",
+            reason: err.to_string(),
+        })?;
 
     // initialize quota service
-    let quota_service = QuotaService::new(
-        db_pool.clone(),
-        get_env_var("DEFAULT_USER_QUOTA_MIB").parse().unwrap(),
-    );
+    let quota_service =
+        QuotaService::new(db_pool.clone(), default_user_quota_mib);
+
+    // initialize virus scanner, uploads are not scanned without clamd
+    let virus_scanner = virus_scan.map(|virus_scan| {
+        tracing::log::info!(
+            "Scanning uploads with clamd at {}",
+            virus_scan.clamd_host
+        );
+        VirusScanner::new(
+            virus_scan.clamd_host,
+            virus_scan.clamd_port,
+            virus_scan.sync_max_bytes,
+        )
+    });
 
     // configure gRPC health reporter
     let (mut health_reporter, health_service) =
@@ -78,62 +184,85 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     health_reporter
         .set_serving::<MediaServiceServer<MediaService>>()
         .await;
+    health_reporter
+        .set_serving::<MediaSubscriptionServiceServer<MediaSubscriptionService>>()
+        .await;
 
     // configure gRPC reflection service
+    validate_reflection_service()?;
     let reflection_service = tonic_reflection::server::Builder::configure()
-        .register_encoded_file_descriptor_set(
-            tonic_health::pb::FILE_DESCRIPTOR_SET,
-        )
-        .register_encoded_file_descriptor_set(
-            media::api::sited_io::FILE_DESCRIPTOR_SET,
-        )
-        .build()
-        .unwrap();
+        .register_encoded_file_descriptor_set(REFLECTED_FILE_DESCRIPTOR_SETS[0])
+        .register_encoded_file_descriptor_set(REFLECTED_FILE_DESCRIPTOR_SETS[1])
+        .build()?;
 
     let media_service = MediaService::build(
         db_pool.clone(),
+        read_db_pool,
         init_jwks_verifier(&jwks_host, &jwks_url)?,
         file_service,
-        commerce_service,
+        commerce_service.clone(),
         quota_service,
-        max_message_size_bytes,
-    );
+        virus_scanner,
+        media_service_config,
+    )
+    .await?;
 
     let media_subscription_service = MediaSubscriptionService::build(
         db_pool,
         init_jwks_verifier(&jwks_host, &jwks_url)?,
         payment_service,
+        commerce_service,
     );
 
-    tracing::log::info!("gRPC+web server listening on {}", host);
+    let mut allowed_headers = vec![AUTHORIZATION, ACCEPT, CONTENT_TYPE];
+    let mut exposed_headers = vec![];
+    if enable_grpc_web {
+        allowed_headers.extend([
+            HeaderName::from_static("grpc-status"),
+            HeaderName::from_static("grpc-message"),
+            HeaderName::from_static("x-grpc-web"),
+            HeaderName::from_static("x-user-agent"),
+        ]);
+        // browsers only let gRPC-web clients read the status of a response if
+        // its headers are exposed
+        exposed_headers.extend([
+            HeaderName::from_static("grpc-status"),
+            HeaderName::from_static("grpc-message"),
+            HeaderName::from_static("grpc-status-details-bin"),
+        ]);
+        tracing::log::info!("gRPC+web server listening on {}", host);
+    } else {
+        tracing::log::info!("gRPC server listening on {}", host);
+    }
 
     Server::builder()
         .layer(
             TraceLayer::new_for_grpc()
+                .make_span_with(MakeRequestSpan::default())
                 .on_request(LogOnRequest::default())
                 .on_response(LogOnResponse::default())
                 .on_failure(LogOnFailure::default()),
         )
         .layer(
             CorsLayer::new()
-                .allow_headers([
-                    AUTHORIZATION,
-                    ACCEPT,
-                    CONTENT_TYPE,
-                    HeaderName::from_static("grpc-status"),
-                    HeaderName::from_static("grpc-message"),
-                    HeaderName::from_static("x-grpc-web"),
-                    HeaderName::from_static("x-user-agent"),
-                ])
+                .allow_headers(allowed_headers)
+                .expose_headers(exposed_headers)
                 .allow_methods([Method::POST])
                 .allow_origin(AllowOrigin::any())
                 .allow_private_network(true),
         )
-        .accept_http1(true)
-        .add_service(tonic_web::enable(reflection_service))
-        .add_service(tonic_web::enable(health_service))
-        .add_service(tonic_web::enable(media_service))
-        .add_service(tonic_web::enable(media_subscription_service))
+        // gRPC-web is translated for all services at once, plain gRPC
+        // deployments skip the translation and HTTP/1.1 entirely. The
+        // translation wraps the limits below, so the statuses they respond
+        // with reach gRPC-web clients as well
+        .layer(option_layer(enable_grpc_web.then(GrpcWebLayer::new)))
+        .layer(RequestSizeLimitLayer::new(max_request_bytes))
+        .layer(RequestTimeoutLayer::new(timeout_policy))
+        .accept_http1(enable_grpc_web)
+        .add_service(reflection_service)
+        .add_service(health_service)
+        .add_service(media_service)
+        .add_service(media_subscription_service)
         .serve(host.parse().unwrap())
         .await?;
 
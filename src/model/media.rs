@@ -1,19 +1,28 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
 use chrono::{DateTime, Utc};
+use deadpool_postgres::tokio_postgres::types::Json;
 use deadpool_postgres::tokio_postgres::Row;
 use deadpool_postgres::{Pool, Transaction};
+use sea_query::extension::postgres::PgExpr;
 use sea_query::{
-    Alias, Asterisk, Expr, Iden, IntoColumnRef, Order, PostgresQueryBuilder,
-    Query, SelectStatement,
+    Alias, Asterisk, BinOper, Cond, Condition, Expr, Func, Iden, LockType,
+    Order, PostgresQueryBuilder, Query, SelectStatement, SimpleExpr, Value,
 };
 use sea_query_postgres::PostgresBinder;
 use uuid::Uuid;
 
 use crate::api::sited_io::media::v1::{MediaFilterField, MediaOrderByField};
 use crate::api::sited_io::ordering::v1::Direction;
-use crate::db::{get_count_from_rows, DbError};
+use crate::db::{
+    get_client, get_count_from_rows, name_collation, name_matching,
+    observe_query, pool_acquire_timeout, DbError, NameMatching,
+};
+use crate::file_info::FileInfo;
 
 use super::media_offer::{MediaOfferIden, MediaOffersVec};
-use super::media_subscription::MediaSubscriptionIden;
+use super::media_subscription::{MediaSubscription, MediaSubscriptionIden};
 use super::MediaOffer;
 
 #[derive(Debug, Clone, Iden)]
@@ -26,9 +35,52 @@ pub enum MediaIden {
     CreatedAt,
     UpdatedAt,
     Name,
+    /// Holds the object key of the file in its bucket, not a fetchable URL.
+    /// The column keeps its original name, the code calls it `object_key`.
     DataUrl,
     SizeBytes,
     FileName,
+    BucketName,
+    ContentType,
+    ProcessingState,
+    StorageRegion,
+    DurationSeconds,
+    ImageWidth,
+    ImageHeight,
+    DeletePending,
+    CustomMetadata,
+    PerceptualHash,
+    /// Background scan the media is pending for
+    ScanId,
+}
+
+/// Optional columns selected by `Media::list`, all selected by default
+#[derive(Debug, Clone, Copy)]
+pub struct MediaProjection {
+    /// Aggregates the offer ids and the ordering of each media
+    pub offer_ids: bool,
+    pub custom_metadata: bool,
+}
+
+impl Default for MediaProjection {
+    fn default() -> Self {
+        Self {
+            offer_ids: true,
+            custom_metadata: true,
+        }
+    }
+}
+
+/// Columns changed by `Media::update`, `None` keeps the stored value
+#[derive(Debug, Clone, Default)]
+pub struct UpdateMediaFields {
+    pub name: Option<String>,
+    /// Key of the object storing the file
+    pub object_key: Option<String>,
+    pub size_bytes: Option<i64>,
+    pub file_name: Option<String>,
+    pub content_type: Option<String>,
+    pub custom_metadata: Option<HashMap<String, String>>,
 }
 
 #[derive(Debug, Clone)]
@@ -40,33 +92,90 @@ pub struct Media {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub name: String,
-    pub data_url: String,
+    /// Key of the object in `bucket_name`, never hand it out as a URL, use
+    /// `FileService::get_presigned_url` for that
+    pub object_key: String,
     pub size_bytes: u64,
     pub file_name: String,
     pub ordering: i64,
+    pub bucket_name: Option<String>,
+    pub content_type: Option<String>,
+    pub processing_state: String,
+    pub storage_region: Option<String>,
+    pub duration_seconds: Option<f64>,
+    pub image_width: Option<i32>,
+    pub image_height: Option<i32>,
+    pub custom_metadata: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MediaStats {
+    pub total_files: u64,
+    pub total_bytes: u64,
+    pub oldest_created_at: Option<DateTime<Utc>>,
+    pub newest_created_at: Option<DateTime<Utc>>,
+    pub content_type_counts: HashMap<String, u64>,
+    pub total_media_offers: u64,
+    /// Media belonging to more offers than the cap
+    pub media_over_offer_cap: Vec<Uuid>,
+    /// Offers holding more media than the cap
+    pub offers_over_media_cap: Vec<Uuid>,
 }
 
 impl Media {
     const MEDIA_OFFERS_ALIAS: &'static str = "offers";
+    /// Position in the offer filtered by, preferred over the aggregated one
+    const OFFER_ORDERING_ALIAS: &'static str = "offer_ordering";
+
+    pub const PROCESSING_STATE_PENDING: &'static str = "pending";
+    pub const PROCESSING_STATE_READY: &'static str = "ready";
+    pub const PROCESSING_STATE_FAILED: &'static str = "failed";
+    /// File was flagged by the virus scanner and must not be served
+    pub const PROCESSING_STATE_QUARANTINED: &'static str = "quarantined";
+
+    /// Stored as JSON object, `NULL` if empty
+    fn custom_metadata_value(
+        custom_metadata: &HashMap<String, String>,
+    ) -> Value {
+        if custom_metadata.is_empty() {
+            return Value::Json(None);
+        }
+
+        Value::Json(Some(Box::new(serde_json::Value::Object(
+            custom_metadata
+                .iter()
+                .map(|(k, v)| (k.clone(), serde_json::Value::String(v.clone())))
+                .collect(),
+        ))))
+    }
 
     fn get_media_offers_alias() -> Alias {
         Alias::new(Self::MEDIA_OFFERS_ALIAS)
     }
 
+    /// Media flagged by `mark_delete_pending` are treated as deleted already
+    fn not_delete_pending() -> SimpleExpr {
+        Expr::col((MediaIden::Table, MediaIden::DeletePending)).eq(false)
+    }
+
     fn select_with_offer_ids() -> SelectStatement {
-        Query::select()
+        Self::select_grouped()
             .expr_as(MediaOffer::get_agg(), Self::get_media_offers_alias())
+            .to_owned()
+    }
+
+    /// Media joined with their offers without aggregating the offer ids,
+    /// grouped by media so media in several offers are listed once
+    fn select_grouped() -> SelectStatement {
+        Query::select()
             .from(MediaIden::Table)
             .left_join(
                 MediaOfferIden::Table,
                 Expr::col((MediaIden::Table, MediaIden::MediaId))
                     .equals((MediaOfferIden::Table, MediaOfferIden::MediaId)),
             )
-            .group_by_columns([
-                (MediaIden::Table, MediaIden::MediaId).into_column_ref(),
-                (MediaOfferIden::Table, MediaOfferIden::Ordering)
-                    .into_column_ref(),
-            ])
+            .and_where(Self::not_delete_pending())
+            .group_by_col((MediaIden::Table, MediaIden::MediaId))
             .to_owned()
     }
 
@@ -74,44 +183,140 @@ impl Media {
         Query::select()
             .expr(Expr::col((MediaIden::Table, Asterisk)).count())
             .from(MediaIden::Table)
-            .left_join(
-                MediaOfferIden::Table,
-                Expr::col((MediaIden::Table, MediaIden::MediaId))
-                    .equals((MediaOfferIden::Table, MediaOfferIden::MediaId)),
-            )
+            .and_where(Self::not_delete_pending())
             .to_owned()
     }
 
-    fn select_accessible(user_id: &String) -> SelectStatement {
+    /// Relation of the outer media to `offer_id`, selected from
+    /// medias_offers aliased as `alias` so it is independent of any join of
+    /// the outer query. Callers add the selected expression.
+    fn select_offer_relation(alias: &str, offer_id: Uuid) -> SelectStatement {
+        let alias = Alias::new(alias);
+
         Query::select()
-            .from(MediaIden::Table)
-            .left_join(
-                MediaOfferIden::Table,
-                Expr::col((MediaIden::Table, MediaIden::MediaId))
-                    .equals((MediaOfferIden::Table, MediaOfferIden::MediaId)),
-            )
-            .left_join(
-                MediaSubscriptionIden::Table,
-                Expr::col((MediaOfferIden::Table, MediaOfferIden::OfferId))
-                    .equals((
-                        MediaSubscriptionIden::Table,
-                        MediaSubscriptionIden::OfferId,
-                    )),
-            )
+            .from_as(MediaOfferIden::Table, alias.clone())
             .and_where(
+                Expr::col((alias.clone(), MediaOfferIden::MediaId))
+                    .equals((MediaIden::Table, MediaIden::MediaId)),
+            )
+            .and_where(Expr::col((alias, MediaOfferIden::OfferId)).eq(offer_id))
+            .to_owned()
+    }
+
+    /// Position of the outer media in `offer_id`
+    fn offer_ordering(offer_id: Uuid) -> SimpleExpr {
+        let ordered_offers = "ordered_offers";
+
+        SimpleExpr::SubQuery(
+            None,
+            Box::new(
+                Self::select_offer_relation(ordered_offers, offer_id)
+                    .column((
+                        Alias::new(ordered_offers),
+                        MediaOfferIden::Ordering,
+                    ))
+                    .to_owned()
+                    .into_sub_query_statement(),
+            ),
+        )
+    }
+
+    /// Offer of the first offer filter in `filters`, which ordering by offer
+    /// refers to
+    fn ordered_offer_id(
+        filters: &[(MediaFilterField, String)],
+    ) -> Option<Uuid> {
+        filters.iter().find_map(|(filter_field, filter_query)| {
+            match filter_field {
+                MediaFilterField::OfferId => filter_query.trim().parse().ok(),
+                _ => None,
+            }
+        })
+    }
+
+    /// Subscriptions of `buyer_user_id` that currently grant access, including
+    /// the ones expired less than `grace_period` ago. The cutoff is computed
+    /// by the database, so it uses the same clock as `payed_until` of
+    /// revoked subscriptions.
+    fn accessible_subscription_condition(
+        buyer_user_id: &str,
+        grace_period: Duration,
+    ) -> Condition {
+        let payed_until_cutoff = Expr::cust_with_values(
+            "NOW() - $1::INT8 * INTERVAL '1 second'",
+            [i64::try_from(grace_period.as_secs()).unwrap_or(i64::MAX)],
+        );
+
+        Cond::all()
+            .add(
                 Expr::col((
                     MediaSubscriptionIden::Table,
                     MediaSubscriptionIden::BuyerUserId,
                 ))
-                .eq(user_id),
+                .eq(buyer_user_id),
             )
-            .and_where(
+            .add(
                 Expr::col((
                     MediaSubscriptionIden::Table,
                     MediaSubscriptionIden::PayedUntil,
                 ))
-                .gte(Utc::now()),
+                .gte(payed_until_cutoff),
+            )
+            .add(
+                Expr::col((
+                    MediaSubscriptionIden::Table,
+                    MediaSubscriptionIden::SubscriptionStatus,
+                ))
+                .is_in(MediaSubscription::accessible_status_values()),
             )
+    }
+
+    /// Whether the media is reachable through an active subscription of
+    /// `buyer_user_id` to any of its offers. Expired and revoked
+    /// subscriptions are excluded by the predicate, so buyers without access
+    /// yield no rows rather than an error.
+    fn accessible_to(
+        buyer_user_id: &str,
+        grace_period: Duration,
+    ) -> SimpleExpr {
+        let subscribed_offers = Alias::new("subscribed_offers");
+
+        Expr::exists(
+            Query::select()
+                .expr(Expr::val(1))
+                .from_as(MediaOfferIden::Table, subscribed_offers.clone())
+                .inner_join(
+                    MediaSubscriptionIden::Table,
+                    Expr::col((
+                        subscribed_offers.clone(),
+                        MediaOfferIden::OfferId,
+                    ))
+                    .equals((
+                        MediaSubscriptionIden::Table,
+                        MediaSubscriptionIden::OfferId,
+                    )),
+                )
+                .and_where(
+                    Expr::col((subscribed_offers, MediaOfferIden::MediaId))
+                        .equals((MediaIden::Table, MediaIden::MediaId)),
+                )
+                .cond_where(Self::accessible_subscription_condition(
+                    buyer_user_id,
+                    grace_period,
+                ))
+                .to_owned(),
+        )
+    }
+
+    /// Media accessible to `buyer_user_id` together with all offers it
+    /// belongs to, each media once
+    fn select_accessible(
+        buyer_user_id: &str,
+        grace_period: Duration,
+    ) -> SelectStatement {
+        Self::select_with_offer_ids()
+            .column((MediaIden::Table, Asterisk))
+            .and_where(Self::accessible_to(buyer_user_id, grace_period))
             .to_owned()
     }
 
@@ -119,35 +324,103 @@ impl Media {
         query: &mut SelectStatement,
         filter_field: MediaFilterField,
         filter_query: String,
+        name_matching: NameMatching,
     ) -> Result<(), DbError> {
         use MediaFilterField::*;
 
         match filter_field {
             Unspecified => Ok(()),
             Name => {
-                query.and_where(
-                    Expr::col((MediaIden::Table, MediaIden::Name))
-                        .eq(filter_query),
-                );
+                let name = Expr::col((MediaIden::Table, MediaIden::Name));
+                // backslash is the default escape character, an explicit
+                // `ESCAPE` is rendered in parentheses after `ILIKE`
+                let pattern =
+                    format!("%{}%", escape_like_pattern(&filter_query));
+                query.and_where(match name_matching {
+                    NameMatching::Trimmed => name.ilike(pattern),
+                    NameMatching::Literal => name.like(pattern),
+                });
                 Ok(())
             }
             OfferId => {
                 let offer_id: Uuid = filter_query
                     .parse()
                     .map_err(|err| DbError::Other(Some(format!("{}", err))))?;
+                query.and_where(Expr::exists(
+                    Self::select_offer_relation("filter_offers", offer_id)
+                        .expr(Expr::val(1))
+                        .to_owned(),
+                ));
+                Ok(())
+            }
+            ContentType => {
+                query.and_where(
+                    Expr::col((MediaIden::Table, MediaIden::ContentType))
+                        .eq(filter_query),
+                );
+                Ok(())
+            }
+            FileExtension => {
+                let extension: String = filter_query
+                    .trim_start_matches('.')
+                    .chars()
+                    .filter(char::is_ascii_alphanumeric)
+                    .collect();
                 query.and_where(
-                    Expr::col((MediaOfferIden::Table, MediaOfferIden::OfferId))
-                        .eq(offer_id),
+                    Expr::expr(Func::lower(Expr::col((
+                        MediaIden::Table,
+                        MediaIden::FileName,
+                    ))))
+                    .like(format!("%.{}", extension.to_lowercase())),
                 );
                 Ok(())
             }
+            MetadataKey => {
+                // JSONB key existence, can use an inverted index unlike `->`
+                query.and_where(
+                    Expr::col((MediaIden::Table, MediaIden::CustomMetadata))
+                        .binary(BinOper::Custom("?"), filter_query),
+                );
+                Ok(())
+            }
+        }
+    }
+
+    /// Adds all `filters` combined with AND. Queries are trimmed unless
+    /// names are matched literally, empty ones are ignored.
+    fn add_filters(
+        query: &mut SelectStatement,
+        filters: &[(MediaFilterField, String)],
+        name_matching: NameMatching,
+    ) -> Result<(), DbError> {
+        for (filter_field, filter_query) in filters {
+            let filter_query = match (filter_field, name_matching) {
+                (MediaFilterField::Name, NameMatching::Literal) => filter_query,
+                _ => filter_query.trim(),
+            };
+            if filter_query.is_empty() {
+                continue;
+            }
+
+            Self::add_filter(
+                query,
+                *filter_field,
+                filter_query.to_owned(),
+                name_matching,
+            )?;
         }
+
+        Ok(())
     }
 
+    /// Ordering by offer uses the position in `ordered_offer_id` if set, the
+    /// lowest position in any offer of the media otherwise. Expects the query
+    /// to be grouped by media.
     fn add_order_by(
         query: &mut SelectStatement,
         order_by_field: MediaOrderByField,
         order_by_direction: Direction,
+        ordered_offer_id: Option<Uuid>,
     ) {
         use MediaOrderByField::*;
 
@@ -163,12 +436,36 @@ impl Media {
             UpdatedAt => {
                 query.order_by((MediaIden::Table, MediaIden::UpdatedAt), order);
             }
-            Ordering => {
-                query.order_by(
-                    (MediaOfferIden::Table, MediaOfferIden::Ordering),
-                    order,
-                );
-            }
+            Ordering => match ordered_offer_id {
+                Some(offer_id) => {
+                    query.order_by_expr(Self::offer_ordering(offer_id), order);
+                }
+                None => {
+                    query.order_by_expr(
+                        Expr::col((
+                            MediaOfferIden::Table,
+                            MediaOfferIden::Ordering,
+                        ))
+                        .min(),
+                        order,
+                    );
+                }
+            },
+            Name => match name_collation() {
+                Some(collation) => {
+                    query.order_by_expr(
+                        Expr::cust(format!(
+                            r#"{}.{} COLLATE "{collation}""#,
+                            MediaIden::Table.to_string(),
+                            MediaIden::Name.to_string()
+                        )),
+                        order,
+                    );
+                }
+                None => {
+                    query.order_by((MediaIden::Table, MediaIden::Name), order);
+                }
+            },
         }
     }
 
@@ -182,6 +479,9 @@ impl Media {
         file_path: &String,
         size_bytes: i64,
         file_name: &String,
+        bucket_name: Option<String>,
+        content_type: Option<String>,
+        custom_metadata: &HashMap<String, String>,
     ) -> Result<Self, DbError> {
         let (sql, values) = Query::insert()
             .into_table(MediaIden::Table)
@@ -193,6 +493,9 @@ impl Media {
                 MediaIden::DataUrl,
                 MediaIden::SizeBytes,
                 MediaIden::FileName,
+                MediaIden::BucketName,
+                MediaIden::ContentType,
+                MediaIden::CustomMetadata,
             ])
             .values([
                 (*media_id).into(),
@@ -202,32 +505,337 @@ impl Media {
                 file_path.into(),
                 size_bytes.into(),
                 file_name.into(),
+                bucket_name.into(),
+                content_type.into(),
+                Self::custom_metadata_value(custom_metadata).into(),
             ])?
             .returning_all()
             .build_postgres(PostgresQueryBuilder);
 
-        let row = transaction
-            .query_one(sql.as_str(), &values.as_params())
-            .await?;
+        let row = observe_query(
+            &sql,
+            transaction.query_one(sql.as_str(), &values.as_params()),
+        )
+        .await?;
 
         Ok(Self::from(row))
     }
 
+    /// Creates media and, if `offer_id` is given, adds it to that offer as
+    /// the last item. Both writes happen in `transaction`.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_with_offer<'a>(
+        transaction: &Transaction<'a>,
+        media_id: &Uuid,
+        shop_id: &Uuid,
+        user_id: &String,
+        name: &String,
+        file_path: &String,
+        size_bytes: i64,
+        file_name: &String,
+        bucket_name: Option<String>,
+        content_type: Option<String>,
+        custom_metadata: &HashMap<String, String>,
+        offer_id: Option<Uuid>,
+    ) -> Result<Self, DbError> {
+        let mut media = Self::create(
+            transaction,
+            media_id,
+            shop_id,
+            user_id,
+            name,
+            file_path,
+            size_bytes,
+            file_name,
+            bucket_name,
+            content_type,
+            custom_metadata,
+        )
+        .await?;
+
+        let Some(offer_id) = offer_id else {
+            return Ok(media);
+        };
+
+        media.ordering =
+            MediaOffer::create_last(transaction, media_id, &offer_id, user_id)
+                .await?;
+        media.offer_ids = Some(vec![offer_id]);
+
+        Ok(media)
+    }
+
+    /// Sets the region the file is stored in, `None` being the primary region
+    pub async fn set_storage_region<'a>(
+        transaction: &Transaction<'a>,
+        media_id: &Uuid,
+        storage_region: Option<String>,
+    ) -> Result<(), DbError> {
+        let (sql, values) = Query::update()
+            .table(MediaIden::Table)
+            .value(MediaIden::StorageRegion, storage_region)
+            .and_where(Expr::col(MediaIden::MediaId).eq(*media_id))
+            .build_postgres(PostgresQueryBuilder);
+
+        observe_query(
+            &sql,
+            transaction.execute(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Also discards the result of any background scan still running
+    pub async fn set_processing_state<'a>(
+        transaction: &Transaction<'a>,
+        media_id: &Uuid,
+        processing_state: &str,
+    ) -> Result<(), DbError> {
+        let (sql, values) = Query::update()
+            .table(MediaIden::Table)
+            .value(MediaIden::ProcessingState, processing_state)
+            .value(MediaIden::ScanId, Option::<Uuid>::None)
+            .and_where(Expr::col(MediaIden::MediaId).eq(*media_id))
+            .build_postgres(PostgresQueryBuilder);
+
+        observe_query(
+            &sql,
+            transaction.execute(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Marks the media pending for a new background scan and returns its id.
+    /// Results of scans started before are discarded by `set_scan_result`.
+    pub async fn start_scan(
+        pool: &Pool,
+        media_id: &Uuid,
+    ) -> Result<Uuid, DbError> {
+        let conn = get_client(pool, pool_acquire_timeout()).await?;
+
+        let scan_id = Uuid::new_v4();
+
+        let (sql, values) = Query::update()
+            .table(MediaIden::Table)
+            .value(MediaIden::ProcessingState, Self::PROCESSING_STATE_PENDING)
+            .value(MediaIden::ScanId, scan_id)
+            .and_where(Expr::col(MediaIden::MediaId).eq(*media_id))
+            .build_postgres(PostgresQueryBuilder);
+
+        observe_query(&sql, conn.execute(sql.as_str(), &values.as_params()))
+            .await?;
+
+        Ok(scan_id)
+    }
+
+    /// Sets the processing state resulting from the scan `scan_id`. Returns
+    /// `false` if the scan is outdated, because another scan was started or
+    /// the state was set otherwise in the meantime.
+    pub async fn set_scan_result(
+        pool: &Pool,
+        media_id: &Uuid,
+        scan_id: &Uuid,
+        processing_state: &str,
+    ) -> Result<bool, DbError> {
+        let conn = get_client(pool, pool_acquire_timeout()).await?;
+
+        let (sql, values) = Query::update()
+            .table(MediaIden::Table)
+            .value(MediaIden::ProcessingState, processing_state)
+            .value(MediaIden::ScanId, Option::<Uuid>::None)
+            .and_where(Expr::col(MediaIden::MediaId).eq(*media_id))
+            .and_where(Expr::col(MediaIden::ScanId).eq(*scan_id))
+            .build_postgres(PostgresQueryBuilder);
+
+        let updated = observe_query(
+            &sql,
+            conn.execute(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+
+        Ok(updated > 0)
+    }
+
+    /// Media still pending for a scan, oldest first
+    pub async fn list_pending_scan(
+        pool: &Pool,
+        limit: u64,
+    ) -> Result<Vec<Self>, DbError> {
+        let conn = get_client(pool, pool_acquire_timeout()).await?;
+
+        let (sql, values) = Query::select()
+            .column(Asterisk)
+            .from(MediaIden::Table)
+            .and_where(
+                Expr::col(MediaIden::ProcessingState)
+                    .eq(Self::PROCESSING_STATE_PENDING),
+            )
+            .and_where(Expr::col(MediaIden::DeletePending).eq(false))
+            .order_by(MediaIden::UpdatedAt, Order::Asc)
+            .limit(limit)
+            .build_postgres(PostgresQueryBuilder);
+
+        let rows =
+            observe_query(&sql, conn.query(sql.as_str(), &values.as_params()))
+                .await?;
+
+        Ok(rows.iter().map(Self::from).collect())
+    }
+
+    /// Media whose deletion was interrupted after `mark_delete_pending`,
+    /// oldest first
+    pub async fn list_delete_pending(
+        pool: &Pool,
+        limit: u64,
+    ) -> Result<Vec<Self>, DbError> {
+        let conn = get_client(pool, pool_acquire_timeout()).await?;
+
+        let (sql, values) = Query::select()
+            .column(Asterisk)
+            .from(MediaIden::Table)
+            .and_where(Expr::col(MediaIden::DeletePending).eq(true))
+            .order_by(MediaIden::UpdatedAt, Order::Asc)
+            .limit(limit)
+            .build_postgres(PostgresQueryBuilder);
+
+        let rows =
+            observe_query(&sql, conn.query(sql.as_str(), &values.as_params()))
+                .await?;
+
+        Ok(rows.iter().map(Self::from).collect())
+    }
+
+    /// Points the media to another object, e.g. after its file changed with
+    /// content based keys
+    pub async fn set_object_key<'a>(
+        transaction: &Transaction<'a>,
+        media_id: &Uuid,
+        object_key: &String,
+    ) -> Result<(), DbError> {
+        let (sql, values) = Query::update()
+            .table(MediaIden::Table)
+            .value(MediaIden::DataUrl, object_key)
+            .and_where(Expr::col(MediaIden::MediaId).eq(*media_id))
+            .build_postgres(PostgresQueryBuilder);
+
+        observe_query(
+            &sql,
+            transaction.execute(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Points the media to the object assembled by a multipart upload. Chunks
+    /// add to the size while they are uploaded, so the size of the replaced
+    /// file is deducted.
+    pub async fn set_uploaded_file<'a>(
+        transaction: &Transaction<'a>,
+        media_id: &Uuid,
+        object_key: &String,
+        content_type: Option<&String>,
+        replaced_size_bytes: i64,
+    ) -> Result<(), DbError> {
+        let (sql, values) = {
+            let mut query = Query::update();
+            query
+                .table(MediaIden::Table)
+                .value(MediaIden::DataUrl, object_key)
+                .value(
+                    MediaIden::SizeBytes,
+                    Expr::cust_with_values(
+                        "size_bytes - $1",
+                        [replaced_size_bytes],
+                    ),
+                );
+
+            if let Some(content_type) = content_type {
+                query.value(MediaIden::ContentType, content_type);
+            }
+
+            query
+                .and_where(Expr::col(MediaIden::MediaId).eq(*media_id))
+                .build_postgres(PostgresQueryBuilder)
+        };
+
+        observe_query(
+            &sql,
+            transaction.execute(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Resets the size after the chunks of an upload were discarded
+    pub async fn set_size_bytes<'a>(
+        transaction: &Transaction<'a>,
+        media_id: &Uuid,
+        size_bytes: i64,
+    ) -> Result<(), DbError> {
+        let (sql, values) = Query::update()
+            .table(MediaIden::Table)
+            .value(MediaIden::SizeBytes, size_bytes)
+            .and_where(Expr::col(MediaIden::MediaId).eq(*media_id))
+            .build_postgres(PostgresQueryBuilder);
+
+        observe_query(
+            &sql,
+            transaction.execute(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Stores metadata read from the current file of the media
+    pub async fn set_file_info<'a>(
+        transaction: &Transaction<'a>,
+        media_id: &Uuid,
+        file_info: &FileInfo,
+    ) -> Result<(), DbError> {
+        let (sql, values) = Query::update()
+            .table(MediaIden::Table)
+            .value(MediaIden::DurationSeconds, file_info.duration_seconds)
+            .value(MediaIden::ImageWidth, file_info.image_width)
+            .value(MediaIden::ImageHeight, file_info.image_height)
+            .value(MediaIden::PerceptualHash, file_info.perceptual_hash)
+            .and_where(Expr::col(MediaIden::MediaId).eq(*media_id))
+            .build_postgres(PostgresQueryBuilder);
+
+        observe_query(
+            &sql,
+            transaction.execute(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+
+        Ok(())
+    }
+
     pub async fn get_for_owner(
         pool: &Pool,
         media_id: &Uuid,
         user_id: &String,
     ) -> Result<Option<Self>, DbError> {
-        let client = pool.get().await?;
+        let client = get_client(pool, pool_acquire_timeout()).await?;
 
         let (sql, values) = Query::select()
             .column(Asterisk)
             .from(MediaIden::Table)
             .and_where(Expr::col(MediaIden::MediaId).eq(*media_id))
             .and_where(Expr::col(MediaIden::UserId).eq(user_id))
+            .and_where(Self::not_delete_pending())
             .build_postgres(PostgresQueryBuilder);
 
-        let row = client.query_opt(sql.as_str(), &values.as_params()).await?;
+        let row = observe_query(
+            &sql,
+            client.query_opt(sql.as_str(), &values.as_params()),
+        )
+        .await?;
 
         Ok(row.map(Self::from))
     }
@@ -236,17 +844,21 @@ impl Media {
         pool: &Pool,
         media_id: &Uuid,
         user_id: &String,
+        grace_period: Duration,
     ) -> Result<Option<Self>, DbError> {
-        let conn = pool.get().await?;
+        let conn = get_client(pool, pool_acquire_timeout()).await?;
 
-        let (sql, values) = Self::select_accessible(user_id)
-            .column((MediaIden::Table, Asterisk))
+        let (sql, values) = Self::select_accessible(user_id, grace_period)
             .and_where(
                 Expr::col((MediaIden::Table, MediaIden::MediaId)).eq(*media_id),
             )
             .build_postgres(PostgresQueryBuilder);
 
-        let row = conn.query_opt(sql.as_str(), &values.as_params()).await?;
+        let row = observe_query(
+            &sql,
+            conn.query_opt(sql.as_str(), &values.as_params()),
+        )
+        .await?;
 
         Ok(row.map(Self::from))
     }
@@ -257,13 +869,18 @@ impl Media {
         user_id: &String,
         limit: u64,
         offset: u64,
-        filter: Option<(MediaFilterField, String)>,
+        filters: Vec<(MediaFilterField, String)>,
         order_by: Option<(MediaOrderByField, Direction)>,
+        projection: MediaProjection,
     ) -> Result<(Vec<Self>, i64), DbError> {
-        let conn = pool.get().await?;
+        let conn = get_client(pool, pool_acquire_timeout()).await?;
 
         let ((sql, values), (count_sql, count_values)) = {
-            let mut query = Self::select_with_offer_ids();
+            let mut query = if projection.offer_ids {
+                Self::select_with_offer_ids()
+            } else {
+                Self::select_grouped()
+            };
             let mut count_query = Self::select_count();
 
             query
@@ -286,9 +903,15 @@ impl Media {
                         .eq(user_id),
                 );
 
-            if let Some((filter_field, filter_query)) = filter {
-                Self::add_filter(&mut query, filter_field, filter_query.clone())?;
-                Self::add_filter(&mut count_query, filter_field, filter_query)?;
+            Self::add_filters(&mut query, &filters, name_matching())?;
+            Self::add_filters(&mut count_query, &filters, name_matching())?;
+
+            let ordered_offer_id = Self::ordered_offer_id(&filters);
+            if let Some(offer_id) = ordered_offer_id {
+                query.expr_as(
+                    Self::offer_ordering(offer_id),
+                    Alias::new(Self::OFFER_ORDERING_ALIAS),
+                );
             }
 
             if let Some((order_by_field, order_by_direction)) = order_by {
@@ -296,12 +919,18 @@ impl Media {
                     &mut query,
                     order_by_field,
                     order_by_direction,
+                    ordered_offer_id,
                 );
             }
 
+            if projection.custom_metadata {
+                query.column((MediaIden::Table, Asterisk));
+            } else {
+                Self::add_columns_without_custom_metadata(&mut query);
+            }
+
             (
                 query
-                    .column((MediaIden::Table, Asterisk))
                     .limit(limit)
                     .offset(offset)
                     .build_postgres(PostgresQueryBuilder),
@@ -311,29 +940,221 @@ impl Media {
             )
         };
 
-        let rows = conn.query(sql.as_str(), &values.as_params()).await?;
-        let count_rows = conn
-            .query(count_sql.as_str(), &count_values.as_params())
-            .await?;
+        let rows =
+            observe_query(&sql, conn.query(sql.as_str(), &values.as_params()))
+                .await?;
+        let count_rows = observe_query(
+            &count_sql,
+            conn.query(count_sql.as_str(), &count_values.as_params()),
+        )
+        .await?;
 
         let count = get_count_from_rows(&count_rows);
 
         Ok((rows.iter().map(Self::from).collect(), count))
     }
 
-    pub async fn list_all_for_user(
-        pool: &Pool,
-        user_id: &String,
-    ) -> Result<Vec<Self>, DbError> {
-        let client = pool.get().await?;
+    /// Selects all columns read by `From<&Row>` except the custom metadata,
+    /// which is selected as `NULL` so it reads as empty
+    fn add_columns_without_custom_metadata(query: &mut SelectStatement) {
+        query
+            .columns([
+                (MediaIden::Table, MediaIden::MediaId),
+                (MediaIden::Table, MediaIden::ShopId),
+                (MediaIden::Table, MediaIden::UserId),
+                (MediaIden::Table, MediaIden::CreatedAt),
+                (MediaIden::Table, MediaIden::UpdatedAt),
+                (MediaIden::Table, MediaIden::Name),
+                (MediaIden::Table, MediaIden::DataUrl),
+                (MediaIden::Table, MediaIden::SizeBytes),
+                (MediaIden::Table, MediaIden::FileName),
+                (MediaIden::Table, MediaIden::BucketName),
+                (MediaIden::Table, MediaIden::ContentType),
+                (MediaIden::Table, MediaIden::ProcessingState),
+                (MediaIden::Table, MediaIden::StorageRegion),
+                (MediaIden::Table, MediaIden::DurationSeconds),
+                (MediaIden::Table, MediaIden::ImageWidth),
+                (MediaIden::Table, MediaIden::ImageHeight),
+            ])
+            .expr_as(
+                Expr::cust("NULL::JSONB"),
+                Alias::new(MediaIden::CustomMetadata.to_string()),
+            );
+    }
+
+    pub async fn get_perceptual_hash(
+        pool: &Pool,
+        media_id: &Uuid,
+    ) -> Result<Option<i64>, DbError> {
+        let conn = get_client(pool, pool_acquire_timeout()).await?;
+
+        let (sql, values) = Query::select()
+            .column(MediaIden::PerceptualHash)
+            .from(MediaIden::Table)
+            .and_where(Expr::col(MediaIden::MediaId).eq(*media_id))
+            .build_postgres(PostgresQueryBuilder);
+
+        let row = observe_query(
+            &sql,
+            conn.query_opt(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+
+        Ok(row.and_then(|row| {
+            row.get(MediaIden::PerceptualHash.to_string().as_str())
+        }))
+    }
+
+    /// Up to `limit` media of the user in the shop that have a perceptual
+    /// hash, newest first, together with the hash. `media_id` is excluded.
+    /// Served by a partial index, at booth scale comparing the hashes in
+    /// memory is cheaper than an index on Hamming distance.
+    pub async fn list_with_perceptual_hash(
+        pool: &Pool,
+        shop_id: &Uuid,
+        user_id: &String,
+        media_id: &Uuid,
+        limit: u64,
+    ) -> Result<Vec<(Self, i64)>, DbError> {
+        let conn = get_client(pool, pool_acquire_timeout()).await?;
 
         let (sql, values) = Query::select()
             .column(Asterisk)
             .from(MediaIden::Table)
+            .and_where(Expr::col(MediaIden::ShopId).eq(*shop_id))
             .and_where(Expr::col(MediaIden::UserId).eq(user_id))
+            .and_where(Expr::col(MediaIden::PerceptualHash).is_not_null())
+            .and_where(Expr::col(MediaIden::MediaId).ne(*media_id))
+            .order_by(MediaIden::CreatedAt, Order::Desc)
+            .limit(limit)
+            .build_postgres(PostgresQueryBuilder);
+
+        let rows =
+            observe_query(&sql, conn.query(sql.as_str(), &values.as_params()))
+                .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                (
+                    Self::from(row),
+                    row.get(MediaIden::PerceptualHash.to_string().as_str()),
+                )
+            })
+            .collect())
+    }
+
+    /// Size of all files of the user including changes not yet committed
+    /// in `transaction`
+    pub async fn get_total_size_for_user<'a>(
+        transaction: &Transaction<'a>,
+        user_id: &String,
+    ) -> Result<u64, DbError> {
+        let (sql, values) = Query::select()
+            .expr_as(
+                Expr::cust("COALESCE(SUM(size_bytes), 0)::INT8"),
+                Alias::new("total_bytes"),
+            )
+            .from(MediaIden::Table)
+            .and_where(Expr::col(MediaIden::UserId).eq(user_id))
+            .build_postgres(PostgresQueryBuilder);
+
+        let row = observe_query(
+            &sql,
+            transaction.query_one(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+
+        Ok(u64::try_from(row.get::<&str, i64>("total_bytes"))
+            .unwrap_or_default())
+    }
+
+    pub async fn list_all_for_user(
+        pool: &Pool,
+        user_id: &String,
+    ) -> Result<Vec<Self>, DbError> {
+        let client = get_client(pool, pool_acquire_timeout()).await?;
+
+        let (sql, values) = Query::select()
+            .column(Asterisk)
+            .from(MediaIden::Table)
+            .and_where(Expr::col(MediaIden::UserId).eq(user_id))
+            .build_postgres(PostgresQueryBuilder);
+
+        let rows = observe_query(
+            &sql,
+            client.query(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+
+        Ok(rows.iter().map(Self::from).collect())
+    }
+
+    /// All media of the user in the shop with their offer ids, oldest first
+    pub async fn list_all_for_shop(
+        pool: &Pool,
+        shop_id: &Uuid,
+        user_id: &String,
+    ) -> Result<Vec<Self>, DbError> {
+        let client = get_client(pool, pool_acquire_timeout()).await?;
+
+        // grouped by media only, so media in several offers are listed once
+        let (sql, values) = Query::select()
+            .expr_as(MediaOffer::get_agg(), Self::get_media_offers_alias())
+            .column((MediaIden::Table, Asterisk))
+            .from(MediaIden::Table)
+            .left_join(
+                MediaOfferIden::Table,
+                Expr::col((MediaIden::Table, MediaIden::MediaId))
+                    .equals((MediaOfferIden::Table, MediaOfferIden::MediaId)),
+            )
+            .and_where(
+                Expr::col((MediaIden::Table, MediaIden::ShopId)).eq(*shop_id),
+            )
+            .and_where(
+                Expr::col((MediaIden::Table, MediaIden::UserId)).eq(user_id),
+            )
+            .group_by_col((MediaIden::Table, MediaIden::MediaId))
+            .order_by((MediaIden::Table, MediaIden::CreatedAt), Order::Asc)
             .build_postgres(PostgresQueryBuilder);
 
-        let rows = client.query(sql.as_str(), &values.as_params()).await?;
+        let rows = observe_query(
+            &sql,
+            client.query(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+
+        Ok(rows.iter().map(Self::from).collect())
+    }
+
+    /// Media whose object key still ends in the legacy
+    /// `{user_id}/{shop_id}/{media_id}` layout, possibly behind a storage
+    /// route prefix
+    pub async fn list_with_legacy_keys(
+        pool: &Pool,
+        limit: u64,
+    ) -> Result<Vec<Self>, DbError> {
+        let client = get_client(pool, pool_acquire_timeout()).await?;
+
+        let (sql, values) = Query::select()
+            .column(Asterisk)
+            .from(MediaIden::Table)
+            // suffix compare, as user ids might contain LIKE wildcards
+            .and_where(Expr::cust(
+                "RIGHT(data_url, LENGTH(user_id || '/' || shop_id::TEXT || '/' \
+                 || media_id::TEXT)) = user_id || '/' || shop_id::TEXT || '/' \
+                 || media_id::TEXT",
+            ))
+            .and_where(Expr::col(MediaIden::DeletePending).eq(false))
+            .order_by(MediaIden::CreatedAt, Order::Asc)
+            .limit(limit)
+            .build_postgres(PostgresQueryBuilder);
+
+        let rows = observe_query(
+            &sql,
+            client.query(sql.as_str(), &values.as_params()),
+        )
+        .await?;
 
         Ok(rows.iter().map(Self::from).collect())
     }
@@ -343,45 +1164,58 @@ impl Media {
         user_id: &String,
         limit: u64,
         offset: u64,
-        filter: Option<(MediaFilterField, String)>,
+        filters: Vec<(MediaFilterField, String)>,
         order_by: Option<(MediaOrderByField, Direction)>,
+        grace_period: Duration,
     ) -> Result<(Vec<Self>, i64), DbError> {
-        let mut conn = pool.get().await?;
+        let mut conn = get_client(pool, pool_acquire_timeout()).await?;
         let transaction = conn.transaction().await?;
 
         let ((sql, values), (count_sql, count_values)) = {
-            let mut query = Self::select_accessible(user_id);
-
-            if let Some((filter_field, filter_query)) = filter {
-                Self::add_filter(&mut query, filter_field, filter_query)?;
+            let mut query = Self::select_accessible(user_id, grace_period);
+            let mut count_query = Self::select_count()
+                .and_where(Self::accessible_to(user_id, grace_period))
+                .to_owned();
+
+            Self::add_filters(&mut query, &filters, name_matching())?;
+            Self::add_filters(&mut count_query, &filters, name_matching())?;
+
+            let ordered_offer_id = Self::ordered_offer_id(&filters);
+            if let Some(offer_id) = ordered_offer_id {
+                query.expr_as(
+                    Self::offer_ordering(offer_id),
+                    Alias::new(Self::OFFER_ORDERING_ALIAS),
+                );
             }
 
-            let mut count_query = query.clone();
-
             if let Some((order_by_field, order_by_direction)) = order_by {
                 Self::add_order_by(
                     &mut query,
                     order_by_field,
                     order_by_direction,
+                    ordered_offer_id,
                 );
             }
 
             (
                 query
-                    .column((MediaIden::Table, Asterisk))
                     .limit(limit)
                     .offset(offset)
                     .build_postgres(PostgresQueryBuilder),
-                count_query
-                    .expr(Expr::col((MediaIden::Table, Asterisk)).count())
-                    .build_postgres(PostgresQueryBuilder),
+                count_query.build_postgres(PostgresQueryBuilder),
             )
         };
 
-        let rows = transaction.query(sql.as_str(), &values.as_params()).await?;
-        let count_rows = transaction
-            .query(count_sql.as_str(), &count_values.as_params())
-            .await?;
+        let rows = observe_query(
+            &sql,
+            transaction.query(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+        let count_rows = observe_query(
+            &count_sql,
+            transaction.query(count_sql.as_str(), &count_values.as_params()),
+        )
+        .await?;
         transaction.commit().await?;
 
         let count = get_count_from_rows(&count_rows);
@@ -389,15 +1223,219 @@ impl Media {
         Ok((rows.iter().map(Self::from).collect(), count))
     }
 
-    pub async fn update(
+    /// Returns `(media_id, user_id)` for each of `media_ids` that exists
+    pub async fn get_owners(
         pool: &Pool,
+        media_ids: &[Uuid],
+    ) -> Result<Vec<(Uuid, String)>, DbError> {
+        let client = get_client(pool, pool_acquire_timeout()).await?;
+
+        let (sql, values) = Query::select()
+            .columns([MediaIden::MediaId, MediaIden::UserId])
+            .from(MediaIden::Table)
+            .and_where(
+                Expr::col(MediaIden::MediaId).is_in(media_ids.iter().copied()),
+            )
+            .build_postgres(PostgresQueryBuilder);
+
+        let rows = observe_query(
+            &sql,
+            client.query(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                (
+                    row.get(MediaIden::MediaId.to_string().as_str()),
+                    row.get(MediaIden::UserId.to_string().as_str()),
+                )
+            })
+            .collect())
+    }
+
+    /// Relations exceeding `max_offers_per_media` or `max_media_per_offer`
+    /// are flagged, at most `max_flagged` of each
+    pub async fn get_stats(
+        pool: &Pool,
+        shop_id: &Uuid,
+        user_id: &String,
+        max_offers_per_media: u64,
+        max_media_per_offer: u64,
+        max_flagged: u64,
+    ) -> Result<MediaStats, DbError> {
+        let (sql, values) = Query::select()
+            .expr_as(Expr::cust("COUNT(*)::INT8"), Alias::new("total_files"))
+            .expr_as(
+                Expr::cust("COALESCE(SUM(size_bytes), 0)::INT8"),
+                Alias::new("total_bytes"),
+            )
+            .expr_as(
+                Expr::col(MediaIden::CreatedAt).min(),
+                Alias::new("oldest_created_at"),
+            )
+            .expr_as(
+                Expr::col(MediaIden::CreatedAt).max(),
+                Alias::new("newest_created_at"),
+            )
+            .from(MediaIden::Table)
+            .and_where(Expr::col(MediaIden::ShopId).eq(*shop_id))
+            .and_where(Expr::col(MediaIden::UserId).eq(user_id))
+            .build_postgres(PostgresQueryBuilder);
+
+        let (content_types_sql, content_types_values) = Query::select()
+            .column(MediaIden::ContentType)
+            .expr_as(Expr::cust("COUNT(*)::INT8"), Alias::new("count"))
+            .from(MediaIden::Table)
+            .and_where(Expr::col(MediaIden::ShopId).eq(*shop_id))
+            .and_where(Expr::col(MediaIden::UserId).eq(user_id))
+            .group_by_col(MediaIden::ContentType)
+            .build_postgres(PostgresQueryBuilder);
+
+        // statements are not Send, so they must not be held across awaits
+        let (
+            (relations_sql, relations_values),
+            (media_over_cap_sql, media_over_cap_values),
+            (offers_over_cap_sql, offers_over_cap_values),
+        ) = {
+            let shop_media_offers = Query::select()
+                .from(MediaOfferIden::Table)
+                .inner_join(
+                    MediaIden::Table,
+                    Expr::col((MediaIden::Table, MediaIden::MediaId)).equals((
+                        MediaOfferIden::Table,
+                        MediaOfferIden::MediaId,
+                    )),
+                )
+                .and_where(
+                    Expr::col((MediaIden::Table, MediaIden::ShopId))
+                        .eq(*shop_id),
+                )
+                .and_where(
+                    Expr::col((MediaIden::Table, MediaIden::UserId))
+                        .eq(user_id),
+                )
+                .to_owned();
+
+            let (relations_sql, relations_values) = shop_media_offers
+                .clone()
+                .expr_as(Expr::cust("COUNT(*)::INT8"), Alias::new("count"))
+                .build_postgres(PostgresQueryBuilder);
+
+            let over_cap = |column: MediaOfferIden, cap: u64| {
+                shop_media_offers
+                    .clone()
+                    .column((MediaOfferIden::Table, column.clone()))
+                    .group_by_col((MediaOfferIden::Table, column))
+                    .and_having(
+                        Expr::expr(Expr::cust("COUNT(*)"))
+                            .gt(i64::try_from(cap).unwrap_or(i64::MAX)),
+                    )
+                    .limit(max_flagged)
+                    .build_postgres(PostgresQueryBuilder)
+            };
+            (
+                (relations_sql, relations_values),
+                over_cap(MediaOfferIden::MediaId, max_offers_per_media),
+                over_cap(MediaOfferIden::OfferId, max_media_per_offer),
+            )
+        };
+
+        let mut conn = get_client(pool, pool_acquire_timeout()).await?;
+        let transaction = conn.transaction().await?;
+
+        let row = observe_query(
+            &sql,
+            transaction.query_one(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+        let relations_row = observe_query(
+            &relations_sql,
+            transaction.query_one(
+                relations_sql.as_str(),
+                &relations_values.as_params(),
+            ),
+        )
+        .await?;
+        let media_over_cap_rows = observe_query(
+            &media_over_cap_sql,
+            transaction.query(
+                media_over_cap_sql.as_str(),
+                &media_over_cap_values.as_params(),
+            ),
+        )
+        .await?;
+        let offers_over_cap_rows = observe_query(
+            &offers_over_cap_sql,
+            transaction.query(
+                offers_over_cap_sql.as_str(),
+                &offers_over_cap_values.as_params(),
+            ),
+        )
+        .await?;
+        let content_type_rows = observe_query(
+            &content_types_sql,
+            transaction.query(
+                content_types_sql.as_str(),
+                &content_types_values.as_params(),
+            ),
+        )
+        .await?;
+        transaction.commit().await?;
+
+        Ok(MediaStats {
+            total_files: u64::try_from(row.get::<&str, i64>("total_files"))
+                .unwrap_or_default(),
+            total_bytes: u64::try_from(row.get::<&str, i64>("total_bytes"))
+                .unwrap_or_default(),
+            oldest_created_at: row.get("oldest_created_at"),
+            newest_created_at: row.get("newest_created_at"),
+            content_type_counts: content_type_rows
+                .iter()
+                .map(|r| {
+                    (
+                        r.get::<&str, Option<String>>(
+                            MediaIden::ContentType.to_string().as_str(),
+                        )
+                        .unwrap_or_default(),
+                        u64::try_from(r.get::<&str, i64>("count"))
+                            .unwrap_or_default(),
+                    )
+                })
+                .collect(),
+            total_media_offers: u64::try_from(
+                relations_row.get::<&str, i64>("count"),
+            )
+            .unwrap_or_default(),
+            media_over_offer_cap: media_over_cap_rows
+                .iter()
+                .map(|r| r.get(MediaOfferIden::MediaId.to_string().as_str()))
+                .collect(),
+            offers_over_media_cap: offers_over_cap_rows
+                .iter()
+                .map(|r| r.get(MediaOfferIden::OfferId.to_string().as_str()))
+                .collect(),
+        })
+    }
+
+    /// Sets the columns of `fields` that are `Some`. With `shop_id` the media
+    /// must also belong to that shop.
+    pub async fn update<'a>(
+        transaction: &Transaction<'a>,
         media_id: &Uuid,
         user_id: &String,
-        name: Option<String>,
-        size_bytes: Option<i64>,
-        file_name: Option<String>,
+        shop_id: Option<&Uuid>,
+        fields: UpdateMediaFields,
     ) -> Result<Self, DbError> {
-        let client = pool.get().await?;
+        let UpdateMediaFields {
+            name,
+            object_key,
+            size_bytes,
+            file_name,
+            content_type,
+            custom_metadata,
+        } = fields;
 
         let (sql, values) = {
             let mut query = Query::update();
@@ -407,6 +1445,10 @@ impl Media {
                 query.value(MediaIden::Name, name);
             }
 
+            if let Some(object_key) = object_key {
+                query.value(MediaIden::DataUrl, object_key);
+            }
+
             if let Some(size_bytes) = size_bytes {
                 query.value(MediaIden::SizeBytes, size_bytes);
             }
@@ -415,14 +1457,33 @@ impl Media {
                 query.value(MediaIden::FileName, file_name);
             }
 
+            if let Some(content_type) = content_type {
+                query.value(MediaIden::ContentType, content_type);
+            }
+
+            if let Some(custom_metadata) = custom_metadata {
+                query.value(
+                    MediaIden::CustomMetadata,
+                    Self::custom_metadata_value(&custom_metadata),
+                );
+            }
+
             query
                 .and_where(Expr::col(MediaIden::MediaId).eq(*media_id))
                 .and_where(Expr::col(MediaIden::UserId).eq(user_id))
+                .and_where_option(
+                    shop_id.map(|s| Expr::col(MediaIden::ShopId).eq(*s)),
+                )
                 .returning_all()
                 .build_postgres(PostgresQueryBuilder)
         };
 
-        let row = client.query_one(sql.as_str(), &values.as_params()).await?;
+        let row = observe_query(
+            &sql,
+            transaction.query_opt(sql.as_str(), &values.as_params()),
+        )
+        .await?
+        .ok_or(DbError::NotFound)?;
 
         Ok(Self::from(row))
     }
@@ -433,7 +1494,7 @@ impl Media {
         user_id: &String,
         additional_size: i64,
     ) -> Result<Self, DbError> {
-        let client = pool.get().await?;
+        let client = get_client(pool, pool_acquire_timeout()).await?;
 
         let (sql, values) = Query::update()
             .table(MediaIden::Table)
@@ -446,46 +1507,112 @@ impl Media {
             .returning_all()
             .build_postgres(PostgresQueryBuilder);
 
-        let row = client.query_one(sql.as_str(), &values.as_params()).await?;
+        let row = observe_query(
+            &sql,
+            client.query_one(sql.as_str(), &values.as_params()),
+        )
+        .await?;
 
         Ok(Self::from(row))
     }
 
-    pub async fn delete(
+    /// Number of other media stored in the object at `object_key` of
+    /// `bucket_name`. Objects with content based keys can be shared. The
+    /// rows are locked until `transaction` ends, so concurrent deletes of the
+    /// media sharing an object can not all see another reference and keep the
+    /// object, nor all see none.
+    pub async fn count_file_references<'a>(
+        transaction: &Transaction<'a>,
+        media_id: &Uuid,
+        bucket_name: Option<&String>,
+        object_key: &String,
+    ) -> Result<usize, DbError> {
+        // aggregates can not be locked, the referencing rows are
+        let (sql, values) = Query::select()
+            .column(MediaIden::MediaId)
+            .from(MediaIden::Table)
+            .and_where(Expr::col(MediaIden::DataUrl).eq(object_key))
+            .and_where(Expr::col(MediaIden::MediaId).ne(*media_id))
+            .and_where(match bucket_name {
+                Some(bucket_name) => {
+                    Expr::col(MediaIden::BucketName).eq(bucket_name)
+                }
+                None => Expr::col(MediaIden::BucketName).is_null(),
+            })
+            .lock(LockType::Update)
+            .build_postgres(PostgresQueryBuilder);
+
+        let rows = observe_query(
+            &sql,
+            transaction.query(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+
+        Ok(rows.len())
+    }
+
+    /// Flags the media before its file is removed, so deletions interrupted
+    /// between removing the file and the row can be retried
+    pub async fn mark_delete_pending(
         pool: &Pool,
         media_id: &Uuid,
         user_id: &String,
     ) -> Result<(), DbError> {
-        let conn = pool.get().await?;
+        let conn = get_client(pool, pool_acquire_timeout()).await?;
 
-        let (sql, values) = Query::delete()
-            .from_table(MediaIden::Table)
+        let (sql, values) = Query::update()
+            .table(MediaIden::Table)
+            .value(MediaIden::DeletePending, true)
             .and_where(Expr::col(MediaIden::MediaId).eq(*media_id))
             .and_where(Expr::col(MediaIden::UserId).eq(user_id))
             .build_postgres(PostgresQueryBuilder);
 
-        conn.execute(sql.as_str(), &values.as_params()).await?;
+        observe_query(&sql, conn.execute(sql.as_str(), &values.as_params()))
+            .await?;
 
         Ok(())
     }
 
-    pub async fn begin_delete<'a>(
+    /// Deletes the media and returns the deleted row. With `shop_id` the
+    /// media must also belong to that shop.
+    pub async fn delete_returning<'a>(
         transaction: &Transaction<'a>,
         media_id: &Uuid,
         user_id: &String,
-    ) -> Result<(), DbError> {
+        shop_id: Option<&Uuid>,
+    ) -> Result<Self, DbError> {
         let (sql, values) = Query::delete()
             .from_table(MediaIden::Table)
             .and_where(Expr::col(MediaIden::MediaId).eq(*media_id))
             .and_where(Expr::col(MediaIden::UserId).eq(user_id))
+            .and_where_option(
+                shop_id.map(|s| Expr::col(MediaIden::ShopId).eq(*s)),
+            )
+            .returning_all()
             .build_postgres(PostgresQueryBuilder);
 
-        transaction
-            .execute(sql.as_str(), &values.as_params())
-            .await?;
+        let row = observe_query(
+            &sql,
+            transaction.query_opt(sql.as_str(), &values.as_params()),
+        )
+        .await?
+        .ok_or(DbError::NotFound)?;
 
-        Ok(())
+        Ok(Self::from(&row))
+    }
+}
+
+/// Escapes the wildcards `%` and `_` and the escape character `\` itself,
+/// so `query` matches literally in a `LIKE` pattern
+fn escape_like_pattern(query: &str) -> String {
+    let mut escaped = String::with_capacity(query.len());
+    for c in query.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
     }
+    escaped
 }
 
 impl From<&Row> for Media {
@@ -503,15 +1630,35 @@ impl From<&Row> for Media {
             created_at: row.get(MediaIden::CreatedAt.to_string().as_str()),
             updated_at: row.get(MediaIden::UpdatedAt.to_string().as_str()),
             name: row.get(MediaIden::Name.to_string().as_str()),
-            data_url: row.get(MediaIden::DataUrl.to_string().as_str()),
+            object_key: row.get(MediaIden::DataUrl.to_string().as_str()),
             size_bytes: u64::try_from(
                 row.get::<&str, i64>(MediaIden::SizeBytes.to_string().as_str()),
             )
             .expect("should fit"),
             file_name: row.get(MediaIden::FileName.to_string().as_str()),
-            ordering: media_offers
-                .and_then(|mo| mo.0.first().map(|m| m.ordering))
+            ordering: row
+                .try_get(Self::OFFER_ORDERING_ALIAS)
+                .ok()
+                .or_else(|| {
+                    media_offers.and_then(|mo| mo.0.first().map(|m| m.ordering))
+                })
                 .unwrap_or(0),
+            bucket_name: row.get(MediaIden::BucketName.to_string().as_str()),
+            content_type: row.get(MediaIden::ContentType.to_string().as_str()),
+            processing_state: row
+                .get(MediaIden::ProcessingState.to_string().as_str()),
+            storage_region: row
+                .get(MediaIden::StorageRegion.to_string().as_str()),
+            duration_seconds: row
+                .get(MediaIden::DurationSeconds.to_string().as_str()),
+            image_width: row.get(MediaIden::ImageWidth.to_string().as_str()),
+            image_height: row.get(MediaIden::ImageHeight.to_string().as_str()),
+            custom_metadata: row
+                .get::<&str, Option<Json<HashMap<String, String>>>>(
+                    MediaIden::CustomMetadata.to_string().as_str(),
+                )
+                .map(|Json(custom_metadata)| custom_metadata)
+                .unwrap_or_default(),
         }
     }
 }
@@ -521,3 +1668,730 @@ impl From<Row> for Media {
         Self::from(&row)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use sea_query_postgres::PostgresValue;
+
+    use super::*;
+    use crate::api::sited_io::media::v1::SubscriptionStatus;
+    use crate::db::test_db::TestDb;
+
+    #[test]
+    fn metadata_key_filter_checks_key_existence() {
+        let mut query = Query::select();
+        query.column(Asterisk).from(MediaIden::Table);
+
+        Media::add_filter(
+            &mut query,
+            MediaFilterField::MetadataKey,
+            "color".to_string(),
+            NameMatching::Trimmed,
+        )
+        .unwrap();
+        let (sql, values) = query.build_postgres(PostgresQueryBuilder);
+
+        assert!(
+            sql.ends_with(r#"WHERE "medias"."custom_metadata" ? $1"#),
+            "{sql}"
+        );
+        assert_eq!(values.0, vec![PostgresValue(Value::from("color"))]);
+    }
+
+    #[test]
+    fn offer_filters_do_not_restrict_the_joined_offers() {
+        let offer_id = Uuid::new_v4();
+        let mut query = Media::select_grouped();
+        let filters = [(MediaFilterField::OfferId, offer_id.to_string())];
+
+        Media::add_filters(&mut query, &filters, NameMatching::Trimmed)
+            .unwrap();
+        Media::add_order_by(
+            &mut query,
+            MediaOrderByField::Ordering,
+            Direction::Asc,
+            Media::ordered_offer_id(&filters),
+        );
+        let (sql, values) = query.build_postgres(PostgresQueryBuilder);
+
+        assert!(
+            sql.contains(
+                r#"EXISTS(SELECT $2 FROM "medias_offers" AS "filter_offers""#
+            ),
+            "{sql}"
+        );
+        assert!(
+            sql.ends_with(
+                r#"ORDER BY (SELECT "ordered_offers"."ordering" FROM "medias_offers" AS "ordered_offers" WHERE "ordered_offers"."media_id" = "medias"."media_id" AND "ordered_offers"."offer_id" = $4) ASC"#
+            ),
+            "{sql}"
+        );
+        assert!(!sql.contains(r#""medias_offers"."offer_id" ="#), "{sql}");
+        assert_eq!(values.0[2], PostgresValue(Value::from(offer_id)));
+        assert_eq!(values.0[3], PostgresValue(Value::from(offer_id)));
+    }
+
+    async fn create_media(
+        pool: &Pool,
+        shop_id: &Uuid,
+        user_id: &String,
+    ) -> Media {
+        create_named_media(pool, shop_id, user_id, "name").await
+    }
+
+    async fn create_named_media(
+        pool: &Pool,
+        shop_id: &Uuid,
+        user_id: &String,
+        name: &str,
+    ) -> Media {
+        let mut conn = pool.get().await.unwrap();
+        let transaction = conn.transaction().await.unwrap();
+        let media_id = Uuid::new_v4();
+
+        let media = Media::create(
+            &transaction,
+            &media_id,
+            shop_id,
+            user_id,
+            &name.to_string(),
+            &format!("{user_id}/{shop_id}/{media_id}"),
+            0,
+            &"file.pdf".to_string(),
+            None,
+            None,
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        transaction.commit().await.unwrap();
+
+        media
+    }
+
+    async fn list_ids(
+        pool: &Pool,
+        shop_id: &Uuid,
+        user_id: &String,
+    ) -> Vec<Uuid> {
+        let (medias, _) = Media::list(
+            pool,
+            shop_id,
+            user_id,
+            10,
+            0,
+            vec![],
+            None,
+            MediaProjection::default(),
+        )
+        .await
+        .unwrap();
+
+        medias.into_iter().map(|m| m.media_id).collect()
+    }
+
+    #[tokio::test]
+    async fn adding_to_offer_twice_keeps_one_relation() {
+        let db = TestDb::start().await;
+        let shop_id = Uuid::new_v4();
+        let offer_id = Uuid::new_v4();
+        let user_id = "user".to_string();
+        let media = create_media(&db.pool, &shop_id, &user_id).await;
+
+        for ordering in [1, 2] {
+            MediaOffer::create(
+                &db.pool,
+                &media.media_id,
+                &offer_id,
+                &user_id,
+                ordering,
+            )
+            .await
+            .unwrap();
+        }
+
+        let relations = MediaOffer::list(&db.pool, &user_id, &offer_id)
+            .await
+            .unwrap();
+        assert_eq!(relations.len(), 1);
+        assert_eq!(relations[0].ordering, 1);
+    }
+
+    #[tokio::test]
+    async fn filters_are_combined() {
+        let db = TestDb::start().await;
+        let shop_id = Uuid::new_v4();
+        let offer_id = Uuid::new_v4();
+        let user_id = "user".to_string();
+        let mut medias = Vec::new();
+        for name in ["Summer photo", "Summer video", "Winter photo"] {
+            medias.push(
+                create_named_media(&db.pool, &shop_id, &user_id, name).await,
+            );
+        }
+        for media in [&medias[0], &medias[2]] {
+            MediaOffer::create(
+                &db.pool,
+                &media.media_id,
+                &offer_id,
+                &user_id,
+                1,
+            )
+            .await
+            .unwrap();
+        }
+
+        let (found, count) = Media::list(
+            &db.pool,
+            &shop_id,
+            &user_id,
+            10,
+            0,
+            vec![
+                (MediaFilterField::Name, "summer".to_string()),
+                (MediaFilterField::OfferId, offer_id.to_string()),
+            ],
+            None,
+            MediaProjection::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(count, 1);
+        assert_eq!(
+            found.iter().map(|m| m.media_id).collect::<Vec<_>>(),
+            vec![medias[0].media_id]
+        );
+    }
+
+    #[tokio::test]
+    async fn offer_filter_orders_by_the_filtered_offer() {
+        let db = TestDb::start().await;
+        let shop_id = Uuid::new_v4();
+        let offer_id = Uuid::new_v4();
+        let other_offer_id = Uuid::new_v4();
+        let user_id = "user".to_string();
+        let first = create_media(&db.pool, &shop_id, &user_id).await;
+        let second = create_media(&db.pool, &shop_id, &user_id).await;
+        for (media, offer_id, ordering) in [
+            (&first, &offer_id, 1),
+            (&second, &offer_id, 2),
+            (&first, &other_offer_id, 3),
+            (&second, &other_offer_id, 0),
+        ] {
+            MediaOffer::create(
+                &db.pool,
+                &media.media_id,
+                offer_id,
+                &user_id,
+                ordering,
+            )
+            .await
+            .unwrap();
+        }
+
+        let (found, count) = Media::list(
+            &db.pool,
+            &shop_id,
+            &user_id,
+            10,
+            0,
+            vec![(MediaFilterField::OfferId, offer_id.to_string())],
+            Some((MediaOrderByField::Ordering, Direction::Asc)),
+            MediaProjection {
+                offer_ids: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(count, 2);
+        assert_eq!(
+            found.iter().map(|m| m.media_id).collect::<Vec<_>>(),
+            vec![first.media_id, second.media_id]
+        );
+        assert_eq!(
+            found.iter().map(|m| m.ordering).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert!(found
+            .iter()
+            .all(|m| m.offer_ids.as_ref().is_some_and(|ids| ids.len() == 2)));
+    }
+
+    /// Sorted ids of the medias found by name filter `query`
+    async fn find_named_ids(
+        pool: &Pool,
+        shop_id: &Uuid,
+        user_id: &String,
+        query: &str,
+    ) -> Vec<Uuid> {
+        let (medias, _) = Media::list(
+            pool,
+            shop_id,
+            user_id,
+            10,
+            0,
+            vec![(MediaFilterField::Name, query.to_string())],
+            None,
+            MediaProjection::default(),
+        )
+        .await
+        .unwrap();
+
+        let mut ids: Vec<_> = medias.into_iter().map(|m| m.media_id).collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn like_wildcards_are_escaped() {
+        assert_eq!(escape_like_pattern("summer"), "summer");
+        assert_eq!(escape_like_pattern("100%"), r"100\%");
+        assert_eq!(escape_like_pattern("a_b"), r"a\_b");
+        assert_eq!(escape_like_pattern(r"a\b"), r"a\\b");
+    }
+
+    /// SQL and values of the name filter `query` matched by `name_matching`
+    fn name_filter_sql(
+        query: &str,
+        name_matching: NameMatching,
+    ) -> (String, Vec<PostgresValue>) {
+        let mut select = Query::select();
+        select.column(Asterisk).from(MediaIden::Table);
+
+        Media::add_filters(
+            &mut select,
+            &[(MediaFilterField::Name, query.to_string())],
+            name_matching,
+        )
+        .unwrap();
+        let (sql, values) = select.build_postgres(PostgresQueryBuilder);
+
+        (sql, values.0)
+    }
+
+    #[test]
+    fn trimmed_name_matching_ignores_whitespace_and_case() {
+        let (sql, values) =
+            name_filter_sql(" 100%_off ", NameMatching::Trimmed);
+
+        assert!(sql.ends_with(r#"WHERE "medias"."name" ILIKE $1"#), "{sql}");
+        assert_eq!(values, vec![PostgresValue(Value::from(r"%100\%\_off%"))]);
+
+        let (sql, values) = name_filter_sql("  ", NameMatching::Trimmed);
+        assert!(!sql.contains("WHERE"), "{sql}");
+        assert!(values.is_empty());
+    }
+
+    #[test]
+    fn literal_name_matching_keeps_whitespace_and_case() {
+        let (sql, values) =
+            name_filter_sql(" 100%_off ", NameMatching::Literal);
+
+        assert!(sql.ends_with(r#"WHERE "medias"."name" LIKE $1"#), "{sql}");
+        assert_eq!(values, vec![PostgresValue(Value::from(r"% 100\%\_off %"))]);
+
+        let (sql, _) = name_filter_sql("  ", NameMatching::Literal);
+        assert!(sql.contains("LIKE"), "{sql}");
+    }
+
+    #[test]
+    fn name_matching_is_parsed() {
+        assert_eq!("trimmed".parse(), Ok(NameMatching::Trimmed));
+        assert_eq!("literal".parse(), Ok(NameMatching::Literal));
+        assert!("exact".parse::<NameMatching>().is_err());
+    }
+
+    #[tokio::test]
+    async fn name_filter_matches_trimmed_query_literally() {
+        let db = TestDb::start().await;
+        let shop_id = Uuid::new_v4();
+        let user_id = "user".to_string();
+        let mut medias = Vec::new();
+        for name in ["100% Cotton", "100 cotton", "a_b", "axb"] {
+            medias.push(
+                create_named_media(&db.pool, &shop_id, &user_id, name).await,
+            );
+        }
+
+        let find = |query: &'static str| {
+            find_named_ids(&db.pool, &shop_id, &user_id, query)
+        };
+        let ids = |indexes: &[usize]| {
+            let mut ids: Vec<_> =
+                indexes.iter().map(|i| medias[*i].media_id).collect();
+            ids.sort();
+            ids
+        };
+
+        assert_eq!(find(" 100% cotton ").await, ids(&[0]));
+        assert_eq!(find("A_B").await, ids(&[2]));
+        assert_eq!(find("   ").await, ids(&[0, 1, 2, 3]));
+    }
+
+    #[tokio::test]
+    async fn legacy_keys_are_matched_as_suffix() {
+        let db = TestDb::start().await;
+        let shop_id = Uuid::new_v4();
+        let legacy =
+            create_media(&db.pool, &shop_id, &"user".to_string()).await;
+
+        // `_` would match any character in a LIKE pattern
+        let mut conn = db.pool.get().await.unwrap();
+        let transaction = conn.transaction().await.unwrap();
+        let media_id = Uuid::new_v4();
+        Media::create(
+            &transaction,
+            &media_id,
+            &shop_id,
+            &"_".to_string(),
+            &"name".to_string(),
+            &format!("x/{shop_id}/{media_id}"),
+            0,
+            &"file.pdf".to_string(),
+            None,
+            None,
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        transaction.commit().await.unwrap();
+
+        let listed: Vec<Uuid> = Media::list_with_legacy_keys(&db.pool, 10)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|m| m.media_id)
+            .collect();
+
+        assert_eq!(listed, vec![legacy.media_id]);
+    }
+
+    #[tokio::test]
+    async fn offer_ids_are_ordered_by_offer() {
+        let db = TestDb::start().await;
+        let shop_id = Uuid::new_v4();
+        let user_id = "user".to_string();
+        let media = create_media(&db.pool, &shop_id, &user_id).await;
+        let mut offer_ids: Vec<Uuid> = (0..3).map(|_| Uuid::new_v4()).collect();
+        offer_ids.sort();
+
+        for offer_id in offer_ids.iter().rev() {
+            MediaOffer::create(
+                &db.pool,
+                &media.media_id,
+                offer_id,
+                &user_id,
+                1,
+            )
+            .await
+            .unwrap();
+        }
+
+        let (found, _) = Media::list(
+            &db.pool,
+            &shop_id,
+            &user_id,
+            10,
+            0,
+            vec![],
+            None,
+            MediaProjection::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(found[0].offer_ids, Some(offer_ids));
+    }
+
+    #[tokio::test]
+    async fn create_with_offer_appends_to_offer() {
+        let db = TestDb::start().await;
+        let shop_id = Uuid::new_v4();
+        let offer_id = Uuid::new_v4();
+        let user_id = "user".to_string();
+
+        let mut orderings = Vec::new();
+        for _ in 0..2 {
+            let mut conn = db.pool.get().await.unwrap();
+            let transaction = conn.transaction().await.unwrap();
+            let media_id = Uuid::new_v4();
+            let media = Media::create_with_offer(
+                &transaction,
+                &media_id,
+                &shop_id,
+                &user_id,
+                &"name".to_string(),
+                &format!("{user_id}/{shop_id}/{media_id}"),
+                0,
+                &"file.pdf".to_string(),
+                None,
+                None,
+                &HashMap::new(),
+                Some(offer_id),
+            )
+            .await
+            .unwrap();
+            transaction.commit().await.unwrap();
+
+            assert_eq!(media.offer_ids, Some(vec![offer_id]));
+            orderings.push(media.ordering);
+        }
+
+        assert_eq!(orderings, vec![1, 2]);
+        let stored: Vec<i64> = MediaOffer::list(&db.pool, &user_id, &offer_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|m| m.ordering)
+            .collect();
+        assert_eq!(stored, vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn subscription_grants_access_within_grace_period() {
+        let db = TestDb::start().await;
+        let shop_id = Uuid::new_v4();
+        let offer_id = Uuid::new_v4();
+        let owner = "owner".to_string();
+        let buyer = "buyer".to_string();
+        let media = create_media(&db.pool, &shop_id, &owner).await;
+        MediaOffer::create(&db.pool, &media.media_id, &offer_id, &owner, 1)
+            .await
+            .unwrap();
+
+        let now = chrono::Utc::now();
+        let payed_until = now - chrono::Duration::hours(1);
+        MediaSubscription::put(
+            &db.pool,
+            &Uuid::new_v4(),
+            &buyer,
+            &offer_id,
+            &shop_id,
+            &(now - chrono::Duration::days(30)),
+            &payed_until,
+            SubscriptionStatus::Active,
+            &(now - chrono::Duration::days(30)),
+            &payed_until,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        let get = |grace_period| {
+            Media::get_accessible(
+                &db.pool,
+                &media.media_id,
+                &buyer,
+                grace_period,
+            )
+        };
+        assert!(get(Duration::from_secs(2 * 3600)).await.unwrap().is_some());
+        assert!(get(Duration::from_secs(1800)).await.unwrap().is_none());
+        assert!(get(Duration::ZERO).await.unwrap().is_none());
+    }
+
+    async fn subscribe(
+        pool: &Pool,
+        buyer: &String,
+        offer_id: &Uuid,
+        shop_id: &Uuid,
+        payed_until: chrono::DateTime<chrono::Utc>,
+    ) -> MediaSubscription {
+        let period_start = payed_until - chrono::Duration::days(30);
+        MediaSubscription::put(
+            pool,
+            &Uuid::new_v4(),
+            buyer,
+            offer_id,
+            shop_id,
+            &period_start,
+            &payed_until,
+            SubscriptionStatus::Active,
+            &period_start,
+            &payed_until,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap()
+        .unwrap()
+    }
+
+    async fn list_accessible_ids(
+        pool: &Pool,
+        buyer: &String,
+    ) -> (Vec<Uuid>, i64) {
+        let (medias, count) = Media::list_accessible(
+            pool,
+            buyer,
+            10,
+            0,
+            vec![],
+            None,
+            Duration::ZERO,
+        )
+        .await
+        .unwrap();
+
+        (medias.into_iter().map(|m| m.media_id).collect(), count)
+    }
+
+    #[tokio::test]
+    async fn only_active_subscriptions_list_accessible_media() {
+        let db = TestDb::start().await;
+        let shop_id = Uuid::new_v4();
+        let offer_id = Uuid::new_v4();
+        let owner = "owner".to_string();
+        let media = create_media(&db.pool, &shop_id, &owner).await;
+        // not part of the subscribed offer
+        create_media(&db.pool, &shop_id, &owner).await;
+        MediaOffer::create(&db.pool, &media.media_id, &offer_id, &owner, 1)
+            .await
+            .unwrap();
+
+        let now = chrono::Utc::now();
+        let active = "active".to_string();
+        let expired = "expired".to_string();
+        let revoked = "revoked".to_string();
+        subscribe(
+            &db.pool,
+            &active,
+            &offer_id,
+            &shop_id,
+            now + chrono::Duration::days(1),
+        )
+        .await;
+        subscribe(
+            &db.pool,
+            &expired,
+            &offer_id,
+            &shop_id,
+            now - chrono::Duration::days(1),
+        )
+        .await;
+        let revoked_subscription = subscribe(
+            &db.pool,
+            &revoked,
+            &offer_id,
+            &shop_id,
+            now + chrono::Duration::days(1),
+        )
+        .await;
+        MediaSubscription::revoke(
+            &db.pool,
+            &revoked_subscription.media_subscription_id,
+            &owner,
+            &"refunded".to_string(),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(
+            list_accessible_ids(&db.pool, &active).await,
+            (vec![media.media_id], 1)
+        );
+        for buyer in ["none", "expired", "revoked"] {
+            assert_eq!(
+                list_accessible_ids(&db.pool, &buyer.to_string()).await,
+                (vec![], 0),
+                "{buyer}"
+            );
+        }
+
+        // listing and getting share the access predicate
+        for (buyer, accessible) in [
+            ("active", true),
+            ("none", false),
+            ("expired", false),
+            ("revoked", false),
+        ] {
+            let found = Media::get_accessible(
+                &db.pool,
+                &media.media_id,
+                &buyer.to_string(),
+                Duration::ZERO,
+            )
+            .await
+            .unwrap();
+            assert_eq!(found.is_some(), accessible, "{buyer}");
+        }
+    }
+
+    #[tokio::test]
+    async fn media_in_several_subscribed_offers_is_listed_once() {
+        let db = TestDb::start().await;
+        let shop_id = Uuid::new_v4();
+        let owner = "owner".to_string();
+        let buyer = "buyer".to_string();
+        let media = create_media(&db.pool, &shop_id, &owner).await;
+        let mut offer_ids = vec![Uuid::new_v4(), Uuid::new_v4()];
+        offer_ids.sort();
+        for offer_id in &offer_ids {
+            MediaOffer::create(&db.pool, &media.media_id, offer_id, &owner, 1)
+                .await
+                .unwrap();
+            subscribe(
+                &db.pool,
+                &buyer,
+                offer_id,
+                &shop_id,
+                chrono::Utc::now() + chrono::Duration::days(1),
+            )
+            .await;
+        }
+
+        let (found, count) = Media::list_accessible(
+            &db.pool,
+            &buyer,
+            10,
+            0,
+            vec![],
+            Some((MediaOrderByField::Ordering, Direction::Asc)),
+            Duration::ZERO,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(count, 1);
+        assert_eq!(
+            found.iter().map(|m| m.media_id).collect::<Vec<_>>(),
+            vec![media.media_id]
+        );
+        assert_eq!(found[0].offer_ids, Some(offer_ids));
+    }
+
+    #[tokio::test]
+    async fn delete_pending_media_is_hidden_until_purged() {
+        let db = TestDb::start().await;
+        let shop_id = Uuid::new_v4();
+        let user_id = "user".to_string();
+        let kept = create_media(&db.pool, &shop_id, &user_id).await;
+        let deleted = create_media(&db.pool, &shop_id, &user_id).await;
+
+        Media::mark_delete_pending(&db.pool, &deleted.media_id, &user_id)
+            .await
+            .unwrap();
+
+        assert!(Media::get_for_owner(&db.pool, &deleted.media_id, &user_id)
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            list_ids(&db.pool, &shop_id, &user_id).await,
+            vec![kept.media_id]
+        );
+
+        let pending = Media::list_delete_pending(&db.pool, 10).await.unwrap();
+        assert_eq!(
+            pending.iter().map(|m| m.media_id).collect::<Vec<_>>(),
+            vec![deleted.media_id]
+        );
+    }
+}
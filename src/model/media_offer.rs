@@ -1,15 +1,18 @@
 use deadpool_postgres::tokio_postgres::types::{private, FromSql, Type};
 use deadpool_postgres::tokio_postgres::Row;
-use deadpool_postgres::Pool;
+use deadpool_postgres::{Pool, Transaction};
 use fallible_iterator::FallibleIterator;
 use postgres_protocol::types;
 use sea_query::{
-    all, Asterisk, Expr, Func, Iden, PostgresQueryBuilder, Query, SimpleExpr,
+    all, Alias, Asterisk, Expr, Iden, InsertStatement, OnConflict,
+    PostgresQueryBuilder, Query, SelectStatement, SimpleExpr,
 };
 use sea_query_postgres::PostgresBinder;
 use uuid::Uuid;
 
-use crate::db::{get_type_from_oid, ArrayAgg, DbError};
+use crate::db::{
+    get_client, get_type_from_oid, observe_query, pool_acquire_timeout, DbError,
+};
 
 #[derive(Debug, Clone, Iden)]
 #[iden(rename = "medias_offers")]
@@ -30,32 +33,30 @@ pub struct MediaOffer {
 }
 
 impl MediaOffer {
+    /// Relations of a media ordered by offer, so that responses listing the
+    /// offers of a media are stable
     pub fn get_agg() -> SimpleExpr {
-        Func::cust(ArrayAgg)
-            .args([Expr::tuple([
-                Expr::col((MediaOfferIden::Table, MediaOfferIden::MediaId))
-                    .into(),
-                Expr::col((MediaOfferIden::Table, MediaOfferIden::OfferId))
-                    .into(),
-                Expr::col((MediaOfferIden::Table, MediaOfferIden::UserId))
-                    .into(),
-                Expr::col((MediaOfferIden::Table, MediaOfferIden::Ordering))
-                    .into(),
-            ])
-            .into()])
-            .into()
+        // fields in the order `from_sql` reads them
+        Expr::cust_with_exprs(
+            "ARRAY_AGG(($1, $2, $3, $4) ORDER BY $2)",
+            [
+                MediaOfferIden::MediaId,
+                MediaOfferIden::OfferId,
+                MediaOfferIden::UserId,
+                MediaOfferIden::Ordering,
+            ]
+            .map(|column| Expr::col((MediaOfferIden::Table, column)).into()),
+        )
     }
 
-    pub async fn create(
-        pool: &Pool,
+    fn insert_statement(
         media_id: &Uuid,
         offer_id: &Uuid,
         user_id: &String,
         ordering: i64,
-    ) -> Result<(), DbError> {
-        let client = pool.get().await?;
-
-        let (sql, values) = Query::insert()
+    ) -> Result<InsertStatement, DbError> {
+        let mut statement = Query::insert();
+        statement
             .into_table(MediaOfferIden::Table)
             .columns([
                 MediaOfferIden::MediaId,
@@ -68,21 +69,107 @@ impl MediaOffer {
                 (*offer_id).into(),
                 user_id.into(),
                 ordering.into(),
-            ])?
-            .returning_all()
+            ])?;
+
+        Ok(statement)
+    }
+
+    /// Highest ordering of the offer as `highest`, 0 if it is empty
+    fn highest_ordering_statement(
+        offer_id: &Uuid,
+        user_id: &String,
+    ) -> SelectStatement {
+        Query::select()
+            .expr_as(
+                Expr::cust("COALESCE(MAX(ordering), 0)::INT8"),
+                Alias::new("highest"),
+            )
+            .from(MediaOfferIden::Table)
+            .and_where(Expr::col(MediaOfferIden::OfferId).eq(*offer_id))
+            .and_where(Expr::col(MediaOfferIden::UserId).eq(user_id))
+            .to_owned()
+    }
+
+    async fn highest_ordering_in_transaction<'a>(
+        transaction: &Transaction<'a>,
+        offer_id: &Uuid,
+        user_id: &String,
+    ) -> Result<i64, DbError> {
+        let (sql, values) = Self::highest_ordering_statement(offer_id, user_id)
             .build_postgres(PostgresQueryBuilder);
 
-        client.execute(sql.as_str(), &values.as_params()).await?;
+        let row = observe_query(
+            &sql,
+            transaction.query_one(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+
+        Ok(row.get("highest"))
+    }
+
+    /// Does nothing if the media already belongs to the offer
+    pub async fn create(
+        pool: &Pool,
+        media_id: &Uuid,
+        offer_id: &Uuid,
+        user_id: &String,
+        ordering: i64,
+    ) -> Result<(), DbError> {
+        let client = get_client(pool, pool_acquire_timeout()).await?;
+
+        let (sql, values) =
+            Self::insert_statement(media_id, offer_id, user_id, ordering)?
+                .on_conflict(
+                    OnConflict::columns([
+                        MediaOfferIden::MediaId,
+                        MediaOfferIden::OfferId,
+                    ])
+                    .do_nothing()
+                    .to_owned(),
+                )
+                .build_postgres(PostgresQueryBuilder);
+
+        observe_query(&sql, client.execute(sql.as_str(), &values.as_params()))
+            .await?;
 
         Ok(())
     }
 
+    /// Adds the media as last item of the offer within `transaction`,
+    /// returns its ordering
+    pub async fn create_last<'a>(
+        transaction: &Transaction<'a>,
+        media_id: &Uuid,
+        offer_id: &Uuid,
+        user_id: &String,
+    ) -> Result<i64, DbError> {
+        let ordering = Self::highest_ordering_in_transaction(
+            transaction,
+            offer_id,
+            user_id,
+        )
+        .await?
+            + 1;
+
+        let (sql, values) =
+            Self::insert_statement(media_id, offer_id, user_id, ordering)?
+                .build_postgres(PostgresQueryBuilder);
+
+        observe_query(
+            &sql,
+            transaction.execute(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+
+        Ok(ordering)
+    }
+
     pub async fn get(
         pool: &Pool,
         media_id: &Uuid,
         offer_id: &Uuid,
     ) -> Result<Option<Self>, DbError> {
-        let conn = pool.get().await?;
+        let conn = get_client(pool, pool_acquire_timeout()).await?;
 
         let (sql, values) = Query::select()
             .column(Asterisk)
@@ -93,7 +180,11 @@ impl MediaOffer {
             ])
             .build_postgres(PostgresQueryBuilder);
 
-        let row = conn.query_opt(sql.as_str(), &values.as_params()).await?;
+        let row = observe_query(
+            &sql,
+            conn.query_opt(sql.as_str(), &values.as_params()),
+        )
+        .await?;
 
         Ok(row.map(Self::from))
     }
@@ -103,26 +194,18 @@ impl MediaOffer {
         offer_id: &Uuid,
         user_id: &String,
     ) -> Result<i64, DbError> {
-        let conn = pool.get().await?;
+        let conn = get_client(pool, pool_acquire_timeout()).await?;
 
-        let (sql, values) = Query::select()
-            .column(Asterisk)
-            .from(MediaOfferIden::Table)
-            .and_where(Expr::col(MediaOfferIden::OfferId).eq(*offer_id))
-            .and_where(Expr::col(MediaOfferIden::UserId).eq(user_id))
-            .order_by(MediaOfferIden::Ordering, sea_query::Order::Desc)
-            .limit(1)
+        let (sql, values) = Self::highest_ordering_statement(offer_id, user_id)
             .build_postgres(PostgresQueryBuilder);
 
-        let row = conn.query_opt(sql.as_str(), &values.as_params()).await?;
-
-        let ordering: i64 = if let Some(row) = row {
-            row.get(MediaOfferIden::Ordering.to_string().as_str())
-        } else {
-            0
-        };
+        let row = observe_query(
+            &sql,
+            conn.query_one(sql.as_str(), &values.as_params()),
+        )
+        .await?;
 
-        Ok(ordering)
+        Ok(row.get("highest"))
     }
 
     pub async fn list(
@@ -130,7 +213,7 @@ impl MediaOffer {
         user_id: &String,
         offer_id: &Uuid,
     ) -> Result<Vec<Self>, DbError> {
-        let conn = pool.get().await?;
+        let conn = get_client(pool, pool_acquire_timeout()).await?;
 
         let (sql, values) = Query::select()
             .column(Asterisk)
@@ -142,7 +225,9 @@ impl MediaOffer {
             .order_by(MediaOfferIden::Ordering, sea_query::Order::Asc)
             .build_postgres(PostgresQueryBuilder);
 
-        let rows = conn.query(sql.as_str(), &values.as_params()).await?;
+        let rows =
+            observe_query(&sql, conn.query(sql.as_str(), &values.as_params()))
+                .await?;
 
         Ok(rows.into_iter().map(Self::from).collect())
     }
@@ -154,7 +239,7 @@ impl MediaOffer {
         user_id: &String,
         ordering: i64,
     ) -> Result<(), DbError> {
-        let conn = pool.get().await?;
+        let conn = get_client(pool, pool_acquire_timeout()).await?;
 
         let (sql, values) = Query::update()
             .table(MediaOfferIden::Table)
@@ -164,7 +249,119 @@ impl MediaOffer {
             .and_where(Expr::col(MediaOfferIden::UserId).eq(user_id))
             .build_postgres(PostgresQueryBuilder);
 
-        conn.execute(sql.as_str(), &values.as_params()).await?;
+        observe_query(&sql, conn.execute(sql.as_str(), &values.as_params()))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Moves media from one offer to another in a single transaction. Puts it
+    /// at `ordering` in the target offer or last if not given. Returns the
+    /// media of the target offer or `None` if media is not in `from_offer_id`.
+    pub async fn move_between(
+        pool: &Pool,
+        media_id: &Uuid,
+        from_offer_id: &Uuid,
+        to_offer_id: &Uuid,
+        user_id: &String,
+        ordering: Option<i64>,
+    ) -> Result<Option<Vec<Self>>, DbError> {
+        let mut conn = get_client(pool, pool_acquire_timeout()).await?;
+        let transaction = conn.transaction().await?;
+
+        let (sql, values) = Query::delete()
+            .from_table(MediaOfferIden::Table)
+            .and_where(Expr::col(MediaOfferIden::MediaId).eq(*media_id))
+            .and_where(Expr::col(MediaOfferIden::OfferId).eq(*from_offer_id))
+            .and_where(Expr::col(MediaOfferIden::UserId).eq(user_id))
+            .build_postgres(PostgresQueryBuilder);
+
+        let deleted = observe_query(
+            &sql,
+            transaction.execute(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+
+        if deleted == 0 {
+            return Ok(None);
+        }
+
+        let ordering = match ordering {
+            Some(ordering) => {
+                Self::shift_orderings(
+                    &transaction,
+                    to_offer_id,
+                    user_id,
+                    ordering,
+                )
+                .await?;
+                ordering
+            }
+            None => {
+                Self::highest_ordering_in_transaction(
+                    &transaction,
+                    to_offer_id,
+                    user_id,
+                )
+                .await?
+                    + 1
+            }
+        };
+
+        let (sql, values) =
+            Self::insert_statement(media_id, to_offer_id, user_id, ordering)?
+                .build_postgres(PostgresQueryBuilder);
+
+        observe_query(
+            &sql,
+            transaction.execute(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+
+        let (sql, values) = Query::select()
+            .column(Asterisk)
+            .from(MediaOfferIden::Table)
+            .cond_where(all![
+                Expr::col(MediaOfferIden::UserId).eq(user_id),
+                Expr::col(MediaOfferIden::OfferId).eq(*to_offer_id),
+            ])
+            .order_by(MediaOfferIden::Ordering, sea_query::Order::Asc)
+            .build_postgres(PostgresQueryBuilder);
+
+        let rows = observe_query(
+            &sql,
+            transaction.query(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+
+        transaction.commit().await?;
+
+        Ok(Some(rows.into_iter().map(Self::from).collect()))
+    }
+
+    /// Makes room at `from_ordering` by moving all following media one back
+    async fn shift_orderings<'a>(
+        transaction: &Transaction<'a>,
+        offer_id: &Uuid,
+        user_id: &String,
+        from_ordering: i64,
+    ) -> Result<(), DbError> {
+        let (sql, values) = Query::update()
+            .table(MediaOfferIden::Table)
+            .value(
+                MediaOfferIden::Ordering,
+                Expr::col(MediaOfferIden::Ordering).add(1),
+            )
+            .and_where(Expr::col(MediaOfferIden::OfferId).eq(*offer_id))
+            .and_where(Expr::col(MediaOfferIden::UserId).eq(user_id))
+            .and_where(Expr::col(MediaOfferIden::Ordering).gte(from_ordering))
+            .build_postgres(PostgresQueryBuilder);
+
+        observe_query(
+            &sql,
+            transaction.execute(sql.as_str(), &values.as_params()),
+        )
+        .await?;
 
         Ok(())
     }
@@ -175,7 +372,7 @@ impl MediaOffer {
         offer_id: &Uuid,
         user_id: &String,
     ) -> Result<(), DbError> {
-        let client = pool.get().await?;
+        let client = get_client(pool, pool_acquire_timeout()).await?;
 
         let (sql, values) = Query::delete()
             .from_table(MediaOfferIden::Table)
@@ -184,10 +381,69 @@ impl MediaOffer {
             .and_where(Expr::col(MediaOfferIden::UserId).eq(user_id))
             .build_postgres(PostgresQueryBuilder);
 
-        client.execute(sql.as_str(), &values.as_params()).await?;
+        observe_query(&sql, client.execute(sql.as_str(), &values.as_params()))
+            .await?;
 
         Ok(())
     }
+
+    /// Removes the media from all offers of the user, returns the ids of the
+    /// offers it was removed from
+    pub async fn delete_all_for_media<'a>(
+        transaction: &Transaction<'a>,
+        media_id: &Uuid,
+        user_id: &String,
+    ) -> Result<Vec<Uuid>, DbError> {
+        let (sql, values) = Query::delete()
+            .from_table(MediaOfferIden::Table)
+            .and_where(Expr::col(MediaOfferIden::MediaId).eq(*media_id))
+            .and_where(Expr::col(MediaOfferIden::UserId).eq(user_id))
+            .returning_col(MediaOfferIden::OfferId)
+            .build_postgres(PostgresQueryBuilder);
+
+        let rows = observe_query(
+            &sql,
+            transaction.query(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| row.get(MediaOfferIden::OfferId.to_string().as_str()))
+            .collect())
+    }
+
+    /// Returns the ids of media that were actually removed from the offer
+    pub async fn delete_many(
+        pool: &Pool,
+        media_ids: &[Uuid],
+        offer_id: &Uuid,
+        user_id: &String,
+    ) -> Result<Vec<Uuid>, DbError> {
+        let client = get_client(pool, pool_acquire_timeout()).await?;
+
+        let (sql, values) = Query::delete()
+            .from_table(MediaOfferIden::Table)
+            .and_where(
+                Expr::col(MediaOfferIden::MediaId)
+                    .is_in(media_ids.iter().copied()),
+            )
+            .and_where(Expr::col(MediaOfferIden::OfferId).eq(*offer_id))
+            .and_where(Expr::col(MediaOfferIden::UserId).eq(user_id))
+            .returning_col(MediaOfferIden::MediaId)
+            .build_postgres(PostgresQueryBuilder);
+
+        let rows = observe_query(
+            &sql,
+            client.query(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| row.get(MediaOfferIden::MediaId.to_string().as_str()))
+            .collect())
+    }
 }
 
 impl From<Row> for MediaOffer {
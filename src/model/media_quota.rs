@@ -1,9 +1,11 @@
 use deadpool_postgres::tokio_postgres::Row;
-use deadpool_postgres::Pool;
-use sea_query::{Asterisk, Expr, Iden, PostgresQueryBuilder, Query};
+use deadpool_postgres::{Pool, Transaction};
+use sea_query::{
+    Asterisk, Expr, Iden, OnConflict, PostgresQueryBuilder, Query,
+};
 use sea_query_postgres::PostgresBinder;
 
-use crate::db::DbError;
+use crate::db::{get_client, observe_query, pool_acquire_timeout, DbError};
 
 #[derive(Iden)]
 #[iden(rename = "medias_quota")]
@@ -25,7 +27,7 @@ impl MediaQuota {
         user_id: &String,
         max_size_mib: u64,
     ) -> Result<Self, DbError> {
-        let client = pool.get().await?;
+        let client = get_client(pool, pool_acquire_timeout()).await?;
 
         let (sql, values) = Query::insert()
             .into_table(MediaQuotaIden::Table)
@@ -37,7 +39,11 @@ impl MediaQuota {
             .returning_all()
             .build_postgres(PostgresQueryBuilder);
 
-        let row = client.query_one(sql.as_str(), &values.as_params()).await?;
+        let row = observe_query(
+            &sql,
+            client.query_one(sql.as_str(), &values.as_params()),
+        )
+        .await?;
 
         Ok(Self::from(row))
     }
@@ -46,7 +52,7 @@ impl MediaQuota {
         pool: &Pool,
         user_id: &String,
     ) -> Result<Option<Self>, DbError> {
-        let client = pool.get().await?;
+        let client = get_client(pool, pool_acquire_timeout()).await?;
 
         let (sql, values) = Query::select()
             .column(Asterisk)
@@ -54,10 +60,60 @@ impl MediaQuota {
             .and_where(Expr::col(MediaQuotaIden::UserId).eq(user_id))
             .build_postgres(PostgresQueryBuilder);
 
-        let row = client.query_opt(sql.as_str(), &values.as_params()).await?;
+        let row = observe_query(
+            &sql,
+            client.query_opt(sql.as_str(), &values.as_params()),
+        )
+        .await?;
 
         Ok(row.map(Self::from))
     }
+
+    /// Creates the quota of the user with `default_max_size_mib` if missing
+    /// and locks it until `transaction` ends. Creating it first means there
+    /// always is a row to lock, also for the first uploads of a user.
+    pub async fn get_or_create_for_update<'a>(
+        transaction: &Transaction<'a>,
+        user_id: &String,
+        default_max_size_mib: u64,
+    ) -> Result<Self, DbError> {
+        let (sql, values) = Query::insert()
+            .into_table(MediaQuotaIden::Table)
+            .columns([MediaQuotaIden::UserId, MediaQuotaIden::MaxSizeMib])
+            .values([
+                user_id.into(),
+                i64::try_from(default_max_size_mib)
+                    .expect("should fit")
+                    .into(),
+            ])?
+            .on_conflict(
+                OnConflict::column(MediaQuotaIden::UserId)
+                    .do_nothing()
+                    .to_owned(),
+            )
+            .build_postgres(PostgresQueryBuilder);
+
+        observe_query(
+            &sql,
+            transaction.execute(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+
+        let (sql, values) = Query::select()
+            .column(Asterisk)
+            .from(MediaQuotaIden::Table)
+            .and_where(Expr::col(MediaQuotaIden::UserId).eq(user_id))
+            .lock_exclusive()
+            .build_postgres(PostgresQueryBuilder);
+
+        let row = observe_query(
+            &sql,
+            transaction.query_one(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+
+        Ok(Self::from(row))
+    }
 }
 
 impl From<Row> for MediaQuota {
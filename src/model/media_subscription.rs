@@ -4,12 +4,17 @@ use chrono::{DateTime, Utc};
 use deadpool_postgres::tokio_postgres::Row;
 use deadpool_postgres::Pool;
 use sea_query::{
-    any, Asterisk, Expr, Iden, OnConflict, PostgresQueryBuilder, Query,
+    Alias, Asterisk, Expr, Iden, Keyword, OnConflict, PostgresQueryBuilder,
+    Query, SimpleExpr,
 };
 use sea_query_postgres::PostgresBinder;
 use uuid::Uuid;
 
-use crate::db::{get_count_from_rows, DbError};
+use crate::api::sited_io::media::v1::SubscriptionStatus;
+use crate::db::{
+    get_client, get_count_from_rows, observe_query, pool_acquire_timeout,
+    DbError,
+};
 
 #[derive(Debug, Clone, Iden)]
 #[iden(rename = "media_subscriptions")]
@@ -29,6 +34,10 @@ pub enum MediaSubscriptionIden {
     StripeSubscriptionId,
     CanceledAt,
     CancelAt,
+    RevokedAt,
+    RevokedBy,
+    RevokeReason,
+    SellerNotes,
 }
 
 #[derive(Debug, Clone)]
@@ -39,7 +48,7 @@ pub struct MediaSubscription {
     pub shop_id: Uuid,
     pub current_period_start: DateTime<Utc>,
     pub current_period_end: DateTime<Utc>,
-    pub subscription_status: String,
+    pub subscription_status: SubscriptionStatus,
     pub payed_at: DateTime<Utc>,
     pub payed_until: DateTime<Utc>,
     pub created_at: DateTime<Utc>,
@@ -47,9 +56,53 @@ pub struct MediaSubscription {
     pub stripe_subscription_id: Option<String>,
     pub canceled_at: Option<DateTime<Utc>>,
     pub cancel_at: Option<DateTime<Utc>>,
+    /// Only to be shown to the seller
+    pub seller_notes: Option<String>,
 }
 
 impl MediaSubscription {
+    /// Statuses granting access to the media of the subscribed offer
+    pub const ACCESSIBLE_STATUSES: [SubscriptionStatus; 2] =
+        [SubscriptionStatus::Active, SubscriptionStatus::Trialing];
+
+    /// Returns the status as stored in the database, matching the values
+    /// allowed by the `check_subscription_status` constraint
+    pub fn status_to_db(status: SubscriptionStatus) -> &'static str {
+        match status {
+            SubscriptionStatus::Unspecified => "unspecified",
+            SubscriptionStatus::Active => "active",
+            SubscriptionStatus::PastDue => "past_due",
+            SubscriptionStatus::Canceled => "canceled",
+            SubscriptionStatus::Trialing => "trialing",
+            SubscriptionStatus::Paused => "paused",
+            SubscriptionStatus::Incomplete => "incomplete",
+            SubscriptionStatus::IncompleteExpired => "incomplete_expired",
+            SubscriptionStatus::Unpaid => "unpaid",
+            SubscriptionStatus::Revoked => "revoked",
+        }
+    }
+
+    fn status_from_db(status: &str) -> SubscriptionStatus {
+        match status {
+            "active" => SubscriptionStatus::Active,
+            "past_due" => SubscriptionStatus::PastDue,
+            "canceled" => SubscriptionStatus::Canceled,
+            "trialing" => SubscriptionStatus::Trialing,
+            "paused" => SubscriptionStatus::Paused,
+            "incomplete" => SubscriptionStatus::Incomplete,
+            "incomplete_expired" => SubscriptionStatus::IncompleteExpired,
+            "unpaid" => SubscriptionStatus::Unpaid,
+            "revoked" => SubscriptionStatus::Revoked,
+            _ => SubscriptionStatus::Unspecified,
+        }
+    }
+
+    pub fn accessible_status_values() -> impl Iterator<Item = &'static str> {
+        Self::ACCESSIBLE_STATUSES
+            .into_iter()
+            .map(Self::status_to_db)
+    }
+
     const PUT_COLUMNS: [MediaSubscriptionIden; 12] = [
         MediaSubscriptionIden::MediaSubscriptionId,
         MediaSubscriptionIden::BuyerUserId,
@@ -74,14 +127,22 @@ impl MediaSubscription {
         shop_id: &Uuid,
         current_period_start: &DateTime<Utc>,
         current_period_end: &DateTime<Utc>,
-        subscription_status: &String,
+        subscription_status: SubscriptionStatus,
         payed_at: &DateTime<Utc>,
         payed_until: &DateTime<Utc>,
         stripe_subscription_id: Option<String>,
         canceled_at: Option<DateTime<Utc>>,
         cancel_at: Option<DateTime<Utc>>,
-    ) -> Result<Self, DbError> {
-        let conn = pool.get().await?;
+    ) -> Result<Option<Self>, DbError> {
+        let conn = get_client(pool, pool_acquire_timeout()).await?;
+
+        // a replaced revoked subscription leaves no revocation behind
+        let not_revoked = [
+            MediaSubscriptionIden::RevokedAt,
+            MediaSubscriptionIden::RevokedBy,
+            MediaSubscriptionIden::RevokeReason,
+        ]
+        .map(|col| (col, SimpleExpr::Keyword(Keyword::Null)));
 
         let (sql, values) = Query::insert()
             .into_table(MediaSubscriptionIden::Table)
@@ -93,34 +154,149 @@ impl MediaSubscription {
                 (*shop_id).into(),
                 (*current_period_start).into(),
                 (*current_period_end).into(),
-                subscription_status.into(),
+                Self::status_to_db(subscription_status).into(),
                 (*payed_at).into(),
                 (*payed_until).into(),
                 stripe_subscription_id.into(),
                 canceled_at.into(),
                 cancel_at.into(),
             ])?
-            .on_conflict(
-                OnConflict::column(MediaSubscriptionIden::MediaSubscriptionId)
-                    .update_columns(Self::PUT_COLUMNS)
-                    .to_owned(),
-            )
             .on_conflict(
                 OnConflict::columns([
                     MediaSubscriptionIden::BuyerUserId,
                     MediaSubscriptionIden::OfferId,
                 ])
                 .update_columns(Self::PUT_COLUMNS)
+                .values(not_revoked)
+                .action_and_where(Self::put_allowed())
                 .to_owned(),
             )
             .returning_all()
             .build_postgres(PostgresQueryBuilder);
 
-        let row = conn
-            .query_one(sql.as_str(), values.as_params().as_ref())
-            .await?;
+        let row = observe_query(
+            &sql,
+            conn.query_opt(sql.as_str(), values.as_params().as_ref()),
+        )
+        .await?;
+
+        Ok(row.map(Self::from))
+    }
+
+    /// Updates of a revoked subscription must not undo the revocation. A new
+    /// subscription of the buyer for the same offer, with another
+    /// `media_subscription_id` and `stripe_subscription_id`, replaces it.
+    fn put_allowed() -> SimpleExpr {
+        let existing = |col| Expr::col((MediaSubscriptionIden::Table, col));
+        let excluded = |col| (Alias::new("excluded"), col);
+
+        let other_stripe_subscription =
+            existing(MediaSubscriptionIden::StripeSubscriptionId)
+                .is_null()
+                .or(Expr::col(excluded(
+                    MediaSubscriptionIden::StripeSubscriptionId,
+                ))
+                .is_null())
+                .or(existing(MediaSubscriptionIden::StripeSubscriptionId)
+                    .not_equals(excluded(
+                        MediaSubscriptionIden::StripeSubscriptionId,
+                    )));
+
+        existing(MediaSubscriptionIden::SubscriptionStatus)
+            .ne(Self::status_to_db(SubscriptionStatus::Revoked))
+            .or(existing(MediaSubscriptionIden::MediaSubscriptionId)
+                .not_equals(excluded(
+                    MediaSubscriptionIden::MediaSubscriptionId,
+                ))
+                .and(other_stripe_subscription))
+    }
+
+    /// Ends access immediately regardless of the paid period and records who
+    /// revoked it and why. Returns `None` if the subscription does not exist.
+    pub async fn revoke(
+        pool: &Pool,
+        media_subscription_id: &Uuid,
+        revoked_by: &String,
+        revoke_reason: &String,
+    ) -> Result<Option<Self>, DbError> {
+        let conn = get_client(pool, pool_acquire_timeout()).await?;
+
+        let (sql, values) = Query::update()
+            .table(MediaSubscriptionIden::Table)
+            .value(
+                MediaSubscriptionIden::SubscriptionStatus,
+                Self::status_to_db(SubscriptionStatus::Revoked),
+            )
+            .value(MediaSubscriptionIden::PayedUntil, Expr::current_timestamp())
+            .value(MediaSubscriptionIden::RevokedAt, Expr::current_timestamp())
+            .value(MediaSubscriptionIden::RevokedBy, revoked_by)
+            .value(MediaSubscriptionIden::RevokeReason, revoke_reason)
+            .and_where(
+                Expr::col(MediaSubscriptionIden::MediaSubscriptionId)
+                    .eq(*media_subscription_id),
+            )
+            .returning_all()
+            .build_postgres(PostgresQueryBuilder);
+
+        let row = observe_query(
+            &sql,
+            conn.query_opt(sql.as_str(), values.as_params().as_ref()),
+        )
+        .await?;
+
+        Ok(row.map(Self::from))
+    }
+
+    /// Sets or with `None` clears the notes of the seller
+    pub async fn set_seller_notes(
+        pool: &Pool,
+        media_subscription_id: &Uuid,
+        seller_notes: Option<String>,
+    ) -> Result<Option<Self>, DbError> {
+        let conn = get_client(pool, pool_acquire_timeout()).await?;
+
+        let (sql, values) = Query::update()
+            .table(MediaSubscriptionIden::Table)
+            .value(MediaSubscriptionIden::SellerNotes, seller_notes)
+            .and_where(
+                Expr::col(MediaSubscriptionIden::MediaSubscriptionId)
+                    .eq(*media_subscription_id),
+            )
+            .returning_all()
+            .build_postgres(PostgresQueryBuilder);
+
+        let row = observe_query(
+            &sql,
+            conn.query_opt(sql.as_str(), values.as_params().as_ref()),
+        )
+        .await?;
+
+        Ok(row.map(Self::from))
+    }
+
+    /// Subscription regardless of its buyer, callers check the access
+    pub async fn get_by_id(
+        pool: &Pool,
+        media_subscription_id: &Uuid,
+    ) -> Result<Option<Self>, DbError> {
+        let conn = get_client(pool, pool_acquire_timeout()).await?;
+
+        let (sql, values) = Query::select()
+            .column(Asterisk)
+            .from(MediaSubscriptionIden::Table)
+            .and_where(
+                Expr::col(MediaSubscriptionIden::MediaSubscriptionId)
+                    .eq(*media_subscription_id),
+            )
+            .build_postgres(PostgresQueryBuilder);
+
+        let row = observe_query(
+            &sql,
+            conn.query_opt(sql.as_str(), values.as_params().as_ref()),
+        )
+        .await?;
 
-        Ok(Self::from(row))
+        Ok(row.map(Self::from))
     }
 
     pub async fn get(
@@ -129,7 +305,7 @@ impl MediaSubscription {
         media_subscription_id: Option<Uuid>,
         offer_id: Option<Uuid>,
     ) -> Result<Option<Self>, DbError> {
-        let conn = pool.get().await?;
+        let conn = get_client(pool, pool_acquire_timeout()).await?;
 
         let (sql, values) = {
             let mut query = Query::select();
@@ -141,12 +317,10 @@ impl MediaSubscription {
                     Expr::col(MediaSubscriptionIden::BuyerUserId)
                         .eq(buyer_user_id),
                 )
-                .cond_where(any![
-                    Expr::col(MediaSubscriptionIden::SubscriptionStatus)
-                        .eq("active"),
+                .cond_where(
                     Expr::col(MediaSubscriptionIden::SubscriptionStatus)
-                        .eq("trialing")
-                ]);
+                        .is_in(Self::accessible_status_values()),
+                );
 
             if let Some(media_subscription_id) = media_subscription_id {
                 query.cond_where(
@@ -164,7 +338,11 @@ impl MediaSubscription {
             query.build_postgres(PostgresQueryBuilder)
         };
 
-        let row = conn.query_opt(sql.as_str(), &values.as_params()).await?;
+        let row = observe_query(
+            &sql,
+            conn.query_opt(sql.as_str(), &values.as_params()),
+        )
+        .await?;
 
         Ok(row.map(Self::from))
     }
@@ -177,7 +355,7 @@ impl MediaSubscription {
         limit: u64,
         offset: u64,
     ) -> Result<(Vec<Self>, i64), DbError> {
-        let mut conn = pool.get().await?;
+        let mut conn = get_client(pool, pool_acquire_timeout()).await?;
         let transaction = conn.transaction().await?;
 
         let ((sql, values), (count_sql, count_values)) = {
@@ -189,12 +367,10 @@ impl MediaSubscription {
                     Expr::col(MediaSubscriptionIden::BuyerUserId)
                         .eq(buyer_user_id),
                 )
-                .cond_where(any![
-                    Expr::col(MediaSubscriptionIden::SubscriptionStatus)
-                        .eq("active"),
+                .cond_where(
                     Expr::col(MediaSubscriptionIden::SubscriptionStatus)
-                        .eq("trialing")
-                ]);
+                        .is_in(Self::accessible_status_values()),
+                );
 
             if let Some(shop_id) = shop_id {
                 query.cond_where(
@@ -208,7 +384,7 @@ impl MediaSubscription {
                         MediaSubscriptionIden::Table,
                         MediaSubscriptionIden::PayedUntil,
                     ))
-                    .gte(Utc::now()),
+                    .gte(Expr::current_timestamp()),
                 );
             }
 
@@ -228,10 +404,16 @@ impl MediaSubscription {
             )
         };
 
-        let rows = transaction.query(sql.as_str(), &values.as_params()).await?;
-        let count_rows = transaction
-            .query(count_sql.as_str(), &count_values.as_params())
-            .await?;
+        let rows = observe_query(
+            &sql,
+            transaction.query(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+        let count_rows = observe_query(
+            &count_sql,
+            transaction.query(count_sql.as_str(), &count_values.as_params()),
+        )
+        .await?;
         transaction.commit().await?;
 
         let count = get_count_from_rows(&count_rows);
@@ -262,10 +444,12 @@ impl From<&Row> for MediaSubscription {
             current_period_end: row.get(
                 MediaSubscriptionIden::CurrentPeriodEnd.to_string().as_str(),
             ),
-            subscription_status: row.get(
-                MediaSubscriptionIden::SubscriptionStatus
-                    .to_string()
-                    .as_str(),
+            subscription_status: Self::status_from_db(
+                row.get(
+                    MediaSubscriptionIden::SubscriptionStatus
+                        .to_string()
+                        .as_str(),
+                ),
             ),
             payed_at: row
                 .get(MediaSubscriptionIden::PayedAt.to_string().as_str()),
@@ -284,6 +468,8 @@ impl From<&Row> for MediaSubscription {
                 .get(MediaSubscriptionIden::CanceledAt.to_string().as_str()),
             cancel_at: row
                 .get(MediaSubscriptionIden::CancelAt.to_string().as_str()),
+            seller_notes: row
+                .get(MediaSubscriptionIden::SellerNotes.to_string().as_str()),
         }
     }
 }
@@ -293,3 +479,151 @@ impl From<Row> for MediaSubscription {
         Self::from(&row)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::db::test_db::TestDb;
+
+    async fn put_active(
+        pool: &Pool,
+        media_subscription_id: &Uuid,
+        buyer_user_id: &String,
+        offer_id: &Uuid,
+        stripe_subscription_id: &str,
+    ) -> Option<MediaSubscription> {
+        let now = Utc::now();
+
+        MediaSubscription::put(
+            pool,
+            media_subscription_id,
+            buyer_user_id,
+            offer_id,
+            &Uuid::new_v4(),
+            &now,
+            &(now + Duration::days(30)),
+            SubscriptionStatus::Active,
+            &now,
+            &(now + Duration::days(30)),
+            Some(stripe_subscription_id.to_string()),
+            None,
+            None,
+        )
+        .await
+        .unwrap()
+    }
+
+    async fn get_accessible(
+        pool: &Pool,
+        buyer_user_id: &String,
+        offer_id: &Uuid,
+    ) -> Option<MediaSubscription> {
+        MediaSubscription::get(pool, buyer_user_id, None, Some(*offer_id))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn revoke_cuts_off_access_until_resubscribed() {
+        let db = TestDb::start().await;
+        let buyer_user_id = "buyer".to_string();
+        let offer_id = Uuid::new_v4();
+        let media_subscription_id = Uuid::new_v4();
+
+        put_active(
+            &db.pool,
+            &media_subscription_id,
+            &buyer_user_id,
+            &offer_id,
+            "sub_1",
+        )
+        .await
+        .unwrap();
+        assert!(get_accessible(&db.pool, &buyer_user_id, &offer_id)
+            .await
+            .is_some());
+
+        MediaSubscription::revoke(
+            &db.pool,
+            &media_subscription_id,
+            &"admin".to_string(),
+            &"chargeback".to_string(),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert!(get_accessible(&db.pool, &buyer_user_id, &offer_id)
+            .await
+            .is_none());
+
+        // later updates of the revoked subscription are ignored
+        let updated = put_active(
+            &db.pool,
+            &media_subscription_id,
+            &buyer_user_id,
+            &offer_id,
+            "sub_1",
+        )
+        .await;
+        assert!(updated.is_none());
+        assert!(get_accessible(&db.pool, &buyer_user_id, &offer_id)
+            .await
+            .is_none());
+
+        let resubscribed = put_active(
+            &db.pool,
+            &Uuid::new_v4(),
+            &buyer_user_id,
+            &offer_id,
+            "sub_2",
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            resubscribed.subscription_status,
+            SubscriptionStatus::Active
+        );
+        assert!(get_accessible(&db.pool, &buyer_user_id, &offer_id)
+            .await
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn revoked_stripe_subscription_is_not_reactivated() {
+        let db = TestDb::start().await;
+        let buyer_user_id = "buyer".to_string();
+        let offer_id = Uuid::new_v4();
+        let media_subscription_id = Uuid::new_v4();
+
+        put_active(
+            &db.pool,
+            &media_subscription_id,
+            &buyer_user_id,
+            &offer_id,
+            "sub_1",
+        )
+        .await
+        .unwrap();
+        MediaSubscription::revoke(
+            &db.pool,
+            &media_subscription_id,
+            &"admin".to_string(),
+            &"fraud".to_string(),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        let updated = put_active(
+            &db.pool,
+            &Uuid::new_v4(),
+            &buyer_user_id,
+            &offer_id,
+            "sub_1",
+        )
+        .await;
+        assert!(updated.is_none());
+    }
+}
@@ -2,8 +2,14 @@ mod media;
 mod media_offer;
 mod media_quota;
 mod media_subscription;
+mod multipart_part;
+mod multipart_upload_session;
+mod shop_bucket;
 
-pub use self::media::Media;
+pub use self::media::{Media, MediaProjection, UpdateMediaFields};
 pub use media_offer::MediaOffer;
 pub use media_quota::MediaQuota;
 pub use media_subscription::MediaSubscription;
+pub use multipart_part::MultipartPart;
+pub use multipart_upload_session::MultipartUploadSession;
+pub use shop_bucket::ShopBucket;
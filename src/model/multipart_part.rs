@@ -0,0 +1,195 @@
+use chrono::{DateTime, Utc};
+use deadpool_postgres::tokio_postgres::Row;
+use deadpool_postgres::{Pool, Transaction};
+use sea_query::{
+    Asterisk, Expr, Iden, OnConflict, Order, PostgresQueryBuilder, Query,
+};
+use sea_query_postgres::PostgresBinder;
+
+use crate::db::{get_client, observe_query, pool_acquire_timeout, DbError};
+
+#[derive(Iden)]
+#[iden(rename = "multipart_parts")]
+pub enum MultipartPartIden {
+    Table,
+    UploadId,
+    PartNumber,
+    Etag,
+    UploadedAt,
+}
+
+#[derive(Debug, Clone)]
+pub struct MultipartPart {
+    pub upload_id: String,
+    pub part_number: u32,
+    pub etag: String,
+    pub uploaded_at: DateTime<Utc>,
+}
+
+impl MultipartPart {
+    pub async fn put(
+        pool: &Pool,
+        upload_id: &String,
+        part_number: u32,
+        etag: &String,
+    ) -> Result<(), DbError> {
+        let client = get_client(pool, pool_acquire_timeout()).await?;
+
+        let (sql, values) = Query::insert()
+            .into_table(MultipartPartIden::Table)
+            .columns([
+                MultipartPartIden::UploadId,
+                MultipartPartIden::PartNumber,
+                MultipartPartIden::Etag,
+            ])
+            .values([
+                upload_id.into(),
+                (part_number as i32).into(),
+                etag.into(),
+            ])?
+            .on_conflict(
+                OnConflict::columns([
+                    MultipartPartIden::UploadId,
+                    MultipartPartIden::PartNumber,
+                ])
+                .update_column(MultipartPartIden::Etag)
+                .value(MultipartPartIden::UploadedAt, Expr::current_timestamp())
+                .to_owned(),
+            )
+            .build_postgres(PostgresQueryBuilder);
+
+        observe_query(&sql, client.execute(sql.as_str(), &values.as_params()))
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn get(
+        pool: &Pool,
+        upload_id: &String,
+        part_number: u32,
+    ) -> Result<Option<Self>, DbError> {
+        let client = get_client(pool, pool_acquire_timeout()).await?;
+
+        let (sql, values) = Query::select()
+            .column(Asterisk)
+            .from(MultipartPartIden::Table)
+            .and_where(Expr::col(MultipartPartIden::UploadId).eq(upload_id))
+            .and_where(
+                Expr::col(MultipartPartIden::PartNumber).eq(part_number as i32),
+            )
+            .build_postgres(PostgresQueryBuilder);
+
+        let row = observe_query(
+            &sql,
+            client.query_opt(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+
+        Ok(row.map(Self::from))
+    }
+
+    pub async fn list(
+        pool: &Pool,
+        upload_id: &String,
+    ) -> Result<Vec<Self>, DbError> {
+        let client = get_client(pool, pool_acquire_timeout()).await?;
+
+        let (sql, values) = Query::select()
+            .column(Asterisk)
+            .from(MultipartPartIden::Table)
+            .and_where(Expr::col(MultipartPartIden::UploadId).eq(upload_id))
+            .order_by(MultipartPartIden::PartNumber, Order::Asc)
+            .build_postgres(PostgresQueryBuilder);
+
+        let rows = observe_query(
+            &sql,
+            client.query(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+
+        Ok(rows.into_iter().map(Self::from).collect())
+    }
+
+    pub async fn count(
+        pool: &Pool,
+        upload_id: &String,
+    ) -> Result<i64, DbError> {
+        let client = get_client(pool, pool_acquire_timeout()).await?;
+
+        let (sql, values) = Query::select()
+            .expr(Expr::col((MultipartPartIden::Table, Asterisk)).count())
+            .from(MultipartPartIden::Table)
+            .and_where(Expr::col(MultipartPartIden::UploadId).eq(upload_id))
+            .build_postgres(PostgresQueryBuilder);
+
+        let row = observe_query(
+            &sql,
+            client.query_one(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+
+        Ok(row.get(0))
+    }
+
+    /// Removes the recorded parts once the upload completed or was aborted
+    pub async fn delete_all_for_upload<'a>(
+        transaction: &Transaction<'a>,
+        upload_id: &String,
+    ) -> Result<(), DbError> {
+        let (sql, values) = Query::delete()
+            .from_table(MultipartPartIden::Table)
+            .and_where(Expr::col(MultipartPartIden::UploadId).eq(upload_id))
+            .build_postgres(PostgresQueryBuilder);
+
+        observe_query(
+            &sql,
+            transaction.execute(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+impl From<Row> for MultipartPart {
+    fn from(row: Row) -> Self {
+        let part_number: i32 =
+            row.get(MultipartPartIden::PartNumber.to_string().as_str());
+
+        Self {
+            upload_id: row
+                .get(MultipartPartIden::UploadId.to_string().as_str()),
+            part_number: part_number.try_into().unwrap_or_default(),
+            etag: row.get(MultipartPartIden::Etag.to_string().as_str()),
+            uploaded_at: row
+                .get(MultipartPartIden::UploadedAt.to_string().as_str()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_db::TestDb;
+
+    #[tokio::test]
+    async fn existing_part_is_found_by_number() {
+        let db = TestDb::start().await;
+        let upload_id = "upload".to_string();
+        let other_upload_id = "other-upload".to_string();
+        MultipartPart::put(&db.pool, &upload_id, 1, &"etag".to_string())
+            .await
+            .unwrap();
+        MultipartPart::put(&db.pool, &other_upload_id, 2, &"other".to_string())
+            .await
+            .unwrap();
+
+        let part = MultipartPart::get(&db.pool, &upload_id, 1).await.unwrap();
+        assert_eq!(part.unwrap().etag, "etag");
+        assert!(MultipartPart::get(&db.pool, &upload_id, 2)
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
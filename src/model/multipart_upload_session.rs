@@ -0,0 +1,350 @@
+use deadpool_postgres::tokio_postgres::Row;
+use deadpool_postgres::{Pool, Transaction};
+use sea_query::{
+    Asterisk, Expr, Iden, OnConflict, Order, PostgresQueryBuilder, Query,
+};
+use sea_query_postgres::PostgresBinder;
+use uuid::Uuid;
+
+use crate::db::{get_client, observe_query, pool_acquire_timeout, DbError};
+
+#[derive(Iden)]
+#[iden(rename = "multipart_upload_sessions")]
+pub enum MultipartUploadSessionIden {
+    Table,
+    UploadId,
+    MediaId,
+    UserId,
+    CreatedAt,
+    LastError,
+    ErrorCount,
+    ObjectKey,
+    ContentType,
+    ReplacedSizeBytes,
+}
+
+/// Multipart upload in progress, removed once it is completed or aborted
+pub struct MultipartUploadSession {
+    pub upload_id: String,
+    pub media_id: Uuid,
+    pub user_id: String,
+    pub last_error: Option<String>,
+    pub error_count: i32,
+    /// Key the parts are uploaded to. The media keeps its current file until
+    /// the upload completes. `None` for uploads initiated before the key was
+    /// recorded, those upload to the key of the media.
+    pub object_key: Option<String>,
+    pub content_type: Option<String>,
+    /// Size of the file the upload replaces, `None` if the media had no file
+    pub replaced_size_bytes: Option<i64>,
+}
+
+impl MultipartUploadSession {
+    pub async fn create(
+        pool: &Pool,
+        upload_id: &String,
+        media_id: &Uuid,
+        user_id: &String,
+        object_key: &String,
+        content_type: &String,
+        replaced_size_bytes: Option<i64>,
+    ) -> Result<(), DbError> {
+        let client = get_client(pool, pool_acquire_timeout()).await?;
+
+        let (sql, values) = Query::insert()
+            .into_table(MultipartUploadSessionIden::Table)
+            .columns([
+                MultipartUploadSessionIden::UploadId,
+                MultipartUploadSessionIden::MediaId,
+                MultipartUploadSessionIden::UserId,
+                MultipartUploadSessionIden::ObjectKey,
+                MultipartUploadSessionIden::ContentType,
+                MultipartUploadSessionIden::ReplacedSizeBytes,
+            ])
+            .values([
+                upload_id.into(),
+                (*media_id).into(),
+                user_id.into(),
+                object_key.into(),
+                content_type.into(),
+                replaced_size_bytes.into(),
+            ])?
+            .on_conflict(
+                OnConflict::column(MultipartUploadSessionIden::UploadId)
+                    .do_nothing()
+                    .to_owned(),
+            )
+            .build_postgres(PostgresQueryBuilder);
+
+        observe_query(&sql, client.execute(sql.as_str(), &values.as_params()))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Keeps the latest failure of the upload. Sessions of uploads
+    /// initiated before sessions were recorded are created on the fly.
+    pub async fn record_error(
+        pool: &Pool,
+        upload_id: &String,
+        media_id: &Uuid,
+        user_id: &String,
+        error: &str,
+    ) -> Result<(), DbError> {
+        let client = get_client(pool, pool_acquire_timeout()).await?;
+
+        let (sql, values) = Query::insert()
+            .into_table(MultipartUploadSessionIden::Table)
+            .columns([
+                MultipartUploadSessionIden::UploadId,
+                MultipartUploadSessionIden::MediaId,
+                MultipartUploadSessionIden::UserId,
+                MultipartUploadSessionIden::LastError,
+                MultipartUploadSessionIden::ErrorCount,
+            ])
+            .values([
+                upload_id.into(),
+                (*media_id).into(),
+                user_id.into(),
+                error.into(),
+                1.into(),
+            ])?
+            .on_conflict(
+                OnConflict::column(MultipartUploadSessionIden::UploadId)
+                    .update_column(MultipartUploadSessionIden::LastError)
+                    .value(
+                        MultipartUploadSessionIden::ErrorCount,
+                        Expr::cust("multipart_upload_sessions.error_count + 1"),
+                    )
+                    .to_owned(),
+            )
+            .build_postgres(PostgresQueryBuilder);
+
+        observe_query(&sql, client.execute(sql.as_str(), &values.as_params()))
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn get(
+        pool: &Pool,
+        upload_id: &String,
+    ) -> Result<Option<Self>, DbError> {
+        let client = get_client(pool, pool_acquire_timeout()).await?;
+
+        let (sql, values) = Query::select()
+            .column(Asterisk)
+            .from(MultipartUploadSessionIden::Table)
+            .and_where(
+                Expr::col(MultipartUploadSessionIden::UploadId).eq(upload_id),
+            )
+            .build_postgres(PostgresQueryBuilder);
+
+        let row = observe_query(
+            &sql,
+            client.query_opt(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+
+        Ok(row.map(Self::from))
+    }
+
+    /// Latest upload in progress of the media
+    pub async fn get_latest_for_media(
+        pool: &Pool,
+        media_id: &Uuid,
+    ) -> Result<Option<Self>, DbError> {
+        let client = get_client(pool, pool_acquire_timeout()).await?;
+
+        let (sql, values) = Query::select()
+            .column(Asterisk)
+            .from(MultipartUploadSessionIden::Table)
+            .and_where(
+                Expr::col(MultipartUploadSessionIden::MediaId).eq(*media_id),
+            )
+            .order_by(MultipartUploadSessionIden::CreatedAt, Order::Desc)
+            .limit(1)
+            .build_postgres(PostgresQueryBuilder);
+
+        let row = observe_query(
+            &sql,
+            client.query_opt(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+
+        Ok(row.map(Self::from))
+    }
+
+    pub async fn delete<'a>(
+        transaction: &Transaction<'a>,
+        upload_id: &String,
+    ) -> Result<(), DbError> {
+        let (sql, values) = Query::delete()
+            .from_table(MultipartUploadSessionIden::Table)
+            .and_where(
+                Expr::col(MultipartUploadSessionIden::UploadId).eq(upload_id),
+            )
+            .build_postgres(PostgresQueryBuilder);
+
+        observe_query(
+            &sql,
+            transaction.execute(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+impl From<Row> for MultipartUploadSession {
+    fn from(row: Row) -> Self {
+        Self {
+            upload_id: row
+                .get(MultipartUploadSessionIden::UploadId.to_string().as_str()),
+            media_id: row
+                .get(MultipartUploadSessionIden::MediaId.to_string().as_str()),
+            user_id: row
+                .get(MultipartUploadSessionIden::UserId.to_string().as_str()),
+            last_error: row.get(
+                MultipartUploadSessionIden::LastError.to_string().as_str(),
+            ),
+            error_count: row.get(
+                MultipartUploadSessionIden::ErrorCount.to_string().as_str(),
+            ),
+            object_key: row.get(
+                MultipartUploadSessionIden::ObjectKey.to_string().as_str(),
+            ),
+            content_type: row.get(
+                MultipartUploadSessionIden::ContentType.to_string().as_str(),
+            ),
+            replaced_size_bytes: row.get(
+                MultipartUploadSessionIden::ReplacedSizeBytes
+                    .to_string()
+                    .as_str(),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_db::TestDb;
+    use crate::model::MultipartPart;
+
+    async fn start_upload(pool: &Pool, upload_id: &String) {
+        MultipartUploadSession::create(
+            pool,
+            upload_id,
+            &Uuid::new_v4(),
+            &"user".to_string(),
+            &"user/shop/media".to_string(),
+            &"application/pdf".to_string(),
+            None,
+        )
+        .await
+        .unwrap();
+        for part_number in 1..=2 {
+            MultipartPart::put(
+                pool,
+                upload_id,
+                part_number,
+                &format!("etag-{part_number}"),
+            )
+            .await
+            .unwrap();
+        }
+    }
+
+    async fn finish_upload(pool: &Pool, upload_id: &String, commit: bool) {
+        let mut conn = get_client(pool, pool_acquire_timeout()).await.unwrap();
+        let transaction = conn.transaction().await.unwrap();
+        MultipartPart::delete_all_for_upload(&transaction, upload_id)
+            .await
+            .unwrap();
+        MultipartUploadSession::delete(&transaction, upload_id)
+            .await
+            .unwrap();
+        if commit {
+            transaction.commit().await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn finished_upload_leaves_no_session_or_parts() {
+        let db = TestDb::start().await;
+        let upload_id = "upload".to_string();
+        let other_upload_id = "other-upload".to_string();
+        start_upload(&db.pool, &upload_id).await;
+        start_upload(&db.pool, &other_upload_id).await;
+
+        finish_upload(&db.pool, &upload_id, true).await;
+
+        assert!(MultipartUploadSession::get(&db.pool, &upload_id)
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            MultipartPart::count(&db.pool, &upload_id).await.unwrap(),
+            0
+        );
+        assert!(MultipartUploadSession::get(&db.pool, &other_upload_id)
+            .await
+            .unwrap()
+            .is_some());
+        assert_eq!(
+            MultipartPart::count(&db.pool, &other_upload_id)
+                .await
+                .unwrap(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn errors_of_upload_are_counted() {
+        let db = TestDb::start().await;
+        let upload_id = "upload".to_string();
+        start_upload(&db.pool, &upload_id).await;
+        let session = MultipartUploadSession::get(&db.pool, &upload_id)
+            .await
+            .unwrap();
+        assert_eq!(session.unwrap().error_count, 0);
+
+        for error in ["first", "second"] {
+            MultipartUploadSession::record_error(
+                &db.pool,
+                &upload_id,
+                &Uuid::new_v4(),
+                &"user".to_string(),
+                error,
+            )
+            .await
+            .unwrap();
+        }
+
+        let session = MultipartUploadSession::get(&db.pool, &upload_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(session.error_count, 2);
+        assert_eq!(session.last_error.as_deref(), Some("second"));
+    }
+
+    #[tokio::test]
+    async fn rolled_back_cleanup_keeps_session_and_parts() {
+        let db = TestDb::start().await;
+        let upload_id = "upload".to_string();
+        start_upload(&db.pool, &upload_id).await;
+
+        finish_upload(&db.pool, &upload_id, false).await;
+
+        assert!(MultipartUploadSession::get(&db.pool, &upload_id)
+            .await
+            .unwrap()
+            .is_some());
+        assert_eq!(
+            MultipartPart::count(&db.pool, &upload_id).await.unwrap(),
+            2
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use deadpool_postgres::tokio_postgres::Row;
+use deadpool_postgres::Pool;
+use sea_query::{Asterisk, Expr, Iden, PostgresQueryBuilder, Query};
+use sea_query_postgres::PostgresBinder;
+use uuid::Uuid;
+
+use crate::db::{get_client, observe_query, pool_acquire_timeout, DbError};
+use crate::files::{FileError, ObjectEncryption};
+
+#[derive(Iden)]
+#[iden(rename = "shop_buckets")]
+pub enum ShopBucketIden {
+    Table,
+    ShopId,
+    BucketName,
+    BucketEndpoint,
+    AccessKeyId,
+    SecretAccessKeyRef,
+    SseAlgorithm,
+    SseKmsKeyId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ShopBucket {
+    pub shop_id: Uuid,
+    pub bucket_name: String,
+    pub bucket_endpoint: Option<String>,
+    pub access_key_id: Option<String>,
+    /// Name of the secret access key in the secret store, the key itself is
+    /// never stored in the database
+    pub secret_access_key_ref: Option<String>,
+    /// Server-side encryption of objects written to the bucket, `AES256` or
+    /// `aws:kms`
+    pub sse_algorithm: Option<String>,
+    pub sse_kms_key_id: Option<String>,
+}
+
+impl ShopBucket {
+    pub async fn get(
+        pool: &Pool,
+        shop_id: &Uuid,
+    ) -> Result<Option<Self>, DbError> {
+        let client = get_client(pool, pool_acquire_timeout()).await?;
+
+        let (sql, values) = Query::select()
+            .column(Asterisk)
+            .from(ShopBucketIden::Table)
+            .and_where(Expr::col(ShopBucketIden::ShopId).eq(*shop_id))
+            .build_postgres(PostgresQueryBuilder);
+
+        let row = observe_query(
+            &sql,
+            client.query_opt(sql.as_str(), &values.as_params()),
+        )
+        .await?;
+
+        Ok(row.map(Self::from))
+    }
+
+    /// Encryption configured for the bucket, none if no algorithm is set
+    pub fn encryption(&self) -> Result<Option<ObjectEncryption>, FileError> {
+        ObjectEncryption::parse(
+            self.sse_algorithm.as_deref().unwrap_or_default(),
+            self.sse_kms_key_id.clone(),
+        )
+        .map_err(|err| FileError::EncryptionConfigError {
+            source: format!("shop bucket {}: {err}", self.bucket_name).into(),
+        })
+    }
+}
+
+impl From<Row> for ShopBucket {
+    fn from(row: Row) -> Self {
+        Self {
+            shop_id: row.get(ShopBucketIden::ShopId.to_string().as_str()),
+            bucket_name: row
+                .get(ShopBucketIden::BucketName.to_string().as_str()),
+            bucket_endpoint: row
+                .get(ShopBucketIden::BucketEndpoint.to_string().as_str()),
+            access_key_id: row
+                .get(ShopBucketIden::AccessKeyId.to_string().as_str()),
+            secret_access_key_ref: row
+                .get(ShopBucketIden::SecretAccessKeyRef.to_string().as_str()),
+            sse_algorithm: row
+                .get(ShopBucketIden::SseAlgorithm.to_string().as_str()),
+            sse_kms_key_id: row
+                .get(ShopBucketIden::SseKmsKeyId.to_string().as_str()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shop_bucket(sse_algorithm: Option<&str>) -> ShopBucket {
+        ShopBucket {
+            shop_id: Uuid::new_v4(),
+            bucket_name: "shop-bucket".to_string(),
+            bucket_endpoint: None,
+            access_key_id: None,
+            secret_access_key_ref: None,
+            sse_algorithm: sse_algorithm.map(str::to_string),
+            sse_kms_key_id: Some("shop-key".to_string()),
+        }
+    }
+
+    #[test]
+    fn encryption_of_shop_bucket() {
+        assert_eq!(shop_bucket(None).encryption().unwrap(), None);
+        assert_eq!(
+            shop_bucket(Some("aws:kms")).encryption().unwrap(),
+            Some(ObjectEncryption::Kms {
+                key_id: Some("shop-key".to_string())
+            })
+        );
+        assert!(matches!(
+            shop_bucket(Some("DES")).encryption(),
+            Err(FileError::EncryptionConfigError { .. })
+        ));
+    }
+}
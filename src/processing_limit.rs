@@ -0,0 +1,126 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tonic::Status;
+
+use crate::StatusBuilder;
+
+/// Bounds CPU heavy processing of uploaded files, like decoding images, that
+/// runs on the blocking thread pool. At most `concurrency` files are
+/// processed at once, up to `max_queued` further requests wait for a slot
+/// and any beyond that are rejected with `resource_exhausted`.
+#[derive(Debug, Clone)]
+pub struct ProcessingLimiter {
+    permits: Arc<Semaphore>,
+    queued: Arc<AtomicUsize>,
+    max_queued: usize,
+}
+
+impl ProcessingLimiter {
+    /// Hint for rejected clients, roughly the time it takes to process a file
+    const RETRY_DELAY_MS: u64 = 1_000;
+
+    pub fn new(concurrency: usize, max_queued: usize) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(concurrency)),
+            queued: Arc::new(AtomicUsize::new(0)),
+            max_queued,
+        }
+    }
+
+    /// Number of CPUs, used as concurrency if none is configured
+    pub fn default_concurrency() -> usize {
+        std::thread::available_parallelism()
+            .map(usize::from)
+            .unwrap_or(1)
+    }
+
+    /// Waits for a processing slot, which is released when the permit is
+    /// dropped
+    pub async fn acquire(&self) -> Result<OwnedSemaphorePermit, Status> {
+        if let Ok(permit) = self.permits.clone().try_acquire_owned() {
+            return Ok(permit);
+        }
+
+        let queued = self.queued.fetch_add(1, Ordering::SeqCst);
+        // decrements the counter again however this function returns, also
+        // when the caller drops the future while waiting
+        let _queued = QueuedGuard(&self.queued);
+        if queued >= self.max_queued {
+            tracing::log::warn!(
+                "[ProcessingLimiter.acquire]: rejected, {queued} already waiting"
+            );
+            return Err(StatusBuilder::resource_exhausted(
+                "too many uploads are being processed, retry later",
+            )
+            .with_retry_info(Self::RETRY_DELAY_MS)
+            .build());
+        }
+
+        let permit = self.permits.clone().acquire_owned().await;
+
+        // the semaphore is never closed
+        permit.map_err(|err| {
+            tracing::log::error!("[ProcessingLimiter.acquire]: {err}");
+            Status::internal("")
+        })
+    }
+}
+
+struct QueuedGuard<'a>(&'a AtomicUsize);
+
+impl Drop for QueuedGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tonic::Code;
+    use tonic_types::StatusExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn rejects_when_queue_is_full() {
+        let limiter = ProcessingLimiter::new(1, 1);
+        let running = limiter.acquire().await.unwrap();
+
+        let waiting = tokio::spawn({
+            let limiter = limiter.clone();
+            async move { limiter.acquire().await.map(drop) }
+        });
+        tokio::task::yield_now().await;
+
+        let err = limiter.acquire().await.unwrap_err();
+        assert_eq!(err.code(), Code::ResourceExhausted);
+        assert_eq!(
+            err.get_details_retry_info().unwrap().retry_delay,
+            Some(Duration::from_millis(ProcessingLimiter::RETRY_DELAY_MS)),
+        );
+        assert_eq!(limiter.queued.load(Ordering::SeqCst), 1);
+
+        drop(running);
+        waiting.await.unwrap().unwrap();
+        assert_eq!(limiter.queued.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn cancelled_wait_releases_queue_slot() {
+        let limiter = ProcessingLimiter::new(1, 1);
+        let running = limiter.acquire().await.unwrap();
+
+        let cancelled =
+            tokio::time::timeout(Duration::from_millis(10), limiter.acquire())
+                .await;
+        assert!(cancelled.is_err());
+        assert_eq!(limiter.queued.load(Ordering::SeqCst), 0);
+
+        drop(running);
+        let _permit = limiter.acquire().await.unwrap();
+    }
+}
@@ -0,0 +1,160 @@
+use std::time::{Duration, Instant};
+
+use tokio::sync::mpsc;
+use tonic::codegen::tokio_stream::wrappers::ReceiverStream;
+use tonic::Status;
+
+use crate::api::sited_io::media::v1::OperationProgress;
+
+/// Reports progress of long running batch loops to the response stream of a
+/// server streaming RPC. Updates are throttled to one per `interval`, the
+/// first and the final update are always sent.
+pub struct ProgressEmitter<T> {
+    sender: mpsc::Sender<Result<T, Status>>,
+    total: u64,
+    processed: u64,
+    interval: Duration,
+    last_emitted: Option<Instant>,
+}
+
+impl<T> ProgressEmitter<T>
+where
+    T: From<OperationProgress>,
+{
+    const CHANNEL_SIZE: usize = 4;
+
+    /// Returns the emitter and the stream to respond with
+    pub fn channel(
+        total: u64,
+        interval: Duration,
+    ) -> (Self, ReceiverStream<Result<T, Status>>) {
+        let (sender, receiver) = mpsc::channel(Self::CHANNEL_SIZE);
+
+        (
+            Self {
+                sender,
+                total,
+                processed: 0,
+                interval,
+                last_emitted: None,
+            },
+            ReceiverStream::new(receiver),
+        )
+    }
+
+    /// Counts `current_item` as processed. Fails with `cancelled` once the
+    /// client went away, so the loop can stop early.
+    pub async fn advance(
+        &mut self,
+        current_item: impl Into<String>,
+    ) -> Result<(), Status> {
+        self.processed += 1;
+
+        let is_due = self
+            .last_emitted
+            .is_none_or(|last| last.elapsed() >= self.interval);
+
+        if is_due || self.processed >= self.total {
+            self.emit(Some(current_item.into())).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Sends `message` with results right away, like the final message of a
+    /// batch or the items of an export. Fails with `cancelled` once the
+    /// client went away.
+    pub async fn send(&mut self, message: T) -> Result<(), Status> {
+        self.sender
+            .send(Ok(message))
+            .await
+            .map_err(|_| Status::cancelled("client went away"))
+    }
+
+    /// Ends the stream with `status`
+    pub async fn fail(self, status: Status) {
+        let _ = self.sender.send(Err(status)).await;
+    }
+
+    async fn emit(
+        &mut self,
+        current_item: Option<String>,
+    ) -> Result<(), Status> {
+        let progress = OperationProgress {
+            processed: self.processed,
+            total: self.total,
+            current_item,
+        };
+
+        self.sender
+            .send(Ok(progress.into()))
+            .await
+            .map_err(|_| Status::cancelled("client went away"))?;
+        self.last_emitted = Some(Instant::now());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic::codegen::tokio_stream::StreamExt;
+    use tonic::Code;
+
+    use crate::api::sited_io::media::v1::MigrateStorageKeysResponse;
+
+    use super::*;
+
+    type Emitter = ProgressEmitter<MigrateStorageKeysResponse>;
+
+    fn progress_of(message: Result<MigrateStorageKeysResponse, Status>) -> u64 {
+        message.unwrap().progress.unwrap().processed
+    }
+
+    #[tokio::test]
+    async fn first_and_final_updates_are_always_sent() {
+        let (mut emitter, mut stream) =
+            Emitter::channel(3, Duration::from_secs(3600));
+
+        for item in ["a", "b", "c"] {
+            emitter.advance(item).await.unwrap();
+        }
+        emitter
+            .send(MigrateStorageKeysResponse {
+                migrated: 3,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        drop(emitter);
+
+        assert_eq!(progress_of(stream.next().await.unwrap()), 1);
+        assert_eq!(progress_of(stream.next().await.unwrap()), 3);
+        let last = stream.next().await.unwrap().unwrap();
+        assert_eq!(last.migrated, 3);
+        assert!(last.progress.is_none());
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn updates_are_sent_once_due() {
+        let (mut emitter, stream) = Emitter::channel(3, Duration::ZERO);
+
+        for item in ["a", "b", "c"] {
+            emitter.advance(item).await.unwrap();
+        }
+        drop(emitter);
+
+        let processed = stream.map(progress_of).collect::<Vec<u64>>().await;
+        assert_eq!(processed, vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn advance_fails_once_client_went_away() {
+        let (mut emitter, stream) = Emitter::channel(2, Duration::ZERO);
+        drop(stream);
+
+        let err = emitter.advance("a").await.unwrap_err();
+        assert_eq!(err.code(), Code::Cancelled);
+    }
+}
@@ -1,4 +1,5 @@
-use deadpool_postgres::Pool;
+use deadpool_postgres::{Pool, Transaction};
+use tonic::metadata::MetadataValue;
 use tonic::Status;
 
 use crate::model::{Media, MediaQuota};
@@ -16,10 +17,25 @@ impl QuotaService {
         }
     }
 
+    /// Metadata key telling clients whether retrying can succeed without
+    /// freeing up storage first
+    const QUOTA_RETRYABLE_KEY: &'static str = "quota-retryable";
+
     fn quota_reached(total_bytes: u64, max_size_mib: u64) -> bool {
         total_bytes > max_size_mib * 1024 * 1024
     }
 
+    /// The quota is a hard limit, so retrying only succeeds once the user
+    /// deleted media.
+    fn quota_reached_status() -> Status {
+        let mut status = Status::resource_exhausted("quota");
+        status.metadata_mut().insert(
+            Self::QUOTA_RETRYABLE_KEY,
+            MetadataValue::from_static("false"),
+        );
+        status
+    }
+
     async fn ensure_user_quota(
         &self,
         user_id: &String,
@@ -49,9 +65,143 @@ impl QuotaService {
         let total_bytes: u64 = found_medias.iter().map(|m| m.size_bytes).sum();
 
         if Self::quota_reached(total_bytes, user_quota.max_size_mib) {
-            Err(Status::out_of_range("quota"))
+            Err(Self::quota_reached_status())
         } else {
             Ok(())
         }
     }
+
+    /// Fails if the files of the user exceed the quota, counting changes
+    /// not yet committed in `transaction`. The quota stays locked until the
+    /// transaction ends, so concurrent uploads of a user are checked one
+    /// after another instead of all passing on the same usage. Uploads
+    /// conflicting anyway fail with `aborted` and can be retried.
+    pub async fn check_quota_in_transaction<'a>(
+        &self,
+        transaction: &Transaction<'a>,
+        user_id: &String,
+    ) -> Result<(), Status> {
+        let user_quota = MediaQuota::get_or_create_for_update(
+            transaction,
+            user_id,
+            self.default_user_quota_mib,
+        )
+        .await?;
+        let total_bytes =
+            Media::get_total_size_for_user(transaction, user_id).await?;
+
+        if Self::quota_reached(total_bytes, user_quota.max_size_mib) {
+            Err(Self::quota_reached_status())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use tonic::Code;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::db::test_db::TestDb;
+    use crate::db::DbError;
+
+    const QUOTA_MIB: u64 = 1;
+
+    /// Creates media of `size_bytes` and commits if the quota allows it
+    async fn upload(
+        pool: &Pool,
+        quota_service: &QuotaService,
+        user_id: &String,
+        size_bytes: i64,
+    ) -> Result<(), Status> {
+        let mut conn = pool.get().await.map_err(DbError::from)?;
+        let transaction = conn.transaction().await.map_err(DbError::from)?;
+        let media_id = Uuid::new_v4();
+
+        Media::create(
+            &transaction,
+            &media_id,
+            &Uuid::new_v4(),
+            user_id,
+            &"name".to_string(),
+            &media_id.to_string(),
+            size_bytes,
+            &"file".to_string(),
+            None,
+            None,
+            &HashMap::new(),
+        )
+        .await?;
+        quota_service
+            .check_quota_in_transaction(&transaction, user_id)
+            .await?;
+        // keeps the quota locked while the other upload checks it
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        transaction.commit().await.map_err(DbError::from)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn quota_reached_above_max_size() {
+        assert!(!QuotaService::quota_reached(1024 * 1024, 1));
+        assert!(QuotaService::quota_reached(1024 * 1024 + 1, 1));
+    }
+
+    #[tokio::test]
+    async fn exceeded_quota_is_resource_exhausted() {
+        let db = TestDb::start().await;
+        let quota_service = QuotaService::new(db.pool.clone(), QUOTA_MIB);
+        let user_id = "user".to_string();
+        upload(&db.pool, &quota_service, &user_id, 1024 * 1024)
+            .await
+            .unwrap();
+        quota_service.check_quota(&user_id).await.unwrap();
+
+        // adding to a multipart upload grows the media after creation
+        let media = Media::list_all_for_user(&db.pool, &user_id).await.unwrap();
+        Media::add_size(&db.pool, &media[0].media_id, &user_id, 1)
+            .await
+            .unwrap();
+
+        let err = quota_service.check_quota(&user_id).await.unwrap_err();
+        assert_eq!(err.code(), Code::ResourceExhausted);
+        assert_eq!(
+            err.metadata()
+                .get(QuotaService::QUOTA_RETRYABLE_KEY)
+                .unwrap(),
+            "false"
+        );
+    }
+
+    #[tokio::test]
+    async fn parallel_uploads_can_not_exceed_quota() {
+        let db = TestDb::start().await;
+        let quota_service = QuotaService::new(db.pool.clone(), QUOTA_MIB);
+        let user_id = "user".to_string();
+        // each upload fits on its own, both together do not
+        let size_bytes = 768 * 1024;
+
+        let (first, second) = tokio::join!(
+            upload(&db.pool, &quota_service, &user_id, size_bytes),
+            upload(&db.pool, &quota_service, &user_id, size_bytes),
+        );
+
+        let results = [first, second];
+        assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 1);
+        let err = results.into_iter().find_map(Result::err).unwrap();
+        assert!(
+            matches!(err.code(), Code::ResourceExhausted | Code::Aborted),
+            "{err:?}"
+        );
+
+        let created =
+            Media::list_all_for_user(&db.pool, &user_id).await.unwrap();
+        assert_eq!(created.len(), 1);
+    }
 }
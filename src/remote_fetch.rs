@@ -0,0 +1,285 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use reqwest::redirect::Policy;
+use reqwest::{StatusCode, Url};
+use tonic::Status;
+
+use crate::StatusBuilder;
+
+/// Fetches user supplied URLs server-side while guarding against requests to
+/// internal networks. Every redirect hop is validated again and the request
+/// is pinned to the address that was validated.
+#[derive(Debug, Clone)]
+pub struct RemoteFetchService {
+    allowed_hosts: Option<Vec<String>>,
+}
+
+impl RemoteFetchService {
+    const MAX_REDIRECTS: usize = 5;
+    const DEFAULT_CONTENT_TYPE: &'static str = "application/octet-stream";
+
+    /// If `allowed_hosts` is given, only those hosts can be fetched from
+    pub fn new(allowed_hosts: Option<Vec<String>>) -> Self {
+        Self { allowed_hosts }
+    }
+
+    pub async fn get(&self, url: &str) -> Result<reqwest::Response, Status> {
+        let mut url = Url::parse(url)
+            .map_err(|_| Status::invalid_argument("url: not a valid url"))?;
+
+        for _ in 0..=Self::MAX_REDIRECTS {
+            let (host, addr) = self.validate_url(&url).await?;
+
+            let client = reqwest::Client::builder()
+                .redirect(Policy::none())
+                .resolve(&host, addr)
+                .build()
+                .map_err(|err| {
+                    tracing::log::error!("[RemoteFetchService.get]: {err}");
+                    Status::internal("")
+                })?;
+
+            let response =
+                client.get(url.clone()).send().await.map_err(|err| {
+                    tracing::log::warn!("[RemoteFetchService.get]: {err}");
+                    Status::unavailable("url: could not be fetched")
+                })?;
+
+            if !response.status().is_redirection()
+                || response.status() == StatusCode::NOT_MODIFIED
+            {
+                return Ok(response);
+            }
+
+            let location = response
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|l| l.to_str().ok())
+                .ok_or_else(|| {
+                    Status::invalid_argument("url: redirect without location")
+                })?;
+
+            url = url.join(location).map_err(|_| {
+                Status::invalid_argument("url: redirect to invalid url")
+            })?;
+        }
+
+        Err(Status::invalid_argument("url: too many redirects"))
+    }
+
+    /// Fetches the file at `url`, returning its content type and data. Fails
+    /// with `resource_exhausted` once more than `max_bytes` are received.
+    pub async fn get_file(
+        &self,
+        url: &str,
+        max_bytes: usize,
+    ) -> Result<(String, Vec<u8>), Status> {
+        let mut response = self.get(url).await?;
+
+        if !response.status().is_success() {
+            return Err(Status::invalid_argument(format!(
+                "url: responded with {}",
+                response.status()
+            )));
+        }
+
+        let too_large = || {
+            StatusBuilder::resource_exhausted(format!(
+                "url: file exceeds the maximum of {max_bytes} bytes"
+            ))
+            .build()
+        };
+
+        if response
+            .content_length()
+            .is_some_and(|length| length > max_bytes as u64)
+        {
+            return Err(too_large());
+        }
+
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|c| c.to_str().ok())
+            .and_then(|c| c.split(';').next())
+            .map(|c| c.trim().to_lowercase())
+            .filter(|c| !c.is_empty())
+            .unwrap_or_else(|| String::from(Self::DEFAULT_CONTENT_TYPE));
+
+        // the length header is only a hint, the body is bounded as it is read
+        let mut data = Vec::new();
+        while let Some(chunk) = response.chunk().await.map_err(|err| {
+            tracing::log::warn!("[RemoteFetchService.get_file]: {err}");
+            Status::unavailable("url: could not be fetched")
+        })? {
+            if data.len() + chunk.len() > max_bytes {
+                return Err(too_large());
+            }
+            data.extend_from_slice(&chunk);
+        }
+
+        Ok((content_type, data))
+    }
+
+    /// Returns host and the resolved address requests must be sent to
+    async fn validate_url(
+        &self,
+        url: &Url,
+    ) -> Result<(String, SocketAddr), Status> {
+        if url.scheme() != "http" && url.scheme() != "https" {
+            return Err(Status::invalid_argument(
+                "url: only http and https are allowed",
+            ));
+        }
+
+        let host = url
+            .host_str()
+            .ok_or_else(|| Status::invalid_argument("url: missing host"))?
+            .to_lowercase();
+
+        if let Some(allowed_hosts) = &self.allowed_hosts {
+            if !allowed_hosts.contains(&host) {
+                return Err(Status::permission_denied(format!(
+                    "url: host {host} is not allowed"
+                )));
+            }
+        }
+
+        let port = url.port_or_known_default().unwrap_or(80);
+
+        let addrs = tokio::net::lookup_host((
+            host.trim_start_matches('[').trim_end_matches(']'),
+            port,
+        ))
+        .await
+        .map_err(|_| {
+            Status::invalid_argument(format!("url: could not resolve {host}"))
+        })?
+        .collect::<Vec<SocketAddr>>();
+
+        // reject if any address is internal, otherwise another lookup could
+        // hand out the internal one
+        if addrs.is_empty() || addrs.iter().any(|a| !is_public_ip(&a.ip())) {
+            return Err(Status::permission_denied(format!(
+                "url: host {host} resolves to a blocked address"
+            )));
+        }
+
+        Ok((host, addrs[0]))
+    }
+}
+
+fn is_public_ip(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        // IPv4-mapped ::ffff:0:0/96 and IPv4-compatible ::/96 addresses
+        // reach the embedded IPv4 address
+        IpAddr::V6(ip) => match ip.to_ipv4() {
+            Some(embedded) => is_public_ipv4(&embedded),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: &Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // shared address space 100.64.0.0/10
+        || (a == 100 && (b & 0b1100_0000) == 64)
+        // "this network" 0.0.0.0/8
+        || a == 0
+        // benchmarking 198.18.0.0/15
+        || (a == 198 && (b & 0b1111_1110) == 18)
+        // reserved 240.0.0.0/4
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: &Ipv6Addr) -> bool {
+    let [first, second, ..] = ip.segments();
+
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // unique local fc00::/7
+        || (first & 0xfe00) == 0xfc00
+        // link local fe80::/10
+        || (first & 0xffc0) == 0xfe80
+        // NAT64 64:ff9b::/96 and local-use 64:ff9b:1::/48 embed IPv4
+        // addresses that are reached through a translator
+        || (first == 0x64 && second == 0xff9b)
+        // Teredo 2001::/32 and 6to4 2002::/16 also embed IPv4 addresses
+        || (first == 0x2001 && second == 0)
+        || first == 0x2002
+        // documentation 2001:db8::/32
+        || (first == 0x2001 && second == 0xdb8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_public(ip: &str) -> bool {
+        is_public_ip(&ip.parse().unwrap())
+    }
+
+    #[test]
+    fn internal_ipv4_ranges_are_blocked() {
+        for ip in [
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "127.0.0.1",
+            "169.254.169.254",
+            "0.0.0.0",
+            "100.64.0.1",
+            "198.18.0.1",
+            "198.19.255.255",
+            "240.0.0.1",
+            "255.255.255.255",
+            "224.0.0.1",
+            "192.0.2.1",
+        ] {
+            assert!(!is_public(ip), "{ip} is not blocked");
+        }
+    }
+
+    #[test]
+    fn internal_ipv6_ranges_are_blocked() {
+        for ip in [
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "ff02::1",
+            "64:ff9b::8.8.8.8",
+            "64:ff9b:1::1",
+            "2002:a9fe:a9fe::1",
+            "2001:0:4136:e378::1",
+            "2001:db8::1",
+        ] {
+            assert!(!is_public(ip), "{ip} is not blocked");
+        }
+    }
+
+    #[test]
+    fn embedded_ipv4_addresses_are_unwrapped() {
+        assert!(!is_public("::ffff:127.0.0.1"));
+        assert!(!is_public("::ffff:169.254.169.254"));
+        assert!(!is_public("::10.0.0.1"));
+        assert!(is_public("::ffff:8.8.8.8"));
+    }
+
+    #[test]
+    fn public_addresses_are_allowed() {
+        for ip in ["8.8.8.8", "1.1.1.1", "198.20.0.1", "2606:4700::1111"] {
+            assert!(is_public(ip), "{ip} is blocked");
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_util::StreamExt;
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use tonic::body::BoxBody;
+use tonic::codegen::StdError;
+use tonic::transport::Body;
+use tonic::Status;
+use tower::{Layer, Service};
+
+use crate::StatusBuilder;
+
+const GRPC_WEB_TEXT_CONTENT_TYPE: &str = "application/grpc-web-text";
+/// Compression flag and length prefix of a gRPC message
+const GRPC_FRAME_HEADER_BYTES: u64 = 5;
+
+/// Rejects requests whose `content-length` exceeds `max_bytes` before the
+/// body is read, and fails the body once more than `max_bytes` were read.
+/// The read bytes also bound requests without `content-length` (e.g.
+/// chunked gRPC-web) and base64 encoded gRPC-web text, which is decoded
+/// before it reaches this layer.
+#[derive(Debug, Clone)]
+pub struct RequestSizeLimitLayer {
+    max_bytes: u64,
+}
+
+impl RequestSizeLimitLayer {
+    /// `max_message_bytes` is the maximum size of a single gRPC message
+    pub fn new(max_message_bytes: u64) -> Self {
+        Self {
+            max_bytes: max_message_bytes + GRPC_FRAME_HEADER_BYTES,
+        }
+    }
+}
+
+impl<S> Layer<S> for RequestSizeLimitLayer {
+    type Service = RequestSizeLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestSizeLimit {
+            inner,
+            max_bytes: self.max_bytes,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RequestSizeLimit<S> {
+    inner: S,
+    max_bytes: u64,
+}
+
+fn too_large(max_bytes: u64) -> Status {
+    StatusBuilder::resource_exhausted(format!(
+        "request exceeds maximum of {max_bytes} bytes"
+    ))
+    .build()
+}
+
+impl<S> Service<http::Request<Body>> for RequestSizeLimit<S>
+where
+    S: Service<http::Request<Body>, Response = http::Response<BoxBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<
+        Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>,
+    >;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<Body>) -> Self::Future {
+        let is_grpc_web_text = request
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with(GRPC_WEB_TEXT_CONTENT_TYPE));
+
+        let content_length = request
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok());
+
+        if let Some(content_length) =
+            content_length.filter(|_| !is_grpc_web_text)
+        {
+            if content_length > self.max_bytes {
+                tracing::log::warn!(
+                    "[RequestSizeLimit]: rejected {} with content-length {content_length}",
+                    request.uri().path()
+                );
+                let response = too_large(self.max_bytes).to_http();
+                return Box::pin(async move { Ok(response) });
+            }
+        }
+
+        // the status is found in the source chain of the body error, so the
+        // handler reading the body responds with it
+        let max_bytes = self.max_bytes;
+        let mut read_bytes = 0;
+        let request = request.map(|body| {
+            Body::wrap_stream(body.map(move |data| {
+                let data = data?;
+                read_bytes += data.len() as u64;
+                if read_bytes > max_bytes {
+                    tracing::log::warn!(
+                        "[RequestSizeLimit]: rejected body exceeding {max_bytes} bytes"
+                    );
+                    return Err(StdError::from(too_large(max_bytes)));
+                }
+                Ok(data)
+            }))
+        });
+
+        Box::pin(self.inner.call(request))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic::Code;
+    use tower::{service_fn, ServiceExt};
+
+    use super::*;
+
+    /// Reads the whole body like a handler decoding the request
+    async fn read_body(
+        request: http::Request<Body>,
+    ) -> Result<http::Response<BoxBody>, StdError> {
+        let mut body = request.into_body();
+        while let Some(data) = body.next().await {
+            if let Err(err) = data {
+                return Ok(Status::from_error(Box::new(err)).to_http());
+            }
+        }
+        Ok(http::Response::new(tonic::body::empty_body()))
+    }
+
+    async fn call(request: http::Request<Body>) -> Code {
+        let response = RequestSizeLimitLayer::new(10)
+            .layer(service_fn(read_body))
+            .oneshot(request)
+            .await
+            .unwrap();
+
+        Status::from_header_map(response.headers())
+            .map_or(Code::Ok, |status| status.code())
+    }
+
+    fn chunked(chunks: Vec<&'static [u8]>) -> Body {
+        Body::wrap_stream(futures_util::stream::iter(
+            chunks.into_iter().map(Ok::<_, StdError>),
+        ))
+    }
+
+    #[tokio::test]
+    async fn content_length_over_limit_is_rejected() {
+        let request = http::Request::builder()
+            .header(CONTENT_LENGTH, "16")
+            .body(Body::from(vec![0; 16]))
+            .unwrap();
+
+        assert_eq!(call(request).await, Code::ResourceExhausted);
+    }
+
+    #[tokio::test]
+    async fn body_over_limit_is_rejected_without_content_length() {
+        let request = http::Request::new(chunked(vec![&[0; 8], &[0; 8]]));
+
+        assert_eq!(call(request).await, Code::ResourceExhausted);
+    }
+
+    #[tokio::test]
+    async fn body_over_limit_is_rejected_despite_smaller_content_length() {
+        let request = http::Request::builder()
+            .header(CONTENT_LENGTH, "8")
+            .body(chunked(vec![&[0; 8], &[0; 8]]))
+            .unwrap();
+
+        assert_eq!(call(request).await, Code::ResourceExhausted);
+    }
+
+    #[tokio::test]
+    async fn body_within_limit_is_passed_on() {
+        let request = http::Request::new(chunked(vec![&[0; 8], &[0; 7]]));
+
+        assert_eq!(call(request).await, Code::Ok);
+    }
+}
@@ -0,0 +1,229 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tonic::body::BoxBody;
+use tonic::Status;
+use tower::{Layer, Service};
+
+/// Which deadline of `TimeoutPolicy` applies to a method
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MethodKind {
+    Read,
+    Write,
+    Upload,
+    Batch,
+}
+
+/// Deadline of every served method by name. Methods missing here, which
+/// are only expected from newly added RPCs, get the write deadline.
+const METHOD_KINDS: [(&str, MethodKind); 40] = [
+    // sited_io.media.v1.MediaService
+    ("CreateMedia", MethodKind::Upload),
+    ("GetMedia", MethodKind::Read),
+    ("DownloadMedia", MethodKind::Read),
+    ("ListMedia", MethodKind::Read),
+    ("ListAccessibleMedia", MethodKind::Read),
+    ("UpdateMedia", MethodKind::Upload),
+    ("DeleteMedia", MethodKind::Write),
+    ("InitiateMultipartUpload", MethodKind::Write),
+    ("PutMultipartChunk", MethodKind::Upload),
+    ("CompleteMultipartUpload", MethodKind::Upload),
+    ("AddMediaToOffer", MethodKind::Write),
+    ("UpdateMediaOfferOrdering", MethodKind::Write),
+    ("RemoveMediaFromOffer", MethodKind::Write),
+    ("DownloadMediaRange", MethodKind::Upload),
+    ("RenameMedia", MethodKind::Write),
+    ("DownloadMediaChunks", MethodKind::Upload),
+    ("GetShopMediaStats", MethodKind::Read),
+    ("CheckMediaOwnership", MethodKind::Read),
+    ("MoveMediaBetweenOffers", MethodKind::Write),
+    ("GetMediaRenditions", MethodKind::Read),
+    ("BatchRemoveMediaFromOffer", MethodKind::Write),
+    ("AbortMultipartUpload", MethodKind::Write),
+    ("MigrateStorageKeys", MethodKind::Batch),
+    ("ResolvePermalink", MethodKind::Read),
+    ("DetachMediaFromAllOffers", MethodKind::Write),
+    ("FindSimilarMedia", MethodKind::Read),
+    ("ExportBoothManifest", MethodKind::Batch),
+    ("GetUploadStatus", MethodKind::Read),
+    ("ImportMediaFromUrl", MethodKind::Upload),
+    // sited_io.media.v1.MediaSubscriptionService
+    ("PutMediaSubscription", MethodKind::Write),
+    ("GetMediaSubscription", MethodKind::Read),
+    ("ListMediaSubscriptions", MethodKind::Read),
+    ("CancelMediaSubscription", MethodKind::Write),
+    ("ResumeMediaSubscription", MethodKind::Write),
+    ("RevokeMediaSubscription", MethodKind::Write),
+    ("UpdateMediaSubscriptionNotes", MethodKind::Write),
+    ("GetShopMediaSubscription", MethodKind::Read),
+    // grpc.health.v1.Health
+    ("Check", MethodKind::Read),
+    ("Watch", MethodKind::Read),
+    // grpc.reflection.v1alpha.ServerReflection
+    ("ServerReflectionInfo", MethodKind::Read),
+];
+
+/// Deadlines per category of RPC method
+#[derive(Debug, Clone, Copy)]
+pub struct TimeoutPolicy {
+    /// Getting, listing, checking and finding
+    pub read: Duration,
+    /// Any other method changing state
+    pub write: Duration,
+    /// Uploads and downloads of file data
+    pub upload: Duration,
+    /// Operations over all media of a shop or the whole service
+    pub batch: Duration,
+}
+
+impl Default for TimeoutPolicy {
+    fn default() -> Self {
+        Self {
+            read: Duration::from_secs(10),
+            write: Duration::from_secs(30),
+            upload: Duration::from_secs(300),
+            batch: Duration::from_secs(900),
+        }
+    }
+}
+
+impl TimeoutPolicy {
+    /// Returns the deadline for a request path like
+    /// `/sited_io.media.v1.MediaService/ListMedia`
+    pub fn timeout_for(&self, path: &str) -> Duration {
+        let method = path.rsplit('/').next().unwrap_or_default();
+
+        let kind = METHOD_KINDS
+            .iter()
+            .find(|(name, _)| *name == method)
+            .map_or(MethodKind::Write, |(_, kind)| *kind);
+
+        match kind {
+            MethodKind::Read => self.read,
+            MethodKind::Write => self.write,
+            MethodKind::Upload => self.upload,
+            MethodKind::Batch => self.batch,
+        }
+    }
+}
+
+/// Fails requests with `deadline_exceeded` if no response was produced
+/// within the deadline of their method. For server streaming methods only
+/// the time until the response starts is limited.
+#[derive(Debug, Clone)]
+pub struct RequestTimeoutLayer {
+    policy: TimeoutPolicy,
+}
+
+impl RequestTimeoutLayer {
+    pub fn new(policy: TimeoutPolicy) -> Self {
+        Self { policy }
+    }
+}
+
+impl<S> Layer<S> for RequestTimeoutLayer {
+    type Service = RequestTimeout<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestTimeout {
+            inner,
+            policy: self.policy,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RequestTimeout<S> {
+    inner: S,
+    policy: TimeoutPolicy,
+}
+
+impl<S, B> Service<http::Request<B>> for RequestTimeout<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<BoxBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<
+        Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>,
+    >;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let path = request.uri().path().to_owned();
+        let timeout = self.policy.timeout_for(&path);
+        let response = self.inner.call(request);
+
+        Box::pin(async move {
+            match tokio::time::timeout(timeout, response).await {
+                Ok(response) => response,
+                Err(_) => {
+                    tracing::log::warn!(
+                        "[RequestTimeout]: {path} exceeded {} ms",
+                        timeout.as_millis()
+                    );
+                    Ok(Status::deadline_exceeded(format!(
+                        "request exceeded deadline of {} ms",
+                        timeout.as_millis()
+                    ))
+                    .to_http())
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prost::Message;
+    use prost_types::FileDescriptorSet;
+
+    use super::*;
+
+    #[test]
+    fn every_method_has_a_deadline() {
+        let file_descriptor_set = FileDescriptorSet::decode(
+            crate::api::sited_io::FILE_DESCRIPTOR_SET,
+        )
+        .unwrap();
+
+        for file in file_descriptor_set.file {
+            for service in file.service {
+                for method in service.method {
+                    let name = method.name();
+                    assert!(
+                        METHOD_KINDS.iter().any(|(m, _)| *m == name),
+                        "{name} has no deadline"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn methods_get_the_deadline_of_their_kind() {
+        let policy = TimeoutPolicy::default();
+        let timeout_for = |method: &str| {
+            policy.timeout_for(&format!(
+                "/sited_io.media.v1.MediaService/{method}"
+            ))
+        };
+
+        assert_eq!(timeout_for("ResolvePermalink"), policy.read);
+        assert_eq!(timeout_for("DownloadMedia"), policy.read);
+        assert_eq!(timeout_for("DeleteMedia"), policy.write);
+        assert_eq!(timeout_for("PutMultipartChunk"), policy.upload);
+        assert_eq!(timeout_for("MigrateStorageKeys"), policy.batch);
+        assert_eq!(timeout_for("ExportBoothManifest"), policy.batch);
+        assert_eq!(timeout_for("Unknown"), policy.write);
+    }
+}
@@ -1,8 +1,20 @@
 use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 
 use aws_sdk_s3::types::CompletedPart;
-use deadpool_postgres::Pool;
+use base64::engine::general_purpose;
+use base64::Engine;
+use chrono::Utc;
+use deadpool_postgres::{Pool, Transaction};
+use futures_util::future::try_join_all;
+use futures_util::StreamExt;
 use jwtk::jwk::RemoteJwksVerifier;
+use moka::future::Cache;
+use prost::Message;
+use tokio::sync::mpsc;
+use tonic::codegen::tokio_stream::wrappers::ReceiverStream;
 use tonic::{async_trait, Request, Response, Status};
 use uuid::Uuid;
 
@@ -10,71 +22,379 @@ use crate::api::sited_io::media::v1::media_service_server::{
     self, MediaServiceServer,
 };
 use crate::api::sited_io::media::v1::{
+    AbortMultipartUploadRequest, AbortMultipartUploadResponse,
     AddMediaToOfferRequest, AddMediaToOfferResponse,
-    CompleteMultipartUploadRequest, CompleteMultipartUploadResponse,
-    CreateMediaRequest, CreateMediaResponse, DeleteMediaRequest,
-    DeleteMediaResponse, DownloadMediaRequest, DownloadMediaResponse,
-    GetMediaRequest, GetMediaResponse, InitiateMultipartUploadRequest,
+    BatchRemoveMediaFromOfferRequest, BatchRemoveMediaFromOfferResponse,
+    BatchRemoveMediaFromOfferResult, CheckMediaOwnershipRequest,
+    CheckMediaOwnershipResponse, CompleteMultipartUploadRequest,
+    CompleteMultipartUploadResponse, CreateMediaRequest, CreateMediaResponse,
+    DeleteMediaRequest, DeleteMediaResponse, DetachMediaFromAllOffersRequest,
+    DetachMediaFromAllOffersResponse, DownloadMediaChunksRequest,
+    DownloadMediaChunksResponse, DownloadMediaRangeRequest,
+    DownloadMediaRangeResponse, DownloadMediaRequest, DownloadMediaResponse,
+    ExportBoothManifestRequest, ExportBoothManifestResponse,
+    FindSimilarMediaRequest, FindSimilarMediaResponse,
+    GetMediaRenditionsRequest, GetMediaRenditionsResponse, GetMediaRequest,
+    GetMediaResponse, GetShopMediaStatsRequest, GetShopMediaStatsResponse,
+    GetUploadStatusRequest, GetUploadStatusResponse, ImportMediaFromUrlRequest,
+    ImportMediaFromUrlResponse, InitiateMultipartUploadRequest,
     InitiateMultipartUploadResponse, ListAccessibleMediaRequest,
     ListAccessibleMediaResponse, ListMediaRequest, ListMediaResponse,
-    MediaResponse, Part, PutMultipartChunkRequest, PutMultipartChunkResponse,
+    MediaFilterField, MediaOwnership, MediaProcessingState, MediaRendition,
+    MediaResponse, MediaUpload, MigrateStorageKeysRequest,
+    MigrateStorageKeysResponse, MoveMediaBetweenOffersRequest,
+    MoveMediaBetweenOffersResponse, OfferMediaItem, OperationProgress, Part,
+    PutMultipartChunkRequest, PutMultipartChunkResponse,
     RemoveMediaFromOfferRequest, RemoveMediaFromOfferResponse,
-    UpdateMediaOfferOrderingRequest, UpdateMediaOfferOrderingResponse,
-    UpdateMediaRequest, UpdateMediaResponse,
+    RenameMediaRequest, RenameMediaResponse, ResolvePermalinkRequest,
+    ResolvePermalinkResponse, SimilarMedia, UpdateMediaOfferOrderingRequest,
+    UpdateMediaOfferOrderingResponse, UpdateMediaRequest, UpdateMediaResponse,
+    UploadState,
 };
-use crate::auth::get_user_id;
-use crate::db::DbError;
-use crate::files::FileService;
-use crate::model::{Media, MediaOffer};
-use crate::{CommerceService, QuotaService};
+use crate::api::sited_io::pagination::v1::PaginationRequest;
+use crate::auth::{get_user_id, verify_admin_user};
+use crate::config::MediaServiceConfig;
+use crate::db::{self, get_client, pool_acquire_timeout, DbError};
+use crate::file_info::FileInfo;
+use crate::files::{
+    FileError, FileService, ObjectKeyStrategy, ObjectTagValues,
+    PresignedUrlExpiry,
+};
+use crate::model::{
+    Media, MediaOffer, MediaProjection, MultipartPart, MultipartUploadSession,
+    ShopBucket, UpdateMediaFields,
+};
+use crate::processing_limit::ProcessingLimiter;
+use crate::progress::ProgressEmitter;
+use crate::remote_fetch::RemoteFetchService;
+use crate::signing::ResponseSigner;
+use crate::upload_policy::{ContentTypeAllowlist, UploadPolicy};
+use crate::virus_scan::{ScanResult, VirusScanner};
+use crate::{BuildError, Commerce, QuotaService, StatusBuilder};
+
+use super::{
+    get_limit_offset_from_pagination, parse_uuid, set_total_elements,
+    DEFAULT_PAGE_SIZE,
+};
+
+/// How `list_media` treats shops that were closed or deleted in commerce
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ClosedShopListing {
+    /// Lists the media regardless of the shop status
+    #[default]
+    Allow,
+    NotFound,
+    FailedPrecondition,
+}
+
+impl FromStr for ClosedShopListing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allow" => Ok(Self::Allow),
+            "not_found" => Ok(Self::NotFound),
+            "failed_precondition" => Ok(Self::FailedPrecondition),
+            _ => Err(format!("unknown closed shop listing '{s}'")),
+        }
+    }
+}
+
+impl From<OperationProgress> for BatchRemoveMediaFromOfferResponse {
+    fn from(progress: OperationProgress) -> Self {
+        Self {
+            progress: Some(progress),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<OperationProgress> for MigrateStorageKeysResponse {
+    fn from(progress: OperationProgress) -> Self {
+        Self {
+            progress: Some(progress),
+            ..Default::default()
+        }
+    }
+}
 
-use super::{get_limit_offset_from_pagination, parse_uuid};
+impl From<OperationProgress> for ExportBoothManifestResponse {
+    fn from(progress: OperationProgress) -> Self {
+        Self {
+            progress: Some(progress),
+            ..Default::default()
+        }
+    }
+}
 
 pub struct MediaService {
     pool: Pool,
+    /// Used for reads that tolerate replication lag, same as `pool` if no
+    /// read replica is configured
+    read_pool: Pool,
     verifier: RemoteJwksVerifier,
     file_service: FileService,
-    commerce_service: CommerceService,
+    key_strategy: ObjectKeyStrategy,
+    /// Keeps prefixing path based keys by the owner as before they were
+    /// grouped by shop
+    legacy_flat_keys: bool,
+    commerce_service: Box<dyn Commerce>,
     quota_service: QuotaService,
+    upload_policy: UploadPolicy,
+    /// Uploads are not scanned if `None`
+    virus_scanner: Option<VirusScanner>,
+    /// Largest file returned inline as `data:` URI, disabled if `None`
+    data_uri_max_bytes: Option<u64>,
+    /// How long subscriptions keep granting access after `payed_until`
+    subscription_grace_period: Duration,
+    /// Recently read media for `get_media`, never holds file contents
+    media_cache: Cache<Uuid, Media>,
+    closed_shop_listing: ClosedShopListing,
+    /// Whether shops are active in commerce, only used if closed shops are
+    /// not listed
+    shop_active_cache: Cache<Uuid, bool>,
+    presigned_url_expiry: PresignedUrlExpiry,
+    /// Permalinks are not returned if `None`
+    permalink_base_url: Option<String>,
+    /// Responses cannot be signed if `None`
+    response_signer: Option<ResponseSigner>,
+    processing_limiter: ProcessingLimiter,
+    similar_media_max_distance: u32,
+    max_decoding_message_size: usize,
+    max_encoding_message_size: usize,
+    remote_fetch: RemoteFetchService,
 }
 
 impl MediaService {
+    const MAX_OWNERSHIP_CHECK_MEDIA_IDS: usize = 100;
+    const MAX_BATCH_REMOVE_MEDIA_IDS: usize = 100;
+    const MAX_LIST_FILTERS: usize = 5;
+    /// Relations beyond these caps are flagged in shop stats as a hint of
+    /// misbehaving integrations, they are not enforced
+    const MAX_OFFERS_PER_MEDIA: u64 = 50;
+    const MAX_MEDIA_PER_OFFER: u64 = 200;
+    const MAX_FLAGGED_RELATIONS: u64 = 100;
+    const MEDIA_CACHE_MAX_CAPACITY: u64 = 10_000;
+    const SHOP_ACTIVE_CACHE_MAX_CAPACITY: u64 = 10_000;
+    const SHOP_ACTIVE_CACHE_TTL: Duration = Duration::from_secs(30);
+    const DEFAULT_MIGRATE_STORAGE_KEYS_LIMIT: u64 = 100;
+    const MAX_MIGRATE_STORAGE_KEYS_LIMIT: u64 = 1000;
+    /// Room left for pagination and other fields of list responses
+    const LIST_RESPONSE_OVERHEAD_BYTES: usize = 128;
+    /// Upper estimate of an encoded `MediaResponse` with the largest custom
+    /// metadata allowed and presigned URLs. Names are not bounded.
+    const MAX_MEDIA_RESPONSE_BYTES: usize = 48 * 1024;
+    const MAX_CUSTOM_METADATA_KEYS: usize = 20;
+    const MAX_CUSTOM_METADATA_CHARS: usize = 256;
+    /// Bits of a perceptual hash
+    const MAX_PERCEPTUAL_HASH_DISTANCE: u32 = 64;
+    /// Least time between progress updates of streaming batch operations
+    const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+    /// Hint for clients polling media that is still being processed
+    const PROCESSING_RETRY_DELAY_MS: u64 = 2_000;
+    const DEFAULT_SIMILAR_MEDIA_LIMIT: u32 = 20;
+    const MAX_SIMILAR_MEDIA_LIMIT: u32 = 100;
+    /// Hashes compared per request, the most recent media of larger shops
+    /// are compared only
+    const MAX_SIMILAR_MEDIA_CANDIDATES: u64 = 10_000;
+    /// Pending media scanned again on start, more wait for the next start
+    const MAX_RESCANNED_MEDIA: u64 = 1000;
+    /// Media left flagged for deletion purged on start, more wait for the
+    /// next start
+    const MAX_PURGED_MEDIA: u64 = 1000;
+
+    #[allow(clippy::too_many_arguments)]
     fn new(
         pool: Pool,
+        read_pool: Pool,
         verifier: RemoteJwksVerifier,
         file_service: FileService,
-        commerce_service: CommerceService,
+        key_strategy: ObjectKeyStrategy,
+        legacy_flat_keys: bool,
+        commerce_service: Box<dyn Commerce>,
         quota_service: QuotaService,
+        upload_policy: UploadPolicy,
+        virus_scanner: Option<VirusScanner>,
+        data_uri_max_bytes: Option<u64>,
+        subscription_grace_period: Duration,
+        media_cache_ttl: Duration,
+        closed_shop_listing: ClosedShopListing,
+        presigned_url_expiry: PresignedUrlExpiry,
+        permalink_base_url: Option<String>,
+        response_signer: Option<ResponseSigner>,
+        processing_limiter: ProcessingLimiter,
+        similar_media_max_distance: u32,
+        max_decoding_message_size: usize,
+        max_encoding_message_size: usize,
+        remote_fetch: RemoteFetchService,
     ) -> Self {
         Self {
             pool,
+            read_pool,
             verifier,
             file_service,
+            key_strategy,
+            legacy_flat_keys,
             commerce_service,
             quota_service,
+            upload_policy,
+            virus_scanner,
+            data_uri_max_bytes,
+            subscription_grace_period,
+            media_cache: Cache::builder()
+                .max_capacity(Self::MEDIA_CACHE_MAX_CAPACITY)
+                .time_to_live(media_cache_ttl)
+                .build(),
+            closed_shop_listing,
+            shop_active_cache: Cache::builder()
+                .max_capacity(Self::SHOP_ACTIVE_CACHE_MAX_CAPACITY)
+                .time_to_live(Self::SHOP_ACTIVE_CACHE_TTL)
+                .build(),
+            presigned_url_expiry,
+            permalink_base_url,
+            response_signer,
+            processing_limiter,
+            similar_media_max_distance,
+            max_decoding_message_size,
+            max_encoding_message_size,
+            remote_fetch,
         }
     }
 
-    pub fn build(
+    /// Fails if the database or storage is unreachable
+    #[allow(clippy::too_many_arguments)]
+    pub async fn build(
         pool: Pool,
+        read_pool: Pool,
         verifier: RemoteJwksVerifier,
         file_service: FileService,
-        commerce_service: CommerceService,
+        commerce_service: impl Commerce + 'static,
         quota_service: QuotaService,
-        max_message_size_bytes: usize,
-    ) -> MediaServiceServer<Self> {
-        MediaServiceServer::new(Self::new(
+        virus_scanner: Option<VirusScanner>,
+        config: MediaServiceConfig,
+    ) -> Result<MediaServiceServer<Self>, BuildError> {
+        Self::check_dependencies(&pool, &file_service, &commerce_service)
+            .await?;
+
+        let MediaServiceConfig {
+            key_strategy,
+            legacy_flat_keys,
+            upload_policy,
+            data_uri_max_bytes,
+            subscription_grace_period,
+            media_cache_ttl,
+            closed_shop_listing,
+            presigned_url_expiry,
+            permalink_base_url,
+            response_signer,
+            processing_limiter,
+            similar_media_max_distance,
+            max_decoding_message_size,
+            max_encoding_message_size,
+            remote_fetch,
+        } = config;
+
+        let media_service = Self::new(
             pool,
+            read_pool,
             verifier,
             file_service,
-            commerce_service,
+            key_strategy,
+            legacy_flat_keys,
+            Box::new(commerce_service),
             quota_service,
-        ))
-        .max_decoding_message_size(max_message_size_bytes)
-        .max_encoding_message_size(max_message_size_bytes)
+            upload_policy,
+            virus_scanner,
+            data_uri_max_bytes,
+            subscription_grace_period,
+            media_cache_ttl,
+            closed_shop_listing,
+            presigned_url_expiry,
+            permalink_base_url,
+            response_signer,
+            processing_limiter,
+            similar_media_max_distance,
+            max_decoding_message_size,
+            max_encoding_message_size,
+            remote_fetch,
+        );
+
+        media_service.purge_delete_pending_media().await;
+        media_service.rescan_pending_media().await;
+
+        Ok(MediaServiceServer::new(media_service)
+            .max_decoding_message_size(max_decoding_message_size)
+            .max_encoding_message_size(max_encoding_message_size))
+    }
+
+    async fn check_dependencies(
+        pool: &Pool,
+        file_service: &FileService,
+        commerce_service: &impl Commerce,
+    ) -> Result<(), BuildError> {
+        let started = Instant::now();
+        db::ping(pool)
+            .await
+            .map_err(|err| BuildError::DbUnreachable(format!("{err:?}")))?;
+        let db_latency = started.elapsed();
+        tracing::log::info!(
+            "[MediaService.build]: database reachable in {} ms",
+            db_latency.as_millis()
+        );
+
+        let started = Instant::now();
+        file_service
+            .head_bucket()
+            .await
+            .map_err(|err| BuildError::StorageUnreachable(err.to_string()))?;
+        let storage_latency = started.elapsed();
+        tracing::log::info!(
+            "[MediaService.build]: storage reachable in {} ms",
+            storage_latency.as_millis()
+        );
+
+        // the connection to commerce is lazy, requests needing it fail until
+        // it is reachable while everything else is served
+        let started = Instant::now();
+        match commerce_service.check_health().await {
+            Ok(()) => tracing::log::info!(
+                "[MediaService.build]: commerce service reachable in {} ms",
+                started.elapsed().as_millis()
+            ),
+            Err(err) => tracing::log::warn!(
+                "[MediaService.build]: commerce service unreachable, starting anyway: {}",
+                err.message()
+            ),
+        }
+
+        tracing::log::info!(
+            "[MediaService.build]: dependency checks passed (database {} ms, storage {} ms)",
+            db_latency.as_millis(),
+            storage_latency.as_millis()
+        );
+
+        Ok(())
     }
 
-    fn to_response(&self, media: Media) -> MediaResponse {
+    /// Every media passed here was already checked to be owned by or
+    /// accessible for `user_id`, so access is implied for authenticated callers.
+    fn to_response(
+        &self,
+        media: Media,
+        user_id: Option<&String>,
+    ) -> MediaResponse {
+        let is_owner = user_id.map(|u| *u == media.user_id);
+        let has_access = user_id.map(|_| true);
+        let permalink = self.permalink(&media);
+        let processing_state = match media.processing_state.as_str() {
+            Media::PROCESSING_STATE_PENDING => MediaProcessingState::Pending,
+            Media::PROCESSING_STATE_FAILED => MediaProcessingState::Failed,
+            Media::PROCESSING_STATE_QUARANTINED => {
+                MediaProcessingState::Quarantined
+            }
+            _ => MediaProcessingState::Ready,
+        };
+
         MediaResponse {
             media_id: media.media_id.to_string(),
             offer_ids: media
@@ -88,536 +408,3285 @@ impl MediaService {
             name: media.name,
             file_name: media.file_name,
             ordering: media.ordering,
+            is_owner,
+            has_access,
+            processing_state: processing_state.into(),
+            duration_seconds: media.duration_seconds.map(|d| d as f32),
+            image_width: media.image_width,
+            image_height: media.image_height,
+            custom_metadata: media.custom_metadata,
+            download_url: None,
+            permalink,
+            signature: None,
+            signed_at: None,
         }
     }
 
-    fn build_file_path(
-        user_id: &String,
-        shop_id: &Uuid,
-        media_id: &Uuid,
-    ) -> String {
-        format!("{user_id}/{shop_id}/{media_id}")
+    /// Fails if responses are requested to be signed without a configured
+    /// signing key
+    fn check_signable(&self, sign_response: bool) -> Result<(), Status> {
+        if sign_response && self.response_signer.is_none() {
+            return Err(StatusBuilder::failed_precondition(
+                "response signing is not configured",
+            )
+            .with_precondition_violation(
+                "NOT_CONFIGURED",
+                "sign_response",
+                "no signing key is configured",
+            )
+            .build());
+        }
+        Ok(())
     }
-}
 
-#[async_trait]
-impl media_service_server::MediaService for MediaService {
-    async fn create_media(
+    /// Fails if replacing the file of `media` would change its content type
+    /// more than the upload policy allows. Media stored without a content
+    /// type accept any replacement.
+    fn check_content_type_replacement(
         &self,
-        request: Request<CreateMediaRequest>,
-    ) -> Result<Response<CreateMediaResponse>, Status> {
-        let metadata = request.metadata().clone();
-
-        let user_id = get_user_id(&metadata, &self.verifier).await?;
-
-        let CreateMediaRequest {
-            shop_id,
-            name,
-            file,
-            file_name,
-        } = request.into_inner();
+        media: &Media,
+        content_type: &str,
+    ) -> Result<(), Status> {
+        let replacement = self.upload_policy.content_type_replacement;
+
+        match media.content_type.as_ref() {
+            Some(original)
+                if !replacement.is_allowed(original, content_type) =>
+            {
+                Err(StatusBuilder::failed_precondition(format!(
+                    "file: content type '{content_type}' cannot replace '{original}' of media {} ({replacement:?})",
+                    media.media_id
+                ))
+                .with_precondition_violation(
+                    "CONTENT_TYPE",
+                    "file",
+                    format!("'{content_type}' cannot replace '{original}'"),
+                )
+                .build())
+            }
+            _ => Ok(()),
+        }
+    }
 
-        let shop_uuid = parse_uuid(&shop_id, "shop_id")?;
+    /// Sets `signature` and `signed_at` of `response` if signing was
+    /// requested
+    fn sign_response(
+        &self,
+        mut response: MediaResponse,
+        sign_response: bool,
+    ) -> MediaResponse {
+        if let (true, Some(signer)) = (sign_response, &self.response_signer) {
+            let signed_at = Utc::now().timestamp();
+            response.signature = Some(signer.sign(&response, signed_at));
+            response.signed_at = Some(signed_at);
+        }
+        response
+    }
 
-        self.quota_service.check_quota(&user_id).await?;
+    /// Columns `Media::list` needs to populate the requested `fields`
+    fn projection_for_fields(fields: &[String]) -> MediaProjection {
+        if fields.is_empty() {
+            return MediaProjection::default();
+        }
+        let has = |name: &str| fields.iter().any(|f| f == name);
 
-        self.commerce_service
-            .check_shop_and_owner(&shop_id, &user_id, &metadata)
-            .await?;
+        MediaProjection {
+            offer_ids: has("offer_ids") || has("ordering"),
+            custom_metadata: has("custom_metadata"),
+        }
+    }
 
-        let media_id = Uuid::new_v4();
+    /// Keeps only the requested `fields` of `response`, besides `media_id`.
+    /// Signing happens afterwards, so the signature covers what is returned.
+    fn project_response(
+        response: MediaResponse,
+        fields: &[String],
+    ) -> MediaResponse {
+        if fields.is_empty() {
+            return response;
+        }
+        let has = |name: &str| fields.iter().any(|f| f == name);
+        let mut projected = MediaResponse {
+            media_id: response.media_id,
+            ..Default::default()
+        };
 
-        let file_path = Self::build_file_path(&user_id, &shop_uuid, &media_id);
+        if has("offer_ids") {
+            projected.offer_ids = response.offer_ids;
+        }
+        if has("shop_id") {
+            projected.shop_id = response.shop_id;
+        }
+        if has("user_id") {
+            projected.user_id = response.user_id;
+        }
+        if has("created_at") {
+            projected.created_at = response.created_at;
+        }
+        if has("updated_at") {
+            projected.updated_at = response.updated_at;
+        }
+        if has("name") {
+            projected.name = response.name;
+        }
+        if has("file_name") {
+            projected.file_name = response.file_name;
+        }
+        if has("ordering") {
+            projected.ordering = response.ordering;
+        }
+        if has("is_owner") {
+            projected.is_owner = response.is_owner;
+        }
+        if has("has_access") {
+            projected.has_access = response.has_access;
+        }
+        if has("processing_state") {
+            projected.processing_state = response.processing_state;
+        }
+        if has("duration_seconds") {
+            projected.duration_seconds = response.duration_seconds;
+        }
+        if has("image_width") {
+            projected.image_width = response.image_width;
+        }
+        if has("image_height") {
+            projected.image_height = response.image_height;
+        }
+        if has("custom_metadata") {
+            projected.custom_metadata = response.custom_metadata;
+        }
+        if has("download_url") {
+            projected.download_url = response.download_url;
+        }
+        if has("permalink") {
+            projected.permalink = response.permalink;
+        }
 
-        let mut conn = self.pool.get().await.map_err(DbError::from)?;
-        let transaction = conn.transaction().await.map_err(DbError::from)?;
+        projected
+    }
 
-        let size = file
-            .as_ref()
-            .map(|f| f.data.len())
-            .unwrap_or(0)
-            .try_into()
-            .map_err(|_| Status::internal(""))?;
+    /// Link of `media` built from ids that never change, unlike its object
+    /// key
+    fn permalink(&self, media: &Media) -> Option<String> {
+        self.permalink_base_url.as_ref().map(|base_url| {
+            format!(
+                "{}/media/{}/{}",
+                base_url.trim_end_matches('/'),
+                media.shop_id,
+                media.media_id
+            )
+        })
+    }
 
-        let created_media = Media::create(
-            &transaction,
-            &media_id,
-            &shop_uuid,
-            &user_id,
-            &name,
-            &file_path,
-            size,
-            &file_name,
-        )
-        .await?;
+    /// Returns the shop and media id of a permalink like
+    /// `https://example.com/media/{shop_id}/{media_id}`
+    fn parse_permalink(permalink: &str) -> Result<(Uuid, Uuid), Status> {
+        let invalid = || {
+            StatusBuilder::invalid_argument("permalink: invalid format")
+                .with_bad_request_field("permalink", "invalid format")
+                .build()
+        };
 
-        if let Some(file) = file {
-            self.file_service
-                .put_file(&file_path, &file.data, &file.content_type)
-                .await?;
+        let mut segments = permalink.trim_end_matches('/').rsplit('/');
+        let media_id = segments.next().ok_or_else(invalid)?;
+        let shop_id = segments.next().ok_or_else(invalid)?;
+        if segments.next() != Some("media") {
+            return Err(invalid());
         }
 
-        transaction.commit().await.map_err(DbError::from)?;
-
-        Ok(Response::new(CreateMediaResponse {
-            media: Some(self.to_response(created_media)),
-        }))
+        Ok((
+            parse_uuid(shop_id, "permalink")?,
+            parse_uuid(media_id, "permalink")?,
+        ))
     }
 
-    async fn get_media(
+    /// Presigned download url of `media`, `None` if it cannot be served
+    async fn get_download_url(
         &self,
-        request: Request<GetMediaRequest>,
-    ) -> Result<Response<GetMediaResponse>, Status> {
-        let user_id = get_user_id(request.metadata(), &self.verifier).await?;
-
-        let GetMediaRequest { media_id } = request.into_inner();
-        let media_uuid = parse_uuid(&media_id, "media_id")?;
+        media: &Media,
+    ) -> Result<Option<String>, Status> {
+        if Self::check_servable(media).is_err() {
+            return Ok(None);
+        }
 
-        let found_media =
-            Media::get_for_owner(&self.pool, &media_uuid, &user_id)
-                .await?
-                .ok_or(Status::not_found(&media_id))?;
+        let url = self
+            .get_file_service_for_media(media)
+            .await?
+            .get_presigned_url(
+                &media.object_key,
+                &media.file_name,
+                self.presigned_url_expiry.owner_download,
+            )
+            .await?;
 
-        Ok(Response::new(GetMediaResponse {
-            media: Some(self.to_response(found_media)),
-        }))
+        Ok(Some(url))
     }
 
-    async fn download_media(
-        &self,
-        request: Request<DownloadMediaRequest>,
-    ) -> Result<Response<DownloadMediaResponse>, Status> {
-        let user_id = get_user_id(request.metadata(), &self.verifier).await?;
+    /// Largest page of ListMedia certain to fit `max_encoding_message_size`
+    fn max_list_page_size(max_encoding_message_size: usize) -> u32 {
+        let page_size = max_encoding_message_size
+            .saturating_sub(Self::LIST_RESPONSE_OVERHEAD_BYTES)
+            / Self::MAX_MEDIA_RESPONSE_BYTES;
 
-        let DownloadMediaRequest { media_id } = request.into_inner();
-        let media_uuid = parse_uuid(&media_id, "media_id")?;
+        u32::try_from(page_size).unwrap_or(u32::MAX).max(1)
+    }
 
-        let found_media =
-            Media::get_accessible(&self.pool, &media_uuid, &user_id)
-                .await?
-                .ok_or(Status::not_found(&media_id))?;
+    /// Keeps as many `medias` as fit into `max_encoding_message_size` and returns
+    /// whether any had to be dropped.
+    fn fit_to_message_size(
+        &self,
+        mut medias: Vec<MediaResponse>,
+    ) -> (Vec<MediaResponse>, bool) {
+        let mut size = Self::LIST_RESPONSE_OVERHEAD_BYTES;
+
+        let fitting = medias
+            .iter()
+            .take_while(|m| {
+                let len = m.encoded_len();
+                size +=
+                    1 + prost::encoding::encoded_len_varint(len as u64) + len;
+                size <= self.max_encoding_message_size
+            })
+            .count();
 
-        let file_path = Self::build_file_path(
-            &found_media.user_id,
-            &found_media.shop_id,
-            &found_media.media_id,
-        );
+        let truncated = fitting < medias.len();
+        medias.truncate(fitting);
 
-        let download_url = self
-            .file_service
-            .get_presigned_url(&file_path, &found_media.file_name)
-            .await?;
+        (medias, truncated)
+    }
 
-        Ok(Response::new(DownloadMediaResponse { download_url }))
+    fn check_content_type(
+        allowlist: Option<&ContentTypeAllowlist>,
+        content_type: &str,
+        upload_path: &str,
+    ) -> Result<(), Status> {
+        match allowlist {
+            Some(allowlist) if !allowlist.is_allowed(content_type) => {
+                Err(StatusBuilder::invalid_argument(format!(
+                    "content type {content_type} is not allowed for {upload_path} upload"
+                ))
+                .with_bad_request_field(
+                    "content_type",
+                    format!("not allowed for {upload_path} upload"),
+                )
+                .build())
+            }
+            _ => Ok(()),
+        }
     }
 
-    async fn list_media(
+    /// Records a failed storage request of a multipart upload. The upload is
+    /// not aborted, the client decides whether to retry or abort.
+    async fn record_multipart_error(
         &self,
-        request: Request<ListMediaRequest>,
-    ) -> Result<Response<ListMediaResponse>, Status> {
-        let user_id = get_user_id(request.metadata(), &self.verifier).await?;
-
-        let ListMediaRequest {
-            shop_id,
-            pagination,
-            order_by,
-            filter,
-        } = request.into_inner();
+        media: &Media,
+        upload_id: &String,
+        err: &FileError,
+    ) {
+        if let Err(db_err) = MultipartUploadSession::record_error(
+            &self.pool,
+            upload_id,
+            &media.media_id,
+            &media.user_id,
+            &err.to_string(),
+        )
+        .await
+        {
+            tracing::log::error!(
+                "[MediaService.record_multipart_error]: {upload_id}: {db_err:?}"
+            );
+        }
+    }
 
-        let shop_id = parse_uuid(&shop_id, "shop_id")?;
+    /// Fails for shops that are not active anymore, unless listing media of
+    /// closed shops is allowed
+    async fn check_shop_listable(&self, shop_id: &Uuid) -> Result<(), Status> {
+        if self.closed_shop_listing == ClosedShopListing::Allow {
+            return Ok(());
+        }
 
-        let (limit, offset, mut pagination) =
-            get_limit_offset_from_pagination(pagination)?;
+        let is_active = match self.shop_active_cache.get(shop_id).await {
+            Some(is_active) => is_active,
+            None => {
+                let is_active = self
+                    .commerce_service
+                    .is_shop_active(&shop_id.to_string())
+                    .await?;
+                self.shop_active_cache.insert(*shop_id, is_active).await;
+                is_active
+            }
+        };
 
-        let filter = filter.map(|f| (f.field(), f.query));
+        if is_active {
+            return Ok(());
+        }
 
-        let order_by = order_by.map(|o| (o.field(), o.direction()));
+        match self.closed_shop_listing {
+            ClosedShopListing::Allow => Ok(()),
+            ClosedShopListing::NotFound => {
+                Err(StatusBuilder::not_found(shop_id.to_string())
+                    .with_resource_info("shop", shop_id.to_string())
+                    .build())
+            }
+            ClosedShopListing::FailedPrecondition => {
+                Err(StatusBuilder::failed_precondition("shop is not active")
+                    .with_resource_info("shop", shop_id.to_string())
+                    .build())
+            }
+        }
+    }
 
-        let (found_medias, count) = Media::list(
-            &self.pool,
-            &shop_id,
-            &user_id,
-            limit.into(),
-            offset.into(),
-            filter,
-            order_by,
-        )
-        .await?;
+    /// Rejects further chunks once an upload reached the configured number
+    /// of parts or bytes
+    async fn check_multipart_limits(
+        &self,
+        uploaded_bytes: u64,
+        upload_id: &String,
+        chunk_len: usize,
+    ) -> Result<(), Status> {
+        let max_parts = self.upload_policy.max_multipart_parts();
+        let part_count = MultipartPart::count(&self.pool, upload_id).await?;
+        if part_count >= i64::from(max_parts) {
+            return Err(StatusBuilder::resource_exhausted(format!(
+                "upload reached the maximum of {max_parts} parts"
+            ))
+            .with_resource_info("multipart_upload", upload_id)
+            .build());
+        }
 
-        pagination.total_elements = count.try_into().map_err(|_| {
-            Status::internal("Could not convert 'count' from i64 to u32")
-        })?;
+        if let Some(max_bytes) = self.upload_policy.max_multipart_bytes {
+            if uploaded_bytes.saturating_add(chunk_len as u64) > max_bytes {
+                return Err(StatusBuilder::resource_exhausted(format!(
+                    "upload exceeds the maximum of {max_bytes} bytes"
+                ))
+                .with_resource_info("multipart_upload", upload_id)
+                .build());
+            }
+        }
 
-        Ok(Response::new(ListMediaResponse {
-            medias: found_medias
-                .into_iter()
-                .map(|m| self.to_response(m))
-                .collect(),
-            pagination: Some(pagination),
-        }))
+        Ok(())
     }
 
-    async fn list_accessible_media(
+    /// Rejects images smaller than the configured minimum. Only single
+    /// uploads are checked, the dimensions of multipart uploads are not known
+    /// before they complete.
+    fn check_image_dimensions(
         &self,
-        request: Request<ListAccessibleMediaRequest>,
-    ) -> Result<Response<ListAccessibleMediaResponse>, Status> {
-        let user_id = get_user_id(request.metadata(), &self.verifier).await;
+        file_info: &FileInfo,
+    ) -> Result<(), Status> {
+        let (Some(width), Some(height)) =
+            (file_info.image_width, file_info.image_height)
+        else {
+            return Ok(());
+        };
 
-        let ListAccessibleMediaRequest {
-            pagination,
-            order_by,
-            filter,
-        } = request.into_inner();
+        let min_width = self.upload_policy.min_image_width.unwrap_or(0);
+        let min_height = self.upload_policy.min_image_height.unwrap_or(0);
 
-        let (limit, offset, mut pagination) =
-            get_limit_offset_from_pagination(pagination)?;
+        if width < min_width || height < min_height {
+            return Err(StatusBuilder::invalid_argument(format!(
+                "image is {width}x{height} pixels, minimum is {min_width}x{min_height}"
+            ))
+            .with_bad_request_field(
+                "file",
+                format!("must be at least {min_width}x{min_height} pixels"),
+            )
+            .build());
+        }
 
-        let filter = filter.map(|f| (f.field(), f.query));
+        Ok(())
+    }
 
-        let order_by = order_by.map(|o| (o.field(), o.direction()));
+    /// Returns the custom metadata to store, `None` if left unchanged. An
+    /// empty map is only stored if clearing is requested explicitly, as
+    /// unset map fields cannot be told apart from empty ones.
+    fn custom_metadata_update(
+        custom_metadata: HashMap<String, String>,
+        clear_custom_metadata: bool,
+    ) -> Result<Option<HashMap<String, String>>, Status> {
+        match (clear_custom_metadata, custom_metadata.is_empty()) {
+            (true, false) => Err(StatusBuilder::invalid_argument(
+                "custom_metadata must be empty if clear_custom_metadata is set",
+            )
+            .with_bad_request_field(
+                "custom_metadata",
+                "must be empty if clear_custom_metadata is set",
+            )
+            .build()),
+            (true, true) => Ok(Some(HashMap::new())),
+            (false, true) => Ok(None),
+            (false, false) => Ok(Some(custom_metadata)),
+        }
+    }
 
-        let (found_medias, count) = match user_id {
-            Ok(user_id) => {
-                Media::list_accessible(
-                    &self.pool,
-                    &user_id,
-                    limit.into(),
-                    offset.into(),
-                    filter,
-                    order_by,
+    fn validate_custom_metadata(
+        custom_metadata: &HashMap<String, String>,
+    ) -> Result<(), Status> {
+        if custom_metadata.len() > Self::MAX_CUSTOM_METADATA_KEYS {
+            return Err(StatusBuilder::invalid_argument(format!(
+                "custom_metadata: more than {} keys",
+                Self::MAX_CUSTOM_METADATA_KEYS
+            ))
+            .with_bad_request_field(
+                "custom_metadata",
+                format!("more than {} keys", Self::MAX_CUSTOM_METADATA_KEYS),
+            )
+            .build());
+        }
+
+        for (key, value) in custom_metadata.iter() {
+            if key.is_empty()
+                || key.chars().count() > Self::MAX_CUSTOM_METADATA_CHARS
+                || value.chars().count() > Self::MAX_CUSTOM_METADATA_CHARS
+            {
+                return Err(StatusBuilder::invalid_argument(format!(
+                    "custom_metadata: keys must not be empty, keys and values must not exceed {} characters",
+                    Self::MAX_CUSTOM_METADATA_CHARS
+                ))
+                .with_bad_request_field(
+                    format!("custom_metadata.{key}"),
+                    format!(
+                        "key must not be empty, key and value must not exceed {} characters",
+                        Self::MAX_CUSTOM_METADATA_CHARS
+                    ),
                 )
-                .await?
+                .build());
             }
-            Err(_) => (vec![], 0),
-        };
+        }
 
-        pagination.total_elements = count.try_into().map_err(|_| {
-            Status::internal("Could not convert 'count' from i64 to u32")
-        })?;
+        Ok(())
+    }
 
-        Ok(Response::new(ListAccessibleMediaResponse {
-            medias: found_medias
-                .into_iter()
-                .map(|m| self.to_response(m))
+    fn fits_data_uri(&self, size_bytes: u64) -> bool {
+        self.data_uri_max_bytes
+            .is_some_and(|max| size_bytes > 0 && size_bytes <= max)
+    }
+
+    /// Embeds the whole file as base64 `data:` URI
+    async fn get_data_uri(
+        file_service: &FileService,
+        media: &Media,
+    ) -> Result<String, Status> {
+        let data = file_service
+            .get_object_range(&media.object_key, 0, media.size_bytes - 1)
+            .await?;
+        let content_type = media
+            .content_type
+            .as_deref()
+            .unwrap_or("application/octet-stream");
+
+        Ok(format!(
+            "data:{content_type};base64,{}",
+            general_purpose::STANDARD.encode(data)
+        ))
+    }
+
+    /// Converts the `offer_id` shortcut of list requests into a filter
+    fn offer_id_filter(
+        offer_id: Option<String>,
+    ) -> Result<Option<(MediaFilterField, String)>, Status> {
+        offer_id
+            .map(|offer_id| {
+                let offer_uuid = parse_uuid(&offer_id, "offer_id")?;
+                Ok((MediaFilterField::OfferId, offer_uuid.to_string()))
+            })
+            .transpose()
+    }
+
+    /// State of the upload of `media` if no multipart upload is in
+    /// progress, with the reason if it failed
+    fn upload_state(media: &Media) -> (UploadState, Option<String>) {
+        match media.processing_state.as_str() {
+            Media::PROCESSING_STATE_PENDING => (UploadState::Processing, None),
+            Media::PROCESSING_STATE_FAILED => (
+                UploadState::Failed,
+                Some(String::from("processing the file failed")),
+            ),
+            Media::PROCESSING_STATE_QUARANTINED => (
+                UploadState::Failed,
+                Some(String::from("the file was flagged by the virus scan")),
+            ),
+            // media created without file wait for a multipart upload
+            _ if media.size_bytes == 0 => (UploadState::PendingUpload, None),
+            _ => (UploadState::Ready, None),
+        }
+    }
+
+    /// State of a multipart upload that is not completed yet. Received parts
+    /// only tell that uploading started, the file is not stored before the
+    /// upload is completed.
+    fn multipart_upload_state(part_count: i64) -> UploadState {
+        if part_count > 0 {
+            UploadState::Uploading
+        } else {
+            UploadState::PendingUpload
+        }
+    }
+
+    /// Media is only served once its file is known to be safe
+    fn check_servable(media: &Media) -> Result<(), Status> {
+        match media.processing_state.as_str() {
+            Media::PROCESSING_STATE_READY => Ok(()),
+            Media::PROCESSING_STATE_PENDING => Err(
+                StatusBuilder::failed_precondition("media is still processing")
+                    .with_resource_info("media", media.media_id.to_string())
+                    .with_retry_info(Self::PROCESSING_RETRY_DELAY_MS)
+                    .build(),
+            ),
+            _ => Err(StatusBuilder::failed_precondition(
+                "media is not available",
+            )
+            .with_resource_info("media", media.media_id.to_string())
+            .build()),
+        }
+    }
+
+    fn processing_state_after_scan(
+        media_id: &Uuid,
+        result: ScanResult,
+    ) -> &'static str {
+        match result {
+            ScanResult::Clean => Media::PROCESSING_STATE_READY,
+            ScanResult::Infected(signature) => {
+                tracing::log::warn!(
+                    "[MediaService]: quarantined media {media_id}: {signature}"
+                );
+                Media::PROCESSING_STATE_QUARANTINED
+            }
+        }
+    }
+
+    /// Scans small uploads right away. Larger ones stay pending until
+    /// `spawn_virus_scan` finished. Returns `None` if uploads are not scanned.
+    async fn scan_upload(
+        &self,
+        media_id: &Uuid,
+        data: &[u8],
+    ) -> Result<Option<&'static str>, Status> {
+        let Some(virus_scanner) = self.virus_scanner.as_ref() else {
+            return Ok(None);
+        };
+
+        if !virus_scanner.scans_synchronously(data.len()) {
+            return Ok(Some(Media::PROCESSING_STATE_PENDING));
+        }
+
+        let result = virus_scanner.scan(data).await?;
+
+        Ok(Some(Self::processing_state_after_scan(media_id, result)))
+    }
+
+    /// Scans the stored file of `media` in the background and updates its
+    /// processing state with the result. Scans failing to read the file or
+    /// to reach the scanner are retried, if all attempts fail the media stays
+    /// pending and is scanned again on the next start.
+    async fn spawn_virus_scan(&self, media: &Media) -> Result<(), Status> {
+        let Some(virus_scanner) = self.virus_scanner.clone() else {
+            return Ok(());
+        };
+        let file_service = self.get_file_service_for_media(media).await?;
+        let pool = self.pool.clone();
+        let media_cache = self.media_cache.clone();
+        let media_id = media.media_id;
+        let file_path = media.object_key.clone();
+
+        // started before the file is read, so only the result of a scan that
+        // read the latest file is applied
+        let scan_id = Media::start_scan(&pool, &media_id).await?;
+
+        tokio::spawn(async move {
+            let mut attempt = 0;
+            let result = loop {
+                let result =
+                    match file_service.get_object_from(&file_path, 0).await {
+                        Ok(body) => virus_scanner.scan_stream(body).await,
+                        Err(err) => Err(err.into()),
+                    };
+
+                match result {
+                    Ok(result) => break result,
+                    Err(err) if attempt + 1 < VirusScanner::MAX_ATTEMPTS => {
+                        let delay = VirusScanner::retry_delay(attempt);
+                        tracing::log::warn!(
+                            "[MediaService.spawn_virus_scan]: {media_id}: retrying in {delay:?}: {err}"
+                        );
+                        tokio::time::sleep(delay).await;
+                        attempt += 1;
+                    }
+                    Err(err) => {
+                        tracing::log::error!(
+                            "[MediaService.spawn_virus_scan]: {media_id}: stays pending after {} attempts: {err}",
+                            VirusScanner::MAX_ATTEMPTS
+                        );
+                        return;
+                    }
+                }
+            };
+
+            let processing_state =
+                Self::processing_state_after_scan(&media_id, result);
+
+            match Media::set_scan_result(
+                &pool,
+                &media_id,
+                &scan_id,
+                processing_state,
+            )
+            .await
+            {
+                Ok(true) => media_cache.invalidate(&media_id).await,
+                Ok(false) => tracing::log::debug!(
+                    "[MediaService.spawn_virus_scan]: {media_id}: discarded result of outdated scan"
+                ),
+                Err(err) => tracing::log::error!(
+                    "[MediaService.spawn_virus_scan]: {media_id}: {err:?}"
+                ),
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Scans media left pending, e.g. because the service stopped while
+    /// scanning them
+    async fn rescan_pending_media(&self) {
+        if self.virus_scanner.is_none() {
+            return;
+        }
+
+        let pending_media = match Media::list_pending_scan(
+            &self.pool,
+            Self::MAX_RESCANNED_MEDIA,
+        )
+        .await
+        {
+            Ok(pending_media) => pending_media,
+            Err(err) => {
+                tracing::log::error!(
+                    "[MediaService.rescan_pending_media]: {err:?}"
+                );
+                return;
+            }
+        };
+
+        if !pending_media.is_empty() {
+            tracing::log::info!(
+                "[MediaService.rescan_pending_media]: scanning {} pending media",
+                pending_media.len()
+            );
+        }
+
+        for media in pending_media {
+            if let Err(err) = self.spawn_virus_scan(&media).await {
+                tracing::log::error!(
+                    "[MediaService.rescan_pending_media]: {}: {err}",
+                    media.media_id
+                );
+            }
+        }
+    }
+
+    /// Deletes media flagged by `mark_delete_pending`. The file is removed
+    /// before the deletion is committed, so a failure in between leaves the
+    /// row flagged instead of an orphaned file.
+    async fn finish_delete(
+        &self,
+        media_id: &Uuid,
+        user_id: &String,
+        shop_id: Option<&Uuid>,
+    ) -> Result<(), Status> {
+        let mut conn = get_client(&self.pool, pool_acquire_timeout()).await?;
+        let transaction = conn.transaction().await.map_err(DbError::from)?;
+        let deleted_media =
+            Media::delete_returning(&transaction, media_id, user_id, shop_id)
+                .await?;
+        self.remove_file_if_unreferenced(&transaction, &deleted_media)
+            .await?;
+        transaction.commit().await.map_err(DbError::from)?;
+
+        Ok(())
+    }
+
+    /// Finishes deletions interrupted after the media was flagged, e.g.
+    /// because removing the file failed or the service stopped
+    async fn purge_delete_pending_media(&self) {
+        let pending_media = match Media::list_delete_pending(
+            &self.pool,
+            Self::MAX_PURGED_MEDIA,
+        )
+        .await
+        {
+            Ok(pending_media) => pending_media,
+            Err(err) => {
+                tracing::log::error!(
+                    "[MediaService.purge_delete_pending_media]: {err:?}"
+                );
+                return;
+            }
+        };
+
+        if !pending_media.is_empty() {
+            tracing::log::info!(
+                "[MediaService.purge_delete_pending_media]: deleting {} media",
+                pending_media.len()
+            );
+        }
+
+        for media in pending_media {
+            if let Err(err) = self
+                .finish_delete(&media.media_id, &media.user_id, None)
+                .await
+            {
+                tracing::log::error!(
+                    "[MediaService.purge_delete_pending_media]: {}: {err}",
+                    media.media_id
+                );
+            }
+        }
+    }
+
+    /// Returns `FileService` for a bucket configured for a shop, its secret
+    /// access key is resolved from the secret store
+    async fn get_file_service_for_shop_bucket(
+        &self,
+        shop_bucket: ShopBucket,
+    ) -> Result<FileService, Status> {
+        let secret_access_key = shop_bucket
+            .secret_access_key_ref
+            .as_deref()
+            .map(|reference| self.file_service.shop_bucket_secret(reference))
+            .transpose()?;
+        let encryption = shop_bucket.encryption()?;
+
+        Ok(self
+            .file_service
+            .with_bucket(
+                shop_bucket.bucket_name,
+                shop_bucket.bucket_endpoint,
+                shop_bucket.access_key_id,
+                secret_access_key,
+                encryption,
+            )
+            .await)
+    }
+
+    /// Returns `FileService` and key for a new upload. Shop buckets take
+    /// precedence over storage routes by content type. The chosen bucket is
+    /// recorded with the media, so later reads and deletes follow it.
+    async fn get_file_service_for_upload(
+        &self,
+        shop_id: &Uuid,
+        content_type: Option<&str>,
+        file_path: String,
+    ) -> Result<(FileService, String), Status> {
+        if let Some(shop_bucket) = ShopBucket::get(&self.pool, shop_id).await? {
+            return Ok((
+                self.get_file_service_for_shop_bucket(shop_bucket).await?,
+                file_path,
+            ));
+        }
+
+        match content_type.and_then(|c| self.file_service.route(c)) {
+            Some(route) => Ok((
+                self.file_service
+                    .with_bucket(
+                        route.bucket_name.clone(),
+                        None,
+                        None,
+                        None,
+                        self.file_service.encryption().cloned(),
+                    )
+                    .await,
+                route.key(file_path),
+            )),
+            None => Ok((self.file_service.clone(), file_path)),
+        }
+    }
+
+    /// Returns `FileService` for the bucket the file of `media` is stored in.
+    /// Files replaced by updates count as freshly written as well.
+    async fn get_file_service_for_media(
+        &self,
+        media: &Media,
+    ) -> Result<FileService, Status> {
+        let bucket_name = match &media.bucket_name {
+            Some(b) if b != self.file_service.bucket_name() => b,
+            _ => {
+                return Ok(self
+                    .file_service
+                    .in_region(media.storage_region.as_ref())
+                    .for_object_written_at(media.updated_at))
+            }
+        };
+
+        match ShopBucket::get(&self.pool, &media.shop_id).await? {
+            Some(shop_bucket) if shop_bucket.bucket_name == *bucket_name => {
+                self.get_file_service_for_shop_bucket(shop_bucket).await
+            }
+            _ => Ok(self
+                .file_service
+                .with_bucket(
+                    bucket_name.to_owned(),
+                    None,
+                    None,
+                    None,
+                    self.file_service.encryption().cloned(),
+                )
+                .await),
+        }
+    }
+
+    fn build_file_path(
+        &self,
+        user_id: &String,
+        shop_id: &Uuid,
+        media_id: &Uuid,
+    ) -> String {
+        ObjectKeyStrategy::path_key(
+            user_id,
+            shop_id,
+            media_id,
+            self.legacy_flat_keys,
+        )
+    }
+
+    /// Key of the object storing `data` for the media according to the
+    /// configured key strategy
+    fn build_file_key(
+        &self,
+        user_id: &String,
+        shop_id: &Uuid,
+        media_id: &Uuid,
+        data: &[u8],
+    ) -> String {
+        self.key_strategy
+            .content_key(data)
+            .unwrap_or_else(|| self.build_file_path(user_id, shop_id, media_id))
+    }
+
+    /// Moves the object of `media` from its legacy key to the key grouped by
+    /// shop, keeping any storage route prefix. Runs in the task streaming
+    /// the progress of MigrateStorageKeys, so it gets what it needs passed.
+    async fn migrate_storage_key(
+        pool: &Pool,
+        media_cache: &Cache<Uuid, Media>,
+        file_service: &FileService,
+        media: &Media,
+    ) -> Result<(), Status> {
+        let legacy_path = ObjectKeyStrategy::path_key(
+            &media.user_id,
+            &media.shop_id,
+            &media.media_id,
+            true,
+        );
+        let route_prefix = media
+            .object_key
+            .strip_suffix(&legacy_path)
+            .unwrap_or_default();
+        let file_path = format!(
+            "{route_prefix}{}",
+            ObjectKeyStrategy::path_key(
+                &media.user_id,
+                &media.shop_id,
+                &media.media_id,
+                false,
+            )
+        );
+
+        file_service
+            .copy_object(&media.object_key, &file_path)
+            .await?;
+
+        let mut conn = get_client(pool, pool_acquire_timeout()).await?;
+        let transaction = conn.transaction().await.map_err(DbError::from)?;
+        Media::set_object_key(&transaction, &media.media_id, &file_path)
+            .await?;
+        transaction.commit().await.map_err(DbError::from)?;
+        media_cache.invalidate(&media.media_id).await;
+
+        // the media already points to the copy, a left over object only
+        // wastes storage
+        if let Err(err) = file_service.remove_file(&media.object_key).await {
+            tracing::log::warn!(
+                "[MediaService.migrate_storage_key]: could not remove {}: {err}",
+                media.object_key
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Removes the file of `media` unless another media shares the object.
+    /// The remaining references are counted and locked in `transaction`,
+    /// which must also hold the change that released the file.
+    async fn remove_file_if_unreferenced(
+        &self,
+        transaction: &Transaction<'_>,
+        media: &Media,
+    ) -> Result<(), Status> {
+        let references = Media::count_file_references(
+            transaction,
+            &media.media_id,
+            media.bucket_name.as_ref(),
+            &media.object_key,
+        )
+        .await?;
+
+        if references > 0 {
+            return Ok(());
+        }
+
+        self.get_file_service_for_media(media)
+            .await?
+            .remove_file(&media.object_key)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Called in the transaction that points `media` to another object. A
+    /// file that can not be removed is left behind rather than failing the
+    /// change.
+    async fn release_previous_file(
+        &self,
+        transaction: &Transaction<'_>,
+        media: &Media,
+    ) {
+        if let Err(err) =
+            self.remove_file_if_unreferenced(transaction, media).await
+        {
+            tracing::log::error!(
+                "[MediaService.release_previous_file]: {}: {err}",
+                media.object_key
+            );
+        }
+    }
+
+    /// Returns the session of `upload_id` if it belongs to `media`
+    async fn get_upload_session(
+        &self,
+        media: &Media,
+        upload_id: &String,
+    ) -> Result<Option<MultipartUploadSession>, Status> {
+        Ok(MultipartUploadSession::get(&self.pool, upload_id)
+            .await?
+            .filter(|session| session.media_id == media.media_id))
+    }
+
+    /// Key the parts of an upload are written to
+    fn upload_object_key<'a>(
+        media: &'a Media,
+        session: Option<&'a MultipartUploadSession>,
+    ) -> &'a String {
+        session
+            .and_then(|session| session.object_key.as_ref())
+            .unwrap_or(&media.object_key)
+    }
+
+    /// Aborts an upload that will not complete and removes its parts and
+    /// session. Media created for the upload are deleted with `delete_media`,
+    /// media that had a file before keep it with its size. Returns whether
+    /// the media was deleted.
+    async fn discard_multipart_upload(
+        &self,
+        file_service: &FileService,
+        media: &Media,
+        upload_id: &String,
+        delete_media: bool,
+    ) -> Result<bool, Status> {
+        let session = self.get_upload_session(media, upload_id).await?;
+        let session = session.as_ref();
+
+        file_service
+            .abort_multipart_upload(
+                Self::upload_object_key(media, session),
+                upload_id,
+            )
+            .await?;
+
+        let (had_file, kept_size_bytes) =
+            match session.filter(|session| session.object_key.is_some()) {
+                Some(session) => (
+                    session.replaced_size_bytes.is_some(),
+                    Some(session.replaced_size_bytes.unwrap_or(0)),
+                ),
+                // older uploads wrote to the key of the media, so only a
+                // missing object tells that it never had a file
+                None => {
+                    (file_service.object_exists(&media.object_key).await?, None)
+                }
+            };
+        let media_deleted = delete_media && !had_file;
+
+        let mut conn = get_client(&self.pool, pool_acquire_timeout()).await?;
+        let transaction = conn.transaction().await.map_err(DbError::from)?;
+        MultipartPart::delete_all_for_upload(&transaction, upload_id).await?;
+        MultipartUploadSession::delete(&transaction, upload_id).await?;
+        if media_deleted {
+            Media::delete_returning(
+                &transaction,
+                &media.media_id,
+                &media.user_id,
+                None,
+            )
+            .await?;
+        } else if let Some(kept_size_bytes) = kept_size_bytes {
+            Media::set_size_bytes(
+                &transaction,
+                &media.media_id,
+                kept_size_bytes,
+            )
+            .await?;
+        }
+        transaction.commit().await.map_err(DbError::from)?;
+        self.media_cache.invalidate(&media.media_id).await;
+
+        Ok(media_deleted)
+    }
+}
+
+#[async_trait]
+impl media_service_server::MediaService for MediaService {
+    type DownloadMediaChunksStream =
+        ReceiverStream<Result<DownloadMediaChunksResponse, Status>>;
+    type ExportBoothManifestStream =
+        ReceiverStream<Result<ExportBoothManifestResponse, Status>>;
+    type BatchRemoveMediaFromOfferStream =
+        ReceiverStream<Result<BatchRemoveMediaFromOfferResponse, Status>>;
+    type MigrateStorageKeysStream =
+        ReceiverStream<Result<MigrateStorageKeysResponse, Status>>;
+
+    async fn create_media(
+        &self,
+        request: Request<CreateMediaRequest>,
+    ) -> Result<Response<CreateMediaResponse>, Status> {
+        let metadata = request.metadata().clone();
+
+        let user_id = get_user_id(&metadata, &self.verifier).await?;
+
+        let CreateMediaRequest {
+            shop_id,
+            name,
+            file,
+            file_name,
+            offer_id,
+            custom_metadata,
+        } = request.into_inner();
+
+        let shop_uuid = parse_uuid(&shop_id, "shop_id")?;
+
+        Self::validate_custom_metadata(&custom_metadata)?;
+
+        if let Some(file) = file.as_ref() {
+            Self::check_content_type(
+                self.upload_policy.single.as_ref(),
+                &file.content_type,
+                "single",
+            )?;
+        }
+
+        let offer_uuid = offer_id
+            .as_ref()
+            .map(|o| parse_uuid(o, "offer_id"))
+            .transpose()?;
+
+        self.quota_service.check_quota(&user_id).await?;
+
+        self.commerce_service
+            .check_shop_and_owner(&shop_uuid.to_string(), &user_id, &metadata)
+            .await?;
+
+        if let Some(offer_uuid) = offer_uuid.as_ref() {
+            self.commerce_service
+                .check_offer_of_shop_and_owner(
+                    &offer_uuid.to_string(),
+                    &shop_uuid.to_string(),
+                    &user_id,
+                    &metadata,
+                )
+                .await?;
+        }
+
+        let (file, file_info) = match file {
+            Some(file) => {
+                let (file, file_info) =
+                    FileInfo::extract(file, &self.processing_limiter).await?;
+                self.check_image_dimensions(&file_info)?;
+                (Some(file), file_info)
+            }
+            None => (None, FileInfo::default()),
+        };
+
+        let media_id = Uuid::new_v4();
+
+        let processing_state = match file.as_ref() {
+            Some(file) => self.scan_upload(&media_id, &file.data).await?,
+            None => None,
+        };
+
+        let file_path = match file.as_ref() {
+            Some(file) => {
+                self.build_file_key(&user_id, &shop_uuid, &media_id, &file.data)
+            }
+            None => self.build_file_path(&user_id, &shop_uuid, &media_id),
+        };
+
+        let (file_service, file_path) = self
+            .get_file_service_for_upload(
+                &shop_uuid,
+                file.as_ref().map(|f| f.content_type.as_str()),
+                file_path,
+            )
+            .await?;
+
+        let mut conn = get_client(&self.pool, pool_acquire_timeout()).await?;
+        let transaction = conn.transaction().await.map_err(DbError::from)?;
+
+        let size = file
+            .as_ref()
+            .map(|f| f.data.len())
+            .unwrap_or(0)
+            .try_into()
+            .map_err(|_| Status::internal(""))?;
+
+        let mut created_media = Media::create_with_offer(
+            &transaction,
+            &media_id,
+            &shop_uuid,
+            &user_id,
+            &name,
+            &file_path,
+            size,
+            &file_name,
+            Some(file_service.bucket_name().to_owned()),
+            file.as_ref().map(|f| f.content_type.clone()),
+            &custom_metadata,
+            offer_uuid,
+        )
+        .await?;
+
+        // the check above can pass for concurrent uploads, this one counts
+        // the new file and rolls back when the quota is exceeded
+        self.quota_service
+            .check_quota_in_transaction(&transaction, &user_id)
+            .await?;
+
+        if let Some(processing_state) = processing_state {
+            Media::set_processing_state(
+                &transaction,
+                &media_id,
+                processing_state,
+            )
+            .await?;
+            created_media.processing_state = processing_state.to_owned();
+        }
+
+        if let Some(file) = file {
+            Media::set_file_info(&transaction, &media_id, &file_info).await?;
+            created_media.duration_seconds = file_info.duration_seconds;
+            created_media.image_width = file_info.image_width;
+            created_media.image_height = file_info.image_height;
+
+            let tag_values = ObjectTagValues {
+                shop_id: shop_uuid.to_string(),
+                media_id: media_id.to_string(),
+                content_type: file.content_type.clone(),
+            };
+            match file_service
+                .put_file(
+                    &file_path,
+                    &file.data,
+                    &file.content_type,
+                    &tag_values,
+                )
+                .await
+            {
+                Ok(None) => {}
+                Ok(storage_region) => {
+                    Media::set_storage_region(
+                        &transaction,
+                        &media_id,
+                        storage_region.clone(),
+                    )
+                    .await?;
+                    created_media.storage_region = storage_region;
+                }
+                Err(err) => {
+                    transaction.rollback().await.map_err(DbError::from)?;
+                    return Err(err.into());
+                }
+            }
+        }
+
+        transaction.commit().await.map_err(DbError::from)?;
+
+        if processing_state == Some(Media::PROCESSING_STATE_PENDING) {
+            self.spawn_virus_scan(&created_media).await?;
+        }
+
+        Ok(Response::new(CreateMediaResponse {
+            media: Some(self.to_response(created_media, Some(&user_id))),
+        }))
+    }
+
+    async fn get_media(
+        &self,
+        request: Request<GetMediaRequest>,
+    ) -> Result<Response<GetMediaResponse>, Status> {
+        let user_id = get_user_id(request.metadata(), &self.verifier).await?;
+
+        let GetMediaRequest {
+            media_id,
+            include_download_url,
+            allow_data_uri,
+            sign_response,
+        } = request.into_inner();
+        let media_uuid = parse_uuid(&media_id, "media_id")?;
+        self.check_signable(sign_response)?;
+
+        let found_media = match self.media_cache.get(&media_uuid).await {
+            Some(media) => {
+                tracing::log::trace!(
+                    "[MediaService.get_media]: cache hit {media_uuid}"
+                );
+                // cached media of other owners is treated as not found,
+                // same as the owner condition of the query
+                Some(media).filter(|m| m.user_id == user_id)
+            }
+            None => {
+                tracing::log::trace!(
+                    "[MediaService.get_media]: cache miss {media_uuid}"
+                );
+                // media created right before might not be replicated yet
+                let found_media = match Media::get_for_owner(
+                    &self.read_pool,
+                    &media_uuid,
+                    &user_id,
+                )
+                .await?
+                {
+                    Some(media) => Some(media),
+                    None => {
+                        Media::get_for_owner(&self.pool, &media_uuid, &user_id)
+                            .await?
+                    }
+                };
+                if let Some(media) = found_media.as_ref() {
+                    self.media_cache.insert(media_uuid, media.clone()).await;
+                }
+                found_media
+            }
+        }
+        .ok_or_else(|| {
+            StatusBuilder::not_found(&media_id)
+                .with_resource_info("media", &media_id)
+        })?;
+
+        let download_url = if include_download_url.unwrap_or(false) {
+            Self::check_servable(&found_media)?;
+            let file_service =
+                self.get_file_service_for_media(&found_media).await?;
+            if allow_data_uri.unwrap_or(false)
+                && self.fits_data_uri(found_media.size_bytes)
+            {
+                Some(Self::get_data_uri(&file_service, &found_media).await?)
+            } else {
+                Some(
+                    file_service
+                        .get_presigned_url(
+                            &found_media.object_key,
+                            &found_media.file_name,
+                            self.presigned_url_expiry.owner_download,
+                        )
+                        .await?,
+                )
+            }
+        } else {
+            None
+        };
+
+        Ok(Response::new(GetMediaResponse {
+            media: Some(self.sign_response(
+                self.to_response(found_media, Some(&user_id)),
+                sign_response,
+            )),
+            download_url,
+        }))
+    }
+
+    async fn download_media(
+        &self,
+        request: Request<DownloadMediaRequest>,
+    ) -> Result<Response<DownloadMediaResponse>, Status> {
+        let user_id = get_user_id(request.metadata(), &self.verifier).await?;
+
+        let DownloadMediaRequest {
+            media_id,
+            expires_in_seconds,
+        } = request.into_inner();
+
+        if expires_in_seconds == Some(0) {
+            return Err(StatusBuilder::invalid_argument(
+                "expires_in_seconds: must be greater than 0",
+            )
+            .with_bad_request_field(
+                "expires_in_seconds",
+                "must be greater than 0",
+            )
+            .build());
+        }
+        let media_uuid = parse_uuid(&media_id, "media_id")?;
+
+        let found_media = Media::get_accessible(
+            &self.pool,
+            &media_uuid,
+            &user_id,
+            self.subscription_grace_period,
+        )
+        .await?
+        .ok_or_else(|| {
+            StatusBuilder::not_found(&media_id)
+                .with_resource_info("media", &media_id)
+        })?;
+
+        Self::check_servable(&found_media)?;
+
+        let download_url = self
+            .get_file_service_for_media(&found_media)
+            .await?
+            .get_presigned_url(
+                &found_media.object_key,
+                &found_media.file_name,
+                self.presigned_url_expiry.bounded(
+                    expires_in_seconds.map(|e| Duration::from_secs(e.into())),
+                    self.presigned_url_expiry.download,
+                ),
+            )
+            .await?;
+
+        Ok(Response::new(DownloadMediaResponse { download_url }))
+    }
+
+    async fn list_media(
+        &self,
+        request: Request<ListMediaRequest>,
+    ) -> Result<Response<ListMediaResponse>, Status> {
+        let metadata = request.metadata().clone();
+
+        let user_id = get_user_id(&metadata, &self.verifier).await?;
+
+        let ListMediaRequest {
+            shop_id,
+            pagination,
+            order_by,
+            filter,
+            filters,
+            offer_id,
+            user_id: owner_user_id,
+            include_urls,
+            sign_response,
+            fields,
+        } = request.into_inner();
+
+        self.check_signable(sign_response)?;
+
+        let is_admin_listing = owner_user_id.is_some();
+        let owner_user_id = match owner_user_id {
+            Some(owner_user_id) => {
+                let admin_user_id =
+                    verify_admin_user(&metadata, &self.verifier).await?;
+                tracing::log::info!(
+                    "[MediaService.list_media]: admin {admin_user_id} lists media of {owner_user_id}"
+                );
+                owner_user_id
+            }
+            None => user_id.clone(),
+        };
+
+        let shop_id = parse_uuid(&shop_id, "shop_id")?;
+
+        // admins still list media of closed shops for cleanups
+        if !is_admin_listing {
+            self.check_shop_listable(&shop_id).await?;
+        }
+
+        // capped before the offset is computed, so the next page continues
+        // where the capped page ended
+        let pagination = pagination.unwrap_or_default();
+        let pagination = PaginationRequest {
+            page: pagination.page.max(1),
+            size: match pagination.size {
+                0 => DEFAULT_PAGE_SIZE,
+                size => size,
+            }
+            .min(Self::max_list_page_size(self.max_encoding_message_size)),
+        };
+        let (limit, offset, mut pagination) =
+            get_limit_offset_from_pagination(Some(pagination))?;
+
+        let filters: Vec<_> = Self::offer_id_filter(offer_id)?
+            .into_iter()
+            .chain(
+                filter
+                    .into_iter()
+                    .chain(filters)
+                    .map(|f| (f.field(), f.query)),
+            )
+            .collect();
+
+        if filters.len() > Self::MAX_LIST_FILTERS {
+            return Err(StatusBuilder::invalid_argument(format!(
+                "filters: at most {} allowed",
+                Self::MAX_LIST_FILTERS
+            ))
+            .with_bad_request_field("filters", "too many filters")
+            .build());
+        }
+
+        let order_by = order_by.map(|o| (o.field(), o.direction()));
+
+        let (found_medias, count) = Media::list(
+            &self.read_pool,
+            &shop_id,
+            &owner_user_id,
+            limit.into(),
+            offset.into(),
+            filters,
+            order_by,
+            Self::projection_for_fields(&fields),
+        )
+        .await?;
+
+        set_total_elements(&mut pagination, count)?;
+
+        // skip presigning urls that would be dropped by the projection
+        let include_urls = include_urls
+            && (fields.is_empty()
+                || fields.iter().any(|f| f == "download_url"));
+
+        let download_urls = if include_urls {
+            try_join_all(found_medias.iter().map(|m| self.get_download_url(m)))
+                .await?
+        } else {
+            vec![None; found_medias.len()]
+        };
+
+        let (medias, truncated) = self.fit_to_message_size(
+            found_medias
+                .into_iter()
+                .zip(download_urls)
+                .map(|(m, download_url)| {
+                    self.sign_response(
+                        Self::project_response(
+                            MediaResponse {
+                                download_url,
+                                ..self.to_response(m, Some(&user_id))
+                            },
+                            &fields,
+                        ),
+                        sign_response,
+                    )
+                })
+                .collect(),
+        );
+
+        if truncated {
+            tracing::log::warn!(
+                "[MediaService.list_media]: truncated page to {} medias to stay within message size",
+                medias.len()
+            );
+        }
+
+        Ok(Response::new(ListMediaResponse {
+            medias,
+            pagination: Some(pagination),
+            truncated,
+        }))
+    }
+
+    async fn list_accessible_media(
+        &self,
+        request: Request<ListAccessibleMediaRequest>,
+    ) -> Result<Response<ListAccessibleMediaResponse>, Status> {
+        let user_id =
+            get_user_id(request.metadata(), &self.verifier).await.ok();
+
+        let ListAccessibleMediaRequest {
+            pagination,
+            order_by,
+            filter,
+            offer_id,
+        } = request.into_inner();
+
+        let (limit, offset, mut pagination) =
+            get_limit_offset_from_pagination(pagination)?;
+
+        let filters: Vec<_> = Self::offer_id_filter(offer_id)?
+            .into_iter()
+            .chain(filter.map(|f| (f.field(), f.query)))
+            .collect();
+
+        let order_by = order_by.map(|o| (o.field(), o.direction()));
+
+        let (found_medias, count) = match &user_id {
+            Some(user_id) => {
+                Media::list_accessible(
+                    &self.read_pool,
+                    user_id,
+                    limit.into(),
+                    offset.into(),
+                    filters,
+                    order_by,
+                    self.subscription_grace_period,
+                )
+                .await?
+            }
+            // anonymous callers have access to nothing, same as buyers
+            // without active subscriptions
+            None => (vec![], 0),
+        };
+
+        set_total_elements(&mut pagination, count)?;
+
+        Ok(Response::new(ListAccessibleMediaResponse {
+            medias: found_medias
+                .into_iter()
+                .map(|m| self.to_response(m, user_id.as_ref()))
                 .collect(),
             pagination: Some(pagination),
         }))
     }
 
-    async fn update_media(
+    async fn update_media(
+        &self,
+        request: Request<UpdateMediaRequest>,
+    ) -> Result<Response<UpdateMediaResponse>, Status> {
+        let user_id = get_user_id(request.metadata(), &self.verifier).await?;
+
+        let UpdateMediaRequest {
+            media_id,
+            name,
+            file,
+            file_name,
+            custom_metadata,
+            shop_id,
+            clear_custom_metadata,
+        } = request.into_inner();
+
+        let media_uuid = parse_uuid(&media_id, "media_id")?;
+        let shop_uuid = shop_id
+            .map(|shop_id| parse_uuid(&shop_id, "shop_id"))
+            .transpose()?;
+
+        Self::validate_custom_metadata(&custom_metadata)?;
+        let custom_metadata = Self::custom_metadata_update(
+            custom_metadata,
+            clear_custom_metadata,
+        )?;
+
+        if let Some(file) = file.as_ref() {
+            Self::check_content_type(
+                self.upload_policy.single.as_ref(),
+                &file.content_type,
+                "single",
+            )?;
+        }
+
+        let found_media =
+            Media::get_for_owner(&self.pool, &media_uuid, &user_id)
+                .await?
+                .filter(|m| shop_uuid.is_none_or(|s| m.shop_id == s))
+                .ok_or_else(|| {
+                    StatusBuilder::not_found(&media_id)
+                        .with_resource_info("media", &media_id)
+                })?;
+
+        if let Some(file) = file.as_ref() {
+            self.check_content_type_replacement(
+                &found_media,
+                &file.content_type,
+            )?;
+        }
+
+        let (file, file_info) = match file {
+            Some(file) => {
+                let (file, file_info) =
+                    FileInfo::extract(file, &self.processing_limiter).await?;
+                self.check_image_dimensions(&file_info)?;
+                (Some(file), file_info)
+            }
+            None => (None, FileInfo::default()),
+        };
+
+        let processing_state = match file.as_ref() {
+            Some(file) => self.scan_upload(&media_uuid, &file.data).await?,
+            None => None,
+        };
+
+        let new_size =
+            file.as_ref().and_then(|f| i64::try_from(f.data.len()).ok());
+
+        // the update is only committed once a new file is stored, so the
+        // media never describes a file that failed to upload
+        let mut conn = get_client(&self.pool, pool_acquire_timeout()).await?;
+        let transaction = conn.transaction().await.map_err(DbError::from)?;
+
+        let mut updated_media = Media::update(
+            &transaction,
+            &media_uuid,
+            &user_id,
+            shop_uuid.as_ref(),
+            UpdateMediaFields {
+                name,
+                size_bytes: new_size,
+                file_name,
+                content_type: file.as_ref().map(|f| f.content_type.clone()),
+                custom_metadata,
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        if let Some(file) = file.as_ref() {
+            let file_path = self.build_file_key(
+                &found_media.user_id,
+                &found_media.shop_id,
+                &found_media.media_id,
+                &file.data,
+            );
+            // replaced files stay in the recorded bucket, keep the prefix of
+            // its route
+            let file_path = match self.file_service.route(&file.content_type) {
+                Some(route)
+                    if found_media.bucket_name.as_ref()
+                        == Some(&route.bucket_name) =>
+                {
+                    route.key(file_path)
+                }
+                _ => file_path,
+            };
+            let tag_values = ObjectTagValues {
+                shop_id: found_media.shop_id.to_string(),
+                media_id: found_media.media_id.to_string(),
+                content_type: file.content_type.clone(),
+            };
+            let storage_region = match self
+                .get_file_service_for_media(&found_media)
+                .await?
+                .put_file(
+                    &file_path,
+                    &file.data,
+                    &file.content_type,
+                    &tag_values,
+                )
+                .await
+            {
+                Ok(storage_region) => storage_region,
+                Err(err) => {
+                    transaction.rollback().await.map_err(DbError::from)?;
+                    return Err(err.into());
+                }
+            };
+
+            if file_path != found_media.object_key {
+                Media::set_object_key(&transaction, &media_uuid, &file_path)
+                    .await?;
+                updated_media.object_key = file_path;
+            }
+            if storage_region.is_some() {
+                Media::set_storage_region(
+                    &transaction,
+                    &media_uuid,
+                    storage_region.clone(),
+                )
+                .await?;
+                updated_media.storage_region = storage_region;
+            }
+            Media::set_file_info(&transaction, &media_uuid, &file_info).await?;
+            if let Some(processing_state) = processing_state {
+                Media::set_processing_state(
+                    &transaction,
+                    &media_uuid,
+                    processing_state,
+                )
+                .await?;
+                updated_media.processing_state = processing_state.to_owned();
+            }
+            if updated_media.object_key != found_media.object_key {
+                self.release_previous_file(&transaction, &found_media).await;
+            }
+
+            updated_media.duration_seconds = file_info.duration_seconds;
+            updated_media.image_width = file_info.image_width;
+            updated_media.image_height = file_info.image_height;
+        }
+
+        transaction.commit().await.map_err(DbError::from)?;
+        self.media_cache.invalidate(&media_uuid).await;
+
+        if processing_state == Some(Media::PROCESSING_STATE_PENDING) {
+            self.spawn_virus_scan(&updated_media).await?;
+        }
+
+        Ok(Response::new(UpdateMediaResponse {
+            media: Some(self.to_response(updated_media, Some(&user_id))),
+        }))
+    }
+
+    async fn delete_media(
+        &self,
+        request: Request<DeleteMediaRequest>,
+    ) -> Result<Response<DeleteMediaResponse>, Status> {
+        let user_id = get_user_id(request.metadata(), &self.verifier).await?;
+
+        let DeleteMediaRequest { media_id, shop_id } = request.into_inner();
+
+        let media_uuid = parse_uuid(&media_id, "media_id")?;
+        let shop_uuid = shop_id
+            .map(|shop_id| parse_uuid(&shop_id, "shop_id"))
+            .transpose()?;
+
+        // media of another shop is reported as missing, not as forbidden
+        Media::get_for_owner(&self.pool, &media_uuid, &user_id)
+            .await?
+            .filter(|m| shop_uuid.is_none_or(|s| m.shop_id == s))
+            .ok_or_else(|| {
+                StatusBuilder::not_found(&media_id)
+                    .with_resource_info("media", &media_id)
+            })?;
+
+        Media::mark_delete_pending(&self.pool, &media_uuid, &user_id).await?;
+        self.media_cache.invalidate(&media_uuid).await;
+
+        self.finish_delete(&media_uuid, &user_id, shop_uuid.as_ref())
+            .await?;
+
+        Ok(Response::new(DeleteMediaResponse {}))
+    }
+
+    async fn initiate_multipart_upload(
+        &self,
+        request: Request<InitiateMultipartUploadRequest>,
+    ) -> Result<Response<InitiateMultipartUploadResponse>, Status> {
+        let user_id = get_user_id(request.metadata(), &self.verifier).await?;
+
+        let InitiateMultipartUploadRequest {
+            media_id,
+            content_type,
+        } = request.into_inner();
+
+        let media_uuid = parse_uuid(&media_id, "media_id")?;
+
+        Self::check_content_type(
+            self.upload_policy.multipart.as_ref(),
+            &content_type,
+            "multipart",
+        )?;
+
+        self.quota_service.check_quota(&user_id).await?;
+
+        let found_media =
+            Media::get_for_owner(&self.pool, &media_uuid, &user_id)
+                .await?
+                .ok_or_else(|| {
+                    StatusBuilder::not_found(&media_id)
+                        .with_resource_info("media", &media_id)
+                })?;
+
+        // Parts are uploaded before the content is known, so multipart
+        // uploads always use a per media key. A shared object with a content
+        // based key must not be overwritten.
+        let file_path = self.build_file_path(
+            &found_media.user_id,
+            &found_media.shop_id,
+            &found_media.media_id,
+        );
+
+        let file_service =
+            self.get_file_service_for_media(&found_media).await?;
+
+        // The media keeps serving its current file until the upload
+        // completes, which then switches key and content type
+        let replaced_size_bytes =
+            if file_service.object_exists(&found_media.object_key).await? {
+                Some(
+                    i64::try_from(found_media.size_bytes)
+                        .map_err(|_| Status::internal(""))?,
+                )
+            } else {
+                None
+            };
+
+        if replaced_size_bytes.is_some() {
+            self.check_content_type_replacement(&found_media, &content_type)?;
+        }
+
+        let upload_id = file_service
+            .initiate_multipart_upload(
+                &file_path,
+                &content_type,
+                &ObjectTagValues {
+                    shop_id: found_media.shop_id.to_string(),
+                    media_id: found_media.media_id.to_string(),
+                    content_type: content_type.clone(),
+                },
+            )
+            .await?;
+
+        MultipartUploadSession::create(
+            &self.pool,
+            &upload_id,
+            &media_uuid,
+            &user_id,
+            &file_path,
+            &content_type,
+            replaced_size_bytes,
+        )
+        .await?;
+
+        Ok(Response::new(InitiateMultipartUploadResponse {
+            key: file_path,
+            upload_id,
+            media_id: media_uuid.to_string(),
+            key_derivation_version: ObjectKeyStrategy::path_key_version(
+                self.legacy_flat_keys,
+            ),
+        }))
+    }
+
+    async fn put_multipart_chunk(
+        &self,
+        request: Request<PutMultipartChunkRequest>,
+    ) -> Result<Response<PutMultipartChunkResponse>, Status> {
+        let user_id = get_user_id(request.metadata(), &self.verifier).await?;
+
+        let PutMultipartChunkRequest {
+            media_id,
+            upload_id,
+            part_number,
+            chunk,
+        } = request.into_inner();
+
+        let media_uuid = parse_uuid(&media_id, "media_id")?;
+
+        let found_media =
+            Media::get_for_owner(&self.pool, &media_uuid, &user_id)
+                .await?
+                .ok_or_else(|| {
+                    StatusBuilder::not_found(&media_id)
+                        .with_resource_info("media", &media_id)
+                })?;
+
+        let file_service =
+            self.get_file_service_for_media(&found_media).await?;
+        let session = self.get_upload_session(&found_media, &upload_id).await?;
+        let object_key =
+            Self::upload_object_key(&found_media, session.as_ref()).clone();
+
+        // parts are recorded once stored, so retried chunks are answered
+        // without asking the storage
+        if let Some(MultipartPart { etag, .. }) =
+            MultipartPart::get(&self.pool, &upload_id, part_number).await?
+        {
+            tracing::log::debug!(
+                "[MediaService.put_multipart_chunk]: part {part_number} of upload {upload_id} already exists"
+            );
+            return Ok(Response::new(PutMultipartChunkResponse {
+                part: Some(Part { part_number, etag }),
+            }));
+        }
+
+        // the size includes the file being replaced until the upload
+        // completes
+        let uploaded_bytes = found_media.size_bytes.saturating_sub(
+            session
+                .as_ref()
+                .and_then(|session| session.replaced_size_bytes)
+                .and_then(|size| u64::try_from(size).ok())
+                .unwrap_or(0),
+        );
+        self.check_multipart_limits(uploaded_bytes, &upload_id, chunk.len())
+            .await?;
+
+        let additional_size =
+            i64::try_from(chunk.len()).map_err(|_| Status::internal(""))?;
+
+        // user_id check is done implicitly in add_size
+        let found_media =
+            Media::add_size(&self.pool, &media_uuid, &user_id, additional_size)
+                .await?;
+
+        if let Err(status) = self.quota_service.check_quota(&user_id).await {
+            if status.code() == tonic::Code::ResourceExhausted {
+                self.discard_multipart_upload(
+                    &file_service,
+                    &found_media,
+                    &upload_id,
+                    true,
+                )
+                .await?;
+            }
+
+            return Err(status);
+        }
+
+        let etag = match file_service
+            .put_multipart_chunk(&object_key, &upload_id, part_number, &chunk)
+            .await
+        {
+            Ok(etag) => etag,
+            Err(err) => {
+                self.record_multipart_error(&found_media, &upload_id, &err)
+                    .await;
+                return Err(err.into());
+            }
+        };
+
+        MultipartPart::put(&self.pool, &upload_id, part_number, &etag).await?;
+
+        Ok(Response::new(PutMultipartChunkResponse {
+            part: Some(Part { part_number, etag }),
+        }))
+    }
+
+    async fn complete_multipart_upload(
+        &self,
+        request: Request<CompleteMultipartUploadRequest>,
+    ) -> Result<Response<CompleteMultipartUploadResponse>, Status> {
+        let user_id = get_user_id(request.metadata(), &self.verifier).await?;
+
+        let CompleteMultipartUploadRequest {
+            media_id,
+            upload_id,
+            parts,
+        } = request.into_inner();
+
+        let media_uuid = parse_uuid(&media_id, "media_id")?;
+
+        let found_media =
+            Media::get_for_owner(&self.pool, &media_uuid, &user_id)
+                .await?
+                .ok_or_else(|| {
+                    StatusBuilder::not_found(&media_id)
+                        .with_resource_info("media", &media_id)
+                })?;
+
+        // Parts recorded by put_multipart_chunk take precedence, uploads
+        // started before parts were recorded rely on the client's list
+        let recorded_parts =
+            MultipartPart::list(&self.pool, &upload_id).await?;
+        let parts = if recorded_parts.is_empty() {
+            if parts.is_empty() {
+                return Err(StatusBuilder::invalid_argument(
+                    "parts: no parts uploaded",
+                )
+                .with_bad_request_field("parts", "no parts uploaded")
+                .build());
+            }
+            parts
+                .into_iter()
+                .map(|p| (p.part_number, p.etag))
+                .collect::<Vec<_>>()
+        } else {
+            recorded_parts
+                .into_iter()
+                .map(|p| (p.part_number, p.etag))
+                .collect()
+        };
+
+        let parts = parts
+            .into_iter()
+            .map(|(part_number, etag)| {
+                CompletedPart::builder()
+                    .e_tag(etag)
+                    .part_number(part_number.try_into().unwrap())
+                    .build()
+            })
+            .collect();
+
+        let session = self.get_upload_session(&found_media, &upload_id).await?;
+        let object_key =
+            Self::upload_object_key(&found_media, session.as_ref()).clone();
+
+        if let Err(err) = self
+            .get_file_service_for_media(&found_media)
+            .await?
+            .complete_multipart_upload(&object_key, &upload_id, parts)
+            .await
+        {
+            self.record_multipart_error(&found_media, &upload_id, &err)
+                .await;
+            return Err(err.into());
+        }
+
+        let mut completed_media = found_media.clone();
+        completed_media.object_key = object_key;
+
+        let mut conn = get_client(&self.pool, pool_acquire_timeout()).await?;
+        let transaction = conn.transaction().await.map_err(DbError::from)?;
+        MultipartPart::delete_all_for_upload(&transaction, &upload_id).await?;
+        MultipartUploadSession::delete(&transaction, &upload_id).await?;
+        if let Some(session) = session.as_ref() {
+            Media::set_uploaded_file(
+                &transaction,
+                &media_uuid,
+                &completed_media.object_key,
+                session.content_type.as_ref(),
+                session.replaced_size_bytes.unwrap_or(0),
+            )
+            .await?;
+            if session.replaced_size_bytes.is_some()
+                && completed_media.object_key != found_media.object_key
+            {
+                self.release_previous_file(&transaction, &found_media).await;
+            }
+        }
+        // the parts are not read, so metadata of a replaced file would be
+        // stale
+        Media::set_file_info(&transaction, &media_uuid, &FileInfo::default())
+            .await?;
+        // parts are not held in memory, so the assembled file is always
+        // scanned in the background
+        if self.virus_scanner.is_some() {
+            Media::set_processing_state(
+                &transaction,
+                &media_uuid,
+                Media::PROCESSING_STATE_PENDING,
+            )
+            .await?;
+        }
+        transaction.commit().await.map_err(DbError::from)?;
+        self.media_cache.invalidate(&media_uuid).await;
+
+        if self.virus_scanner.is_some() {
+            self.spawn_virus_scan(&completed_media).await?;
+        }
+
+        Ok(Response::new(CompleteMultipartUploadResponse {}))
+    }
+
+    async fn add_media_to_offer(
+        &self,
+        request: Request<AddMediaToOfferRequest>,
+    ) -> Result<Response<AddMediaToOfferResponse>, Status> {
+        let metadata = request.metadata().clone();
+
+        let user_id = get_user_id(&metadata, &self.verifier).await?;
+
+        let AddMediaToOfferRequest {
+            media_id,
+            offer_id,
+            ordering,
+        } = request.into_inner();
+
+        let media_uuid = parse_uuid(&media_id, "media_id")?;
+        let offer_uuid = parse_uuid(&offer_id, "offer_id")?;
+
+        // Check if user is owner of the offer
+        self.commerce_service
+            .check_offer_and_owner(&offer_uuid.to_string(), &user_id, &metadata)
+            .await?;
+
+        // Check if user is owner of media
+        Media::get_for_owner(&self.pool, &media_uuid, &user_id)
+            .await?
+            .ok_or_else(|| {
+                StatusBuilder::not_found(&media_id)
+                    .with_resource_info("media", &media_id)
+            })?;
+
+        let ord = match ordering {
+            Some(o) => o,
+            None => {
+                let highest = MediaOffer::get_highest_ordering(
+                    &self.pool,
+                    &offer_uuid,
+                    &user_id,
+                )
+                .await?;
+
+                highest + 1
+            }
+        };
+
+        MediaOffer::create(&self.pool, &media_uuid, &offer_uuid, &user_id, ord)
+            .await?;
+        self.media_cache.invalidate(&media_uuid).await;
+
+        Ok(Response::new(AddMediaToOfferResponse {}))
+    }
+
+    async fn update_media_offer_ordering(
+        &self,
+        request: Request<UpdateMediaOfferOrderingRequest>,
+    ) -> Result<Response<UpdateMediaOfferOrderingResponse>, Status> {
+        let user_id = get_user_id(request.metadata(), &self.verifier).await?;
+
+        let UpdateMediaOfferOrderingRequest {
+            media_id,
+            offer_id,
+            ordering,
+        } = request.into_inner();
+
+        let media_id = parse_uuid(&media_id, "media_id")?;
+        let offer_id = parse_uuid(&offer_id, "offer_id")?;
+
+        let found_media_offer =
+            MediaOffer::get(&self.pool, &media_id, &offer_id)
+                .await?
+                .ok_or_else(|| {
+                    StatusBuilder::not_found("").with_resource_info(
+                        "media_offer",
+                        &media_id.to_string(),
+                    )
+                })?;
+
+        let old_ordering = found_media_offer.ordering;
+
+        let mut found_media_offers =
+            MediaOffer::list(&self.pool, &user_id, &offer_id).await?;
+
+        // update media_offer with new_ordering
+        MediaOffer::update_ordering(
+            &self.pool, &media_id, &offer_id, &user_id, ordering,
+        )
+        .await?;
+        self.media_cache.invalidate(&media_id).await;
+
+        match old_ordering.cmp(&ordering) {
+            Ordering::Less => {
+                // decrement [old_ordering + 1, new_ordering] by 1
+                found_media_offers.retain(|m| {
+                    m.ordering >= (old_ordering + 1) && m.ordering <= ordering
+                });
+                for m in found_media_offers {
+                    MediaOffer::update_ordering(
+                        &self.pool,
+                        &m.media_id,
+                        &m.offer_id,
+                        &user_id,
+                        m.ordering - 1,
+                    )
+                    .await?;
+                    self.media_cache.invalidate(&m.media_id).await;
+                }
+            }
+            Ordering::Greater => {
+                // increment [new_ordering, old_ordering -1] by 1
+                found_media_offers.retain(|m| {
+                    m.ordering >= ordering && m.ordering <= (old_ordering - 1)
+                });
+                for m in found_media_offers {
+                    MediaOffer::update_ordering(
+                        &self.pool,
+                        &m.media_id,
+                        &m.offer_id,
+                        &user_id,
+                        m.ordering + 1,
+                    )
+                    .await?;
+                    self.media_cache.invalidate(&m.media_id).await;
+                }
+            }
+            Ordering::Equal => {}
+        }
+
+        Ok(Response::new(UpdateMediaOfferOrderingResponse {}))
+    }
+
+    async fn remove_media_from_offer(
+        &self,
+        request: Request<RemoveMediaFromOfferRequest>,
+    ) -> Result<Response<RemoveMediaFromOfferResponse>, Status> {
+        let user_id = get_user_id(request.metadata(), &self.verifier).await?;
+
+        let RemoveMediaFromOfferRequest { media_id, offer_id } =
+            request.into_inner();
+
+        let media_id = parse_uuid(&media_id, "media_id")?;
+        let offer_id = parse_uuid(&offer_id, "offer_id")?;
+
+        MediaOffer::delete(&self.pool, &media_id, &offer_id, &user_id).await?;
+        self.media_cache.invalidate(&media_id).await;
+
+        Ok(Response::new(RemoveMediaFromOfferResponse {}))
+    }
+
+    async fn detach_media_from_all_offers(
+        &self,
+        request: Request<DetachMediaFromAllOffersRequest>,
+    ) -> Result<Response<DetachMediaFromAllOffersResponse>, Status> {
+        let user_id = get_user_id(request.metadata(), &self.verifier).await?;
+
+        let DetachMediaFromAllOffersRequest { media_id } = request.into_inner();
+
+        let media_uuid = parse_uuid(&media_id, "media_id")?;
+
+        Media::get_for_owner(&self.pool, &media_uuid, &user_id)
+            .await?
+            .ok_or_else(|| {
+                StatusBuilder::not_found(&media_id)
+                    .with_resource_info("media", &media_id)
+            })?;
+
+        let mut conn = get_client(&self.pool, pool_acquire_timeout()).await?;
+        let transaction = conn.transaction().await.map_err(DbError::from)?;
+        let offer_ids = MediaOffer::delete_all_for_media(
+            &transaction,
+            &media_uuid,
+            &user_id,
+        )
+        .await?;
+        transaction.commit().await.map_err(DbError::from)?;
+        self.media_cache.invalidate(&media_uuid).await;
+
+        tracing::log::info!(
+            "[MediaService.detach_media_from_all_offers]: removed {media_id} from {} offers",
+            offer_ids.len()
+        );
+
+        Ok(Response::new(DetachMediaFromAllOffersResponse {
+            removed_count: u64::try_from(offer_ids.len()).unwrap_or_default(),
+            offer_ids: offer_ids.iter().map(|o| o.to_string()).collect(),
+        }))
+    }
+
+    async fn download_media_range(
         &self,
-        request: Request<UpdateMediaRequest>,
-    ) -> Result<Response<UpdateMediaResponse>, Status> {
+        request: Request<DownloadMediaRangeRequest>,
+    ) -> Result<Response<DownloadMediaRangeResponse>, Status> {
         let user_id = get_user_id(request.metadata(), &self.verifier).await?;
 
-        let UpdateMediaRequest {
+        let DownloadMediaRangeRequest {
             media_id,
-            name,
-            file,
-            file_name,
+            start_byte,
+            end_byte,
         } = request.into_inner();
 
         let media_uuid = parse_uuid(&media_id, "media_id")?;
 
-        let found_media =
-            Media::get_for_owner(&self.pool, &media_uuid, &user_id)
-                .await?
-                .ok_or(Status::not_found(&media_id))?;
+        let found_media = Media::get_accessible(
+            &self.pool,
+            &media_uuid,
+            &user_id,
+            self.subscription_grace_period,
+        )
+        .await?
+        .ok_or_else(|| {
+            StatusBuilder::not_found(&media_id)
+                .with_resource_info("media", &media_id)
+        })?;
 
-        let new_size =
-            file.as_ref().and_then(|f| i64::try_from(f.data.len()).ok());
+        Self::check_servable(&found_media)?;
+
+        let total_size = found_media.size_bytes;
+
+        // end_byte is inclusive, so it must point to an existing byte
+        if start_byte > end_byte || end_byte >= total_size {
+            return Err(StatusBuilder::out_of_range(format!(
+                "requested range {start_byte}-{end_byte} not satisfiable for size {total_size}"
+            ))
+            .with_bad_request_field("end_byte", "range not satisfiable")
+            .build());
+        }
+
+        let data = self
+            .get_file_service_for_media(&found_media)
+            .await?
+            .get_object_range(&found_media.object_key, start_byte, end_byte)
+            .await?;
+
+        Ok(Response::new(DownloadMediaRangeResponse {
+            data,
+            content_range: format!(
+                "bytes {start_byte}-{end_byte}/{total_size}"
+            ),
+            total_size,
+        }))
+    }
+
+    async fn rename_media(
+        &self,
+        request: Request<RenameMediaRequest>,
+    ) -> Result<Response<RenameMediaResponse>, Status> {
+        let user_id = get_user_id(request.metadata(), &self.verifier).await?;
+
+        let RenameMediaRequest { media_id, name } = request.into_inner();
+
+        let media_uuid = parse_uuid(&media_id, "media_id")?;
+
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(StatusBuilder::invalid_argument(
+                "field name must not be empty",
+            )
+            .with_bad_request_field("name", "must not be empty")
+            .build());
+        }
+
+        Media::get_for_owner(&self.pool, &media_uuid, &user_id)
+            .await?
+            .ok_or_else(|| {
+                StatusBuilder::not_found(&media_id)
+                    .with_resource_info("media", &media_id)
+            })?;
 
+        let mut conn = get_client(&self.pool, pool_acquire_timeout()).await?;
+        let transaction = conn.transaction().await.map_err(DbError::from)?;
         let updated_media = Media::update(
+            &transaction,
+            &media_uuid,
+            &user_id,
+            None,
+            UpdateMediaFields {
+                name: Some(name.to_string()),
+                ..Default::default()
+            },
+        )
+        .await?;
+        transaction.commit().await.map_err(DbError::from)?;
+        self.media_cache.invalidate(&media_uuid).await;
+
+        Ok(Response::new(RenameMediaResponse {
+            media: Some(self.to_response(updated_media, Some(&user_id))),
+        }))
+    }
+
+    async fn download_media_chunks(
+        &self,
+        request: Request<DownloadMediaChunksRequest>,
+    ) -> Result<Response<Self::DownloadMediaChunksStream>, Status> {
+        let user_id = get_user_id(request.metadata(), &self.verifier).await?;
+
+        let DownloadMediaChunksRequest { media_id, offset } =
+            request.into_inner();
+
+        let media_uuid = parse_uuid(&media_id, "media_id")?;
+
+        let found_media = Media::get_accessible(
             &self.pool,
             &media_uuid,
             &user_id,
-            name,
-            new_size,
-            file_name,
+            self.subscription_grace_period,
+        )
+        .await?
+        .ok_or_else(|| {
+            StatusBuilder::not_found(&media_id)
+                .with_resource_info("media", &media_id)
+        })?;
+
+        Self::check_servable(&found_media)?;
+
+        let total_size = found_media.size_bytes;
+        let mut offset = offset.unwrap_or(0);
+
+        if offset > 0 && offset >= total_size {
+            return Err(StatusBuilder::out_of_range(format!(
+                "offset {offset} not satisfiable for size {total_size}"
+            ))
+            .with_bad_request_field("offset", "offset not satisfiable")
+            .build());
+        }
+
+        let mut body = self
+            .get_file_service_for_media(&found_media)
+            .await?
+            .get_object_from(&found_media.object_key, offset)
+            .await?;
+
+        let (sender, receiver) = mpsc::channel(4);
+
+        tokio::spawn(async move {
+            let mut total_size = Some(total_size);
+
+            while let Some(chunk) = body.next().await {
+                let message = match chunk {
+                    Ok(chunk) => {
+                        let message = DownloadMediaChunksResponse {
+                            offset,
+                            total_size: total_size.take(),
+                            chunk: chunk.to_vec(),
+                        };
+                        offset += chunk.len() as u64;
+                        Ok(message)
+                    }
+                    Err(err) => {
+                        tracing::log::error!(
+                            "[MediaService.download_media_chunks]: {err}"
+                        );
+                        Err(Status::internal(""))
+                    }
+                };
+
+                let is_err = message.is_err();
+                if sender.send(message).await.is_err() || is_err {
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+
+    async fn get_shop_media_stats(
+        &self,
+        request: Request<GetShopMediaStatsRequest>,
+    ) -> Result<Response<GetShopMediaStatsResponse>, Status> {
+        let metadata = request.metadata().clone();
+
+        let user_id = get_user_id(&metadata, &self.verifier).await?;
+
+        let GetShopMediaStatsRequest { shop_id } = request.into_inner();
+
+        let shop_uuid = parse_uuid(&shop_id, "shop_id")?;
+
+        self.commerce_service
+            .check_shop_and_owner(&shop_uuid.to_string(), &user_id, &metadata)
+            .await?;
+
+        let stats = Media::get_stats(
+            &self.read_pool,
+            &shop_uuid,
+            &user_id,
+            Self::MAX_OFFERS_PER_MEDIA,
+            Self::MAX_MEDIA_PER_OFFER,
+            Self::MAX_FLAGGED_RELATIONS,
         )
         .await?;
 
-        if let Some(file) = file {
-            self.file_service
-                .put_file(&found_media.data_url, &file.data, &file.content_type)
-                .await?;
+        Ok(Response::new(GetShopMediaStatsResponse {
+            total_files: stats.total_files,
+            total_bytes: stats.total_bytes,
+            oldest_created_at: stats.oldest_created_at.map(|c| c.timestamp()),
+            newest_created_at: stats.newest_created_at.map(|c| c.timestamp()),
+            content_type_counts: stats.content_type_counts,
+            total_media_offers: stats.total_media_offers,
+            media_over_offer_cap: stats
+                .media_over_offer_cap
+                .iter()
+                .map(Uuid::to_string)
+                .collect(),
+            offers_over_media_cap: stats
+                .offers_over_media_cap
+                .iter()
+                .map(Uuid::to_string)
+                .collect(),
+            max_offers_per_media: Self::MAX_OFFERS_PER_MEDIA,
+            max_media_per_offer: Self::MAX_MEDIA_PER_OFFER,
+        }))
+    }
+
+    async fn check_media_ownership(
+        &self,
+        request: Request<CheckMediaOwnershipRequest>,
+    ) -> Result<Response<CheckMediaOwnershipResponse>, Status> {
+        let user_id = get_user_id(request.metadata(), &self.verifier).await?;
+
+        let CheckMediaOwnershipRequest { media_ids } = request.into_inner();
+
+        if media_ids.len() > Self::MAX_OWNERSHIP_CHECK_MEDIA_IDS {
+            return Err(StatusBuilder::invalid_argument(format!(
+                "media_ids: at most {} allowed",
+                Self::MAX_OWNERSHIP_CHECK_MEDIA_IDS
+            ))
+            .with_bad_request_field("media_ids", "too many media ids")
+            .build());
+        }
+
+        let media_uuids = media_ids
+            .iter()
+            .map(|id| parse_uuid(id, "media_ids"))
+            .collect::<Result<Vec<Uuid>, Status>>()?;
+
+        let owners = Media::get_owners(&self.read_pool, &media_uuids).await?;
+
+        let ownerships = media_uuids
+            .into_iter()
+            .map(|media_uuid| {
+                let owner = owners.iter().find(|(id, _)| *id == media_uuid);
+                MediaOwnership {
+                    media_id: media_uuid.to_string(),
+                    exists: owner.is_some(),
+                    is_owner: owner.is_some_and(|(_, u)| *u == user_id),
+                }
+            })
+            .collect();
+
+        Ok(Response::new(CheckMediaOwnershipResponse { ownerships }))
+    }
+
+    async fn move_media_between_offers(
+        &self,
+        request: Request<MoveMediaBetweenOffersRequest>,
+    ) -> Result<Response<MoveMediaBetweenOffersResponse>, Status> {
+        let metadata = request.metadata().clone();
+
+        let user_id = get_user_id(&metadata, &self.verifier).await?;
+
+        let MoveMediaBetweenOffersRequest {
+            media_id,
+            from_offer_id,
+            to_offer_id,
+            ordering,
+        } = request.into_inner();
+
+        let media_uuid = parse_uuid(&media_id, "media_id")?;
+        let from_offer_uuid = parse_uuid(&from_offer_id, "from_offer_id")?;
+        let to_offer_uuid = parse_uuid(&to_offer_id, "to_offer_id")?;
+
+        self.commerce_service
+            .check_offer_and_owner(
+                &from_offer_uuid.to_string(),
+                &user_id,
+                &metadata,
+            )
+            .await?;
+        self.commerce_service
+            .check_offer_and_owner(
+                &to_offer_uuid.to_string(),
+                &user_id,
+                &metadata,
+            )
+            .await?;
+
+        let media_offers = MediaOffer::move_between(
+            &self.pool,
+            &media_uuid,
+            &from_offer_uuid,
+            &to_offer_uuid,
+            &user_id,
+            ordering,
+        )
+        .await?
+        .ok_or_else(|| {
+            StatusBuilder::not_found(&media_id)
+                .with_resource_info("media_offer", &media_id)
+        })?;
+        self.media_cache.invalidate(&media_uuid).await;
+
+        Ok(Response::new(MoveMediaBetweenOffersResponse {
+            medias: media_offers
+                .into_iter()
+                .map(|m| OfferMediaItem {
+                    media_id: m.media_id.to_string(),
+                    ordering: m.ordering,
+                })
+                .collect(),
+        }))
+    }
+
+    async fn get_media_renditions(
+        &self,
+        request: Request<GetMediaRenditionsRequest>,
+    ) -> Result<Response<GetMediaRenditionsResponse>, Status> {
+        let user_id = get_user_id(request.metadata(), &self.verifier).await?;
+
+        let GetMediaRenditionsRequest { media_id } = request.into_inner();
+
+        let media_uuid = parse_uuid(&media_id, "media_id")?;
+
+        let found_media =
+            match Media::get_for_owner(&self.pool, &media_uuid, &user_id)
+                .await?
+            {
+                Some(media) => Some(media),
+                None => {
+                    Media::get_accessible(
+                        &self.pool,
+                        &media_uuid,
+                        &user_id,
+                        self.subscription_grace_period,
+                    )
+                    .await?
+                }
+            }
+            .ok_or_else(|| {
+                StatusBuilder::not_found(&media_id)
+                    .with_resource_info("media", &media_id)
+            })?;
+
+        let content_type = found_media.content_type.clone().unwrap_or_default();
+        let (Some(format), Some(width), Some(height)) = (
+            content_type.strip_prefix("image/"),
+            found_media.image_width,
+            found_media.image_height,
+        ) else {
+            return Ok(Response::new(GetMediaRenditionsResponse {
+                renditions: Vec::new(),
+            }));
+        };
+
+        Self::check_servable(&found_media)?;
+
+        // Only the uploaded file exists so far, derived sizes and formats
+        // are added here once they are generated
+        let url = self
+            .get_file_service_for_media(&found_media)
+            .await?
+            .get_presigned_url(
+                &found_media.object_key,
+                &found_media.file_name,
+                self.presigned_url_expiry.owner_download,
+            )
+            .await?;
+
+        Ok(Response::new(GetMediaRenditionsResponse {
+            renditions: vec![MediaRendition {
+                name: String::from("original"),
+                format: format.to_owned(),
+                content_type,
+                url,
+                width,
+                height,
+                size_bytes: found_media.size_bytes,
+            }],
+        }))
+    }
+
+    async fn batch_remove_media_from_offer(
+        &self,
+        request: Request<BatchRemoveMediaFromOfferRequest>,
+    ) -> Result<Response<Self::BatchRemoveMediaFromOfferStream>, Status> {
+        let metadata = request.metadata().clone();
+
+        let user_id = get_user_id(&metadata, &self.verifier).await?;
+
+        let BatchRemoveMediaFromOfferRequest {
+            offer_id,
+            media_ids,
+        } = request.into_inner();
+
+        if media_ids.len() > Self::MAX_BATCH_REMOVE_MEDIA_IDS {
+            return Err(StatusBuilder::invalid_argument(format!(
+                "media_ids: at most {} allowed",
+                Self::MAX_BATCH_REMOVE_MEDIA_IDS
+            ))
+            .with_bad_request_field("media_ids", "too many media ids")
+            .build());
         }
 
-        Ok(Response::new(UpdateMediaResponse {
-            media: Some(self.to_response(updated_media)),
-        }))
+        let offer_uuid = parse_uuid(&offer_id, "offer_id")?;
+        let media_uuids = media_ids
+            .iter()
+            .map(|id| parse_uuid(id, "media_ids"))
+            .collect::<Result<Vec<Uuid>, Status>>()?;
+
+        // Check if user is owner of the offer
+        self.commerce_service
+            .check_offer_and_owner(&offer_uuid.to_string(), &user_id, &metadata)
+            .await?;
+
+        let pool = self.pool.clone();
+        let media_cache = self.media_cache.clone();
+        let (mut progress, stream) = ProgressEmitter::channel(
+            media_uuids.len().try_into().unwrap_or_default(),
+            Self::PROGRESS_INTERVAL,
+        );
+
+        tokio::spawn(async move {
+            let mut results = Vec::with_capacity(media_uuids.len());
+            for media_uuid in media_uuids {
+                // relations not existing or not owned by the user are left
+                // untouched
+                let removed = match MediaOffer::delete_many(
+                    &pool,
+                    &[media_uuid],
+                    &offer_uuid,
+                    &user_id,
+                )
+                .await
+                {
+                    Ok(removed) => !removed.is_empty(),
+                    Err(err) => return progress.fail(err.into()).await,
+                };
+                if removed {
+                    media_cache.invalidate(&media_uuid).await;
+                }
+                results.push(BatchRemoveMediaFromOfferResult {
+                    media_id: media_uuid.to_string(),
+                    removed,
+                });
+
+                if progress.advance(media_uuid).await.is_err() {
+                    return;
+                }
+            }
+
+            let _ = progress
+                .send(BatchRemoveMediaFromOfferResponse {
+                    results,
+                    progress: None,
+                })
+                .await;
+        });
+
+        Ok(Response::new(stream))
     }
 
-    async fn delete_media(
+    async fn abort_multipart_upload(
         &self,
-        request: Request<DeleteMediaRequest>,
-    ) -> Result<Response<DeleteMediaResponse>, Status> {
+        request: Request<AbortMultipartUploadRequest>,
+    ) -> Result<Response<AbortMultipartUploadResponse>, Status> {
         let user_id = get_user_id(request.metadata(), &self.verifier).await?;
 
-        let DeleteMediaRequest { media_id } = request.into_inner();
+        let AbortMultipartUploadRequest {
+            media_id,
+            upload_id,
+            delete_on_abort,
+        } = request.into_inner();
 
         let media_uuid = parse_uuid(&media_id, "media_id")?;
 
         let found_media =
             Media::get_for_owner(&self.pool, &media_uuid, &user_id)
                 .await?
-                .ok_or(Status::not_found(&media_id))?;
-
-        let mut conn = self.pool.get().await.map_err(DbError::from)?;
-        let transaction = conn.transaction().await.map_err(DbError::from)?;
-        Media::begin_delete(&transaction, &media_uuid, &user_id).await?;
-        self.file_service.remove_file(&found_media.data_url).await?;
-        transaction.commit().await.map_err(DbError::from)?;
+                .ok_or_else(|| {
+                    StatusBuilder::not_found(&media_id)
+                        .with_resource_info("media", &media_id)
+                })?;
+
+        let file_service =
+            self.get_file_service_for_media(&found_media).await?;
+
+        let media_deleted = self
+            .discard_multipart_upload(
+                &file_service,
+                &found_media,
+                &upload_id,
+                delete_on_abort.unwrap_or(true),
+            )
+            .await?;
 
-        Ok(Response::new(DeleteMediaResponse {}))
+        Ok(Response::new(AbortMultipartUploadResponse {
+            media_deleted,
+        }))
     }
 
-    async fn initiate_multipart_upload(
+    async fn migrate_storage_keys(
         &self,
-        request: Request<InitiateMultipartUploadRequest>,
-    ) -> Result<Response<InitiateMultipartUploadResponse>, Status> {
-        let user_id = get_user_id(request.metadata(), &self.verifier).await?;
+        request: Request<MigrateStorageKeysRequest>,
+    ) -> Result<Response<Self::MigrateStorageKeysStream>, Status> {
+        verify_admin_user(request.metadata(), &self.verifier).await?;
 
-        let InitiateMultipartUploadRequest {
-            media_id,
-            content_type,
-        } = request.into_inner();
+        if self.legacy_flat_keys {
+            return Err(StatusBuilder::failed_precondition(
+                "new objects are still stored with legacy keys",
+            )
+            .with_precondition_violation(
+                "LEGACY_KEYS",
+                "storage",
+                "new objects are still stored with legacy keys",
+            )
+            .build());
+        }
 
-        let media_uuid = parse_uuid(&media_id, "media_id")?;
+        let MigrateStorageKeysRequest { limit } = request.into_inner();
 
-        self.quota_service.check_quota(&user_id).await?;
+        let limit = limit
+            .map(u64::from)
+            .unwrap_or(Self::DEFAULT_MIGRATE_STORAGE_KEYS_LIMIT)
+            .min(Self::MAX_MIGRATE_STORAGE_KEYS_LIMIT);
 
-        let found_media =
-            Media::get_for_owner(&self.pool, &media_uuid, &user_id)
-                .await?
-                .ok_or(Status::not_found(&media_id))?;
+        let legacy_media =
+            Media::list_with_legacy_keys(&self.pool, limit).await?;
 
-        let upload_id = self
-            .file_service
-            .initiate_multipart_upload(&found_media.data_url, &content_type)
-            .await?;
+        let mut migrations = Vec::with_capacity(legacy_media.len());
+        for media in legacy_media {
+            let file_service = self.get_file_service_for_media(&media).await;
+            migrations.push((media, file_service));
+        }
 
-        Ok(Response::new(InitiateMultipartUploadResponse {
-            key: found_media.data_url,
-            upload_id,
-        }))
+        let pool = self.pool.clone();
+        let media_cache = self.media_cache.clone();
+        let (mut progress, stream) = ProgressEmitter::channel(
+            migrations.len().try_into().unwrap_or_default(),
+            Self::PROGRESS_INTERVAL,
+        );
+
+        tokio::spawn(async move {
+            let mut migrated = 0;
+            let mut failed = 0;
+            for (media, file_service) in migrations {
+                let result = match file_service {
+                    Ok(file_service) => {
+                        Self::migrate_storage_key(
+                            &pool,
+                            &media_cache,
+                            &file_service,
+                            &media,
+                        )
+                        .await
+                    }
+                    Err(err) => Err(err),
+                };
+                match result {
+                    Ok(()) => migrated += 1,
+                    Err(err) => {
+                        tracing::log::warn!(
+                            "[MediaService.migrate_storage_keys]: {}: {}",
+                            media.media_id,
+                            err.message()
+                        );
+                        failed += 1;
+                    }
+                }
+
+                // migrated objects are not affected by stopping early
+                if progress.advance(media.media_id).await.is_err() {
+                    break;
+                }
+            }
+
+            tracing::log::info!(
+                "[MediaService.migrate_storage_keys]: migrated {}, failed {}",
+                migrated,
+                failed
+            );
+
+            let _ = progress
+                .send(MigrateStorageKeysResponse {
+                    migrated,
+                    failed,
+                    progress: None,
+                })
+                .await;
+        });
+
+        Ok(Response::new(stream))
     }
 
-    async fn put_multipart_chunk(
+    async fn resolve_permalink(
         &self,
-        request: Request<PutMultipartChunkRequest>,
-    ) -> Result<Response<PutMultipartChunkResponse>, Status> {
+        request: Request<ResolvePermalinkRequest>,
+    ) -> Result<Response<ResolvePermalinkResponse>, Status> {
         let user_id = get_user_id(request.metadata(), &self.verifier).await?;
 
-        let PutMultipartChunkRequest {
-            media_id,
-            upload_id,
-            part_number,
-            chunk,
-        } = request.into_inner();
-
-        let media_uuid = parse_uuid(&media_id, "media_id")?;
+        let ResolvePermalinkRequest { permalink } = request.into_inner();
 
-        let additional_size =
-            i64::try_from(chunk.len()).map_err(|_| Status::internal(""))?;
+        let (shop_uuid, media_uuid) = Self::parse_permalink(&permalink)?;
 
-        // user_id check is done implicitly in add_size
         let found_media =
-            Media::add_size(&self.pool, &media_uuid, &user_id, additional_size)
-                .await?;
-
-        if self.quota_service.check_quota(&user_id).await.is_err() {
-            self.file_service
-                .abort_multipart_upload(&found_media.data_url, &upload_id)
-                .await?;
-
-            Media::delete(&self.pool, &media_uuid, &user_id).await?;
+            match Media::get_for_owner(&self.pool, &media_uuid, &user_id)
+                .await?
+            {
+                Some(media) => Some(media),
+                None => {
+                    Media::get_accessible(
+                        &self.pool,
+                        &media_uuid,
+                        &user_id,
+                        self.subscription_grace_period,
+                    )
+                    .await?
+                }
+            }
+            .filter(|m| m.shop_id == shop_uuid)
+            .ok_or_else(|| {
+                StatusBuilder::not_found(&permalink)
+                    .with_resource_info("media", &permalink)
+            })?;
 
-            return Err(Status::aborted("quota reached"));
-        }
+        Self::check_servable(&found_media)?;
 
-        let etag = self
-            .file_service
-            .put_multipart_chunk(
-                &found_media.data_url,
-                &upload_id,
-                part_number,
-                &chunk,
+        let download_url = self
+            .get_file_service_for_media(&found_media)
+            .await?
+            .get_presigned_url(
+                &found_media.object_key,
+                &found_media.file_name,
+                self.presigned_url_expiry.download,
             )
             .await?;
 
-        Ok(Response::new(PutMultipartChunkResponse {
-            part: Some(Part { part_number, etag }),
+        Ok(Response::new(ResolvePermalinkResponse {
+            media_id: found_media.media_id.to_string(),
+            download_url,
         }))
     }
 
-    async fn complete_multipart_upload(
+    async fn find_similar_media(
         &self,
-        request: Request<CompleteMultipartUploadRequest>,
-    ) -> Result<Response<CompleteMultipartUploadResponse>, Status> {
+        request: Request<FindSimilarMediaRequest>,
+    ) -> Result<Response<FindSimilarMediaResponse>, Status> {
         let user_id = get_user_id(request.metadata(), &self.verifier).await?;
 
-        let CompleteMultipartUploadRequest {
+        let FindSimilarMediaRequest {
             media_id,
-            upload_id,
-            parts,
+            max_distance,
+            limit,
         } = request.into_inner();
 
         let media_uuid = parse_uuid(&media_id, "media_id")?;
+        let max_distance =
+            max_distance.unwrap_or(self.similar_media_max_distance);
+
+        if max_distance > Self::MAX_PERCEPTUAL_HASH_DISTANCE {
+            return Err(StatusBuilder::invalid_argument(format!(
+                "max_distance: at most {} allowed",
+                Self::MAX_PERCEPTUAL_HASH_DISTANCE
+            ))
+            .with_bad_request_field("max_distance", "too large")
+            .build());
+        }
 
         let found_media =
             Media::get_for_owner(&self.pool, &media_uuid, &user_id)
                 .await?
-                .ok_or(Status::not_found(&media_id))?;
+                .ok_or_else(|| {
+                    StatusBuilder::not_found(&media_id)
+                        .with_resource_info("media", &media_id)
+                })?;
 
-        let parts = parts
+        let limit = limit
+            .unwrap_or(Self::DEFAULT_SIMILAR_MEDIA_LIMIT)
+            .min(Self::MAX_SIMILAR_MEDIA_LIMIT);
+
+        let hash = Media::get_perceptual_hash(&self.read_pool, &media_uuid)
+            .await?
+            .ok_or_else(|| {
+                StatusBuilder::failed_precondition(
+                    "media has no perceptual hash, only images are hashed",
+                )
+                .with_resource_info("media", &media_id)
+                .build()
+            })?;
+
+        let hashed_medias = Media::list_with_perceptual_hash(
+            &self.read_pool,
+            &found_media.shop_id,
+            &user_id,
+            &media_uuid,
+            Self::MAX_SIMILAR_MEDIA_CANDIDATES,
+        )
+        .await?;
+
+        let mut similar_medias: Vec<_> = hashed_medias
             .into_iter()
-            .map(|p| {
-                CompletedPart::builder()
-                    .e_tag(p.etag)
-                    .part_number(p.part_number.try_into().unwrap())
-                    .build()
-            })
+            .map(|(m, other_hash)| (m, (hash ^ other_hash).count_ones()))
+            .filter(|(_, distance)| *distance <= max_distance)
             .collect();
+        similar_medias.sort_by_key(|(_, distance)| *distance);
+        similar_medias.truncate(usize::try_from(limit).unwrap_or(usize::MAX));
 
-        self.file_service
-            .complete_multipart_upload(&found_media.data_url, &upload_id, parts)
-            .await?;
-
-        Ok(Response::new(CompleteMultipartUploadResponse {}))
+        Ok(Response::new(FindSimilarMediaResponse {
+            similar_medias: similar_medias
+                .into_iter()
+                .map(|(m, distance)| SimilarMedia {
+                    media: Some(self.to_response(m, Some(&user_id))),
+                    distance,
+                })
+                .collect(),
+        }))
     }
 
-    async fn add_media_to_offer(
+    async fn export_booth_manifest(
         &self,
-        request: Request<AddMediaToOfferRequest>,
-    ) -> Result<Response<AddMediaToOfferResponse>, Status> {
+        request: Request<ExportBoothManifestRequest>,
+    ) -> Result<Response<Self::ExportBoothManifestStream>, Status> {
         let metadata = request.metadata().clone();
 
         let user_id = get_user_id(&metadata, &self.verifier).await?;
 
-        let AddMediaToOfferRequest {
-            media_id,
-            offer_id,
-            ordering,
-        } = request.into_inner();
+        let ExportBoothManifestRequest { shop_id } = request.into_inner();
 
-        let media_uuid = parse_uuid(&media_id, "media_id")?;
-        let offer_uuid = parse_uuid(&offer_id, "media_id")?;
+        let shop_uuid = parse_uuid(&shop_id, "shop_id")?;
 
-        // Check if user is owner of the offer
         self.commerce_service
-            .check_offer_and_owner(&offer_id, &user_id, &metadata)
+            .check_shop_and_owner(&shop_uuid.to_string(), &user_id, &metadata)
             .await?;
 
-        // Check if user is owner of media
-        Media::get_for_owner(&self.pool, &media_uuid, &user_id)
-            .await?
-            .ok_or(Status::not_found(media_id))?;
+        let medias =
+            Media::list_all_for_shop(&self.read_pool, &shop_uuid, &user_id)
+                .await?;
 
-        let ord = match ordering {
-            Some(o) => o,
-            None => {
-                let highest = MediaOffer::get_highest_ordering(
+        tracing::log::info!(
+            "[MediaService.export_booth_manifest]: exporting {} medias of shop {shop_id}",
+            medias.len()
+        );
+
+        // media not downloadable yet are exported without url and etag
+        let mut entries = Vec::with_capacity(medias.len());
+        for media in medias {
+            let file_service = match Self::check_servable(&media) {
+                Ok(()) => Some(self.get_file_service_for_media(&media).await?),
+                Err(_) => None,
+            };
+            entries.push((media, file_service));
+        }
+
+        let expires_in = self.presigned_url_expiry.owner_download;
+        let (mut progress, stream) = ProgressEmitter::channel(
+            entries.len().try_into().unwrap_or_default(),
+            Self::PROGRESS_INTERVAL,
+        );
+
+        tokio::spawn(async move {
+            for (media, file_service) in entries {
+                let mut entry = ExportBoothManifestResponse {
+                    media_id: media.media_id.to_string(),
+                    shop_id: media.shop_id.to_string(),
+                    name: media.name,
+                    file_name: media.file_name,
+                    content_type: media.content_type,
+                    size_bytes: media.size_bytes,
+                    created_at: media.created_at.timestamp(),
+                    updated_at: media.updated_at.timestamp(),
+                    offer_ids: media
+                        .offer_ids
+                        .unwrap_or_default()
+                        .iter()
+                        .map(Uuid::to_string)
+                        .collect(),
+                    custom_metadata: media.custom_metadata,
+                    download_url: None,
+                    etag: None,
+                    sha256: ObjectKeyStrategy::content_hash(&media.object_key)
+                        .map(String::from),
+                    progress: None,
+                };
+
+                let entry = match file_service {
+                    Some(file_service) => {
+                        let file = async {
+                            let download_url = file_service
+                                .get_presigned_url(
+                                    &media.object_key,
+                                    &entry.file_name,
+                                    expires_in,
+                                )
+                                .await?;
+                            let etag = file_service
+                                .get_etag(&media.object_key)
+                                .await?;
+                            Ok::<_, FileError>((download_url, etag))
+                        };
+                        match file.await {
+                            Ok((download_url, etag)) => {
+                                entry.download_url = Some(download_url);
+                                entry.etag = Some(etag);
+                                Ok(entry)
+                            }
+                            Err(err) => Err(Status::from(err)),
+                        }
+                    }
+                    None => Ok(entry),
+                };
+
+                let sent = match entry {
+                    Ok(entry) => progress.send(entry).await,
+                    Err(err) => return progress.fail(err).await,
+                };
+                if sent.is_err()
+                    || progress.advance(media.media_id).await.is_err()
+                {
+                    return;
+                }
+            }
+        });
+
+        Ok(Response::new(stream))
+    }
+
+    async fn get_upload_status(
+        &self,
+        request: Request<GetUploadStatusRequest>,
+    ) -> Result<Response<GetUploadStatusResponse>, Status> {
+        let user_id = get_user_id(request.metadata(), &self.verifier).await?;
+
+        let GetUploadStatusRequest {
+            media_id,
+            upload_id,
+        } = request.into_inner();
+
+        let (media_id, session) = match (media_id, upload_id) {
+            (Some(media_id), None) => {
+                let media_uuid = parse_uuid(&media_id, "media_id")?;
+                let session = MultipartUploadSession::get_latest_for_media(
                     &self.pool,
-                    &offer_uuid,
-                    &user_id,
+                    &media_uuid,
                 )
                 .await?;
-
-                highest + 1
+                (media_uuid, session)
             }
+            (None, Some(upload_id)) => {
+                let session =
+                    MultipartUploadSession::get(&self.pool, &upload_id)
+                        .await?
+                        .filter(|s| s.user_id == user_id)
+                        .ok_or_else(|| {
+                            StatusBuilder::not_found(&upload_id)
+                                .with_resource_info(
+                                    "multipart_upload",
+                                    &upload_id,
+                                )
+                        })?;
+                (session.media_id, Some(session))
+            }
+            _ => return Err(StatusBuilder::invalid_argument(
+                "media_id: exactly one of media_id and upload_id must be set",
+            )
+            .with_bad_request_field(
+                "media_id",
+                "exactly one of media_id and upload_id must be set",
+            )
+            .build()),
         };
 
-        MediaOffer::create(&self.pool, &media_uuid, &offer_uuid, &user_id, ord)
-            .await?;
+        let found_media = Media::get_for_owner(&self.pool, &media_id, &user_id)
+            .await?
+            .ok_or_else(|| {
+                StatusBuilder::not_found(media_id.to_string())
+                    .with_resource_info("media", media_id.to_string())
+            })?;
+
+        let (state, error_reason) = match &session {
+            Some(session) => {
+                let part_count =
+                    MultipartPart::count(&self.pool, &session.upload_id)
+                        .await?;
+                (
+                    Self::multipart_upload_state(part_count),
+                    session.last_error.clone(),
+                )
+            }
+            None => Self::upload_state(&found_media),
+        };
 
-        Ok(Response::new(AddMediaToOfferResponse {}))
+        Ok(Response::new(GetUploadStatusResponse {
+            media_id: media_id.to_string(),
+            state: state.into(),
+            error_reason,
+            error_count: session
+                .as_ref()
+                .map(|s| s.error_count.try_into().unwrap_or_default())
+                .unwrap_or_default(),
+            upload_id: session.map(|s| s.upload_id),
+        }))
     }
 
-    async fn update_media_offer_ordering(
+    async fn import_media_from_url(
         &self,
-        request: Request<UpdateMediaOfferOrderingRequest>,
-    ) -> Result<Response<UpdateMediaOfferOrderingResponse>, Status> {
-        let user_id = get_user_id(request.metadata(), &self.verifier).await?;
+        request: Request<ImportMediaFromUrlRequest>,
+    ) -> Result<Response<ImportMediaFromUrlResponse>, Status> {
+        let metadata = request.metadata().clone();
 
-        let UpdateMediaOfferOrderingRequest {
-            media_id,
+        let user_id = get_user_id(&metadata, &self.verifier).await?;
+
+        let ImportMediaFromUrlRequest {
+            shop_id,
+            name,
+            url,
+            file_name,
             offer_id,
-            ordering,
+            custom_metadata,
         } = request.into_inner();
 
-        let media_id = parse_uuid(&media_id, "media_id")?;
-        let offer_id = parse_uuid(&offer_id, "offer_id")?;
+        let shop_uuid = parse_uuid(&shop_id, "shop_id")?;
 
-        let found_media_offer =
-            MediaOffer::get(&self.pool, &media_id, &offer_id)
-                .await?
-                .ok_or_else(|| Status::not_found(""))?;
+        // only owners can make the server fetch anything
+        self.commerce_service
+            .check_shop_and_owner(&shop_uuid.to_string(), &user_id, &metadata)
+            .await?;
 
-        let old_ordering = found_media_offer.ordering;
+        let (content_type, data) = self
+            .remote_fetch
+            .get_file(&url, self.max_decoding_message_size)
+            .await?;
 
-        let mut found_media_offers =
-            MediaOffer::list(&self.pool, &user_id, &offer_id).await?;
+        let mut create_request = Request::new(CreateMediaRequest {
+            shop_id,
+            name,
+            file: Some(MediaUpload { content_type, data }),
+            file_name,
+            offer_id,
+            custom_metadata,
+        });
+        *create_request.metadata_mut() = metadata;
 
-        // update media_offer with new_ordering
-        MediaOffer::update_ordering(
-            &self.pool, &media_id, &offer_id, &user_id, ordering,
-        )
-        .await?;
+        let CreateMediaResponse { media } =
+            self.create_media(create_request).await?.into_inner();
 
-        match old_ordering.cmp(&ordering) {
-            Ordering::Less => {
-                // decrement [old_ordering + 1, new_ordering] by 1
-                found_media_offers.retain(|m| {
-                    m.ordering >= (old_ordering + 1) && m.ordering <= ordering
-                });
-                for m in found_media_offers {
-                    MediaOffer::update_ordering(
-                        &self.pool,
-                        &m.media_id,
-                        &m.offer_id,
-                        &user_id,
-                        m.ordering - 1,
-                    )
-                    .await?;
-                }
-            }
-            Ordering::Greater => {
-                // increment [new_ordering, old_ordering -1] by 1
-                found_media_offers.retain(|m| {
-                    m.ordering >= ordering && m.ordering <= (old_ordering - 1)
-                });
-                for m in found_media_offers {
-                    MediaOffer::update_ordering(
-                        &self.pool,
-                        &m.media_id,
-                        &m.offer_id,
-                        &user_id,
-                        m.ordering + 1,
-                    )
-                    .await?;
-                }
-            }
-            Ordering::Equal => {}
-        }
+        Ok(Response::new(ImportMediaFromUrlResponse { media }))
+    }
+}
 
-        Ok(Response::new(UpdateMediaOfferOrderingResponse {}))
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn max_list_page_size_fits_message_size() {
+        assert_eq!(MediaService::max_list_page_size(4 * 1024 * 1024), 85);
+        assert_eq!(MediaService::max_list_page_size(usize::MAX), u32::MAX);
     }
 
-    async fn remove_media_from_offer(
-        &self,
-        request: Request<RemoveMediaFromOfferRequest>,
-    ) -> Result<Response<RemoveMediaFromOfferResponse>, Status> {
-        let user_id = get_user_id(request.metadata(), &self.verifier).await?;
+    #[test]
+    fn custom_metadata_is_only_cleared_explicitly() {
+        let custom_metadata =
+            HashMap::from([("color".to_string(), "red".to_string())]);
 
-        let RemoveMediaFromOfferRequest { media_id, offer_id } =
-            request.into_inner();
+        assert_eq!(
+            MediaService::custom_metadata_update(HashMap::new(), false)
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            MediaService::custom_metadata_update(HashMap::new(), true).unwrap(),
+            Some(HashMap::new())
+        );
+        assert_eq!(
+            MediaService::custom_metadata_update(
+                custom_metadata.clone(),
+                false
+            )
+            .unwrap(),
+            Some(custom_metadata.clone())
+        );
+        assert_eq!(
+            MediaService::custom_metadata_update(custom_metadata, true)
+                .unwrap_err()
+                .code(),
+            tonic::Code::InvalidArgument
+        );
+    }
 
-        let media_id = parse_uuid(&media_id, "media_id")?;
-        let offer_id = parse_uuid(&offer_id, "offer_id")?;
+    #[test]
+    fn multipart_upload_in_progress_is_not_done() {
+        assert_eq!(
+            MediaService::multipart_upload_state(0),
+            UploadState::PendingUpload
+        );
+        assert_eq!(
+            MediaService::multipart_upload_state(3),
+            UploadState::Uploading
+        );
+    }
 
-        MediaOffer::delete(&self.pool, &media_id, &offer_id, &user_id).await?;
+    #[test]
+    fn max_list_page_size_is_at_least_one() {
+        assert_eq!(MediaService::max_list_page_size(0), 1);
+        assert_eq!(MediaService::max_list_page_size(1024), 1);
+    }
 
-        Ok(Response::new(RemoveMediaFromOfferResponse {}))
+    #[test]
+    fn progress_updates_carry_no_results() {
+        let progress = OperationProgress {
+            processed: 1,
+            total: 2,
+            current_item: Some(String::from("item")),
+        };
+
+        let update = ExportBoothManifestResponse::from(progress.clone());
+        assert!(update.media_id.is_empty());
+        assert_eq!(update.progress, Some(progress.clone()));
+
+        let update = BatchRemoveMediaFromOfferResponse::from(progress.clone());
+        assert!(update.results.is_empty());
+        assert_eq!(update.progress, Some(progress.clone()));
+
+        let update = MigrateStorageKeysResponse::from(progress.clone());
+        assert_eq!((update.migrated, update.failed), (0, 0));
+        assert_eq!(update.progress, Some(progress));
     }
 }
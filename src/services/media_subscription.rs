@@ -1,6 +1,7 @@
 use chrono::{DateTime, Utc};
 use deadpool_postgres::Pool;
 use jwtk::jwk::RemoteJwksVerifier;
+use tonic::metadata::MetadataMap;
 use tonic::{async_trait, Request, Response, Status};
 
 use crate::api::sited_io::media::v1::media_subscription_service_server::{
@@ -9,35 +10,45 @@ use crate::api::sited_io::media::v1::media_subscription_service_server::{
 use crate::api::sited_io::media::v1::{
     CancelMediaSubscriptionRequest, CancelMediaSubscriptionResponse,
     GetMediaSubscriptionRequest, GetMediaSubscriptionResponse,
+    GetShopMediaSubscriptionRequest, GetShopMediaSubscriptionResponse,
     ListMediaSubscriptionsRequest, ListMediaSubscriptionsResponse,
     MediaSubscriptionResponse, PutMediaSubscriptionRequest,
     PutMediaSubscriptionResponse, ResumeMediaSubscriptionRequest,
-    ResumeMediaSubscriptionResponse,
+    ResumeMediaSubscriptionResponse, RevokeMediaSubscriptionRequest,
+    RevokeMediaSubscriptionResponse, SubscriptionStatus,
+    UpdateMediaSubscriptionNotesRequest, UpdateMediaSubscriptionNotesResponse,
 };
-use crate::auth::{get_user_id, verify_service_user};
+use crate::auth::{get_user_id, verify_admin_user, verify_service_user};
 use crate::model::MediaSubscription;
 use crate::payment::PaymentService;
+use crate::{Commerce, StatusBuilder};
 
 use super::{
     get_limit_offset_from_pagination, parse_optional_uuid, parse_uuid,
+    set_total_elements,
 };
 
 pub struct MediaSubscriptionService {
     pool: Pool,
     verifier: RemoteJwksVerifier,
     payment_service: PaymentService,
+    commerce_service: Box<dyn Commerce>,
 }
 
 impl MediaSubscriptionService {
+    const MAX_SELLER_NOTES_CHARS: usize = 2000;
+
     fn new(
         pool: Pool,
         verifier: RemoteJwksVerifier,
         payment_service: PaymentService,
+        commerce_service: Box<dyn Commerce>,
     ) -> Self {
         Self {
             pool,
             verifier,
             payment_service,
+            commerce_service,
         }
     }
 
@@ -45,11 +56,13 @@ impl MediaSubscriptionService {
         pool: Pool,
         verifier: RemoteJwksVerifier,
         payment_service: PaymentService,
+        commerce_service: impl Commerce + 'static,
     ) -> MediaSubscriptionServiceServer<Self> {
         MediaSubscriptionServiceServer::new(Self::new(
             pool,
             verifier,
             payment_service,
+            Box::new(commerce_service),
         ))
     }
 
@@ -72,7 +85,7 @@ impl MediaSubscriptionService {
                 media_subscription.current_period_end.timestamp(),
             )
             .unwrap(),
-            subscription_status: media_subscription.subscription_status,
+            subscription_status: media_subscription.subscription_status.into(),
             payed_at: u64::try_from(media_subscription.payed_at.timestamp())
                 .unwrap(),
             payed_until: u64::try_from(
@@ -86,15 +99,147 @@ impl MediaSubscriptionService {
             cancel_at: media_subscription
                 .cancel_at
                 .map(|c| u64::try_from(c.timestamp()).unwrap()),
+            // only returned to sellers, see to_seller_response
+            seller_notes: None,
+        }
+    }
+
+    /// Response including the notes of the seller
+    fn to_seller_response(
+        &self,
+        media_subscription: MediaSubscription,
+    ) -> MediaSubscriptionResponse {
+        let seller_notes = media_subscription.seller_notes.clone();
+        MediaSubscriptionResponse {
+            seller_notes,
+            ..self.to_response(media_subscription)
+        }
+    }
+
+    /// Returns the media subscription if it belongs to a shop of `user_id`
+    async fn get_for_seller(
+        pool: &Pool,
+        commerce_service: &dyn Commerce,
+        media_subscription_id: &String,
+        user_id: &String,
+        metadata: &MetadataMap,
+    ) -> Result<MediaSubscription, Status> {
+        let media_subscription_uuid =
+            parse_uuid(media_subscription_id, "media_subscription_id")?;
+
+        let found_media_subscription =
+            MediaSubscription::get_by_id(pool, &media_subscription_uuid)
+                .await?
+                .ok_or_else(|| {
+                    StatusBuilder::not_found("").with_resource_info(
+                        "media_subscription",
+                        media_subscription_id,
+                    )
+                })?;
+
+        commerce_service
+            .check_shop_and_owner(
+                &found_media_subscription.shop_id.to_string(),
+                user_id,
+                metadata,
+            )
+            .await?;
+
+        Ok(found_media_subscription)
+    }
+
+    /// Latest accepted timestamp (9999-12-31T23:59:59Z). Anything above is
+    /// most likely given in milliseconds instead of seconds.
+    const MAX_TIMESTAMP_SECS: u64 = 253_402_300_799;
+
+    /// Converts unix timestamp in seconds to `DateTime<Utc>`
+    fn timestamp_to_datetime(
+        timestamp: u64,
+        field: &str,
+    ) -> Result<DateTime<Utc>, Status> {
+        if timestamp > Self::MAX_TIMESTAMP_SECS {
+            return Err(StatusBuilder::invalid_argument(format!(
+                "field {field} must be a unix timestamp in seconds"
+            ))
+            .with_bad_request_field(
+                field,
+                "must be a unix timestamp in seconds",
+            )
+            .build());
+        }
+
+        i64::try_from(timestamp)
+            .ok()
+            .and_then(|t| DateTime::<Utc>::from_timestamp(t, 0))
+            .ok_or_else(|| {
+                StatusBuilder::invalid_argument(format!(
+                    "field {field} is not a valid timestamp"
+                ))
+                .with_bad_request_field(field, "not a valid timestamp")
+                .build()
+            })
+    }
+
+    fn optional_timestamp_to_datetime(
+        timestamp: Option<u64>,
+        field: &str,
+    ) -> Result<Option<DateTime<Utc>>, Status> {
+        timestamp
+            .map(|t| Self::timestamp_to_datetime(t, field))
+            .transpose()
+    }
+
+    fn parse_subscription_status(
+        subscription_status: i32,
+    ) -> Result<SubscriptionStatus, Status> {
+        match SubscriptionStatus::try_from(subscription_status) {
+            Ok(SubscriptionStatus::Unspecified) => {
+                Err(StatusBuilder::invalid_argument(
+                    "field subscription_status must be specified",
+                )
+                .with_bad_request_field(
+                    "subscription_status",
+                    "must be specified",
+                )
+                .build())
+            }
+            // revoking also records who revoked and why
+            Ok(SubscriptionStatus::Revoked) => {
+                Err(StatusBuilder::invalid_argument(
+                    "field subscription_status cannot be revoked, use RevokeMediaSubscription",
+                )
+                .with_bad_request_field(
+                    "subscription_status",
+                    "revoked is set by RevokeMediaSubscription only",
+                )
+                .build())
+            }
+            Ok(status) => Ok(status),
+            Err(_) => Err(StatusBuilder::invalid_argument(format!(
+                "field subscription_status has unknown value {subscription_status}"
+            ))
+            .with_bad_request_field("subscription_status", "unknown value")
+            .build()),
         }
     }
 
-    fn timestamp_to_datetime(timestamp: u64) -> Result<DateTime<Utc>, Status> {
-        if let Ok(timestamp) = i64::try_from(timestamp) {
-            DateTime::<Utc>::from_timestamp(timestamp, 0)
-                .ok_or_else(|| Status::invalid_argument(timestamp.to_string()))
+    fn validate_period(
+        start: &DateTime<Utc>,
+        start_field: &str,
+        end: &DateTime<Utc>,
+        end_field: &str,
+    ) -> Result<(), Status> {
+        if end < start {
+            Err(StatusBuilder::invalid_argument(format!(
+                "field {end_field} must not be before {start_field}"
+            ))
+            .with_bad_request_field(
+                end_field,
+                format!("must not be before {start_field}"),
+            )
+            .build())
         } else {
-            Err(Status::invalid_argument(timestamp.to_string()))
+            Ok(())
         }
     }
 }
@@ -128,23 +273,61 @@ impl media_subscription_service_server::MediaSubscriptionService
             cancel_at,
         } = request.into_inner();
 
-        MediaSubscription::put(
+        let current_period_start = Self::timestamp_to_datetime(
+            current_period_start,
+            "current_period_start",
+        )?;
+        let current_period_end = Self::timestamp_to_datetime(
+            current_period_end,
+            "current_period_end",
+        )?;
+        let payed_at = Self::timestamp_to_datetime(payed_at, "payed_at")?;
+        let payed_until =
+            Self::timestamp_to_datetime(payed_until, "payed_until")?;
+        let canceled_at =
+            Self::optional_timestamp_to_datetime(canceled_at, "canceled_at")?;
+        let cancel_at =
+            Self::optional_timestamp_to_datetime(cancel_at, "cancel_at")?;
+
+        let subscription_status =
+            Self::parse_subscription_status(subscription_status)?;
+
+        Self::validate_period(
+            &current_period_start,
+            "current_period_start",
+            &current_period_end,
+            "current_period_end",
+        )?;
+        Self::validate_period(
+            &payed_at,
+            "payed_at",
+            &payed_until,
+            "payed_until",
+        )?;
+
+        let put_media_subscription = MediaSubscription::put(
             &self.pool,
             &parse_uuid(&media_subscription_id, "media_subscription_id")?,
             &buyer_user_id,
             &parse_uuid(&offer_id, "offer_id")?,
             &parse_uuid(&shop_id, "shop_id")?,
-            &Self::timestamp_to_datetime(current_period_start)?,
-            &Self::timestamp_to_datetime(current_period_end)?,
-            &subscription_status,
-            &Self::timestamp_to_datetime(payed_at)?,
-            &Self::timestamp_to_datetime(payed_until)?,
+            &current_period_start,
+            &current_period_end,
+            subscription_status,
+            &payed_at,
+            &payed_until,
             stripe_subscription_id,
-            canceled_at.and_then(|c| Self::timestamp_to_datetime(c).ok()),
-            cancel_at.and_then(|c| Self::timestamp_to_datetime(c).ok()),
+            canceled_at,
+            cancel_at,
         )
         .await?;
 
+        if put_media_subscription.is_none() {
+            tracing::log::info!(
+                "[MediaSubscriptionService.put_media_subscription]: ignored update of revoked media subscription {media_subscription_id}"
+            );
+        }
+
         Ok(Response::new(PutMediaSubscriptionResponse {}))
     }
 
@@ -172,7 +355,15 @@ impl media_subscription_service_server::MediaSubscriptionService
             offer_uuid,
         )
         .await?
-        .ok_or(Status::not_found(""))?;
+        .ok_or_else(|| {
+            StatusBuilder::not_found("").with_resource_info(
+                "media_subscription",
+                media_subscription_uuid
+                    .or(offer_uuid)
+                    .map(|id| id.to_string())
+                    .unwrap_or_default(),
+            )
+        })?;
 
         Ok(Response::new(GetMediaSubscriptionResponse {
             media_subscription: Some(
@@ -208,9 +399,7 @@ impl media_subscription_service_server::MediaSubscriptionService
         )
         .await?;
 
-        pagination.total_elements = count.try_into().map_err(|_| {
-            Status::internal("Could not convert 'count' from i64 to u32")
-        })?;
+        set_total_elements(&mut pagination, count)?;
 
         Ok(Response::new(ListMediaSubscriptionsResponse {
             media_subscriptions: found_media_subscriptions
@@ -241,7 +430,12 @@ impl media_subscription_service_server::MediaSubscriptionService
             None,
         )
         .await?
-        .ok_or(Status::not_found(""))?;
+        .ok_or_else(|| {
+            StatusBuilder::not_found("").with_resource_info(
+                "media_subscription",
+                &media_subscription_id,
+            )
+        })?;
 
         if let Some(stripe_subscription_id) =
             found_media_subscritpion.stripe_subscription_id
@@ -277,10 +471,16 @@ impl media_subscription_service_server::MediaSubscriptionService
             None,
         )
         .await?
-        .ok_or(Status::not_found(format!(
-            "media_subscription_id {}",
-            media_subscription_id
-        )))?;
+        .ok_or_else(|| {
+            StatusBuilder::not_found(format!(
+                "media_subscription_id {}",
+                media_subscription_id
+            ))
+            .with_resource_info(
+                "media_subscription",
+                media_subscription_id.to_string(),
+            )
+        })?;
 
         if let Some(stripe_subscription_id) =
             found_media_subscritpion.stripe_subscription_id
@@ -295,4 +495,266 @@ impl media_subscription_service_server::MediaSubscriptionService
 
         Ok(Response::new(ResumeMediaSubscriptionResponse {}))
     }
+
+    async fn revoke_media_subscription(
+        &self,
+        request: Request<RevokeMediaSubscriptionRequest>,
+    ) -> Result<Response<RevokeMediaSubscriptionResponse>, Status> {
+        let admin_user_id =
+            verify_admin_user(request.metadata(), &self.verifier).await?;
+
+        let RevokeMediaSubscriptionRequest {
+            media_subscription_id,
+            reason,
+        } = request.into_inner();
+
+        let media_subscription_uuid =
+            parse_uuid(&media_subscription_id, "media_subscription_id")?;
+
+        if reason.trim().is_empty() {
+            return Err(StatusBuilder::invalid_argument(
+                "field reason must not be empty",
+            )
+            .with_bad_request_field("reason", "must not be empty")
+            .build());
+        }
+
+        let revoked_media_subscription = MediaSubscription::revoke(
+            &self.pool,
+            &media_subscription_uuid,
+            &admin_user_id,
+            &reason,
+        )
+        .await?
+        .ok_or_else(|| {
+            StatusBuilder::not_found("").with_resource_info(
+                "media_subscription",
+                &media_subscription_id,
+            )
+        })?;
+
+        tracing::log::warn!(
+            "[MediaSubscriptionService.revoke_media_subscription]: {admin_user_id} revoked media subscription {media_subscription_id} of {}: {reason}",
+            revoked_media_subscription.buyer_user_id
+        );
+
+        Ok(Response::new(RevokeMediaSubscriptionResponse {
+            media_subscription: Some(
+                self.to_response(revoked_media_subscription),
+            ),
+        }))
+    }
+
+    async fn update_media_subscription_notes(
+        &self,
+        request: Request<UpdateMediaSubscriptionNotesRequest>,
+    ) -> Result<Response<UpdateMediaSubscriptionNotesResponse>, Status> {
+        let metadata = request.metadata().clone();
+
+        let user_id = get_user_id(&metadata, &self.verifier).await?;
+
+        let UpdateMediaSubscriptionNotesRequest {
+            media_subscription_id,
+            notes,
+        } = request.into_inner();
+
+        if notes
+            .as_ref()
+            .is_some_and(|n| n.chars().count() > Self::MAX_SELLER_NOTES_CHARS)
+        {
+            return Err(StatusBuilder::invalid_argument(format!(
+                "field notes must not exceed {} characters",
+                Self::MAX_SELLER_NOTES_CHARS
+            ))
+            .with_bad_request_field(
+                "notes",
+                format!(
+                    "must not exceed {} characters",
+                    Self::MAX_SELLER_NOTES_CHARS
+                ),
+            )
+            .build());
+        }
+
+        let found_media_subscription = Self::get_for_seller(
+            &self.pool,
+            self.commerce_service.as_ref(),
+            &media_subscription_id,
+            &user_id,
+            &metadata,
+        )
+        .await?;
+
+        let updated_media_subscription = MediaSubscription::set_seller_notes(
+            &self.pool,
+            &found_media_subscription.media_subscription_id,
+            notes,
+        )
+        .await?
+        .ok_or_else(|| {
+            StatusBuilder::not_found("").with_resource_info(
+                "media_subscription",
+                &media_subscription_id,
+            )
+        })?;
+
+        Ok(Response::new(UpdateMediaSubscriptionNotesResponse {
+            media_subscription: Some(
+                self.to_seller_response(updated_media_subscription),
+            ),
+        }))
+    }
+
+    async fn get_shop_media_subscription(
+        &self,
+        request: Request<GetShopMediaSubscriptionRequest>,
+    ) -> Result<Response<GetShopMediaSubscriptionResponse>, Status> {
+        let metadata = request.metadata().clone();
+
+        let user_id = get_user_id(&metadata, &self.verifier).await?;
+
+        let GetShopMediaSubscriptionRequest {
+            media_subscription_id,
+        } = request.into_inner();
+
+        let found_media_subscription = Self::get_for_seller(
+            &self.pool,
+            self.commerce_service.as_ref(),
+            &media_subscription_id,
+            &user_id,
+            &metadata,
+        )
+        .await?;
+
+        Ok(Response::new(GetShopMediaSubscriptionResponse {
+            media_subscription: Some(
+                self.to_seller_response(found_media_subscription),
+            ),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use tonic::Code;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::db::test_db::TestDb;
+    use crate::services::MockCommerceService;
+
+    #[test]
+    fn timestamps_must_be_seconds() {
+        let timestamp = MediaSubscriptionService::timestamp_to_datetime(
+            1_700_000_000,
+            "payed_at",
+        )
+        .unwrap();
+        assert_eq!(timestamp.timestamp(), 1_700_000_000);
+
+        let err = MediaSubscriptionService::timestamp_to_datetime(
+            1_700_000_000_000,
+            "payed_at",
+        )
+        .unwrap_err();
+        assert_eq!(err.code(), Code::InvalidArgument);
+    }
+
+    #[test]
+    fn revoked_status_cannot_be_put() {
+        let err = MediaSubscriptionService::parse_subscription_status(
+            SubscriptionStatus::Revoked.into(),
+        )
+        .unwrap_err();
+        assert_eq!(err.code(), Code::InvalidArgument);
+
+        assert_eq!(
+            MediaSubscriptionService::parse_subscription_status(
+                SubscriptionStatus::Canceled.into()
+            )
+            .unwrap(),
+            SubscriptionStatus::Canceled
+        );
+    }
+
+    #[test]
+    fn period_must_not_end_before_start() {
+        let start = Utc::now();
+        let validate = |end| {
+            MediaSubscriptionService::validate_period(
+                &start,
+                "current_period_start",
+                &end,
+                "current_period_end",
+            )
+        };
+
+        assert!(validate(start).is_ok());
+        assert!(validate(start + Duration::days(30)).is_ok());
+        assert_eq!(
+            validate(start - Duration::seconds(1)).unwrap_err().code(),
+            Code::InvalidArgument
+        );
+    }
+
+    #[tokio::test]
+    async fn seller_reads_notes_of_own_shop_only() {
+        let db = TestDb::start().await;
+        let shop_id = Uuid::new_v4();
+        let media_subscription_id = Uuid::new_v4();
+        let now = Utc::now();
+
+        MediaSubscription::put(
+            &db.pool,
+            &media_subscription_id,
+            &"buyer".to_string(),
+            &Uuid::new_v4(),
+            &shop_id,
+            &now,
+            &(now + Duration::days(30)),
+            SubscriptionStatus::Active,
+            &now,
+            &(now + Duration::days(30)),
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        MediaSubscription::set_seller_notes(
+            &db.pool,
+            &media_subscription_id,
+            Some("prefers email".to_string()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        let commerce_service = MockCommerceService::builder()
+            .allow_shop(shop_id, "seller")
+            .build();
+        let get_for_seller = |user_id: &str| {
+            let user_id = user_id.to_string();
+            let pool = db.pool.clone();
+            let commerce_service = commerce_service.clone();
+            async move {
+                MediaSubscriptionService::get_for_seller(
+                    &pool,
+                    &commerce_service,
+                    &media_subscription_id.to_string(),
+                    &user_id,
+                    &MetadataMap::new(),
+                )
+                .await
+            }
+        };
+
+        let found = get_for_seller("seller").await.unwrap();
+        assert_eq!(found.seller_notes.as_deref(), Some("prefers email"));
+
+        let err = get_for_seller("buyer").await.unwrap_err();
+        assert_eq!(err.code(), Code::NotFound);
+    }
 }
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use tonic::metadata::MetadataMap;
+use tonic::{async_trait, Status};
+use uuid::Uuid;
+
+use crate::Commerce;
+
+/// `Commerce` implementation answering ownership checks from a fixed set of
+/// shops and offers instead of calling the commerce service.
+#[derive(Debug, Clone, Default)]
+pub struct MockCommerceService {
+    shop_owners: HashMap<String, String>,
+    offer_owners: HashMap<String, String>,
+    offer_shops: HashMap<String, String>,
+}
+
+impl MockCommerceService {
+    pub fn builder() -> MockCommerceServiceBuilder {
+        MockCommerceServiceBuilder::default()
+    }
+
+    fn check_owner(
+        owners: &HashMap<String, String>,
+        id: &String,
+        user_id: &String,
+        kind: &str,
+    ) -> Result<(), Status> {
+        match owners.get(id) {
+            Some(owner) if owner == user_id => Ok(()),
+            Some(_) => Err(Status::not_found(format!(
+                "user is not owner of this {kind}"
+            ))),
+            None => Err(Status::permission_denied(format!(
+                "{kind} {id} is not configured"
+            ))),
+        }
+    }
+}
+
+#[async_trait]
+impl Commerce for MockCommerceService {
+    async fn check_shop_and_owner(
+        &self,
+        shop_id: &String,
+        user_id: &String,
+        _metadata: &MetadataMap,
+    ) -> Result<(), Status> {
+        Self::check_owner(&self.shop_owners, shop_id, user_id, "shop")
+    }
+
+    async fn check_offer_and_owner(
+        &self,
+        offer_id: &String,
+        user_id: &String,
+        _metadata: &MetadataMap,
+    ) -> Result<(), Status> {
+        Self::check_owner(&self.offer_owners, offer_id, user_id, "offer")
+    }
+
+    async fn check_offer_of_shop_and_owner(
+        &self,
+        offer_id: &String,
+        shop_id: &String,
+        user_id: &String,
+        _metadata: &MetadataMap,
+    ) -> Result<(), Status> {
+        Self::check_owner(&self.offer_owners, offer_id, user_id, "offer")?;
+
+        if self.offer_shops.get(offer_id) == Some(shop_id) {
+            Ok(())
+        } else {
+            Err(Status::invalid_argument(
+                "offer does not belong to this shop",
+            ))
+        }
+    }
+
+    /// Configured shops are active
+    async fn is_shop_active(&self, shop_id: &String) -> Result<bool, Status> {
+        Ok(self.shop_owners.contains_key(shop_id))
+    }
+
+    async fn check_health(&self) -> Result<(), Status> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MockCommerceServiceBuilder {
+    shop_owners: HashMap<String, String>,
+    offer_owners: HashMap<String, String>,
+    offer_shops: HashMap<String, String>,
+}
+
+impl MockCommerceServiceBuilder {
+    pub fn allow_shop(mut self, shop_id: Uuid, user_id: &str) -> Self {
+        self.shop_owners
+            .insert(shop_id.to_string(), user_id.to_owned());
+        self
+    }
+
+    pub fn allow_offer(mut self, offer_id: Uuid, user_id: &str) -> Self {
+        self.offer_owners
+            .insert(offer_id.to_string(), user_id.to_owned());
+        self
+    }
+
+    /// Offers allowed this way belong to `shop_id`
+    pub fn allow_offer_of_shop(
+        mut self,
+        offer_id: Uuid,
+        shop_id: Uuid,
+        user_id: &str,
+    ) -> Self {
+        self.offer_shops
+            .insert(offer_id.to_string(), shop_id.to_string());
+        self.allow_offer(offer_id, user_id)
+    }
+
+    pub fn build(self) -> MockCommerceService {
+        MockCommerceService {
+            shop_owners: self.shop_owners,
+            offer_owners: self.offer_owners,
+            offer_shops: self.offer_shops,
+        }
+    }
+}
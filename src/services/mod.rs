@@ -1,8 +1,12 @@
 mod media;
 mod media_subscription;
+#[cfg(test)]
+mod mock;
 
-pub use self::media::MediaService;
+pub use self::media::{ClosedShopListing, MediaService};
 pub use media_subscription::MediaSubscriptionService;
+#[cfg(test)]
+pub use mock::{MockCommerceService, MockCommerceServiceBuilder};
 
 use tonic::Status;
 use uuid::Uuid;
@@ -10,12 +14,25 @@ use uuid::Uuid;
 use crate::api::sited_io::pagination::v1::{
     PaginationRequest, PaginationResponse,
 };
+use crate::StatusBuilder;
 
 fn uuid_err_to_grpc_status(field: &str) -> Status {
-    Status::invalid_argument(format!("field {field} is not a valid UUID v4"))
+    StatusBuilder::invalid_argument(format!(
+        "field {field} is not a valid UUID v4"
+    ))
+    .with_bad_request_field(field, "not a valid UUID v4")
+    .build()
 }
 
+/// Accepts hyphenated and simple UUIDs in any case. Braced and URN forms are
+/// rejected. Ids passed on or returned must be formatted from the parsed
+/// `Uuid`, which is always lowercase hyphenated.
 fn parse_uuid(uuid_string: &str, field: &str) -> Result<Uuid, Status> {
+    // hyphenated or simple
+    if !matches!(uuid_string.len(), 36 | 32) {
+        return Err(uuid_err_to_grpc_status(field));
+    }
+
     uuid_string
         .parse()
         .map_err(|_| uuid_err_to_grpc_status(field))
@@ -33,29 +50,129 @@ fn parse_optional_uuid(
     }
 }
 
-/// Returns limit and offset from PaginationRequest
+const DEFAULT_PAGE_SIZE: u32 = 10;
+
+/// Returns limit and offset from PaginationRequest. A `size` of 0 means the
+/// field was not set and falls back to the default page size.
 fn get_limit_offset_from_pagination(
     request: Option<PaginationRequest>,
 ) -> Result<(u32, u32, PaginationResponse), Status> {
-    let mut limit = 10;
+    let mut limit = DEFAULT_PAGE_SIZE;
     let mut offset = 0;
     let mut pagination = PaginationResponse {
         page: 1,
         size: limit,
         total_elements: 0,
+        total_pages: 0,
+        has_next_page: false,
+        has_prev_page: false,
     };
 
     if let Some(request) = request {
         if request.page < 1 {
-            return Err(Status::invalid_argument(
+            return Err(StatusBuilder::invalid_argument(
                 "pagination.page less than 1",
-            ));
+            )
+            .with_bad_request_field("pagination.page", "less than 1")
+            .build());
+        }
+        if request.size > 0 {
+            limit = request.size;
         }
-        limit = request.size;
-        offset = (request.page - 1) * request.size;
+        offset = (request.page - 1).checked_mul(limit).ok_or_else(|| {
+            StatusBuilder::invalid_argument("pagination.page too large")
+                .with_bad_request_field("pagination.page", "too large")
+                .build()
+        })?;
         pagination.page = request.page;
-        pagination.size = request.size;
+        pagination.size = limit;
     }
 
     Ok((limit, offset, pagination))
 }
+
+/// Sets `total_elements` and the page counts derived from it
+fn set_total_elements(
+    pagination: &mut PaginationResponse,
+    count: i64,
+) -> Result<(), Status> {
+    pagination.total_elements = count.try_into().map_err(|_| {
+        Status::internal("Could not convert 'count' from i64 to u32")
+    })?;
+
+    let size = u64::from(pagination.size.max(1));
+    pagination.total_pages =
+        u64::from(pagination.total_elements).div_ceil(size);
+    pagination.has_next_page =
+        u64::from(pagination.page) < pagination.total_pages;
+    pagination.has_prev_page = pagination.page > 1;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic::Code;
+
+    use super::*;
+
+    #[test]
+    fn parsed_uuids_round_trip_canonically() {
+        let canonical = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+
+        for input in [
+            canonical,
+            "67E55044-10B1-426F-9247-BB680E5FE0C8",
+            "67e5504410b1426f9247bb680e5fe0c8",
+        ] {
+            let uuid = parse_uuid(input, "media_id").unwrap();
+            assert_eq!(uuid.to_string(), canonical);
+            assert_eq!(
+                parse_uuid(&uuid.to_string(), "media_id").unwrap(),
+                uuid
+            );
+        }
+    }
+
+    #[test]
+    fn braced_and_urn_uuids_are_rejected() {
+        for input in [
+            "{67e55044-10b1-426f-9247-bb680e5fe0c8}",
+            "urn:uuid:67e55044-10b1-426f-9247-bb680e5fe0c8",
+            "",
+        ] {
+            assert_eq!(
+                parse_uuid(input, "media_id").unwrap_err().code(),
+                Code::InvalidArgument
+            );
+        }
+    }
+
+    fn paginate(page: u32, size: u32) -> Result<(u32, u32), Status> {
+        get_limit_offset_from_pagination(Some(PaginationRequest { page, size }))
+            .map(|(limit, offset, _)| (limit, offset))
+    }
+
+    #[test]
+    fn page_zero_is_rejected() {
+        assert_eq!(paginate(0, 10).unwrap_err().code(), Code::InvalidArgument);
+    }
+
+    #[test]
+    fn huge_page_is_rejected() {
+        assert_eq!(
+            paginate(u32::MAX, 10).unwrap_err().code(),
+            Code::InvalidArgument
+        );
+        assert_eq!(paginate(3, 10).unwrap(), (10, 20));
+    }
+
+    #[test]
+    fn size_zero_uses_default_page_size() {
+        assert_eq!(paginate(2, 0).unwrap(), (DEFAULT_PAGE_SIZE, 10));
+        assert_eq!(
+            get_limit_offset_from_pagination(None).unwrap().0,
+            DEFAULT_PAGE_SIZE
+        );
+    }
+}
@@ -0,0 +1,172 @@
+use std::str::FromStr;
+
+/// Content types permitted for an upload path. Entries are either exact
+/// (`image/png`) or match a whole top-level type (`image/*`).
+#[derive(Debug, Clone)]
+pub struct ContentTypeAllowlist {
+    content_types: Vec<String>,
+}
+
+impl ContentTypeAllowlist {
+    /// Parses comma separated list like `image/*,application/pdf`
+    pub fn parse(content_types: &str) -> Self {
+        Self {
+            content_types: content_types
+                .split(',')
+                .map(|c| c.trim().to_lowercase())
+                .filter(|c| !c.is_empty())
+                .collect(),
+        }
+    }
+
+    pub fn is_allowed(&self, content_type: &str) -> bool {
+        let content_type = essence(content_type);
+
+        self.content_types.iter().any(|allowed| {
+            match allowed.strip_suffix("/*") {
+                Some(top_level) => content_type
+                    .split_once('/')
+                    .is_some_and(|(t, _)| t == top_level),
+                None => *allowed == content_type,
+            }
+        })
+    }
+}
+
+fn essence(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase()
+}
+
+/// How far the content type of a file replacing the file of a media may
+/// differ from the content type of the original file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ContentTypeReplacement {
+    /// Same content type, ignoring parameters like `charset`
+    Strict,
+    /// Same top-level type, e.g. `image/png` replaced by `image/jpeg`
+    #[default]
+    SameCategory,
+    Any,
+}
+
+impl FromStr for ContentTypeReplacement {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "strict" => Ok(Self::Strict),
+            "same_category" => Ok(Self::SameCategory),
+            "any" => Ok(Self::Any),
+            _ => Err(format!("unknown content type replacement '{s}'")),
+        }
+    }
+}
+
+impl ContentTypeReplacement {
+    pub fn is_allowed(&self, original: &str, replacement: &str) -> bool {
+        let (original, replacement) = (essence(original), essence(replacement));
+
+        match self {
+            Self::Strict => original == replacement,
+            Self::SameCategory => {
+                original.split('/').next() == replacement.split('/').next()
+            }
+            Self::Any => true,
+        }
+    }
+}
+
+/// S3 rejects multipart uploads with more parts, so larger values for
+/// `UploadPolicy::max_multipart_parts` have no effect
+pub const S3_MAX_MULTIPART_PARTS: u32 = 10_000;
+
+/// Restrictions of uploads. Content type allowlists of `None` allow any
+/// content type for single-shot or multipart uploads.
+#[derive(Debug, Clone, Default)]
+pub struct UploadPolicy {
+    pub single: Option<ContentTypeAllowlist>,
+    pub multipart: Option<ContentTypeAllowlist>,
+    /// Smallest width in pixels of uploaded images, not checked if `None`
+    pub min_image_width: Option<i32>,
+    /// Smallest height in pixels of uploaded images, not checked if `None`
+    pub min_image_height: Option<i32>,
+    /// Most parts recorded for a single multipart upload, capped by
+    /// `S3_MAX_MULTIPART_PARTS`. Recorded parts are removed when the upload
+    /// is completed or aborted.
+    pub max_multipart_parts: Option<u32>,
+    /// Most bytes uploaded in the chunks of a single multipart upload, not
+    /// checked if `None`
+    pub max_multipart_bytes: Option<u64>,
+    /// Checked when `UpdateMedia` or a multipart upload replaces the file of
+    /// a media
+    pub content_type_replacement: ContentTypeReplacement,
+}
+
+impl UploadPolicy {
+    pub fn max_multipart_parts(&self) -> u32 {
+        self.max_multipart_parts
+            .unwrap_or(S3_MAX_MULTIPART_PARTS)
+            .min(S3_MAX_MULTIPART_PARTS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strict_replacement_allows_same_content_type() {
+        let replacement = ContentTypeReplacement::Strict;
+
+        assert!(replacement.is_allowed("image/png", "image/png"));
+        assert!(replacement.is_allowed("image/png", "IMAGE/PNG; q=1"));
+        assert!(!replacement.is_allowed("image/png", "image/jpeg"));
+    }
+
+    #[test]
+    fn strict_replacement_rejects_other_category() {
+        assert!(!ContentTypeReplacement::Strict
+            .is_allowed("image/png", "application/pdf"));
+    }
+
+    #[test]
+    fn same_category_replacement() {
+        let replacement = ContentTypeReplacement::SameCategory;
+
+        assert!(replacement.is_allowed("image/png", "image/jpeg"));
+        assert!(!replacement.is_allowed("image/png", "application/pdf"));
+    }
+
+    #[test]
+    fn any_replacement() {
+        assert!(ContentTypeReplacement::Any
+            .is_allowed("image/png", "application/pdf"));
+    }
+
+    #[test]
+    fn parses_replacement() {
+        assert_eq!(
+            "strict".parse::<ContentTypeReplacement>(),
+            Ok(ContentTypeReplacement::Strict)
+        );
+        assert_eq!(
+            "same_category".parse::<ContentTypeReplacement>(),
+            Ok(ContentTypeReplacement::SameCategory)
+        );
+        assert!("image".parse::<ContentTypeReplacement>().is_err());
+    }
+
+    #[test]
+    fn allowlist_matches_top_level_type() {
+        let allowlist = ContentTypeAllowlist::parse("image/*, application/pdf");
+
+        assert!(allowlist.is_allowed("image/webp"));
+        assert!(allowlist.is_allowed("application/pdf; charset=binary"));
+        assert!(!allowlist.is_allowed("video/mp4"));
+    }
+}
@@ -0,0 +1,225 @@
+use std::io;
+use std::time::Duration;
+
+use aws_sdk_s3::primitives::ByteStream;
+use futures_util::StreamExt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tonic::Status;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanResult {
+    Clean,
+    /// Contains the signature reported by the scanner
+    Infected(String),
+}
+
+/// Scans uploaded files by streaming them to a ClamAV daemon using the
+/// `INSTREAM` command.
+#[derive(Debug, Clone)]
+pub struct VirusScanner {
+    address: String,
+    sync_max_bytes: usize,
+}
+
+impl VirusScanner {
+    /// clamd rejects chunks above its `StreamMaxLength`, keep them small
+    const CHUNK_SIZE: usize = 64 * 1024;
+    const TIMEOUT: Duration = Duration::from_secs(120);
+    /// Background scans failing to reach clamd or the file are retried
+    pub const MAX_ATTEMPTS: u32 = 5;
+    const RETRY_BASE_DELAY: Duration = Duration::from_secs(5);
+
+    /// Files up to `sync_max_bytes` are scanned while the upload request is
+    /// handled, larger ones are scanned in the background.
+    pub fn new(host: String, port: u16, sync_max_bytes: usize) -> Self {
+        Self {
+            address: format!("{host}:{port}"),
+            sync_max_bytes,
+        }
+    }
+
+    pub fn scans_synchronously(&self, size: usize) -> bool {
+        size <= self.sync_max_bytes
+    }
+
+    /// Doubles with each failed `attempt`, starting at 0
+    pub fn retry_delay(attempt: u32) -> Duration {
+        Self::RETRY_BASE_DELAY * 2u32.saturating_pow(attempt)
+    }
+
+    pub async fn scan(&self, data: &[u8]) -> Result<ScanResult, Status> {
+        self.with_timeout(async {
+            let mut stream = Self::connect(&self.address).await?;
+            Self::send(&mut stream, data).await?;
+            Self::finish(stream).await
+        })
+        .await
+    }
+
+    pub async fn scan_stream(
+        &self,
+        mut body: ByteStream,
+    ) -> Result<ScanResult, Status> {
+        self.with_timeout(async {
+            let mut stream = Self::connect(&self.address).await?;
+            while let Some(chunk) = body.next().await {
+                let chunk = chunk
+                    .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+                Self::send(&mut stream, &chunk).await?;
+            }
+            Self::finish(stream).await
+        })
+        .await
+    }
+
+    async fn with_timeout(
+        &self,
+        scan: impl std::future::Future<Output = io::Result<ScanResult>>,
+    ) -> Result<ScanResult, Status> {
+        match tokio::time::timeout(Self::TIMEOUT, scan).await {
+            Ok(Ok(result)) => Ok(result),
+            Ok(Err(err)) => {
+                tracing::log::error!("[VirusScanner.scan]: {err}");
+                Err(Status::unavailable("virus scanner is unavailable"))
+            }
+            Err(_) => {
+                tracing::log::error!("[VirusScanner.scan]: timed out");
+                Err(Status::unavailable("virus scanner is unavailable"))
+            }
+        }
+    }
+
+    async fn connect(address: &str) -> io::Result<TcpStream> {
+        let mut stream = TcpStream::connect(address).await?;
+        stream.write_all(b"zINSTREAM\0").await?;
+        Ok(stream)
+    }
+
+    /// Sends `data` as length prefixed chunks
+    async fn send(stream: &mut TcpStream, data: &[u8]) -> io::Result<()> {
+        for chunk in data.chunks(Self::CHUNK_SIZE) {
+            let len = u32::try_from(chunk.len()).unwrap();
+            stream.write_all(&len.to_be_bytes()).await?;
+            stream.write_all(chunk).await?;
+        }
+        Ok(())
+    }
+
+    /// Terminates the stream with a zero length chunk and reads the reply,
+    /// e.g. `stream: OK` or `stream: Eicar-Signature FOUND`
+    async fn finish(mut stream: TcpStream) -> io::Result<ScanResult> {
+        stream.write_all(&0u32.to_be_bytes()).await?;
+
+        let mut reply = Vec::new();
+        stream.read_to_end(&mut reply).await?;
+        let reply = String::from_utf8_lossy(&reply);
+        let reply = reply.trim_end_matches(['\0', '\n']).trim();
+
+        let result = reply.strip_prefix("stream:").map(str::trim);
+        match result {
+            Some("OK") => Ok(ScanResult::Clean),
+            Some(result) if result.ends_with(" FOUND") => {
+                Ok(ScanResult::Infected(
+                    result.trim_end_matches(" FOUND").to_owned(),
+                ))
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unexpected reply: {reply}"),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+    use tonic::Code;
+
+    use super::*;
+
+    /// Accepts one `INSTREAM` scan and answers with `reply`, returns the
+    /// scanned bytes
+    async fn fake_clamd(
+        reply: &'static str,
+    ) -> (u16, tokio::task::JoinHandle<Vec<u8>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            let mut command = [0; 10];
+            stream.read_exact(&mut command).await.unwrap();
+            assert_eq!(&command, b"zINSTREAM\0");
+
+            let mut scanned = Vec::new();
+            loop {
+                let len = stream.read_u32().await.unwrap();
+                if len == 0 {
+                    break;
+                }
+                let mut chunk = vec![0; usize::try_from(len).unwrap()];
+                stream.read_exact(&mut chunk).await.unwrap();
+                scanned.extend(chunk);
+            }
+
+            stream.write_all(reply.as_bytes()).await.unwrap();
+            scanned
+        });
+
+        (port, handle)
+    }
+
+    #[tokio::test]
+    async fn reports_clean_file() {
+        let (port, clamd) = fake_clamd("stream: OK\0").await;
+        let scanner = VirusScanner::new("127.0.0.1".to_owned(), port, 0);
+
+        let data = vec![7; VirusScanner::CHUNK_SIZE + 1];
+        let result = scanner.scan(&data).await.unwrap();
+
+        assert_eq!(result, ScanResult::Clean);
+        assert_eq!(clamd.await.unwrap(), data);
+    }
+
+    #[tokio::test]
+    async fn reports_signature_of_infected_file() {
+        let (port, _clamd) =
+            fake_clamd("stream: Eicar-Signature FOUND\0").await;
+        let scanner = VirusScanner::new("127.0.0.1".to_owned(), port, 0);
+
+        let result = scanner.scan(b"X5O!P%@AP").await.unwrap();
+
+        assert_eq!(result, ScanResult::Infected("Eicar-Signature".to_owned()));
+    }
+
+    #[tokio::test]
+    async fn unreachable_scanner_is_unavailable() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        let scanner = VirusScanner::new("127.0.0.1".to_owned(), port, 0);
+
+        let err = scanner.scan(b"data").await.unwrap_err();
+
+        assert_eq!(err.code(), Code::Unavailable);
+    }
+
+    #[tokio::test]
+    async fn unexpected_reply_is_unavailable() {
+        let (port, _clamd) = fake_clamd("INSTREAM size limit exceeded\0").await;
+        let scanner = VirusScanner::new("127.0.0.1".to_owned(), port, 0);
+
+        let err = scanner.scan(b"data").await.unwrap_err();
+
+        assert_eq!(err.code(), Code::Unavailable);
+    }
+
+    #[test]
+    fn retry_delay_doubles() {
+        assert_eq!(VirusScanner::retry_delay(0), Duration::from_secs(5));
+        assert_eq!(VirusScanner::retry_delay(2), Duration::from_secs(20));
+    }
+}